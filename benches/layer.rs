@@ -0,0 +1,177 @@
+//! Overhead of the layer on the application thread and end-to-end latency
+//! through the worker.
+//!
+//! Run with `cargo bench --bench layer`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tracing::{error, info};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Registry;
+
+use tracing_log_sink::handle::LayerHandle;
+use tracing_log_sink::init::LayerConfig;
+use tracing_log_sink::layer::ErrorLogLayer;
+use tracing_log_sink::noop_sink::NoopSink;
+use tracing_log_sink::record::LogRecord;
+use tracing_log_sink::sink::{LogSink, SinkError};
+
+/// Layer without stdout output, with a channel large enough that the
+/// benchmarks measure enqueueing rather than drops.
+fn config() -> LayerConfig {
+    LayerConfig {
+        channel_buffer: 1 << 20,
+        enable_stdout: false,
+        ..LayerConfig::default()
+    }
+}
+
+fn subscriber(sink: Arc<dyn LogSink>, config: &LayerConfig) -> (impl tracing::Subscriber + Send + Sync, LayerHandle) {
+    let (layer, _worker) = ErrorLogLayer::with_config(sink, config);
+    let handle = layer.handle();
+    (Registry::default().with(layer), handle)
+}
+
+fn on_event(c: &mut Criterion) {
+    let (subscriber, handle) = subscriber(Arc::new(NoopSink), &config());
+    let mut group = c.benchmark_group("on_event");
+    group.throughput(Throughput::Elements(1));
+
+    tracing::subscriber::with_default(subscriber, || {
+        group.bench_function("filtered_by_level", |b| {
+            b.iter(|| info!(user_id = 42, "filtered event"));
+        });
+        group.bench_function("captured", |b| {
+            b.iter(|| error!(user_id = 42, path = "/api/orders", "captured event"));
+        });
+        group.bench_function("captured_in_span", |b| {
+            let span = tracing::info_span!("request", method = "GET");
+            let _guard = span.enter();
+            b.iter(|| error!(user_id = 42, "captured event"));
+        });
+        handle.flush_blocking(Duration::from_secs(30)).ok();
+    });
+    group.finish();
+}
+
+fn contention(c: &mut Criterion) {
+    const EVENTS_PER_THREAD: u64 = 10_000;
+    let mut group = c.benchmark_group("enqueue_contention");
+
+    for threads in [1u64, 4, 8] {
+        for shards in [1usize, 8] {
+            let config = LayerConfig { channel_shards: shards, ..config() };
+            let (subscriber, handle) = subscriber(Arc::new(NoopSink), &config);
+            let dispatch = tracing::Dispatch::new(subscriber);
+            group.throughput(Throughput::Elements(threads * EVENTS_PER_THREAD));
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_shards", shards), threads),
+                &threads,
+                |b, &threads| {
+                    b.iter_custom(|iters| {
+                        let mut total = Duration::ZERO;
+                        for _ in 0..iters {
+                            let start = Instant::now();
+                            thread::scope(|scope| {
+                                for _ in 0..threads {
+                                    let dispatch = dispatch.clone();
+                                    scope.spawn(move || {
+                                        tracing::dispatcher::with_default(&dispatch, || {
+                                            for i in 0..EVENTS_PER_THREAD {
+                                                error!(iteration = i, "contended event");
+                                            }
+                                        })
+                                    });
+                                }
+                            });
+                            total += start.elapsed();
+                            handle.flush_blocking(Duration::from_secs(30)).ok();
+                        }
+                        total
+                    });
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Events with eight fields, with and without records recycled through
+/// `LayerConfig::record_pool_size`; the pool is refilled between rounds,
+/// so every round can draw on records of the previous one.
+fn record_pool(c: &mut Criterion) {
+    const EVENTS_PER_ROUND: u64 = 1_000;
+    let mut group = c.benchmark_group("record_pool");
+    group.throughput(Throughput::Elements(EVENTS_PER_ROUND));
+
+    for pool in [0usize, 1_024] {
+        let config = LayerConfig { record_pool_size: pool, ..config() };
+        let (subscriber, handle) = subscriber(Arc::new(NoopSink), &config);
+        let dispatch = tracing::Dispatch::new(subscriber);
+        group.bench_with_input(BenchmarkId::from_parameter(pool), &pool, |b, _| {
+            b.iter_custom(|iters| {
+                let mut total = Duration::ZERO;
+                for _ in 0..iters {
+                    let start = Instant::now();
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for i in 0..EVENTS_PER_ROUND {
+                            error!(a = i, b = 2, c = 3, d = 4, e = "e", f = "f", g = true, h = 8.5, "pooled event");
+                        }
+                    });
+                    total += start.elapsed();
+                    handle.flush_blocking(Duration::from_secs(30)).ok();
+                }
+                total
+            });
+        });
+    }
+    group.finish();
+}
+
+/// Sink that counts accepted records.
+#[derive(Default)]
+struct CountingSink(AtomicUsize);
+
+#[async_trait]
+impl LogSink for CountingSink {
+    async fn send(&self, _record: &LogRecord) -> Result<(), SinkError> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+fn end_to_end(c: &mut Criterion) {
+    let mut group = c.benchmark_group("end_to_end");
+
+    for batch in [1usize, 100, 1_000] {
+        let sink = Arc::new(CountingSink::default());
+        let (subscriber, handle) = subscriber(sink.clone(), &config());
+        let dispatch = tracing::Dispatch::new(subscriber);
+        group.throughput(Throughput::Elements(batch as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(batch), &batch, |b, &batch| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    tracing::dispatcher::with_default(&dispatch, || {
+                        for i in 0..batch {
+                            error!(iteration = i, "end-to-end event");
+                        }
+                    });
+                    // Returns once every record above reached the sink.
+                    handle.flush_blocking(Duration::from_secs(30)).expect("flush");
+                }
+                start.elapsed()
+            });
+        });
+        assert!(sink.0.load(Ordering::Relaxed) > 0);
+    }
+    group.finish();
+}
+
+criterion_group!(benches, on_event, contention, record_pool, end_to_end);
+criterion_main!(benches);
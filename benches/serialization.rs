@@ -0,0 +1,76 @@
+//! Per-record serialization cost of the representations used by the
+//! built-in sinks.
+//!
+//! Run with `cargo bench --bench serialization` (add `--all-features` to
+//! include the binary encodings).
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::hint::black_box;
+
+use tracing_log_sink::encoding::PayloadEncoding;
+use tracing_log_sink::record::{LogRecord, SCHEMA_VERSION};
+use tracing_log_sink::timestamp::TimestampFormat;
+
+/// A typical captured error with a handful of fields.
+fn record() -> LogRecord {
+    serde_json::from_value(serde_json::json!({
+        "schema_version": SCHEMA_VERSION,
+        "timestamp": "2026-01-01T12:00:00.123456Z",
+        "level": "ERROR",
+        "target": "orders::api",
+        "module_path": "orders::api::handlers",
+        "file": "src/api/handlers.rs",
+        "line": 128,
+        "fields": {
+            "user_id": 42,
+            "path": "/api/orders/17",
+            "error.message": "connection reset by peer",
+            "error.chain": ["io error", "connection reset by peer"],
+            "retry": false,
+        },
+        "message": "failed to load order",
+        "hostname": "orders-7f9c",
+        "pid": 4242,
+        "thread_id": "ThreadId(7)",
+        "thread_name": "tokio-runtime-worker",
+        "resource": { "env": "prod", "region": "eu-west-1", "version": "1.4.2" },
+        "event_id": "01890a5d-ac96-774b-bcce-b302099a8057",
+        "fingerprint": "9f2c1e0b7a3d4c55",
+    }))
+    .expect("valid record")
+}
+
+fn serialization(c: &mut Criterion) {
+    let record = record();
+    let mut group = c.benchmark_group("serialize");
+    group.throughput(Throughput::Elements(1));
+
+    // Postgres stores the record as JSONB.
+    group.bench_function("json_value", |b| b.iter(|| serde_json::to_value(black_box(&record))));
+    // ClickHouse restricts the row to the table's schema version.
+    group.bench_function("json_for_version", |b| {
+        b.iter(|| black_box(&record).to_json_for_version(SCHEMA_VERSION))
+    });
+    // OpenSearch renders the timestamp in the configured format.
+    group.bench_function("json_with_timestamp", |b| {
+        b.iter(|| black_box(&record).to_json_with_timestamp(TimestampFormat::default()))
+    });
+    // Kafka encodes one message per record.
+    let encodings = [
+        ("json", PayloadEncoding::Json),
+        #[cfg(feature = "msgpack")]
+        ("msgpack", PayloadEncoding::MessagePack),
+        #[cfg(feature = "cbor")]
+        ("cbor", PayloadEncoding::Cbor),
+        #[cfg(feature = "proto")]
+        ("protobuf", PayloadEncoding::Protobuf),
+    ];
+    for (name, encoding) in encodings {
+        group.bench_function(format!("encode_{}", name), |b| b.iter(|| encoding.encode(black_box(&record))));
+    }
+    group.bench_function("estimated_size", |b| b.iter(|| black_box(&record).estimated_size()));
+    group.finish();
+}
+
+criterion_group!(benches, serialization);
+criterion_main!(benches);
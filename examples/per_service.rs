@@ -17,6 +17,7 @@ async fn main() {
             service_name: None,
             user: Some("default".to_string()),
             password: None,
+            ..ClickHouseConfig::default()
         };
         let sink = Arc::new(ClickHouseSink::new(config));
         init_tracing(sink);
@@ -1,4 +1,3 @@
-use std::error::Error;
 use std::sync::Arc;
 
 use async_trait::async_trait;
@@ -7,7 +6,7 @@ use tracing::{error, info};
 
 use tracing_log_sink::init::init_tracing;
 use tracing_log_sink::record::LogRecord;
-use tracing_log_sink::sink::LogSink;
+use tracing_log_sink::sink::{LogSink, SinkError};
 
 /// Simple `LogSink` implementation that writes `LogRecord`s
 /// into a Postgres table using `sqlx`.
@@ -24,7 +23,23 @@ use tracing_log_sink::sink::LogSink;
 ///   line         int4,
 ///   message      text,
 ///   fields       jsonb         NOT NULL,
-///   service_name text
+///   service_name text,
+///   schema_version int4,
+///   hostname     text,
+///   pid          int4,
+///   thread_id    text,
+///   thread_name  text,
+///   resource     jsonb,
+///   event_id     uuid,
+///   repeat_count int8,
+///   fingerprint  text,
+///   trace_id     text,
+///   span_id      text,
+///   span_name    text,
+///   duration_ms  int8,
+///   span_elapsed_ms int8,
+///   error_kind   text,
+///   error_message text
 /// );
 /// ```
 #[derive(Clone)]
@@ -48,26 +63,45 @@ impl PostgresSink {
 
 #[async_trait]
 impl LogSink for PostgresSink {
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
         // Insert a single normalized `LogRecord` into the `error_logs` table.
         sqlx::query(
             r#"
             INSERT INTO error_logs
-                (ts, level, target, module_path, file, line, message, fields, service_name)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                (ts, level, target, module_path, file, line, message, fields, service_name,
+                 schema_version, hostname, pid, thread_id, thread_name, resource, event_id,
+                 repeat_count, fingerprint, trace_id, span_id, span_name, duration_ms, span_elapsed_ms, error_kind, error_message)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16::uuid, $17, $18, $19, $20, $21, $22, $23, $24, $25)
             "#,
         )
         .bind(record.timestamp)
-        .bind(&record.level)
-        .bind(&record.target)
-        .bind(&record.module_path)
-        .bind(&record.file)
+        .bind(record.level.as_str())
+        .bind(record.target.as_ref())
+        .bind(record.module_path.as_deref())
+        .bind(record.file.as_deref())
         .bind(record.line.map(|l| l as i32))
         .bind(&record.message)
         .bind(serde_json::to_value(&record.fields)?)
         .bind(&record.service_name)
+        .bind(record.schema_version as i32)
+        .bind(&record.hostname)
+        .bind(record.pid.map(|p| p as i32))
+        .bind(&record.thread_id)
+        .bind(&record.thread_name)
+        .bind(serde_json::to_value(&record.resource)?)
+        .bind(record.event_id.map(|id| id.to_string()))
+        .bind(record.repeat_count.map(|n| n as i64))
+        .bind(&record.fingerprint)
+        .bind(&record.trace_id)
+        .bind(&record.span_id)
+        .bind(&record.span_name)
+        .bind(record.duration_ms.map(|n| n as i64))
+        .bind(record.span_elapsed_ms.map(|n| n as i64))
+        .bind(record.error_kind.as_deref())
+        .bind(record.error_message.as_deref())
         .execute(&self.pool)
-        .await?;
+        .await
+        .map_err(SinkError::transient)?;
 
         Ok(())
     }
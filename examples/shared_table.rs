@@ -5,6 +5,8 @@ use tracing::{error, info};
 #[cfg(feature = "clickhouse")]
 use tracing_log_sink::clickhouse::{ClickHouseConfig, ClickHouseSink};
 use tracing_log_sink::init::{init_tracing_with_config, LayerConfig};
+#[cfg(feature = "clickhouse")]
+use tracing_log_sink::resource::ResourceInfo;
 
 #[tokio::main]
 async fn main() {
@@ -17,6 +19,7 @@ async fn main() {
             service_name: Some("auth-service".to_string()),
             user: Some("default".to_string()),
             password: None,
+            ..ClickHouseConfig::default()
         };
         let sink = Arc::new(ClickHouseSink::new(config));
         let layer_config = LayerConfig {
@@ -24,6 +27,12 @@ async fn main() {
             batch_size: 500,
             flush_interval: Duration::from_millis(500),
             enable_stdout: true,
+            resource: ResourceInfo {
+                environment: Some("staging".to_string()),
+                version: Some(env!("CARGO_PKG_VERSION").to_string()),
+                ..ResourceInfo::default()
+            },
+            ..LayerConfig::default()
         };
         init_tracing_with_config(sink, layer_config);
     }
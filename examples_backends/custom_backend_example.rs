@@ -3,9 +3,10 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use tracing::{error, info};
 use tracing_log_sink::{
+    backend,
     init::init_tracing,
     record::LogRecord,
-    sink::LogSink,
+    sink::{LogSink, SinkError},
 };
 
 /// Example of integrating a completely custom backend by implementing
@@ -16,7 +17,7 @@ struct MyCustomDbSink;
 
 #[async_trait]
 impl LogSink for MyCustomDbSink {
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
         // Here you would call your own client library for the target DB.
         // For the sake of example we just print the record.
         println!("[my-custom-db] {:?}", record);
@@ -26,7 +27,12 @@ impl LogSink for MyCustomDbSink {
 
 #[tokio::main]
 async fn main() {
-    let sink: Arc<dyn LogSink> = Arc::new(MyCustomDbSink);
+    // Plug the sink into the DSN mechanism so it can be selected with
+    // e.g. `LOG_SINK_DSN=mydb://...` like the built-in backends.
+    backend::register_scheme("mydb", |_cfg| Ok(Arc::new(MyCustomDbSink) as Arc<dyn LogSink>));
+
+    let backend_cfg = backend::parse_dsn("mydb://localhost/logs").expect("parse DSN");
+    let sink = backend::make_sink_from_config_async(&backend_cfg).await.expect("build sink");
 
     init_tracing(sink);
 
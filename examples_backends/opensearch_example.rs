@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use tracing::{error, info};
 use tracing_log_sink::{
-    backend::{make_sink_from_config, parse_dsn},
+    backend::{make_sink_from_config_async, parse_dsn},
     init::init_tracing,
     sink::LogSink,
 };
@@ -14,7 +14,8 @@ async fn main() {
         .unwrap_or_else(|_| "opensearch://localhost:9200/logs".to_string());
 
     let backend_cfg = parse_dsn(&dsn).expect("invalid LOG_SINK_DSN");
-    let sink: Arc<dyn LogSink> = make_sink_from_config(&backend_cfg)
+    let sink: Arc<dyn LogSink> = make_sink_from_config_async(&backend_cfg)
+        .await
         .expect("failed to build opensearch backend sink");
 
     init_tracing(sink);
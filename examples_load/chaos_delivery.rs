@@ -0,0 +1,93 @@
+use std::collections::{HashMap, HashSet};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::time::Duration;
+use tracing::error;
+use uuid::Uuid;
+
+use tracing_log_sink::chaos::{ChaosConfig, ChaosSink};
+use tracing_log_sink::diagnostics::{DiagnosticsConfig, DiagnosticsOutput};
+use tracing_log_sink::init::{init_tracing_with_config, LayerConfig};
+use tracing_log_sink::record::LogRecord;
+use tracing_log_sink::sink::{LogSink, SinkError};
+
+/// Remembers the `event_id`s received for every `seq` field.
+#[derive(Default)]
+struct Collector {
+    received: Mutex<HashMap<u64, HashSet<Option<Uuid>>>>,
+    deliveries: Mutex<u64>,
+}
+
+#[async_trait]
+impl LogSink for Collector {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let seq = record.fields.get("seq").and_then(|v| v.as_u64()).expect("seq field");
+        self.received.lock().unwrap().entry(seq).or_default().insert(record.event_id);
+        *self.deliveries.lock().unwrap() += 1;
+        Ok(())
+    }
+}
+
+/// Checks the at-least-once guarantee of the worker: with 15% of the
+/// sends failing and some failing after the record was stored, every
+/// event still arrives, and repeated deliveries keep their `event_id`.
+#[tokio::main]
+async fn main() -> ExitCode {
+    let collector = Arc::new(Collector::default());
+    let chaos = Arc::new(ChaosSink::new(
+        collector.clone(),
+        ChaosConfig {
+            failure_rate: 0.1,
+            ambiguous_rate: 0.05,
+            max_latency: Duration::from_millis(2),
+            seed: 42,
+            ..ChaosConfig::default()
+        },
+    ));
+
+    let n: u64 = 2_000;
+    let layer_config = LayerConfig {
+        // Room for every event: this checks delivery, not backpressure.
+        channel_buffer: n as usize,
+        batch_size: 100,
+        max_in_flight: 4,
+        flush_interval: Duration::from_millis(50),
+        enable_stdout: false,
+        diagnostics: DiagnosticsConfig { output: DiagnosticsOutput::Silent, ..DiagnosticsConfig::default() },
+        ..LayerConfig::default()
+    };
+    let handle = init_tracing_with_config(chaos.clone(), layer_config);
+
+    for seq in 0..n {
+        error!(seq, "chaos delivery test error");
+    }
+    handle.shutdown(Duration::from_secs(120)).await.expect("shutdown");
+
+    let received = collector.received.lock().unwrap();
+    let missing = (0..n).filter(|seq| !received.contains_key(seq)).count();
+    let unstable = received.values().filter(|ids| ids.len() != 1).count();
+    let deliveries = *collector.deliveries.lock().unwrap();
+    let stats = chaos.stats();
+
+    println!(
+        "chaos: {} attempts, {} failed, {} acknowledgements lost",
+        stats.attempts, stats.failed, stats.ambiguous
+    );
+    println!(
+        "received {} of {} events in {} deliveries ({} duplicates), {} missing, {} with changing event_id",
+        received.len(),
+        n,
+        deliveries,
+        deliveries - received.len() as u64,
+        missing,
+        unstable
+    );
+
+    if missing == 0 && unstable == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -8,13 +8,14 @@ use tracing_log_sink::noop_sink::NoopSink;
 
 #[tokio::main]
 async fn main() {
-    let sink = Arc::new(NoopSink::default());
+    let sink = Arc::new(NoopSink);
 
     let layer_config = LayerConfig {
         channel_buffer: 50_000,
         batch_size: 1_000,
         flush_interval: Duration::from_millis(200),
         enable_stdout: false,
+        ..LayerConfig::default()
     };
 
     init_tracing_with_config(sink, layer_config);
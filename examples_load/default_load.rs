@@ -8,7 +8,7 @@ use tracing_log_sink::noop_sink::NoopSink;
 
 #[tokio::main]
 async fn main() {
-    let sink = Arc::new(NoopSink::default());
+    let sink = Arc::new(NoopSink);
     init_tracing(sink);
 
     let n: u64 = 100_000;
@@ -2,13 +2,29 @@
 DROP TABLE IF EXISTS default.auth_errors;
 
 CREATE TABLE default.auth_errors (
-    timestamp    String,
-    level        String,
-    target       String,
-    module_path  Nullable(String),
-    file         Nullable(String),
-    line         Nullable(UInt32),
-    message      Nullable(String),
-    fields       String
+    timestamp      String,
+    level          String,
+    target         String,
+    module_path    Nullable(String),
+    file           Nullable(String),
+    line           Nullable(UInt32),
+    message        Nullable(String),
+    schema_version Nullable(UInt32),
+    hostname       Nullable(String),
+    pid            Nullable(UInt32),
+    thread_id      Nullable(String),
+    thread_name    Nullable(String),
+    resource       Map(String, String),
+    event_id       Nullable(UUID),
+    repeat_count   Nullable(UInt64),
+    fingerprint    Nullable(String),
+    trace_id       Nullable(String),
+    span_id        Nullable(String),
+    span_name      Nullable(String),
+    duration_ms    Nullable(UInt64),
+    span_elapsed_ms Nullable(UInt64),
+    error_kind     Nullable(String),
+    error_message  Nullable(String),
+    fields         String
 ) ENGINE = MergeTree
 ORDER BY (timestamp, level, target);
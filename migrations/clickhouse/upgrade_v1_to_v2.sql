@@ -0,0 +1,11 @@
+-- Upgrade a table created with LogRecord schema version 1 to version 2.
+-- After applying, set `ClickHouseConfig::schema_version = 2`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS schema_version Nullable(UInt32),
+    ADD COLUMN IF NOT EXISTS hostname       Nullable(String),
+    ADD COLUMN IF NOT EXISTS pid            Nullable(UInt32),
+    ADD COLUMN IF NOT EXISTS thread_id      Nullable(String),
+    ADD COLUMN IF NOT EXISTS thread_name    Nullable(String),
+    ADD COLUMN IF NOT EXISTS resource       Map(String, String);
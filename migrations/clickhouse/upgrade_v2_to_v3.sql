@@ -0,0 +1,6 @@
+-- Upgrade a table created with LogRecord schema version 2 to version 3.
+-- After applying, set `ClickHouseConfig::schema_version = 3`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS event_id Nullable(UUID);
@@ -0,0 +1,6 @@
+-- Upgrade a table created with LogRecord schema version 3 to version 4.
+-- After applying, set `ClickHouseConfig::schema_version = 4`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS repeat_count Nullable(UInt64);
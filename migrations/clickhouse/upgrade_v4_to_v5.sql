@@ -0,0 +1,6 @@
+-- Upgrade a table created with LogRecord schema version 4 to version 5.
+-- After applying, set `ClickHouseConfig::schema_version = 5`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS fingerprint Nullable(String);
@@ -0,0 +1,7 @@
+-- Upgrade a table created with LogRecord schema version 5 to version 6.
+-- After applying, set `ClickHouseConfig::schema_version = 6`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS trace_id Nullable(String),
+    ADD COLUMN IF NOT EXISTS span_id Nullable(String);
@@ -0,0 +1,7 @@
+-- Upgrade a table created with LogRecord schema version 6 to version 7.
+-- After applying, set `ClickHouseConfig::schema_version = 7`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS span_name Nullable(String),
+    ADD COLUMN IF NOT EXISTS duration_ms Nullable(UInt64);
@@ -0,0 +1,6 @@
+-- Upgrade a table created with LogRecord schema version 7 to version 8.
+-- After applying, set `ClickHouseConfig::schema_version = 8`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS span_elapsed_ms Nullable(UInt64);
@@ -0,0 +1,7 @@
+-- Upgrade a table created with LogRecord schema version 8 to version 9.
+-- After applying, set `ClickHouseConfig::schema_version = 9`.
+-- Replace `default.service_logs` with your table name.
+
+ALTER TABLE default.service_logs
+    ADD COLUMN IF NOT EXISTS error_kind Nullable(String),
+    ADD COLUMN IF NOT EXISTS error_message Nullable(String);
@@ -2,13 +2,29 @@
 -- Adjust schema/database as needed for your environment.
 
 CREATE TABLE IF NOT EXISTS error_logs (
-    ts           TIMESTAMPTZ   NOT NULL,
-    level        TEXT          NOT NULL,
-    target       TEXT          NOT NULL,
-    module_path  TEXT,
-    file         TEXT,
-    line         INT4,
-    message      TEXT,
-    fields       JSONB         NOT NULL,
-    service_name TEXT
+    ts             TIMESTAMPTZ   NOT NULL,
+    level          TEXT          NOT NULL,
+    target         TEXT          NOT NULL,
+    module_path    TEXT,
+    file           TEXT,
+    line           INT4,
+    message        TEXT,
+    fields         JSONB         NOT NULL,
+    service_name   TEXT,
+    schema_version INT4,
+    hostname       TEXT,
+    pid            INT4,
+    thread_id      TEXT,
+    thread_name    TEXT,
+    resource       JSONB,
+    event_id       UUID,
+    repeat_count   INT8,
+    fingerprint    TEXT,
+    trace_id       TEXT,
+    span_id        TEXT,
+    span_name      TEXT,
+    duration_ms    INT8,
+    span_elapsed_ms INT8,
+    error_kind     TEXT,
+    error_message  TEXT
 );
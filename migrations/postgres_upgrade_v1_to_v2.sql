@@ -0,0 +1,9 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 1 to version 2.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS schema_version INT4,
+    ADD COLUMN IF NOT EXISTS hostname       TEXT,
+    ADD COLUMN IF NOT EXISTS pid            INT4,
+    ADD COLUMN IF NOT EXISTS thread_id      TEXT,
+    ADD COLUMN IF NOT EXISTS thread_name    TEXT,
+    ADD COLUMN IF NOT EXISTS resource       JSONB;
@@ -0,0 +1,4 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 2 to version 3.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS event_id UUID;
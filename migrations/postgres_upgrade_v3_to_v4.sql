@@ -0,0 +1,4 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 3 to version 4.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS repeat_count INT8;
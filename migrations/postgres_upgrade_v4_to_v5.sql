@@ -0,0 +1,4 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 4 to version 5.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS fingerprint TEXT;
@@ -0,0 +1,5 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 5 to version 6.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS trace_id TEXT,
+    ADD COLUMN IF NOT EXISTS span_id TEXT;
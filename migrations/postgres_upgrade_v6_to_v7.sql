@@ -0,0 +1,5 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 6 to version 7.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS span_name TEXT,
+    ADD COLUMN IF NOT EXISTS duration_ms INT8;
@@ -0,0 +1,4 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 7 to version 8.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS span_elapsed_ms INT8;
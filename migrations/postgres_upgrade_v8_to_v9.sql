@@ -0,0 +1,5 @@
+-- Upgrade the `error_logs` table from LogRecord schema version 8 to version 9.
+
+ALTER TABLE error_logs
+    ADD COLUMN IF NOT EXISTS error_kind TEXT,
+    ADD COLUMN IF NOT EXISTS error_message TEXT;
@@ -0,0 +1,343 @@
+//! Actix-web middleware recording HTTP requests.
+//!
+//! [`RequestTracing`] is the actix-web counterpart of
+//! `tower::RequestContext`: every request runs in an `http_request` span
+//! with the fields `method`, `path`, `request_id` and `peer_ip`, and
+//! `status` and `latency_ms` added once the response is ready. With span
+//! field inheritance enabled ([`SpanFieldsConfig`]), records emitted
+//! while the request is handled carry these fields.
+//!
+//! ```ignore
+//! HttpServer::new(|| App::new().wrap(RequestTracing::default()).service(index))
+//!     .bind(("0.0.0.0", 8080))?
+//!     .run()
+//!     .await
+//! ```
+//!
+//! Responses with a 5xx status are recorded as `ERROR` events with target
+//! [`HTTP_TARGET`] and the fields `method`, `path`, `status` and
+//! `latency_ms`; a handler error behind a response, or an error returned
+//! by an inner middleware, is attached as the `error` field, so the record
+//! gets `error_kind` and `error_message`. Handler errors with a status
+//! below 500 are recorded at `WARN`, letting the layer's `level` decide
+//! whether client errors are recorded.
+//!
+//! [`SpanFieldsConfig`]: crate::span_fields::SpanFieldsConfig
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::Error;
+use std::future::{Future, Ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Target of the events emitted by [`RequestTracing`].
+pub const HTTP_TARGET: &str = "http";
+
+/// How [`RequestTracing`] fills in `request_id` and `peer_ip`.
+///
+/// **Fields**
+/// - `request_id_header`: header holding the request id (default
+///   `x-request-id`). Requests without it get a new UUID, which is also
+///   set on the request so handlers see the same id.
+/// - `trust_forwarded_for`: take `peer_ip` from the first address of
+///   `X-Forwarded-For` (or from `X-Real-IP`) when present. Enable it only
+///   behind a proxy that sets these headers, since clients can forge
+///   them. Default `false`.
+///
+/// Otherwise `peer_ip` is the address of the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestTracingConfig {
+    pub request_id_header: HeaderName,
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RequestTracingConfig {
+    fn default() -> Self {
+        Self {
+            request_id_header: HeaderName::from_static("x-request-id"),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// Middleware factory for `App::wrap`, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct RequestTracing {
+    config: Arc<RequestTracingConfig>,
+}
+
+impl RequestTracing {
+    /// Trace requests, filling in fields as `config` says.
+    pub fn with_config(config: RequestTracingConfig) -> Self {
+        Self { config: Arc::new(config) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTracing
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestTracingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        std::future::ready(Ok(RequestTracingMiddleware {
+            service: Rc::new(service),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+/// Service built by [`RequestTracing`] around the application.
+#[derive(Debug)]
+pub struct RequestTracingMiddleware<S> {
+    service: Rc<S>,
+    config: Arc<RequestTracingConfig>,
+}
+
+impl<S> RequestTracingMiddleware<S> {
+    fn span(&self, req: &mut ServiceRequest) -> tracing::Span {
+        let header = &self.config.request_id_header;
+        let request_id = match req.headers().get(header).and_then(|v| v.to_str().ok()) {
+            Some(id) => id.to_string(),
+            None => {
+                let id = Uuid::now_v7().to_string();
+                if let Ok(value) = HeaderValue::from_str(&id) {
+                    req.headers_mut().insert(header.clone(), value);
+                }
+                id
+            }
+        };
+        let span = tracing::info_span!(
+            "http_request",
+            method = %req.method(),
+            path = req.path(),
+            request_id = request_id.as_str(),
+            peer_ip = tracing::field::Empty,
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        );
+        if let Some(ip) = peer_ip(req, self.config.trust_forwarded_for) {
+            span.record("peer_ip", tracing::field::display(ip));
+        }
+        span
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTracingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<ServiceResponse<B>, Error>>>>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let span = self.span(&mut req);
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let started = Instant::now();
+        let future = {
+            let _entered = span.enter();
+            self.service.call(req)
+        };
+        let outcome_span = span.clone();
+        Box::pin(
+            async move {
+                let result = future.await;
+                let latency_ms = started.elapsed().as_millis() as u64;
+                let (status, error) = match &result {
+                    Ok(res) => (res.status(), res.response().error()),
+                    Err(e) => (e.as_response_error().status_code(), Some(e)),
+                };
+                outcome_span.record("status", status.as_u16());
+                outcome_span.record("latency_ms", latency_ms);
+                record(&method, &path, status, latency_ms, error);
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
+
+/// Emit the event for a finished request, if it failed.
+fn record(method: &str, path: &str, status: StatusCode, latency_ms: u64, error: Option<&Error>) {
+    let status_code = status.as_u16();
+    let error = error.map(|e| e as &(dyn std::error::Error + 'static));
+    if status.is_server_error() {
+        tracing::error!(
+            target: HTTP_TARGET,
+            method,
+            path,
+            status = status_code,
+            latency_ms,
+            error,
+            "{} {} responded {}",
+            method,
+            path,
+            status
+        );
+    } else if error.is_some() {
+        tracing::warn!(
+            target: HTTP_TARGET,
+            method,
+            path,
+            status = status_code,
+            latency_ms,
+            error,
+            "{} {} responded {}",
+            method,
+            path,
+            status
+        );
+    }
+}
+
+/// Client address of `req`: the forwarding headers when they are
+/// trusted, else the address of the connection.
+fn peer_ip(req: &ServiceRequest, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let headers = req.headers();
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()));
+        if let Some(ip) = forwarded.and_then(|v| v.trim().parse().ok()) {
+            return Some(ip);
+        }
+    }
+    req.peer_addr().map(|addr| addr.ip())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use std::sync::Mutex;
+    use tracing::field::{Field, Visit};
+    use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+
+    /// Level and fields of an event.
+    type Captured = (tracing::Level, Vec<(String, String)>);
+
+    /// Every event with target [`HTTP_TARGET`].
+    #[derive(Clone, Default)]
+    struct Events(Arc<Mutex<Vec<Captured>>>);
+
+    struct Fields(Vec<(String, String)>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name().to_string(), format!("{:?}", value)));
+        }
+
+        fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Events {
+        fn on_event(&self, event: &tracing::Event<'_>, _ctx: LayerContext<'_, S>) {
+            if event.metadata().target() == HTTP_TARGET {
+                let mut fields = Fields(Vec::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push((*event.metadata().level(), fields.0));
+            }
+        }
+    }
+
+    impl Events {
+        fn field(&self, index: usize, name: &str) -> Option<String> {
+            let events = self.0.lock().unwrap();
+            events[index].1.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone())
+        }
+    }
+
+    async fn request(events: &Events, path: &str) -> StatusCode {
+        let _guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(events.clone()));
+        let app = init_service(
+            App::new()
+                .wrap(RequestTracing::default())
+                .route("/ok", web::get().to(|| async { HttpResponse::Ok().finish() }))
+                .route("/down", web::get().to(|| async { HttpResponse::ServiceUnavailable().finish() }))
+                .route(
+                    "/missing",
+                    web::get().to(|| async { Err::<HttpResponse, _>(actix_web::error::ErrorNotFound("no such item")) }),
+                )
+                .route(
+                    "/broken",
+                    web::get().to(|| async {
+                        Err::<HttpResponse, _>(actix_web::error::ErrorInternalServerError("database is gone"))
+                    }),
+                ),
+        )
+        .await;
+        call_service(&app, TestRequest::get().uri(path).to_request()).await.status()
+    }
+
+    #[tokio::test]
+    async fn successful_requests_are_not_recorded() {
+        let events = Events::default();
+        assert_eq!(request(&events, "/ok").await, StatusCode::OK);
+        assert!(events.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_recorded() {
+        let events = Events::default();
+        assert_eq!(request(&events, "/down").await, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(events.0.lock().unwrap()[0].0, tracing::Level::ERROR);
+        assert_eq!(events.field(0, "status").as_deref(), Some("503"));
+        assert_eq!(events.field(0, "path").as_deref(), Some("\"/down\""));
+        assert!(events.field(0, "latency_ms").is_some());
+        assert_eq!(events.field(0, "error"), None);
+    }
+
+    #[tokio::test]
+    async fn handler_errors_are_recorded_with_the_error() {
+        let events = Events::default();
+        assert_eq!(request(&events, "/broken").await, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(request(&events, "/missing").await, StatusCode::NOT_FOUND);
+        let recorded = events.0.lock().unwrap().iter().map(|(level, _)| *level).collect::<Vec<_>>();
+        assert_eq!(recorded, [tracing::Level::ERROR, tracing::Level::WARN]);
+        assert_eq!(events.field(0, "error").as_deref(), Some("database is gone"));
+        assert_eq!(events.field(1, "error").as_deref(), Some("no such item"));
+        assert_eq!(events.field(1, "status").as_deref(), Some("404"));
+    }
+
+    #[tokio::test]
+    async fn requests_without_an_id_get_one() {
+        let app = init_service(App::new().wrap(RequestTracing::default()).route(
+            "/",
+            web::get().to(|req: actix_web::HttpRequest| async move {
+                let id = req.headers().get("x-request-id").and_then(|v| v.to_str().ok()).unwrap_or_default();
+                HttpResponse::Ok().body(id.to_string())
+            }),
+        ))
+        .await;
+        let generated = actix_web::test::call_and_read_body(&app, TestRequest::get().uri("/").to_request()).await;
+        assert!(Uuid::parse_str(std::str::from_utf8(&generated).unwrap()).is_ok());
+        let request = TestRequest::get().uri("/").insert_header(("x-request-id", "abc")).to_request();
+        assert_eq!(actix_web::test::call_and_read_body(&app, request).await, "abc");
+    }
+}
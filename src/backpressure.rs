@@ -0,0 +1,125 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+use crate::channel::RecordSender;
+use tokio::sync::mpsc::error::TrySendError;
+
+/// Lets producers wait a bounded time for channel capacity, see
+/// [`crate::init::LayerConfig::enqueue_timeout`].
+///
+/// The worker calls [`CapacitySignal::released`] after taking a record
+/// off the channel; producers that found the channel full block in
+/// [`CapacitySignal::send_timeout`] until then or until their deadline. The
+/// worker only touches the mutex while someone is actually waiting.
+#[derive(Default)]
+pub(crate) struct CapacitySignal {
+    waiters: AtomicUsize,
+    lock: Mutex<()>,
+    condvar: Condvar,
+}
+
+impl CapacitySignal {
+    /// Notify waiting producers that a slot has been freed.
+    pub(crate) fn released(&self) {
+        if self.waiters.load(Ordering::Acquire) > 0 {
+            let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+            self.condvar.notify_all();
+        }
+    }
+
+    /// Retry `sender.try_send` until it succeeds, the channel closes or
+    /// `timeout` elapses, sleeping until capacity is released in between.
+    pub(crate) fn send_timeout<T>(
+        &self,
+        sender: &RecordSender<T>,
+        value: T,
+        timeout: Duration,
+    ) -> Result<(), TrySendError<T>> {
+        let mut value = Some(value);
+        let mut result = None;
+        self.wait(timeout, || match sender.try_send(value.take().expect("value present until sent")) {
+            Err(TrySendError::Full(full)) => {
+                value = Some(full);
+                false
+            }
+            other => {
+                result = Some(other);
+                true
+            }
+        });
+        result.unwrap_or_else(|| Err(TrySendError::Full(value.take().expect("value kept on timeout"))))
+    }
+
+    /// Retry [`ByteBudget::try_reserve`] until it succeeds or `timeout`
+    /// elapses, sleeping until capacity is released in between.
+    pub(crate) fn reserve_timeout(&self, budget: &ByteBudget, size: usize, timeout: Duration) -> bool {
+        self.wait(timeout, || budget.try_reserve(size))
+    }
+
+    /// Call `ready` until it returns `true` or `timeout` elapses.
+    fn wait(&self, timeout: Duration, mut ready: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.waiters.fetch_add(1, Ordering::AcqRel);
+        let mut guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let done = loop {
+            // Retrying under the lock closes the race with a release that
+            // happened between the caller's failed attempt and our wait.
+            if ready() {
+                break true;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break false;
+            }
+            guard = self
+                .condvar
+                .wait_timeout(guard, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        };
+        drop(guard);
+        self.waiters.fetch_sub(1, Ordering::AcqRel);
+        done
+    }
+}
+
+/// Approximate size of the records waiting in the channel, see
+/// [`crate::init::LayerConfig::max_buffered_bytes`].
+///
+/// The layer reserves [`LogRecord::estimated_size`] before enqueueing a
+/// record and the worker releases the same amount when it takes the
+/// record off the channel. A record is always admitted into an empty
+/// budget, so one record larger than the limit is not dropped for that
+/// alone.
+///
+/// [`LogRecord::estimated_size`]: crate::record::LogRecord::estimated_size
+#[derive(Debug)]
+pub(crate) struct ByteBudget {
+    used: AtomicUsize,
+    max: usize,
+}
+
+impl ByteBudget {
+    pub(crate) fn new(max: usize) -> Self {
+        Self { used: AtomicUsize::new(0), max }
+    }
+
+    /// Account `size` more bytes unless that would exceed the limit.
+    pub(crate) fn try_reserve(&self, size: usize) -> bool {
+        self.used
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+                (used == 0 || used.saturating_add(size) <= self.max).then(|| used.saturating_add(size))
+            })
+            .is_ok()
+    }
+
+    /// Bytes currently reserved.
+    pub(crate) fn used(&self) -> usize {
+        self.used.load(Ordering::Acquire)
+    }
+
+    /// Give back bytes reserved with [`ByteBudget::try_reserve`].
+    pub(crate) fn release(&self, size: usize) {
+        self.used.fetch_sub(size, Ordering::AcqRel);
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+/// Bounds for adaptive batching, see [`LayerConfig::adaptive_batching`].
+///
+/// After every batch the worker compares how long the sink took to send
+/// it with the current flush interval:
+/// - slower than the flush interval (the sink cannot keep up with the
+///   current cadence): batch size and flush interval are doubled, so fewer,
+///   larger requests are made;
+/// - faster than a quarter of the flush interval: both are halved, so
+///   records reach the backend sooner;
+/// - otherwise nothing changes.
+///
+/// Both values always stay within the configured bounds. The starting
+/// point is [`LayerConfig::batch_size`] and [`LayerConfig::flush_interval`],
+/// clamped to the bounds.
+///
+/// **Fields**
+/// - `min_batch_size` / `max_batch_size`: range for the batch size.
+/// - `min_flush_interval` / `max_flush_interval`: range for the flush
+///   interval.
+///
+/// [`LayerConfig::adaptive_batching`]: crate::init::LayerConfig::adaptive_batching
+/// [`LayerConfig::batch_size`]: crate::init::LayerConfig::batch_size
+/// [`LayerConfig::flush_interval`]: crate::init::LayerConfig::flush_interval
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveBatching {
+    pub min_batch_size: usize,
+    pub max_batch_size: usize,
+    pub min_flush_interval: Duration,
+    pub max_flush_interval: Duration,
+}
+
+impl Default for AdaptiveBatching {
+    fn default() -> Self {
+        Self {
+            min_batch_size: 10,
+            max_batch_size: 1_000,
+            min_flush_interval: Duration::from_millis(100),
+            max_flush_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+impl AdaptiveBatching {
+    /// Clamp `batch_size` and `flush_interval` to the configured bounds.
+    pub(crate) fn clamp(&self, batch_size: usize, flush_interval: Duration) -> (usize, Duration) {
+        let min_batch_size = self.min_batch_size.max(1);
+        let min_flush_interval = self.min_flush_interval.max(Duration::from_millis(1));
+        (
+            batch_size.clamp(min_batch_size, self.max_batch_size.max(min_batch_size)),
+            flush_interval.clamp(min_flush_interval, self.max_flush_interval.max(min_flush_interval)),
+        )
+    }
+
+    /// New batch size and flush interval after a batch took `latency` to
+    /// send.
+    pub(crate) fn adjust(
+        &self,
+        latency: Duration,
+        batch_size: usize,
+        flush_interval: Duration,
+    ) -> (usize, Duration) {
+        if latency > flush_interval {
+            self.clamp(batch_size.saturating_mul(2), flush_interval.saturating_mul(2))
+        } else if latency < flush_interval / 4 {
+            self.clamp(batch_size / 2, flush_interval / 2)
+        } else {
+            (batch_size, flush_interval)
+        }
+    }
+}
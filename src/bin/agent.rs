@@ -0,0 +1,268 @@
+//! `log-recorder-agent`: forwards log records written by
+//! `socket::SocketSink` in many processes to one backend.
+//!
+//! ```text
+//! log-recorder-agent --listen unix:///run/log-recorder.sock \
+//!     --dsn clickhouse://127.0.0.1:8123/default/logs?batch_size=500 \
+//!     [--listen tcp://127.0.0.1:5170] [--spool-dir /var/lib/log-recorder]
+//! ```
+//!
+//! Each connection carries newline-delimited JSON records. They are
+//! batched (`batch_size`, `flush_ms` of the DSN) and, with `--spool-dir`,
+//! kept in a `durable::DurableQueueSink` log until the backend accepts
+//! them. `--dsn` defaults to `LOG_SINK_DSN`. With feature `encryption`,
+//! `--spool-key-file` encrypts the spooled records with an
+//! `encryption::SpillKey` read from that file.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, BufReader};
+use tracing_log_sink::backend;
+use tracing_log_sink::buffered::{BufferConfig, BufferedSink};
+use tracing_log_sink::diagnostics::{DiagnosticsConfig, DiagnosticsOutput};
+use tracing_log_sink::durable::{DurableQueueConfig, DurableQueueSink};
+#[cfg(feature = "encryption")]
+use tracing_log_sink::encryption::SpillKey;
+use tracing_log_sink::env::LOG_SINK_DSN_ENV;
+use tracing_log_sink::record::LogRecord;
+use tracing_log_sink::sink::LogSink;
+use tracing_log_sink::socket::SocketAddress;
+
+const USAGE: &str = "usage: log-recorder-agent --listen <tcp://host:port|unix:///path>... [--dsn <dsn>] [--spool-dir <dir>] [--spool-key-file <path>]";
+
+/// Longest accepted record line; longer lines are skipped with a warning
+/// instead of growing the read buffer without bound.
+const MAX_LINE_BYTES: u64 = 1024 * 1024;
+
+/// Time given to the backend to take the last batches on exit.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Args {
+    listen: Vec<SocketAddress>,
+    dsn: String,
+    spool_dir: Option<PathBuf>,
+    #[cfg(feature = "encryption")]
+    spool_key: Option<SpillKey>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut listen = Vec::new();
+    let mut dsn = None;
+    let mut spool_dir = None;
+    #[cfg(feature = "encryption")]
+    let mut spool_key = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--listen" => listen.push(value()?.parse::<SocketAddress>().map_err(|e| e.to_string())?),
+            "--dsn" => dsn = Some(value()?),
+            "--spool-dir" => spool_dir = Some(PathBuf::from(value()?)),
+            #[cfg(feature = "encryption")]
+            "--spool-key-file" => spool_key = Some(SpillKey::from_file(value()?).map_err(|e| e.to_string())?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    if listen.is_empty() {
+        return Err(format!("no --listen address\n{}", USAGE));
+    }
+    let dsn = match dsn.or_else(|| std::env::var(LOG_SINK_DSN_ENV).ok()) {
+        Some(dsn) => dsn,
+        None => return Err(format!("neither --dsn nor {} is set\n{}", LOG_SINK_DSN_ENV, USAGE)),
+    };
+    Ok(Args {
+        listen,
+        dsn,
+        spool_dir,
+        #[cfg(feature = "encryption")]
+        spool_key,
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(args).await {
+        eprintln!("log-recorder-agent: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let config = backend::parse_dsn(&args.dsn)?;
+    let backend = backend::make_sink_from_config_async(&config).await?;
+    let buffer = BufferConfig::default();
+    let batch_size = config.options.batch_size.unwrap_or(buffer.max_records);
+    let sink: Arc<dyn LogSink> = match args.spool_dir {
+        Some(dir) => Arc::new(DurableQueueSink::open(
+            backend,
+            DurableQueueConfig {
+                relay_batch: batch_size,
+                diagnostics: DiagnosticsConfig {
+                    output: DiagnosticsOutput::Tracing,
+                    ..DiagnosticsConfig::default()
+                },
+                #[cfg(feature = "encryption")]
+                encryption: args.spool_key,
+                ..DurableQueueConfig::new(dir)
+            },
+        )?),
+        None => Arc::new(BufferedSink::new(
+            backend,
+            BufferConfig {
+                max_records: batch_size,
+                flush_interval: config.options.flush_interval.unwrap_or(buffer.flush_interval),
+                ..buffer
+            },
+        )),
+    };
+
+    for address in &args.listen {
+        listen(address, sink.clone()).await?;
+        tracing::info!(%address, "listening for log records");
+    }
+
+    shutdown_signal().await;
+    tracing::info!("shutting down");
+    match tokio::time::timeout(SHUTDOWN_TIMEOUT, sink.shutdown()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => tracing::warn!(error = %e, "sink shutdown failed"),
+        Err(_) => tracing::warn!("sink shutdown timed out"),
+    }
+    #[cfg(unix)]
+    for address in &args.listen {
+        if let SocketAddress::Unix(path) = address {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+    Ok(())
+}
+
+/// Bind `address` and serve its connections on background tasks.
+async fn listen(address: &SocketAddress, sink: Arc<dyn LogSink>) -> std::io::Result<()> {
+    match address {
+        SocketAddress::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr.as_str()).await?;
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(serve(stream, sink.clone()));
+                        }
+                        Err(e) => tracing::warn!(error = %e, "accept failed"),
+                    }
+                }
+            });
+        }
+        #[cfg(unix)]
+        SocketAddress::Unix(path) => {
+            // A socket file left by a previous run blocks the bind.
+            let _ = std::fs::remove_file(path);
+            let listener = tokio::net::UnixListener::bind(path)?;
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, _)) => {
+                            tokio::spawn(serve(stream, sink.clone()));
+                        }
+                        Err(e) => tracing::warn!(error = %e, "accept failed"),
+                    }
+                }
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Forward the records of one connection until it closes.
+async fn serve<R: AsyncRead + Unpin>(stream: R, sink: Arc<dyn LogSink>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match (&mut reader).take(MAX_LINE_BYTES + 1).read_until(b'\n', &mut line).await {
+            Ok(0) => return,
+            Ok(read) if read as u64 > MAX_LINE_BYTES && !line.ends_with(b"\n") => {
+                tracing::warn!(limit = MAX_LINE_BYTES, "skipping a line longer than the limit");
+                match skip_line(&mut reader).await {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "connection failed");
+                        return;
+                    }
+                }
+            }
+            Ok(_) if line.trim_ascii().is_empty() => {}
+            Ok(_) => match serde_json::from_slice::<LogRecord>(&line) {
+                Ok(record) => forward(&*sink, &record).await,
+                Err(e) => tracing::warn!(error = %e, "skipping a line that is not a log record"),
+            },
+            Err(e) => {
+                tracing::warn!(error = %e, "connection failed");
+                return;
+            }
+        }
+    }
+}
+
+/// Discard the rest of the current line without buffering it.
+///
+/// **Returns**
+/// - `false` if the connection closed before the end of the line.
+async fn skip_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf().await?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        if let Some(end) = buf.iter().position(|&b| b == b'\n') {
+            reader.consume(end + 1);
+            return Ok(true);
+        }
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Hand `record` to the sink, waiting while it is full; reading from the
+/// connection pauses meanwhile, which slows the sender down.
+async fn forward(sink: &dyn LogSink, record: &LogRecord) {
+    let mut backoff = Duration::from_millis(100);
+    loop {
+        match sink.send(record).await {
+            Ok(()) => return,
+            Err(e) if e.is_retryable() => {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(10));
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "record rejected");
+                return;
+            }
+        }
+    }
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("install SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -0,0 +1,346 @@
+//! `logrec`: print records shipped to ClickHouse or Postgres.
+//!
+//! ```text
+//! logrec --dsn clickhouse://127.0.0.1:8123/default/logs --service auth --level error --since 1h
+//! logrec -f --target my_app::db        # follow new records, like `tail -f`
+//! logrec replay backlog.jsonl postgres://user@db/logs?table=logs
+//! logrec schema clickhouse --table default.logs --column user_id=user.id:string
+//! ```
+//!
+//! `--dsn` and the DSN of `replay` default to `LOG_SINK_DSN`; `--since`
+//! takes `30s`, `15m`, `1h`, `2d` or an RFC 3339 time. `replay` sends the
+//! JSON Lines records of a file (see `replay::replay_file`) to the backend
+//! of the DSN, which may be any backend compiled in; with feature
+//! `encryption`, `--key-file` decrypts files written with an
+//! `encryption::SpillKey`. `schema` prints the
+//! `CREATE TABLE` statement (ClickHouse, Postgres, MySQL) or index
+//! template (OpenSearch) of `schema::ddl`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+use tracing_log_sink::backend;
+use tracing_log_sink::env::LOG_SINK_DSN_ENV;
+use tracing_log_sink::query::{LogQuery, LogReader};
+use tracing_log_sink::record::{LogLevel, LogRecord};
+#[cfg(feature = "encryption")]
+use tracing_log_sink::encryption::SpillKey;
+use tracing_log_sink::replay::{replay_file, ReplayConfig, ReplayError, ReplayProgress};
+use tracing_log_sink::schema::{self, SchemaBackend, SchemaOptions};
+use tracing_log_sink::timestamp::{Timestamp, TimestampFormat};
+
+const USAGE: &str = "usage: logrec [--dsn <dsn>] [--service <name>] [--level <level>] [--since <30m|rfc3339>] \
+[--target <prefix>] [-n <limit>] [-f] [--json]
+       logrec replay [--batch-size <n>] [--skip <lines>] [--key-file <path>] <file> [<dsn>]
+       logrec schema [--table <name>] [--schema-version <n>] [--per-service] \
+[--timestamp-format <rfc3339|rfc3339-millis|epoch-millis|epoch-micros|datetime64-millis>] \
+[--column <column=field:string|int|float|bool>]... <clickhouse|postgres|mysql|opensearch>";
+
+/// Records fetched per poll while following.
+const FOLLOW_LIMIT: usize = 10_000;
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+struct Args {
+    dsn: String,
+    query: LogQuery,
+    follow: bool,
+    json: bool,
+}
+
+struct ReplayArgs {
+    file: PathBuf,
+    dsn: String,
+    config: ReplayConfig,
+    skip: u64,
+}
+
+struct SchemaArgs {
+    backend: SchemaBackend,
+    options: SchemaOptions,
+}
+
+enum Command {
+    Query(Args),
+    Replay(ReplayArgs),
+    Schema(SchemaArgs),
+}
+
+fn parse_args() -> Result<Command, String> {
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("replay") {
+        args.next();
+        return parse_replay_args(args).map(Command::Replay);
+    }
+    if args.peek().map(String::as_str) == Some("schema") {
+        args.next();
+        return parse_schema_args(args).map(Command::Schema);
+    }
+    let mut dsn = None;
+    let mut query = LogQuery::default();
+    let mut follow = false;
+    let mut json = false;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--dsn" => dsn = Some(value()?),
+            "--service" => query.service = Some(value()?),
+            "--level" => query.min_level = Some(value()?.parse::<LogLevel>().map_err(|e| e.to_string())?),
+            "--since" => query.since = Some(parse_since(&value()?)?),
+            "--target" => query.target = Some(value()?),
+            "-n" | "--limit" => query.limit = value()?.parse().map_err(|_| "invalid --limit".to_string())?,
+            "-f" | "--follow" => follow = true,
+            "--json" => json = true,
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    let dsn = match dsn.or_else(|| std::env::var(LOG_SINK_DSN_ENV).ok()) {
+        Some(dsn) => dsn,
+        None => return Err(format!("neither --dsn nor {} is set\n{}", LOG_SINK_DSN_ENV, USAGE)),
+    };
+    Ok(Command::Query(Args { dsn, query, follow, json }))
+}
+
+fn parse_replay_args(mut args: impl Iterator<Item = String>) -> Result<ReplayArgs, String> {
+    let mut config = ReplayConfig::default();
+    let mut skip = 0;
+    let mut positional = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--batch-size" => config.batch_size = value()?.parse().map_err(|_| "invalid --batch-size".to_string())?,
+            "--skip" => skip = value()?.parse().map_err(|_| "invalid --skip".to_string())?,
+            #[cfg(feature = "encryption")]
+            "--key-file" => {
+                let key = SpillKey::from_file(value()?).map_err(|e| e.to_string())?;
+                config.encryption = Some(key);
+            }
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+            _ => positional.push(arg),
+        }
+    }
+    let mut positional = positional.into_iter();
+    let file = match positional.next() {
+        Some(file) => PathBuf::from(file),
+        None => return Err(format!("no file to replay\n{}", USAGE)),
+    };
+    let dsn = match positional.next().or_else(|| std::env::var(LOG_SINK_DSN_ENV).ok()) {
+        Some(dsn) => dsn,
+        None => return Err(format!("neither a DSN nor {} is given\n{}", LOG_SINK_DSN_ENV, USAGE)),
+    };
+    if positional.next().is_some() {
+        return Err(format!("too many arguments\n{}", USAGE));
+    }
+    Ok(ReplayArgs { file, dsn, config, skip })
+}
+
+fn parse_schema_args(mut args: impl Iterator<Item = String>) -> Result<SchemaArgs, String> {
+    let mut options = SchemaOptions::default();
+    let mut backend = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--table" => options.table = value()?,
+            "--schema-version" => {
+                options.version = value()?.parse().map_err(|_| "invalid --schema-version".to_string())?
+            }
+            "--per-service" => options.shared = false,
+            "--timestamp-format" => options.timestamp_format = parse_timestamp_format(&value()?)?,
+            "--column" => options.columns.push(value()?.parse().map_err(|e: schema::SchemaError| e.to_string())?),
+            "--help" | "-h" => return Err(USAGE.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+            _ if backend.is_none() => backend = Some(arg.parse().map_err(|e: schema::SchemaError| e.to_string())?),
+            _ => return Err(format!("too many arguments\n{}", USAGE)),
+        }
+    }
+    match backend {
+        Some(backend) => Ok(SchemaArgs { backend, options }),
+        None => Err(format!("no backend given\n{}", USAGE)),
+    }
+}
+
+fn parse_timestamp_format(value: &str) -> Result<TimestampFormat, String> {
+    Ok(match value {
+        "rfc3339" => TimestampFormat::Rfc3339,
+        "rfc3339-millis" => TimestampFormat::Rfc3339Millis,
+        "epoch-millis" => TimestampFormat::EpochMillis,
+        "epoch-micros" => TimestampFormat::EpochMicros,
+        "datetime64-millis" => TimestampFormat::DateTime64Millis,
+        _ => return Err(format!("invalid --timestamp-format {:?}", value)),
+    })
+}
+
+/// `30s`, `15m`, `1h`, `2d` ago, or an RFC 3339 time.
+fn parse_since(value: &str) -> Result<Timestamp, String> {
+    let unit = match value.chars().last() {
+        Some('s') => Some(1),
+        Some('m') => Some(60),
+        Some('h') => Some(60 * 60),
+        Some('d') => Some(24 * 60 * 60),
+        _ => None,
+    };
+    if let Some(amount) = unit.and_then(|unit| Some(value[..value.len() - 1].parse::<u64>().ok()? * unit)) {
+        let since = SystemTime::now()
+            .checked_sub(Duration::from_secs(amount))
+            .ok_or_else(|| format!("--since {} is too far back", value))?;
+        return Ok(Timestamp::from(since));
+    }
+    value.parse().map_err(|_| format!("invalid --since {:?}, expected e.g. 30m or an RFC 3339 time", value))
+}
+
+#[tokio::main]
+async fn main() {
+    let command = match parse_args() {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+    let result = match command {
+        Command::Query(args) => run(args).await,
+        Command::Replay(args) => replay(args).await,
+        Command::Schema(args) => schema::ddl(args.backend, &args.options)
+            .map(|ddl| print!("{}", ddl))
+            .map_err(Into::into),
+    };
+    if let Err(e) = result {
+        eprintln!("logrec: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let reader = LogReader::connect(&args.dsn).await?;
+    let mut follower = Follower::default();
+    follower.print(&reader.query(&args.query).await?, args.json);
+    if !args.follow {
+        return Ok(());
+    }
+    let poll = |since| LogQuery { since, limit: FOLLOW_LIMIT, ..args.query.clone() };
+    // Records at the last timestamp cut off by `--limit` are not new.
+    if let Some(last) = follower.last {
+        follower.skip(&reader.query(&poll(Some(last))).await?);
+    }
+    loop {
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+        match reader.query(&poll(follower.last.or(args.query.since))).await {
+            Ok(records) => follower.print(&records, args.json),
+            Err(e) => eprintln!("logrec: {}", e),
+        }
+    }
+}
+
+async fn replay(args: ReplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let config = backend::parse_dsn(&args.dsn)?;
+    let sink = backend::make_sink_from_config_async(&config).await?;
+    let result = replay_file(&args.file, &*sink, &args.config, args.skip, report).await;
+    eprintln!();
+    // Sinks that buffer deliver the rest now.
+    let closed = sink.shutdown().await;
+    let progress = match result {
+        Ok(progress) => progress,
+        Err(e) => {
+            if let ReplayError::Sink { progress, .. } = &e {
+                eprintln!("resume with: logrec replay --skip {} ...", progress.lines);
+            }
+            return Err(e.into());
+        }
+    };
+    closed?;
+    eprintln!(
+        "replayed {} records ({} rejected, {} unreadable lines)",
+        progress.sent, progress.rejected, progress.invalid
+    );
+    Ok(())
+}
+
+/// Progress line on stderr, rewritten after every batch.
+fn report(progress: &ReplayProgress) {
+    let percent = match progress.total_bytes {
+        0 => 100,
+        total => progress.bytes_read * 100 / total,
+    };
+    eprint!(
+        "\r{}%, {} sent, {} rejected, {} unreadable",
+        percent, progress.sent, progress.rejected, progress.invalid
+    );
+    let _ = std::io::stderr().flush();
+}
+
+/// Newest timestamp printed, and how often each record was printed with
+/// it, so polls starting at that timestamp do not print them again.
+#[derive(Default)]
+struct Follower {
+    last: Option<Timestamp>,
+    printed: HashMap<String, usize>,
+}
+
+impl Follower {
+    /// Count the records at the last timestamp as printed.
+    fn skip(&mut self, records: &[LogRecord]) {
+        self.printed.clear();
+        for record in records.iter().filter(|record| self.last == Some(record.timestamp)) {
+            *self.printed.entry(key(record)).or_default() += 1;
+        }
+    }
+
+    fn print(&mut self, records: &[LogRecord], json: bool) {
+        let mut earlier = std::mem::take(&mut self.printed);
+        for record in records {
+            let key = key(record);
+            if self.last == Some(record.timestamp) {
+                if let Some(count) = earlier.get_mut(&key).filter(|count| **count > 0) {
+                    *count -= 1;
+                    *self.printed.entry(key).or_default() += 1;
+                    continue;
+                }
+            } else {
+                self.last = Some(record.timestamp);
+                earlier.clear();
+                self.printed.clear();
+            }
+            *self.printed.entry(key).or_default() += 1;
+            print_record(record, json);
+        }
+        // Still printed at `last`, whether or not this poll returned them.
+        for (key, count) in earlier {
+            *self.printed.entry(key).or_default() += count;
+        }
+    }
+}
+
+/// Identity of a record when deduplicating polls.
+fn key(record: &LogRecord) -> String {
+    match record.event_id {
+        Some(id) => id.to_string(),
+        None => serde_json::to_string(record).unwrap_or_default(),
+    }
+}
+
+fn print_record(record: &LogRecord, json: bool) {
+    if json {
+        match serde_json::to_string(record) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("logrec: {}", e),
+        }
+        return;
+    }
+    let mut line = format!("{} {:<5}", record.timestamp.to_rfc3339(), record.level.as_str());
+    if let Some(service) = &record.service_name {
+        line.push_str(&format!(" [{}]", service));
+    }
+    line.push_str(&format!(" {}:", record.target));
+    if let Some(message) = record.message.as_deref().or(record.error_message.as_deref()) {
+        line.push_str(&format!(" {}", message));
+    }
+    for (key, value) in &record.fields {
+        match value {
+            serde_json::Value::String(s) => line.push_str(&format!(" {}={}", key, s)),
+            other => line.push_str(&format!(" {}={}", key, other)),
+        }
+    }
+    println!("{}", line);
+}
@@ -0,0 +1,221 @@
+//! Batching around sinks that only implement single-record sends.
+//!
+//! [`BufferedSink`] keeps records in memory and hands them to
+//! [`LogSink::send_batch`] once enough have accumulated or enough time has
+//! passed, independently of the layer's own `batch_size` and
+//! `flush_interval`.
+
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use crate::worker::WorkerScope;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+
+/// Flush thresholds of a [`BufferedSink`].
+///
+/// **Fields**
+/// - `max_records`: flush once this many records are buffered.
+/// - `max_bytes`: flush once the buffered records' estimated size reaches
+///   this; `None` disables the check.
+/// - `flush_interval`: flush buffered records at least this often.
+/// - `max_buffered`: records kept while batches keep failing; beyond it
+///   new sends fail with [`SinkError::Transient`], so the layer's worker
+///   holds on to them and retries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferConfig {
+    pub max_records: usize,
+    pub max_bytes: Option<usize>,
+    pub flush_interval: Duration,
+    pub max_buffered: usize,
+}
+
+impl Default for BufferConfig {
+    fn default() -> Self {
+        Self {
+            max_records: 500,
+            max_bytes: Some(4 * 1024 * 1024),
+            flush_interval: Duration::from_secs(1),
+            max_buffered: 10_000,
+        }
+    }
+}
+
+/// [`LogSink`] decorator that accumulates records and writes them with
+/// `S::send_batch`, see [`BufferConfig`].
+///
+/// `send` returns as soon as the record is buffered; the record is then
+/// the sink's responsibility. Batches failing with a retryable error are
+/// put back and sent again with the next flush, so a record may arrive
+/// twice with the same `event_id`; batches rejected permanently are
+/// dropped. Records still buffered when the process exits without
+/// [`LogSink::shutdown`] are lost.
+///
+/// The timed flush runs on a task spawned on the Tokio runtime of the
+/// first send and stops when the sink is dropped.
+pub struct BufferedSink<S> {
+    shared: Arc<Shared<S>>,
+    timer: OnceLock<tokio::task::JoinHandle<()>>,
+}
+
+struct Shared<S> {
+    inner: S,
+    config: BufferConfig,
+    buffer: Mutex<Buffer>,
+    /// Held while a batch is being sent, so batches go out in order.
+    sending: tokio::sync::Mutex<()>,
+}
+
+#[derive(Default)]
+struct Buffer {
+    records: VecDeque<LogRecord>,
+    bytes: usize,
+}
+
+impl<S: LogSink + 'static> BufferedSink<S> {
+    /// Wrap `inner`, flushing as `config` says.
+    pub fn new(inner: S, config: BufferConfig) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                inner,
+                config,
+                buffer: Mutex::new(Buffer::default()),
+                sending: tokio::sync::Mutex::new(()),
+            }),
+            timer: OnceLock::new(),
+        }
+    }
+
+    /// The wrapped sink.
+    pub fn inner(&self) -> &S {
+        &self.shared.inner
+    }
+
+    /// Number of records waiting for the next batch.
+    pub fn buffered(&self) -> usize {
+        self.shared.lock().records.len()
+    }
+
+    fn start_timer(&self) {
+        self.timer.get_or_init(|| {
+            let shared = Arc::downgrade(&self.shared);
+            let interval = self.shared.config.flush_interval;
+            tokio::spawn(WorkerScope::new(flush_periodically(shared, interval)))
+        });
+    }
+}
+
+impl<S> Drop for BufferedSink<S> {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.get() {
+            timer.abort();
+        }
+    }
+}
+
+async fn flush_periodically<S: LogSink>(shared: Weak<Shared<S>>, interval: Duration) {
+    let mut ticks = tokio::time::interval(interval);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    ticks.tick().await;
+    loop {
+        ticks.tick().await;
+        let Some(shared) = shared.upgrade() else { return };
+        if let Err(e) = shared.send_buffered().await {
+            tracing::warn!(target: DIAGNOSTICS_TARGET, error = %e, "buffered log sink batch failed");
+        }
+    }
+}
+
+impl<S: LogSink> Shared<S> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_full(&self, buffer: &Buffer) -> bool {
+        buffer.records.len() >= self.config.max_records
+            || self.config.max_bytes.is_some_and(|max| buffer.bytes >= max)
+    }
+
+    /// Send everything buffered as one batch, putting it back in front of
+    /// newer records if it failed with a retryable error.
+    async fn send_buffered(&self) -> Result<(), SinkError> {
+        let _sending = self.sending.lock().await;
+        let (records, bytes) = {
+            let mut buffer = self.lock();
+            let bytes = std::mem::take(&mut buffer.bytes);
+            (Vec::from(std::mem::take(&mut buffer.records)), bytes)
+        };
+        if records.is_empty() {
+            return Ok(());
+        }
+        let result = self.inner.send_batch(&records).await;
+        if matches!(&result, Err(e) if e.is_retryable()) {
+            let mut buffer = self.lock();
+            buffer.bytes += bytes;
+            for record in records.into_iter().rev() {
+                buffer.records.push_front(record);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl<S: LogSink + 'static> LogSink for BufferedSink<S> {
+    /// Buffers `record`, sending the batch first if the buffer is full.
+    ///
+    /// **Returns**
+    /// - `Err(SinkError::Transient)` only if the buffer is at
+    ///   `max_buffered` and the batch could not be sent.
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.start_timer();
+        if self.shared.lock().records.len() >= self.shared.config.max_buffered {
+            // Failures of this batch are the next send's concern; this
+            // record fails only if there is still no room.
+            let _ = self.shared.send_buffered().await;
+            if self.shared.lock().records.len() >= self.shared.config.max_buffered {
+                return Err(SinkError::transient("log sink buffer is full"));
+            }
+        }
+        let full = {
+            let mut buffer = self.shared.lock();
+            buffer.bytes += record.estimated_size();
+            buffer.records.push_back(record.clone());
+            self.shared.is_full(&buffer)
+        };
+        if full {
+            if let Err(e) = self.shared.send_buffered().await {
+                tracing::warn!(target: DIAGNOSTICS_TARGET, error = %e, "buffered log sink batch failed");
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the buffered records, then flushes the wrapped sink.
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.shared.send_buffered().await?;
+        self.shared.inner.flush().await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.shared.inner.health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.shared.inner.schema_drift().await
+    }
+
+    /// Sends the buffered records, then shuts the wrapped sink down; the
+    /// sink is shut down even if the last batch failed.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        if let Some(timer) = self.timer.get() {
+            timer.abort();
+        }
+        let sent = self.shared.send_buffered().await;
+        let shut_down = self.shared.inner.shutdown().await;
+        sent.and(shut_down)
+    }
+}
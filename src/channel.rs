@@ -0,0 +1,73 @@
+use crate::record::LogRecord;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc::{self, error::TryRecvError, error::TrySendError};
+
+/// Sending half of one channel shard, bounded or unbounded as
+/// [`crate::init::ChannelKind`] says.
+#[derive(Debug)]
+pub(crate) enum RecordSender<T = LogRecord> {
+    Bounded(mpsc::Sender<T>),
+    Unbounded(mpsc::UnboundedSender<T>),
+}
+
+/// Receiving half of one channel shard.
+#[derive(Debug)]
+pub(crate) enum RecordReceiver {
+    Bounded(mpsc::Receiver<LogRecord>),
+    Unbounded(mpsc::UnboundedReceiver<LogRecord>),
+}
+
+/// A bounded channel of `buffer` records, or an unbounded one for `None`.
+pub(crate) fn channel(buffer: Option<usize>) -> (RecordSender, RecordReceiver) {
+    match buffer {
+        Some(buffer) => {
+            let (tx, rx) = mpsc::channel(buffer);
+            (RecordSender::Bounded(tx), RecordReceiver::Bounded(rx))
+        }
+        None => {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (RecordSender::Unbounded(tx), RecordReceiver::Unbounded(rx))
+        }
+    }
+}
+
+impl<T> RecordSender<T> {
+    /// Enqueue without waiting; an unbounded channel is never full.
+    pub(crate) fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        match self {
+            RecordSender::Bounded(tx) => tx.try_send(value),
+            RecordSender::Unbounded(tx) => tx.send(value).map_err(|e| TrySendError::Closed(e.0)),
+        }
+    }
+}
+
+impl RecordReceiver {
+    pub(crate) fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<LogRecord>> {
+        match self {
+            RecordReceiver::Bounded(rx) => rx.poll_recv(cx),
+            RecordReceiver::Unbounded(rx) => rx.poll_recv(cx),
+        }
+    }
+
+    pub(crate) fn try_recv(&mut self) -> Result<LogRecord, TryRecvError> {
+        match self {
+            RecordReceiver::Bounded(rx) => rx.try_recv(),
+            RecordReceiver::Unbounded(rx) => rx.try_recv(),
+        }
+    }
+
+    /// Records currently queued.
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            RecordReceiver::Bounded(rx) => rx.len(),
+            RecordReceiver::Unbounded(rx) => rx.len(),
+        }
+    }
+
+    pub(crate) fn close(&mut self) {
+        match self {
+            RecordReceiver::Bounded(rx) => rx.close(),
+            RecordReceiver::Unbounded(rx) => rx.close(),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Fault injection for checking delivery guarantees (feature `chaos`).
+//!
+//! [`ChaosSink`] wraps a real sink and makes a share of sends fail,
+//! including the ambiguous case of a send that reached the backend but
+//! whose acknowledgement was lost, like a request that timed out after
+//! the server committed it. Run the layer against it to check that
+//! nothing is lost and that duplicates carry the same `event_id`; see
+//! the `chaos_delivery` example.
+
+use crate::encoding::SerializedBatch;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Failure rates and latency of a [`ChaosSink`].
+///
+/// Rates are probabilities in `0.0..=1.0`, checked in field order for
+/// every send.
+///
+/// **Fields**
+/// - `failure_rate`: fail with [`SinkError::Transient`] without
+///   forwarding the record.
+/// - `ambiguous_rate`: forward the record, then fail with
+///   [`SinkError::Transient`] anyway, as if the acknowledgement timed out.
+/// - `reject_rate`: fail with [`SinkError::Permanent`] without forwarding.
+/// - `max_latency`: each send first sleeps a random time up to this.
+/// - `seed`: seed of the pseudo-random sequence, for reproducible runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosConfig {
+    pub failure_rate: f64,
+    pub ambiguous_rate: f64,
+    pub reject_rate: f64,
+    pub max_latency: Duration,
+    pub seed: u64,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate: 0.1,
+            ambiguous_rate: 0.05,
+            reject_rate: 0.0,
+            max_latency: Duration::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+/// What a [`ChaosSink`] did so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChaosStats {
+    /// Calls to the sink.
+    pub attempts: u64,
+    /// Sends failed without forwarding.
+    pub failed: u64,
+    /// Sends forwarded but reported as failed.
+    pub ambiguous: u64,
+    /// Sends rejected as permanent failures.
+    pub rejected: u64,
+    /// Sends forwarded and acknowledged.
+    pub delivered: u64,
+}
+
+/// [`LogSink`] decorator that injects failures, see [`ChaosConfig`].
+///
+/// Faults are drawn per record: batches go through the default
+/// [`LogSink::send_batch`], so a rejection drops only the rejected record,
+/// as that method's contract requires.
+pub struct ChaosSink {
+    inner: Arc<dyn LogSink>,
+    config: ChaosConfig,
+    state: AtomicU64,
+    attempts: AtomicU64,
+    failed: AtomicU64,
+    ambiguous: AtomicU64,
+    rejected: AtomicU64,
+    delivered: AtomicU64,
+}
+
+impl ChaosSink {
+    /// Wrap `inner`, failing sends as `config` asks.
+    pub fn new(inner: Arc<dyn LogSink>, config: ChaosConfig) -> Self {
+        Self {
+            inner,
+            state: AtomicU64::new(config.seed),
+            config,
+            attempts: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            ambiguous: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+        }
+    }
+
+    /// Snapshot of the counters.
+    pub fn stats(&self) -> ChaosStats {
+        ChaosStats {
+            attempts: self.attempts.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            ambiguous: self.ambiguous.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Uniform value in `0.0..1.0` (SplitMix64 over a shared counter).
+    fn next_unit(&self) -> f64 {
+        let mut z = self.state.fetch_add(0x9E37_79B9_7F4A_7C15, Ordering::Relaxed);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Run one send through the configured faults; `forward` performs
+    /// the actual send to the inner sink.
+    async fn inject<F>(&self, forward: F) -> Result<(), SinkError>
+    where
+        F: std::future::Future<Output = Result<(), SinkError>>,
+    {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        if !self.config.max_latency.is_zero() {
+            tokio::time::sleep(self.config.max_latency.mul_f64(self.next_unit())).await;
+        }
+
+        let roll = self.next_unit();
+        let failed = self.config.failure_rate;
+        let ambiguous = failed + self.config.ambiguous_rate;
+        let rejected = ambiguous + self.config.reject_rate;
+        if roll < failed {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            return Err(SinkError::transient("chaos: injected failure"));
+        }
+        if roll >= ambiguous && roll < rejected {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(SinkError::permanent("chaos: injected rejection"));
+        }
+        forward.await?;
+        if roll < ambiguous {
+            self.ambiguous.fetch_add(1, Ordering::Relaxed);
+            return Err(SinkError::transient("chaos: acknowledgement lost"));
+        }
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for ChaosSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.inject(self.inner.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.inject(self.inner.send_serialized(batch, index)).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.inner.flush().await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.inner.shutdown().await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.inner.health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.inner.schema_drift().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{DiagnosticsConfig, DiagnosticsOutput};
+    use crate::init::{with_scoped_recorder, LayerConfig};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    /// `event_id`s received per `seq` field.
+    type Received = HashMap<u64, HashSet<Option<Uuid>>>;
+
+    /// What arrived, and the number of deliveries.
+    #[derive(Default)]
+    struct Collect(Mutex<(Received, u64)>);
+
+    #[async_trait]
+    impl LogSink for Collect {
+        async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+            let seq = record.fields.get("seq").and_then(|v| v.as_u64()).expect("seq field");
+            let mut received = self.0.lock().unwrap();
+            received.0.entry(seq).or_default().insert(record.event_id);
+            received.1 += 1;
+            Ok(())
+        }
+    }
+
+    /// Log `n` events through a [`ChaosSink`] with `config`.
+    async fn run(config: ChaosConfig, n: u64) -> (Received, u64, ChaosStats) {
+        let collect = Arc::new(Collect::default());
+        let chaos = Arc::new(ChaosSink::new(collect.clone(), config));
+        let layer_config = LayerConfig {
+            batch_size: 10,
+            max_in_flight: 1,
+            flush_interval: Duration::from_millis(10),
+            diagnostics: DiagnosticsConfig { output: DiagnosticsOutput::Silent, ..DiagnosticsConfig::default() },
+            ..LayerConfig::default()
+        };
+        with_scoped_recorder(chaos.clone(), layer_config, |_| async {
+            for seq in 0..n {
+                tracing::error!(target: "app", seq, "chaos");
+            }
+        })
+        .await;
+        let (received, deliveries) = std::mem::take(&mut *collect.0.lock().unwrap());
+        (received, deliveries, chaos.stats())
+    }
+
+    #[tokio::test]
+    async fn failed_sends_are_retried_until_delivered() {
+        let config = ChaosConfig { failure_rate: 0.3, ambiguous_rate: 0.0, seed: 7, ..ChaosConfig::default() };
+        let (received, deliveries, stats) = run(config, 20).await;
+        assert!(stats.failed > 0);
+        // A batch failing halfway is resent record by record, so records
+        // before the failure may arrive twice, but none is missing.
+        assert_eq!(received.len(), 20);
+        assert!(deliveries >= 20);
+        assert!(received.values().all(|ids| ids.len() == 1), "{:?}", received);
+    }
+
+    #[tokio::test]
+    async fn records_whose_acknowledgement_was_lost_are_resent_with_their_event_id() {
+        let config = ChaosConfig { failure_rate: 0.0, ambiguous_rate: 0.3, seed: 7, ..ChaosConfig::default() };
+        let (received, deliveries, stats) = run(config, 20).await;
+        assert!(stats.ambiguous > 0);
+        assert_eq!(received.len(), 20);
+        assert!(deliveries > 20);
+        assert!(received.values().all(|ids| ids.len() == 1), "{:?}", received);
+    }
+
+    #[tokio::test]
+    async fn rejections_drop_only_the_rejected_records() {
+        let config = ChaosConfig {
+            failure_rate: 0.0,
+            ambiguous_rate: 0.0,
+            reject_rate: 0.2,
+            seed: 7,
+            ..ChaosConfig::default()
+        };
+        let (received, deliveries, stats) = run(config, 40).await;
+        assert!(stats.rejected > 0);
+        assert_eq!(received.len() as u64, 40 - stats.rejected);
+        assert_eq!(deliveries, received.len() as u64);
+    }
+}
@@ -1,16 +1,54 @@
-use crate::record::LogRecord;
-use crate::sink::LogSink;
+use crate::destination::DestinationTemplate;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::probe;
+use crate::encoding::SerializedBatch;
+use crate::http::{HttpOptions, HttpRequest, HttpTransport};
+use crate::record::{self, LogRecord, SCHEMA_VERSION};
+use crate::schema::{self, ColumnType, SchemaBackend, SchemaDrift, SchemaOptions};
+use crate::sink::{LogSink, SinkError};
+use crate::timestamp::TimestampFormat;
+use crate::tls::{TlsError, TlsOptions};
 use async_trait::async_trait;
-use reqwest::Client;
 use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::error::Error;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 use urlencoding;
+use uuid::Uuid;
 
 /// Configuration for [`ClickHouseSink`].
 ///
 /// The sink talks to ClickHouse over HTTP using the `JSONEachRow` format.
 /// It supports both dedicated-table per service and shared-table modes by
 /// toggling the `service_name` field and selected table.
+///
+/// `schema_version` is the [`LogRecord`] schema version the target table
+/// was created with; columns introduced by later versions are not sent,
+/// so old tables keep working until they are migrated.
+///
+/// `timestamp_format` controls how the `timestamp` column is written; use
+/// [`TimestampFormat::DateTime64Millis`] for `DateTime64(3)` columns.
+///
+/// `timeout` bounds each HTTP request (default 30 s); `None` waits
+/// indefinitely. It is ignored on `wasm32`, where `fetch` has no timeout.
+/// `http` sets the connect timeout, pooling and keep-alive of the
+/// connections, see [`HttpOptions`].
+///
+/// `table` may contain placeholders such as `{service}` or `{date}`,
+/// filled per record, see [`DestinationTemplate`]; the tables must exist.
+///
+/// `tls` adds a private CA, a client certificate for mTLS or an SNI
+/// override for `https` URLs, see [`TlsOptions`].
+///
+/// `probe_interval` is the idle time after which the sink runs `SELECT 1`,
+/// so a broken pooled connection is replaced, and a fresh one ready,
+/// before the next insert; `None` disables probes. Probes start with the
+/// first insert and are not run on `wasm32`.
 #[derive(Clone, Debug)]
 pub struct ClickHouseConfig {
     /// Base URL without query, e.g. "http://127.0.0.1:8123"
@@ -20,13 +58,48 @@ pub struct ClickHouseConfig {
     pub service_name: Option<String>,
     pub user: Option<String>,
     pub password: Option<String>,
+    pub schema_version: u32,
+    pub timestamp_format: TimestampFormat,
+    pub timeout: Option<Duration>,
+    pub http: HttpOptions,
+    pub tls: TlsOptions,
+    pub probe_interval: Option<Duration>,
+}
+
+impl Default for ClickHouseConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:8123".to_string(),
+            database: "default".to_string(),
+            table: "logs".to_string(),
+            service_name: None,
+            user: None,
+            password: None,
+            schema_version: SCHEMA_VERSION,
+            timestamp_format: TimestampFormat::default(),
+            timeout: Some(Duration::from_secs(30)),
+            http: HttpOptions::default(),
+            tls: TlsOptions::default(),
+            probe_interval: Some(Duration::from_secs(30)),
+        }
+    }
 }
 
 /// ClickHouse implementation of [`LogSink`] using the HTTP interface.
 #[derive(Clone)]
 pub struct ClickHouseSink {
-    client: Client,
+    client: HttpTransport,
+    table: DestinationTemplate,
     config: ClickHouseConfig,
+    #[cfg(not(target_arch = "wasm32"))]
+    probe: Arc<Probe>,
+}
+
+/// Health probe state shared by clones of a [`ClickHouseSink`].
+#[cfg(not(target_arch = "wasm32"))]
+struct Probe {
+    idle: probe::Idle,
+    task: OnceLock<tokio::task::JoinHandle<()>>,
 }
 
 impl ClickHouseSink {
@@ -39,15 +112,64 @@ impl ClickHouseSink {
     /// **Returns**
     /// - A ready-to-use [`ClickHouseSink`] that can be passed into
     ///   [`init_tracing`] / [`init_tracing_with_config`].
+    ///
+    /// Panics if the HTTP client cannot be built, e.g. because a file of
+    /// [`ClickHouseConfig::tls`] is missing; see [`ClickHouseSink::try_new`].
     pub fn new(config: ClickHouseConfig) -> Self {
-        let client = Client::new();
-        Self { client, config }
+        Self::try_new(config).expect("build ClickHouse HTTP client")
+    }
+
+    /// Like [`ClickHouseSink::new`], but returns an error instead of
+    /// panicking when the HTTP client cannot be built.
+    pub fn try_new(mut config: ClickHouseConfig) -> Result<Self, TlsError> {
+        let (client, url) = HttpTransport::new(&config.url, config.timeout, &config.http, &config.tls)?;
+        config.url = url;
+        Ok(Self {
+            client,
+            table: DestinationTemplate::new(&config.table),
+            config,
+            #[cfg(not(target_arch = "wasm32"))]
+            probe: Arc::new(Probe { idle: probe::Idle::new(), task: OnceLock::new() }),
+        })
     }
 
-    fn endpoint(&self) -> String {
+    /// Mark the connection as used, starting the health probes on the
+    /// first call.
+    fn touch(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.probe.idle.touch();
+            let Some(interval) = self.config.probe_interval else { return };
+            self.probe.task.get_or_init(|| {
+                let (client, url) = (self.client.clone(), self.query_url("SELECT 1"));
+                probe::spawn(Arc::downgrade(&self.probe), interval, move |probe| {
+                    let (client, url) = (client.clone(), url.clone());
+                    async move {
+                        if probe.idle.elapsed() >= interval {
+                            let healthy = client.send(HttpRequest::get(&url)).await.is_ok_and(|resp| resp.is_success());
+                            if !healthy {
+                                tracing::warn!(target: DIAGNOSTICS_TARGET, "ClickHouse sink health probe failed");
+                            }
+                        }
+                        true
+                    }
+                })
+            });
+        }
+    }
+
+    fn endpoint(&self, record: &LogRecord) -> String {
+        let table = self.table.render(record);
+        // Rendered values may contain `-` or `.`, e.g. from `{date}`.
+        let table = if self.table.is_templated() && !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            Cow::Owned(format!("`{}`", table))
+        } else {
+            table
+        };
         let mut query = format!(
             "database={}&query=INSERT%20INTO%20{}%20FORMAT%20JSONEachRow",
-            self.config.database, self.config.table
+            self.config.database,
+            urlencoding::encode(&table)
         );
 
         if let Some(user) = &self.config.user {
@@ -60,34 +182,59 @@ impl ClickHouseSink {
         format!("{}/?{}", self.config.url, query)
     }
 
-    fn map_record(&self, record: &LogRecord) -> ClickHouseRow {
+    fn map_record<'a>(&'a self, record: &'a LogRecord, fields: &'a str) -> ClickHouseRow<'a> {
+        let has = |field: &str| {
+            record::field_since(field).is_some_and(|since| since <= self.config.schema_version)
+        };
+
         ClickHouseRow {
-            timestamp: record.timestamp.to_rfc3339(),
-            level: record.level.clone(),
-            target: record.target.clone(),
-            module_path: record.module_path.clone(),
-            file: record.file.clone(),
+            timestamp: self.config.timestamp_format.format(&record.timestamp),
+            level: record.level.as_str(),
+            target: &record.target,
+            module_path: record.module_path.as_deref(),
+            file: record.file.as_deref(),
             line: record.line.map(|l| l as u64),
-            message: record.message.clone(),
-            service_name: self.config.service_name.clone().or_else(|| record.service_name.clone()),
-            fields: serde_json::to_string(&record.fields).unwrap_or_else(|_| "{}".to_string()),
+            message: record.message.as_deref(),
+            service_name: self.config.service_name.as_deref().or(record.service_name.as_deref()),
+            schema_version: has("schema_version").then_some(record.schema_version),
+            hostname: record.hostname.as_deref().filter(|_| has("hostname")),
+            pid: record.pid.filter(|_| has("pid")),
+            thread_id: record.thread_id.as_deref().filter(|_| has("thread_id")),
+            thread_name: record.thread_name.as_deref().filter(|_| has("thread_name")),
+            resource: has("resource").then_some(&record.resource),
+            event_id: record.event_id.filter(|_| has("event_id")),
+            repeat_count: record.repeat_count.filter(|_| has("repeat_count")),
+            fingerprint: record.fingerprint.as_deref().filter(|_| has("fingerprint")),
+            trace_id: record.trace_id.as_deref().filter(|_| has("trace_id")),
+            span_id: record.span_id.as_deref().filter(|_| has("span_id")),
+            span_name: record.span_name.as_deref().filter(|_| has("span_name")),
+            duration_ms: record.duration_ms.filter(|_| has("duration_ms")),
+            span_elapsed_ms: record.span_elapsed_ms.filter(|_| has("span_elapsed_ms")),
+            error_kind: record.error_kind.as_deref().filter(|_| has("error_kind")),
+            error_message: record.error_message.as_deref().filter(|_| has("error_message")),
+            fields,
         }
     }
 
-    /// Validate that the target ClickHouse table exposes the expected
-    /// columns. This is optional and is not called automatically.
-    ///
-    /// **Returns**
-    /// - `Ok(())` if the `DESCRIBE TABLE` query succeeded.
-    /// - `Err(..)` if ClickHouse responded with a non-success status.
-    pub async fn validate_schema(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let mut query = format!(
-            "query={}",
-            urlencoding::encode(&format!(
-                "DESCRIBE TABLE {}.{} FORMAT JSON",
-                self.config.database, self.config.table
-            ))
-        );
+    /// Insert one row with `JSONEachRow`.
+    async fn insert(&self, record: &LogRecord, row: ClickHouseRow<'_>) -> Result<(), SinkError> {
+        let body = serde_json::to_string(&row)? + "\n";
+        self.post(self.endpoint(record), body).await
+    }
+
+    /// Send `JSONEachRow` rows to an insert endpoint.
+    async fn post(&self, endpoint: String, body: String) -> Result<(), SinkError> {
+        self.touch();
+        let resp = self.client.send(HttpRequest::post(endpoint).body(body)).await?;
+        if resp.is_success() {
+            Ok(())
+        } else {
+            Err(resp.error("ClickHouse insert"))
+        }
+    }
+
+    fn query_url(&self, sql: &str) -> String {
+        let mut query = format!("query={}", urlencoding::encode(sql));
 
         if let Some(user) = &self.config.user {
             query.push_str(&format!("&user={}", urlencoding::encode(user)));
@@ -96,42 +243,214 @@ impl ClickHouseSink {
             query.push_str(&format!("&password={}", urlencoding::encode(password)));
         }
 
-        let url = format!("{}/?{}", self.config.url, query);
-        let resp = self.client.get(&url).send().await?;
-        if !resp.status().is_success() {
-            return Err(format!("ClickHouse schema validation failed with status {}", resp.status()).into());
+        format!("{}/?{}", self.config.url, query)
+    }
+
+    /// Validate that the target ClickHouse table exposes the expected
+    /// columns. This is optional and is not called automatically; see
+    /// [`LogSink::schema_drift`] for a column-by-column comparison.
+    ///
+    /// **Returns**
+    /// - `Ok(())` if the `DESCRIBE TABLE` query succeeded.
+    /// - `Err(..)` if ClickHouse responded with a non-success status, or
+    ///   if the table name is a template.
+    pub async fn validate_schema(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if self.table.is_templated() {
+            return Err(format!("cannot validate templated table name {:?}", self.config.table).into());
+        }
+        let url = self.query_url(&format!(
+            "DESCRIBE TABLE {}.{} FORMAT JSON",
+            self.config.database, self.config.table
+        ));
+        let resp = self.client.send(HttpRequest::get(url)).await?;
+        if !resp.is_success() {
+            return Err(format!("ClickHouse schema validation failed with status {}", resp.status).into());
         }
         Ok(())
     }
+
+    /// The columns of the target tables from `system.columns`, per table;
+    /// `MATERIALIZED` and `ALIAS` columns are left out, as inserts do not
+    /// write them.
+    async fn table_columns(&self) -> Result<Vec<(String, Vec<ColumnType>)>, SinkError> {
+        let filter = if self.table.is_templated() {
+            let pattern = self.table.pattern().replace('%', "\\%").replace('_', "\\_").replace('*', "%");
+            format!("table LIKE {}", quote(&pattern))
+        } else {
+            format!("table = {}", quote(&self.config.table))
+        };
+        let sql = format!(
+            "SELECT table, name, type FROM system.columns WHERE database = {} AND {} \
+             AND default_kind NOT IN ('MATERIALIZED', 'ALIAS') ORDER BY table, position FORMAT JSON",
+            quote(&self.config.database),
+            filter
+        );
+        let resp = self.client.send(HttpRequest::get(self.query_url(&sql))).await?;
+        if !resp.is_success() {
+            return Err(resp.error("ClickHouse schema check"));
+        }
+        let body: serde_json::Value = resp.json()?;
+        let mut tables: Vec<(String, Vec<ColumnType>)> = Vec::new();
+        for row in body["data"].as_array().into_iter().flatten() {
+            let text = |key: &str| row[key].as_str().unwrap_or_default().to_string();
+            let table = text("table");
+            if tables.last().is_none_or(|(t, _)| *t != table) {
+                tables.push((table, Vec::new()));
+            }
+            if let Some((_, columns)) = tables.last_mut() {
+                columns.push(ColumnType::new(text("name"), text("type")));
+            }
+        }
+        Ok(tables)
+    }
+}
+
+/// `value` as a ClickHouse string literal.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
 }
 
 #[cfg(feature = "clickhouse")]
 #[derive(Serialize)]
-struct ClickHouseRow {
-    timestamp: String,
-    level: String,
-    target: String,
-    module_path: Option<String>,
-    file: Option<String>,
+struct ClickHouseRow<'a> {
+    timestamp: serde_json::Value,
+    level: &'static str,
+    target: &'a str,
+    module_path: Option<&'a str>,
+    file: Option<&'a str>,
     line: Option<u64>,
-    message: Option<String>,
-    service_name: Option<String>,
-    fields: String,
+    message: Option<&'a str>,
+    service_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thread_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resource: Option<&'a BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    repeat_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    span_elapsed_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_kind: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_message: Option<&'a str>,
+    fields: &'a str,
 }
 
 #[cfg(feature = "clickhouse")]
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl LogSink for ClickHouseSink {
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let row = self.map_record(record);
-        let body = serde_json::to_string(&row)? + "\n";
-        let resp = self.client.post(self.endpoint()).body(body).send().await?;
-        if resp.status().is_success() {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let fields = serde_json::to_string(&record.fields).unwrap_or_else(|_| "{}".to_string());
+        self.insert(record, self.map_record(record, &fields)).await
+    }
+
+    /// Takes the `fields` column from the batch, so a retried record does
+    /// not serialize its fields again.
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        let fields = batch.fields_json(index).unwrap_or("{}");
+        let record = batch.record(index);
+        self.insert(record, self.map_record(record, fields)).await
+    }
+
+    /// Inserts the records with one request per target table; a table
+    /// rejecting its rows does not keep the others from being written.
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        let mut inserts: Vec<(String, String)> = Vec::new();
+        for record in records {
+            let fields = serde_json::to_string(&record.fields).unwrap_or_else(|_| "{}".to_string());
+            let row = serde_json::to_string(&self.map_record(record, &fields))?;
+            let endpoint = self.endpoint(record);
+            let index = match inserts.iter().position(|(e, _)| *e == endpoint) {
+                Some(index) => index,
+                None => {
+                    inserts.push((endpoint, String::new()));
+                    inserts.len() - 1
+                }
+            };
+            let body = &mut inserts[index].1;
+            body.push_str(&row);
+            body.push('\n');
+        }
+        let mut rejected = None;
+        for (endpoint, body) in inserts {
+            match self.post(endpoint, body).await {
+                Ok(()) => {}
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(e) => rejected = Some(e),
+            }
+        }
+        rejected.map_or(Ok(()), Err)
+    }
+
+    /// Runs `SELECT 1` with the configured credentials.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        let resp = self.client.send(HttpRequest::get(self.query_url("SELECT 1"))).await?;
+        if resp.is_success() {
             Ok(())
         } else {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_else(|_| "<no body>".to_string());
-            Err(format!("ClickHouse insert failed with status {}: {}", status, text).into())
+            Err(resp.error("ClickHouse health check"))
+        }
+    }
+
+    /// Compares the columns of the target table, or of every existing
+    /// table matching a templated name, with those of
+    /// [`schema::expected_columns`] for `schema_version`,
+    /// `timestamp_format` and, with `service_name` set, a shared table.
+    ///
+    /// **Returns**
+    /// - `Err(..)` if a table without placeholders does not exist.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        let options = SchemaOptions {
+            version: self.config.schema_version,
+            shared: self.config.service_name.is_some(),
+            timestamp_format: self.config.timestamp_format,
+            ..SchemaOptions::default()
+        };
+        let expected = schema::expected_columns(SchemaBackend::ClickHouse, &options).map_err(SinkError::permanent)?;
+        let tables = self.table_columns().await?;
+        if tables.is_empty() && !self.table.is_templated() {
+            return Err(SinkError::permanent(format!(
+                "ClickHouse table {}.{} does not exist",
+                self.config.database, self.config.table
+            )));
         }
+        Ok(tables
+            .into_iter()
+            .map(|(table, actual)| {
+                let destination = format!("{}.{}", self.config.database, table);
+                schema::drift(SchemaBackend::ClickHouse, destination, &expected, &actual)
+            })
+            .filter(|drift| !drift.is_empty())
+            .collect())
+    }
+
+    /// Stops the health probes.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(task) = self.probe.task.get() {
+            task.abort();
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,74 @@
+use crate::timestamp::{self, Timestamp};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Source of time for the layer, the worker and sinks.
+///
+/// Injected via [`LayerConfig::clock`] so timestamp-sensitive behavior
+/// (record timestamps, duplicate-collapsing windows) can be tested
+/// deterministically with [`ManualClock`].
+///
+/// [`LayerConfig::clock`]: crate::init::LayerConfig::clock
+pub trait Clock: Send + Sync {
+    /// Current wall-clock time, used for [`LogRecord::timestamp`].
+    ///
+    /// [`LogRecord::timestamp`]: crate::record::LogRecord::timestamp
+    fn now(&self) -> Timestamp;
+
+    /// Current monotonic time, used for measuring windows and intervals.
+    fn instant(&self) -> Instant;
+}
+
+/// [`Clock`] backed by the operating system. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Timestamp {
+        timestamp::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// [`Clock`] that only moves when told to, for tests.
+///
+/// Both the wall-clock and the monotonic reading advance together through
+/// [`ManualClock::advance`].
+#[derive(Debug)]
+pub struct ManualClock {
+    state: Mutex<(Timestamp, Instant)>,
+}
+
+impl ManualClock {
+    /// Create a clock frozen at `start`.
+    pub fn new(start: Timestamp) -> Self {
+        ManualClock {
+            state: Mutex::new((start, Instant::now())),
+        }
+    }
+
+    /// Move the clock forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        let mut state = self.state.lock().expect("manual clock poisoned");
+        state.0 += by;
+        state.1 += by;
+    }
+
+    /// Set the wall-clock time without touching the monotonic reading.
+    pub fn set(&self, now: Timestamp) {
+        self.state.lock().expect("manual clock poisoned").0 = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Timestamp {
+        self.state.lock().expect("manual clock poisoned").0
+    }
+
+    fn instant(&self) -> Instant {
+        self.state.lock().expect("manual clock poisoned").1
+    }
+}
@@ -0,0 +1,192 @@
+use crate::record::LogRecord;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Collapses identical records seen within a time window.
+///
+/// The first occurrence of a record is forwarded immediately so alerting
+/// is not delayed. Further identical records inside the window are
+/// suppressed and counted; when the window closes a single summary record
+/// (the last suppressed occurrence) is emitted with
+/// [`LogRecord::repeat_count`] set to the number of suppressed duplicates.
+///
+/// Records are considered identical when their target, level, message,
+/// source location and fingerprint match.
+pub(crate) struct Collapser {
+    window: Duration,
+    entries: HashMap<u64, Entry>,
+}
+
+struct Entry {
+    window_start: Instant,
+    suppressed: u64,
+    last: Option<LogRecord>,
+}
+
+impl Collapser {
+    pub(crate) fn new(window: Duration) -> Self {
+        Collapser {
+            window,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Admit a record, pushing whatever should be sent now into `out`.
+    pub(crate) fn admit(&mut self, record: LogRecord, now: Instant, out: &mut Vec<LogRecord>) {
+        let key = Self::key(&record);
+        match self.entries.get_mut(&key) {
+            Some(entry) if now.duration_since(entry.window_start) < self.window => {
+                entry.suppressed += 1;
+                entry.last = Some(record);
+            }
+            Some(entry) => {
+                if let Some(summary) = entry.summary() {
+                    out.push(summary);
+                }
+                entry.window_start = now;
+                out.push(record);
+            }
+            None => {
+                self.entries.insert(
+                    key,
+                    Entry {
+                        window_start: now,
+                        suppressed: 0,
+                        last: None,
+                    },
+                );
+                out.push(record);
+            }
+        }
+    }
+
+    /// Close all windows that ended before `now`, pushing their summary
+    /// records into `out`.
+    pub(crate) fn expire(&mut self, now: Instant, out: &mut Vec<LogRecord>) {
+        let window = self.window;
+        self.entries.retain(|_, entry| {
+            if now.duration_since(entry.window_start) < window {
+                return true;
+            }
+            if let Some(summary) = entry.summary() {
+                out.push(summary);
+            }
+            false
+        });
+    }
+
+    /// Close every open window regardless of its age, e.g. on shutdown.
+    pub(crate) fn drain(&mut self, out: &mut Vec<LogRecord>) {
+        for (_, mut entry) in self.entries.drain() {
+            if let Some(summary) = entry.summary() {
+                out.push(summary);
+            }
+        }
+    }
+
+    fn key(record: &LogRecord) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        record.target.hash(&mut hasher);
+        record.level.hash(&mut hasher);
+        record.message.hash(&mut hasher);
+        record.file.hash(&mut hasher);
+        record.line.hash(&mut hasher);
+        record.fingerprint.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl Entry {
+    fn summary(&mut self) -> Option<LogRecord> {
+        let suppressed = std::mem::take(&mut self.suppressed);
+        let mut record = self.last.take()?;
+        record.repeat_count = Some(suppressed);
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(message: &str) -> LogRecord {
+        serde_json::from_value(json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": message,
+            "fields": {},
+        }))
+        .unwrap()
+    }
+
+    fn messages(records: &[LogRecord]) -> Vec<(&str, Option<u64>)> {
+        records.iter().map(|r| (r.message.as_deref().unwrap_or_default(), r.repeat_count)).collect()
+    }
+
+    #[test]
+    fn duplicates_are_suppressed_within_the_window() {
+        let start = Instant::now();
+        let mut collapser = Collapser::new(Duration::from_secs(10));
+        let mut out = Vec::new();
+        collapser.admit(record("boom"), start, &mut out);
+        collapser.admit(record("other"), start, &mut out);
+        assert_eq!(messages(&out), [("boom", None), ("other", None)]);
+
+        for i in 1..=3 {
+            collapser.admit(record("boom"), start + Duration::from_secs(i), &mut out);
+        }
+        // Still inside the window: nothing more is sent.
+        collapser.expire(start + Duration::from_secs(9), &mut out);
+        assert_eq!(out.len(), 2);
+    }
+
+    #[test]
+    fn an_expired_window_emits_a_summary_with_the_repeat_count() {
+        let start = Instant::now();
+        let mut collapser = Collapser::new(Duration::from_secs(10));
+        let mut out = Vec::new();
+        for i in 0..4 {
+            collapser.admit(record("boom"), start + Duration::from_secs(i), &mut out);
+        }
+        collapser.admit(record("once"), start, &mut out);
+        collapser.expire(start + Duration::from_secs(10), &mut out);
+        assert_eq!(messages(&out), [("boom", None), ("once", None), ("boom", Some(3))]);
+
+        // The next occurrence opens a new window and passes through.
+        out.clear();
+        collapser.admit(record("boom"), start + Duration::from_secs(11), &mut out);
+        assert_eq!(messages(&out), [("boom", None)]);
+    }
+
+    #[test]
+    fn a_duplicate_after_the_window_flushes_the_summary_first() {
+        let start = Instant::now();
+        let mut collapser = Collapser::new(Duration::from_secs(10));
+        let mut out = Vec::new();
+        collapser.admit(record("boom"), start, &mut out);
+        collapser.admit(record("boom"), start + Duration::from_secs(1), &mut out);
+        collapser.admit(record("boom"), start + Duration::from_secs(12), &mut out);
+        assert_eq!(messages(&out), [("boom", None), ("boom", Some(1)), ("boom", None)]);
+    }
+
+    #[test]
+    fn drain_flushes_open_windows() {
+        let start = Instant::now();
+        let mut collapser = Collapser::new(Duration::from_secs(10));
+        let mut out = Vec::new();
+        for message in ["a", "a", "a", "b", "b", "c"] {
+            collapser.admit(record(message), start, &mut out);
+        }
+        out.clear();
+        collapser.drain(&mut out);
+        out.sort_by(|a, b| a.message.cmp(&b.message));
+        assert_eq!(messages(&out), [("a", Some(2)), ("b", Some(1))]);
+
+        collapser.admit(record("a"), start, &mut out);
+        assert_eq!(out.len(), 3);
+    }
+}
@@ -0,0 +1,217 @@
+//! Loading layer and sink configuration from TOML or YAML files.
+//!
+//! Lets ops teams manage logging settings without recompiling services.
+//! A TOML document looks like this (every section and key is optional):
+//!
+//! ```toml
+//! [sink]
+//! dsn = "clickhouse://127.0.0.1:8123"
+//!
+//! [layer]
+//! channel_buffer = 10000
+//! batch_size = 500
+//! flush_interval_ms = 500
+//! level = "warn"
+//! enable_stdout = false
+//! collapse_window_ms = 5000
+//!
+//! [resource]
+//! environment = "prod"
+//! region = "eu-west-1"
+//! labels = { team = "payments" }
+//!
+//! [level_policy]
+//! warn = { sample = 10 }
+//!
+//! [limits]
+//! max_message_bytes = 16384
+//!
+//! [redaction]
+//! field_patterns = ["password", "token"]
+//!
+//! [field_map]
+//! case = "snake"
+//! rename = { uid = "user_id" }
+//! ```
+//!
+//! The YAML form uses the same keys.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::backend::{self, BackendBuildError, DsnError};
+use crate::field_map::{FieldMap, FieldMapConfig};
+use crate::fingerprint::FingerprintConfig;
+use crate::flatten::FlattenConfig;
+use crate::init::LayerConfig;
+use crate::level_policy::ShipPolicy;
+use crate::limits::RecordLimits;
+use crate::record::LogLevel;
+use crate::redaction::{RedactionConfig, RedactionProcessor};
+use crate::resource::ResourceInfo;
+use crate::sink::LogSink;
+
+/// Error returned when loading a configuration file.
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigFileError {
+    #[error("failed to read {path}: {source}")]
+    Io { path: PathBuf, source: std::io::Error },
+
+    #[error("unsupported config file extension: {0}")]
+    UnsupportedFormat(PathBuf),
+
+    #[error("invalid TOML config: {0}")]
+    Toml(#[from] toml::de::Error),
+
+    #[error("invalid YAML config: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("invalid redaction pattern: {0}")]
+    Redaction(#[from] regex::Error),
+
+    #[error("config file has no [sink] section")]
+    NoSink,
+
+    #[error(transparent)]
+    Dsn(#[from] DsnError),
+
+    #[error(transparent)]
+    Build(#[from] BackendBuildError),
+}
+
+/// Parsed contents of a configuration file.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub sink: Option<SinkSection>,
+    pub layer: LayerSection,
+    pub resource: ResourceInfo,
+    pub level_policy: BTreeMap<LogLevel, ShipPolicy>,
+    pub limits: Option<RecordLimits>,
+    pub flatten: Option<FlattenConfig>,
+    pub fingerprint: Option<FingerprintConfig>,
+    pub redaction: Option<RedactionConfig>,
+    pub field_map: Option<FieldMapConfig>,
+}
+
+/// `[sink]` section: which backend to ship records to.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SinkSection {
+    /// Backend DSN, as accepted by [`backend::parse_dsn`].
+    pub dsn: String,
+}
+
+/// `[layer]` section: buffering, batching and filtering options of
+/// [`LayerConfig`]. Missing keys keep their default values.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayerSection {
+    pub channel_buffer: Option<usize>,
+    pub channel_shards: Option<usize>,
+    pub batch_size: Option<usize>,
+    pub flush_interval_ms: Option<u64>,
+    pub level: Option<LogLevel>,
+    pub enable_stdout: Option<bool>,
+    pub collapse_window_ms: Option<u64>,
+    pub field_allowlist: Option<Vec<String>>,
+}
+
+impl FileConfig {
+    /// Read and parse a configuration file.
+    ///
+    /// The format is chosen by extension: `.toml`, `.yaml` or `.yml`.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigFileError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Ok(toml::from_str(&text)?),
+            Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(&text)?),
+            _ => Err(ConfigFileError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+
+    /// Convert the parsed file into a [`LayerConfig`].
+    ///
+    /// Options in the query string of the `[sink]` DSN (see
+    /// [`backend::DsnOptions`]) apply first and are overridden by the
+    /// `[layer]` section. A `[redaction]` section is turned into a [`RedactionProcessor`] at
+    /// the front of [`LayerConfig::processors`], followed by a [`FieldMap`]
+    /// for a `[field_map]` section.
+    pub fn layer_config(&self) -> Result<LayerConfig, ConfigFileError> {
+        let mut config = LayerConfig::default();
+        if let Some(sink) = &self.sink {
+            backend::parse_dsn(&sink.dsn)?.options.apply_to(&mut config);
+        }
+        let layer = &self.layer;
+
+        if let Some(v) = layer.channel_buffer {
+            config.channel_buffer = v;
+        }
+        if let Some(v) = layer.channel_shards {
+            config.channel_shards = v;
+        }
+        if let Some(v) = layer.batch_size {
+            config.batch_size = v;
+        }
+        if let Some(ms) = layer.flush_interval_ms {
+            config.flush_interval = Duration::from_millis(ms);
+        }
+        if let Some(v) = layer.level {
+            config.level = v;
+        }
+        if let Some(v) = layer.enable_stdout {
+            config.enable_stdout = v;
+        }
+        if let Some(ms) = layer.collapse_window_ms {
+            config.collapse_window = Some(Duration::from_millis(ms));
+        }
+        if let Some(keys) = &layer.field_allowlist {
+            config.field_allowlist = Some(keys.clone());
+        }
+
+        config.resource = self.resource.clone();
+        config.level_policy = self.level_policy.clone();
+        if let Some(limits) = &self.limits {
+            config.limits = limits.clone();
+        }
+        if let Some(flatten) = &self.flatten {
+            config.flatten_fields = Some(flatten.clone());
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            config.fingerprint = Some(fingerprint.clone());
+        }
+        if let Some(field_map) = &self.field_map {
+            config.processors.insert(0, Arc::new(FieldMap::new(field_map.clone())));
+        }
+        if let Some(redaction) = &self.redaction {
+            let processor = RedactionProcessor::new(redaction.clone())?;
+            config.processors.insert(0, Arc::new(processor));
+        }
+
+        Ok(config)
+    }
+
+    /// Build the sink described by the `[sink]` section.
+    pub fn make_sink(&self) -> Result<Arc<dyn LogSink>, ConfigFileError> {
+        let sink = self.sink.as_ref().ok_or(ConfigFileError::NoSink)?;
+        let backend_cfg = backend::parse_dsn(&sink.dsn)?;
+        Ok(backend::make_sink_blocking(&backend_cfg)?)
+    }
+}
+
+impl LayerConfig {
+    /// Load layer settings from a TOML or YAML file, see [`FileConfig`].
+    ///
+    /// Use [`FileConfig::from_file`] directly to also build the sink.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigFileError> {
+        FileConfig::from_file(path)?.layer_config()
+    }
+}
@@ -0,0 +1,387 @@
+//! Composable [`LogSink`] wrappers adding retries, timeouts, metrics and
+//! logging to any sink.
+//!
+//! Each decorator takes the sink it wraps as `Arc<dyn LogSink>` and is a
+//! `LogSink` itself, so they stack:
+//! `MetricsSink::new(Arc::new(TimeoutSink::new(inner, timeout)))`.
+//! All of them forward [`LogSink::send_serialized`], so the wrapped sink
+//! keeps reusing cached payloads.
+
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::encoding::SerializedBatch;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often and how fast [`RetrySink`] retries.
+///
+/// **Fields**
+/// - `max_retries`: retries after the first attempt; `0` disables
+///   retrying.
+/// - `initial_backoff`: delay before the first retry; doubled for every
+///   further one.
+/// - `max_backoff`: upper bound of the delay.
+///
+/// [`SinkError::RateLimited`] with a `retry_after` waits that long instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+/// [`LogSink`] decorator that retries failed sends with backoff, see
+/// [`RetryPolicy`].
+///
+/// Only retryable [`SinkError`]s are retried; the last error is returned
+/// once the retries are used up. The layer's worker retries on its own
+/// until a record is delivered, so inside the layer this mostly shortens
+/// the wait after a brief failure; it is meant for sinks used on their
+/// own, or behind routers that should not see every hiccup.
+pub struct RetrySink {
+    inner: Arc<dyn LogSink>,
+    policy: RetryPolicy,
+}
+
+impl RetrySink {
+    /// Wrap `inner`, retrying as `policy` says.
+    pub fn new(inner: Arc<dyn LogSink>, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    async fn retry<F, Fut>(&self, mut attempt: F) -> Result<(), SinkError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<(), SinkError>>,
+    {
+        let mut backoff = self.policy.initial_backoff;
+        let mut retries = 0;
+        loop {
+            match attempt().await {
+                Err(e) if e.is_retryable() && retries < self.policy.max_retries => {
+                    let delay = match e {
+                        SinkError::RateLimited { retry_after: Some(retry_after) } => retry_after,
+                        _ => backoff,
+                    };
+                    tokio::time::sleep(delay).await;
+                    backoff = std::cmp::min(backoff * 2, self.policy.max_backoff);
+                    retries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl LogSink for RetrySink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.retry(|| self.inner.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.retry(|| self.inner.send_serialized(batch, index)).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.retry(|| self.inner.send_batch(records)).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.retry(|| self.inner.flush()).await
+    }
+
+    /// Not retried, so probes report the current state.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.inner.health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.inner.schema_drift().await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.inner.shutdown().await
+    }
+}
+
+/// [`LogSink`] decorator that fails calls taking longer than a timeout
+/// with [`SinkError::Transient`].
+///
+/// A timed-out send may still have reached the backend, so the layer
+/// retries the record and it can arrive twice with the same `event_id`,
+/// as with any other ambiguous failure.
+pub struct TimeoutSink {
+    inner: Arc<dyn LogSink>,
+    timeout: Duration,
+}
+
+impl TimeoutSink {
+    /// Wrap `inner`, limiting every call to `timeout`.
+    pub fn new(inner: Arc<dyn LogSink>, timeout: Duration) -> Self {
+        Self { inner, timeout }
+    }
+
+    async fn limit<T, F>(&self, operation: &str, call: F) -> Result<T, SinkError>
+    where
+        F: Future<Output = Result<T, SinkError>>,
+    {
+        match tokio::time::timeout(self.timeout, call).await {
+            Ok(result) => result,
+            Err(_) => Err(SinkError::transient(format!("{} timed out after {:?}", operation, self.timeout))),
+        }
+    }
+}
+
+#[async_trait]
+impl LogSink for TimeoutSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.limit("send", self.inner.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.limit("send", self.inner.send_serialized(batch, index)).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.limit("send", self.inner.send_batch(records)).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.limit("flush", self.inner.flush()).await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.limit("health check", self.inner.health_check()).await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.limit("schema check", self.inner.schema_drift()).await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.limit("shutdown", self.inner.shutdown()).await
+    }
+}
+
+/// What the sink behind a [`MetricsSink`] did so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SinkMetrics {
+    /// Calls to `send` and `send_batch`, retries included.
+    pub sends: u64,
+    /// Sends that succeeded.
+    pub delivered: u64,
+    /// Sends that failed with a retryable error.
+    pub failed: u64,
+    /// Sends that failed with a non-retryable error.
+    pub rejected: u64,
+    /// Failed calls to `flush`.
+    pub flush_failures: u64,
+    /// Sum of the latencies of all sends.
+    pub total_latency: Duration,
+    /// Slowest send.
+    pub max_latency: Duration,
+}
+
+impl SinkMetrics {
+    /// Average latency of a send, or zero before the first one.
+    pub fn mean_latency(&self) -> Duration {
+        match u32::try_from(self.sends) {
+            Ok(0) => Duration::ZERO,
+            Ok(sends) => self.total_latency / sends,
+            Err(_) => Duration::from_secs_f64(self.total_latency.as_secs_f64() / self.sends as f64),
+        }
+    }
+}
+
+/// [`LogSink`] decorator that counts sends, failures and latency of the
+/// sink it wraps, for export to the application's metrics system.
+pub struct MetricsSink {
+    inner: Arc<dyn LogSink>,
+    sends: AtomicU64,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    rejected: AtomicU64,
+    flush_failures: AtomicU64,
+    total_latency_us: AtomicU64,
+    max_latency_us: AtomicU64,
+}
+
+impl MetricsSink {
+    /// Wrap `inner`, counting from zero.
+    pub fn new(inner: Arc<dyn LogSink>) -> Self {
+        Self {
+            inner,
+            sends: AtomicU64::new(0),
+            delivered: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            flush_failures: AtomicU64::new(0),
+            total_latency_us: AtomicU64::new(0),
+            max_latency_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Snapshot of the counters.
+    pub fn stats(&self) -> SinkMetrics {
+        SinkMetrics {
+            sends: self.sends.load(Ordering::Relaxed),
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            flush_failures: self.flush_failures.load(Ordering::Relaxed),
+            total_latency: Duration::from_micros(self.total_latency_us.load(Ordering::Relaxed)),
+            max_latency: Duration::from_micros(self.max_latency_us.load(Ordering::Relaxed)),
+        }
+    }
+
+    async fn measure<F>(&self, send: F) -> Result<(), SinkError>
+    where
+        F: Future<Output = Result<(), SinkError>>,
+    {
+        let started = Instant::now();
+        let result = send.await;
+        let micros = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        self.sends.fetch_add(1, Ordering::Relaxed);
+        self.total_latency_us.fetch_add(micros, Ordering::Relaxed);
+        self.max_latency_us.fetch_max(micros, Ordering::Relaxed);
+        let counter = match &result {
+            Ok(()) => &self.delivered,
+            Err(e) if e.is_retryable() => &self.failed,
+            Err(_) => &self.rejected,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        result
+    }
+}
+
+#[async_trait]
+impl LogSink for MetricsSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.measure(self.inner.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.measure(self.inner.send_serialized(batch, index)).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.measure(self.inner.send_batch(records)).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        let result = self.inner.flush().await;
+        if result.is_err() {
+            self.flush_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.inner.health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.inner.schema_drift().await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.inner.shutdown().await
+    }
+}
+
+/// [`LogSink`] decorator that reports every call of the sink it wraps as
+/// a `tracing` event with target [`DIAGNOSTICS_TARGET`]: failures at
+/// `WARN`, successful sends at `TRACE` and other successful calls at
+/// `DEBUG`.
+///
+/// The error-log layer ignores that target, so the events reach other
+/// layers such as `fmt` without being shipped themselves.
+pub struct LoggingSink {
+    inner: Arc<dyn LogSink>,
+    name: String,
+}
+
+impl LoggingSink {
+    /// Wrap `inner`; `name` identifies it in the events, e.g.
+    /// `"clickhouse"`.
+    pub fn new(inner: Arc<dyn LogSink>, name: impl Into<String>) -> Self {
+        Self {
+            inner,
+            name: name.into(),
+        }
+    }
+
+    async fn log<F>(&self, operation: &str, record: Option<&LogRecord>, call: F) -> Result<(), SinkError>
+    where
+        F: Future<Output = Result<(), SinkError>>,
+    {
+        let started = Instant::now();
+        let result = call.await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        let event_id = record.and_then(|r| r.event_id).map(tracing::field::display);
+        match &result {
+            Ok(()) if record.is_some() => tracing::trace!(
+                target: DIAGNOSTICS_TARGET,
+                sink = %self.name, operation, event_id, elapsed_ms, "log sink call succeeded"
+            ),
+            Ok(()) => tracing::debug!(
+                target: DIAGNOSTICS_TARGET,
+                sink = %self.name, operation, elapsed_ms, "log sink call succeeded"
+            ),
+            Err(e) => tracing::warn!(
+                target: DIAGNOSTICS_TARGET,
+                sink = %self.name, operation, event_id, elapsed_ms, retryable = e.is_retryable(),
+                error = %e, "log sink call failed"
+            ),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl LogSink for LoggingSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.log("send", Some(record), self.inner.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.log("send", Some(batch.record(index)), self.inner.send_serialized(batch, index))
+            .await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.log("send_batch", None, self.inner.send_batch(records)).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.log("flush", None, self.inner.flush()).await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.log("health_check", None, self.inner.health_check()).await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.inner.schema_drift().await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.log("shutdown", None, self.inner.shutdown()).await
+    }
+}
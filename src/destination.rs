@@ -0,0 +1,138 @@
+use crate::record::LogRecord;
+use crate::timestamp;
+use std::borrow::Cow;
+
+/// Table, index or topic name with placeholders filled from each record,
+/// e.g. `"logs_{service}"` for a table per service or `"logs-{date}"` for
+/// daily OpenSearch indices.
+///
+/// Placeholders:
+/// - `{service}`: `service_name` of the record.
+/// - `{level}`: level, e.g. `error`.
+/// - `{date}`: UTC date of the record's timestamp as `YYYY-MM-DD`.
+/// - `{env}`: `environment` resource attribute, see
+///   [`ResourceInfo::environment`].
+/// - `{fields.NAME}`: event field `NAME` if it is a string, number or
+///   boolean, e.g. `{fields.tenant_id}`.
+///
+/// Values are lowercased and every character other than ASCII letters,
+/// digits, `_`, `-` and `.` becomes `_`, so they are valid in names of all
+/// built-in backends; a missing value renders as `unknown`. Other text in
+/// braces is kept as is.
+///
+/// [`ResourceInfo::environment`]: crate::resource::ResourceInfo::environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DestinationTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Service,
+    Level,
+    Date,
+    Env,
+    Field(String),
+}
+
+impl DestinationTemplate {
+    /// Parse `template`; a name without placeholders renders to itself.
+    pub fn new(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let Some(end) = rest.find('}') else { break };
+            let part = match &rest[1..end] {
+                "service" => Part::Service,
+                "level" => Part::Level,
+                "date" => Part::Date,
+                "env" => Part::Env,
+                name if name.starts_with("fields.") => Part::Field(name["fields.".len()..].to_string()),
+                _ => {
+                    literal.push_str(&rest[..=end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            };
+            if !literal.is_empty() {
+                parts.push(Part::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(part);
+            rest = &rest[end + 1..];
+        }
+        literal.push_str(rest);
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Self {
+            template: template.to_string(),
+            parts,
+        }
+    }
+
+    /// The template as written.
+    pub fn as_str(&self) -> &str {
+        &self.template
+    }
+
+    /// Whether the template has placeholders, i.e. names differ between
+    /// records.
+    pub fn is_templated(&self) -> bool {
+        self.parts.iter().any(|part| !matches!(part, Part::Literal(_)))
+    }
+
+    /// Pattern matching every name the template renders to, with `*` for
+    /// each placeholder, e.g. `logs-*` for `logs-{date}`.
+    pub fn pattern(&self) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.as_str(),
+                _ => "*",
+            })
+            .collect()
+    }
+
+    /// Destination name for `record`.
+    ///
+    /// **Returns**
+    /// - The template itself, without allocating, if it has no
+    ///   placeholders.
+    pub fn render(&self, record: &LogRecord) -> Cow<'_, str> {
+        if !self.is_templated() {
+            return Cow::Borrowed(&self.template);
+        }
+        let mut out = String::with_capacity(self.template.len() + 16);
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => out.push_str(text),
+                Part::Service => push_value(&mut out, record.service_name.as_deref()),
+                Part::Level => push_value(&mut out, Some(record.level.as_str())),
+                Part::Date => push_value(&mut out, Some(&timestamp::rfc3339_millis(&record.timestamp)[..10])),
+                Part::Env => push_value(&mut out, record.resource.get("environment").map(String::as_str)),
+                Part::Field(name) => match record.fields.get(name) {
+                    Some(serde_json::Value::String(s)) => push_value(&mut out, Some(s)),
+                    Some(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => {
+                        push_value(&mut out, Some(&v.to_string()))
+                    }
+                    _ => push_value(&mut out, None),
+                },
+            }
+        }
+        Cow::Owned(out)
+    }
+}
+
+fn push_value(out: &mut String, value: Option<&str>) {
+    let value = value.filter(|v| !v.is_empty()).unwrap_or("unknown");
+    out.extend(value.chars().map(|c| match c {
+        'A'..='Z' => c.to_ascii_lowercase(),
+        'a'..='z' | '0'..='9' | '_' | '-' | '.' => c,
+        _ => '_',
+    }));
+}
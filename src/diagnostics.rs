@@ -0,0 +1,187 @@
+//! Reporting of the crate's own operational problems (dropped records,
+//! sink failures, retries) without `eprintln!`.
+//!
+//! Diagnostics are rate limited per [`DiagnosticKind`] and delivered to
+//! the [`DiagnosticsOutput`] chosen in [`crate::init::LayerConfig::diagnostics`].
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// `tracing` target of diagnostics emitted with [`DiagnosticsOutput::Tracing`].
+///
+/// [`crate::layer::ErrorLogLayer`] never captures events of the crate's
+/// own targets, so diagnostics about a failing sink cannot feed back into it.
+pub const DIAGNOSTICS_TARGET: &str = "tracing_log_sink::diagnostics";
+
+/// What went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticKind {
+    /// A record was dropped because the channel was full or over
+    /// [`crate::init::LayerConfig::max_buffered_bytes`].
+    ChannelFull,
+    /// Sending a record failed and will be retried.
+    SendRetry,
+    /// The sink permanently rejected a record, which was dropped.
+    RecordRejected,
+    /// [`crate::sink::LogSink::flush`] failed.
+    FlushFailed,
+    /// [`crate::sink::LogSink::shutdown`] failed.
+    ShutdownFailed,
+    /// The startup [`crate::sink::LogSink::health_check`] failed.
+    HealthCheckFailed,
+    /// Writing or replaying the backlog of
+    /// [`crate::init::LayerConfig::persist_unsent`] failed, or a
+    /// `durable::DurableQueueSink` skipped unreadable records.
+    PersistFailed,
+    /// The worker panicked and was restarted, see
+    /// [`crate::stats::LayerStats::worker_restarts`].
+    WorkerRestarted,
+    /// The worker made no progress for
+    /// [`crate::init::LayerConfig::stall_timeout`].
+    WorkerStalled,
+    /// An unbounded channel holds more than its high watermark, see
+    /// [`crate::init::ChannelKind::Unbounded`].
+    ChannelHighWatermark,
+    /// The startup [`crate::sink::LogSink::schema_drift`] found tables
+    /// differing from the expected schema, or could not read them; see
+    /// [`crate::init::LayerConfig::schema_check`].
+    SchemaDrift,
+}
+
+impl DiagnosticKind {
+    const COUNT: usize = 11;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// A single diagnostic message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub message: String,
+    /// Diagnostics of the same kind suppressed by rate limiting since
+    /// the previous one was delivered.
+    pub suppressed: u64,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if self.suppressed > 0 {
+            write!(f, " ({} similar messages suppressed)", self.suppressed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Callback receiving diagnostics, see [`DiagnosticsOutput::Callback`].
+pub type DiagnosticHook = Arc<dyn Fn(&Diagnostic) + Send + Sync>;
+
+/// Where diagnostics go.
+#[derive(Clone, Default)]
+pub enum DiagnosticsOutput {
+    /// Print to stderr.
+    #[default]
+    Stderr,
+    /// Emit a `WARN` event with target [`DIAGNOSTICS_TARGET`], visible to
+    /// other layers (e.g. `fmt`) but ignored by the error-log layer.
+    Tracing,
+    /// Call the given function. It may run on application threads.
+    Callback(DiagnosticHook),
+    /// Discard.
+    Silent,
+}
+
+impl fmt::Debug for DiagnosticsOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiagnosticsOutput::Stderr => f.write_str("Stderr"),
+            DiagnosticsOutput::Tracing => f.write_str("Tracing"),
+            DiagnosticsOutput::Callback(_) => f.write_str("Callback(..)"),
+            DiagnosticsOutput::Silent => f.write_str("Silent"),
+        }
+    }
+}
+
+/// Diagnostics settings of [`crate::init::LayerConfig`].
+#[derive(Debug, Clone)]
+pub struct DiagnosticsConfig {
+    pub output: DiagnosticsOutput,
+    /// Minimal interval between two delivered diagnostics of the same
+    /// kind; the ones in between are only counted.
+    pub min_interval: Duration,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self {
+            output: DiagnosticsOutput::default(),
+            min_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Rate-limited dispatcher shared by the layer and the worker.
+pub(crate) struct Diagnostics {
+    output: DiagnosticsOutput,
+    min_interval_ms: u64,
+    start: Instant,
+    /// Milliseconds since `start` (plus one, so zero means never) of the
+    /// last delivered diagnostic per kind.
+    last: [AtomicU64; DiagnosticKind::COUNT],
+    suppressed: [AtomicU64; DiagnosticKind::COUNT],
+}
+
+impl Diagnostics {
+    pub(crate) fn new(config: &DiagnosticsConfig) -> Self {
+        Diagnostics {
+            output: config.output.clone(),
+            min_interval_ms: config.min_interval.as_millis() as u64,
+            start: Instant::now(),
+            last: Default::default(),
+            suppressed: Default::default(),
+        }
+    }
+
+    /// Report a diagnostic; `message` is only built if it is delivered.
+    pub(crate) fn emit(&self, kind: DiagnosticKind, message: impl FnOnce() -> String) {
+        if matches!(self.output, DiagnosticsOutput::Silent) {
+            return;
+        }
+
+        let idx = kind.index();
+        let now = self.start.elapsed().as_millis() as u64 + 1;
+        let last = self.last[idx].load(Ordering::Relaxed);
+        let due = last == 0 || now.saturating_sub(last) >= self.min_interval_ms;
+        if !due
+            || self.last[idx]
+                .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            self.suppressed[idx].fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        let diagnostic = Diagnostic {
+            kind,
+            message: message(),
+            suppressed: self.suppressed[idx].swap(0, Ordering::Relaxed),
+        };
+        match &self.output {
+            DiagnosticsOutput::Stderr => eprintln!("{}", diagnostic),
+            DiagnosticsOutput::Tracing => tracing::warn!(
+                target: DIAGNOSTICS_TARGET,
+                kind = ?diagnostic.kind,
+                suppressed = diagnostic.suppressed,
+                "{}",
+                diagnostic.message
+            ),
+            DiagnosticsOutput::Callback(hook) => hook(&diagnostic),
+            DiagnosticsOutput::Silent => {}
+        }
+    }
+}
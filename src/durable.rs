@@ -0,0 +1,623 @@
+//! Crash-safe local queue in front of a sink.
+//!
+//! [`DurableQueueSink`] appends every record to a write-ahead log on local
+//! disk before accepting it, and relays the log to the wrapped sink in the
+//! background, deleting records only once the sink has acknowledged them.
+//!
+//! The log is a directory of JSON Lines segments (`<n>.wal`) and an `ack`
+//! file holding the position up to which records were delivered. Segments
+//! are deleted once fully delivered; a torn last line left by a crash is
+//! cut off when the queue is opened again. With
+//! `DurableQueueConfig::encryption` the records in the segments are
+//! encrypted, see `encryption::SpillKey`.
+
+use crate::diagnostics::{DiagnosticKind, Diagnostics, DiagnosticsConfig};
+#[cfg(feature = "encryption")]
+use crate::encryption::SpillKey;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use crate::worker::WorkerScope;
+use async_trait::async_trait;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+/// Where and how a [`DurableQueueSink`] keeps its log.
+///
+/// **Fields**
+/// - `dir`: directory of the log, created if missing. Use a distinct
+///   directory per process.
+/// - `sync`: `fsync` every appended record before `send` returns, so an
+///   accepted record survives a power loss; without it, it survives only a
+///   crash of the process. Default `true`.
+/// - `segment_bytes`: size after which a new segment file is started.
+/// - `max_bytes`: undelivered bytes kept on disk; beyond it new sends fail
+///   with [`SinkError::Transient`], so the layer's worker holds on to them
+///   and retries. `None` leaves the log unbounded.
+/// - `relay_batch`: records handed to [`LogSink::send_batch`] at once.
+/// - `encryption` (feature `encryption`): encrypt appended records with
+///   this key. Records written without it are skipped as unreadable, so
+///   drain the log before setting a key. Default `None`.
+/// - `diagnostics`: where relay retries, rejected batches and unreadable
+///   records are reported, as [`DiagnosticKind::SendRetry`],
+///   [`DiagnosticKind::RecordRejected`] and [`DiagnosticKind::PersistFailed`].
+#[derive(Debug, Clone)]
+pub struct DurableQueueConfig {
+    pub dir: PathBuf,
+    pub sync: bool,
+    pub segment_bytes: u64,
+    pub max_bytes: Option<u64>,
+    pub relay_batch: usize,
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<SpillKey>,
+    pub diagnostics: DiagnosticsConfig,
+}
+
+impl DurableQueueConfig {
+    /// Keep the log in `dir`, with 16 MiB segments and up to 1 GiB of
+    /// undelivered records.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            sync: true,
+            segment_bytes: 16 * 1024 * 1024,
+            max_bytes: Some(1024 * 1024 * 1024),
+            relay_batch: 100,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+            diagnostics: DiagnosticsConfig::default(),
+        }
+    }
+
+    /// One line of the log for `record`, with its newline.
+    fn encode(&self, record: &LogRecord) -> Result<Vec<u8>, SinkError> {
+        let mut line = serde_json::to_vec(record)?;
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption {
+            line = key.seal(&line)?;
+        }
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    /// The record on a line of the log, `None` if it is unreadable.
+    fn decode(&self, line: &[u8]) -> Option<LogRecord> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption {
+            return serde_json::from_slice(&key.open(line)?).ok();
+        }
+        serde_json::from_slice(line).ok()
+    }
+}
+
+/// [`LogSink`] decorator that persists records locally before relaying
+/// them, see the [module documentation](self).
+///
+/// `send` returns once the record is in the log; a record accepted this
+/// way is delivered even if the process crashes or the wrapped sink is
+/// down for a long time, and is sent again after a restart with the same
+/// [`DurableQueueConfig::dir`]. Delivery is at-least-once: a batch failing
+/// with a retryable error is sent again with backoff, so records may
+/// arrive twice with the same `event_id`. Records the wrapped sink rejects
+/// permanently are dropped.
+///
+/// The relay runs on a task spawned on the Tokio runtime
+/// [`DurableQueueSink::open`] is called on, or that of the first send,
+/// and stops when the sink is dropped. Log writes are blocking file I/O
+/// on the calling task.
+pub struct DurableQueueSink<S> {
+    shared: Arc<Shared<S>>,
+    relay: OnceLock<tokio::task::JoinHandle<()>>,
+}
+
+struct Shared<S> {
+    inner: S,
+    config: DurableQueueConfig,
+    writer: Mutex<Writer>,
+    /// Held while a batch is relayed, so batches go out in order.
+    reader: tokio::sync::Mutex<Position>,
+    /// Bytes appended but not yet acknowledged.
+    queued: AtomicU64,
+    appended: tokio::sync::Notify,
+    diagnostics: Diagnostics,
+}
+
+/// Tail segment that records are appended to.
+struct Writer {
+    segment: u64,
+    file: File,
+    len: u64,
+}
+
+/// Offset in a segment of the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    segment: u64,
+    offset: u64,
+}
+
+/// Records read from the log by [`Shared::read`].
+struct Chunk {
+    records: Vec<LogRecord>,
+    /// Position after the last complete line read.
+    end: Position,
+}
+
+impl<S: LogSink + 'static> DurableQueueSink<S> {
+    /// Open or create the log in `config.dir` and relay it to `inner`,
+    /// starting with records left by a previous process.
+    pub fn open(inner: S, config: DurableQueueConfig) -> io::Result<Self> {
+        fs::create_dir_all(&config.dir)?;
+        let segments = segments(&config.dir)?;
+        let mut ack = read_ack(&config.dir)?.unwrap_or(Position {
+            segment: segments.first().copied().unwrap_or(1),
+            offset: 0,
+        });
+        // Segments before the acknowledged one were delivered; a crash
+        // may have left them behind.
+        for &segment in segments.iter().filter(|&&s| s < ack.segment) {
+            remove(&segment_path(&config.dir, segment))?;
+        }
+        if !segments.contains(&ack.segment) {
+            ack = Position {
+                segment: segments.iter().copied().find(|&s| s > ack.segment).unwrap_or(ack.segment),
+                offset: 0,
+            };
+        }
+
+        let tail = segments.last().copied().unwrap_or(ack.segment).max(ack.segment);
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(segment_path(&config.dir, tail))?;
+        let len = truncate_torn(&mut file)?;
+        let mut queued = 0;
+        for &segment in segments.iter().filter(|&&s| s >= ack.segment && s != tail) {
+            queued += fs::metadata(segment_path(&config.dir, segment))?.len();
+        }
+        queued = (queued + len).saturating_sub(ack.offset);
+
+        let sink = Self {
+            shared: Arc::new(Shared {
+                inner,
+                diagnostics: Diagnostics::new(&config.diagnostics),
+                config,
+                writer: Mutex::new(Writer { segment: tail, file, len }),
+                reader: tokio::sync::Mutex::new(ack),
+                queued: AtomicU64::new(queued),
+                appended: tokio::sync::Notify::new(),
+            }),
+            relay: OnceLock::new(),
+        };
+        if tokio::runtime::Handle::try_current().is_ok() {
+            sink.start_relay();
+        }
+        Ok(sink)
+    }
+
+    /// The wrapped sink.
+    pub fn inner(&self) -> &S {
+        &self.shared.inner
+    }
+
+    /// Bytes of records in the log that were not delivered yet.
+    pub fn queued_bytes(&self) -> u64 {
+        self.shared.queued.load(Ordering::Relaxed)
+    }
+
+    fn start_relay(&self) {
+        self.relay.get_or_init(|| tokio::spawn(WorkerScope::new(relay(self.shared.clone()))));
+    }
+}
+
+impl<S> Drop for DurableQueueSink<S> {
+    fn drop(&mut self) {
+        if let Some(relay) = self.relay.get() {
+            relay.abort();
+        }
+    }
+}
+
+/// Initial and maximum pause of the relay after a retryable failure.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+async fn relay<S: LogSink>(shared: Arc<Shared<S>>) {
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        let appended = shared.appended.notified();
+        match shared.relay_once().await {
+            Ok(true) => backoff = INITIAL_BACKOFF,
+            Ok(false) => appended.await,
+            Err(e) => {
+                shared.diagnostics.emit(DiagnosticKind::SendRetry, || {
+                    format!("durable queue relay failed, retrying: {}", e)
+                });
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+    }
+}
+
+impl<S: LogSink> Shared<S> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Writer> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Append one encoded record to the tail segment.
+    fn append(&self, line: &[u8]) -> Result<(), SinkError> {
+        let len = line.len() as u64;
+        if self
+            .config
+            .max_bytes
+            .is_some_and(|max| self.queued.load(Ordering::Relaxed) + len > max)
+        {
+            return Err(SinkError::transient("durable log queue is full"));
+        }
+        let mut writer = self.lock();
+        if writer.len > 0 && writer.len + len > self.config.segment_bytes {
+            if self.config.sync {
+                writer.file.sync_all()?;
+            }
+            let segment = writer.segment + 1;
+            let file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(segment_path(&self.config.dir, segment))?;
+            *writer = Writer { segment, file, len: 0 };
+        }
+        let written = writer.file.write_all(line).and_then(|()| {
+            if self.config.sync {
+                writer.file.sync_data()
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = written {
+            // Cut a partly written line, so the next record starts on a
+            // line of its own.
+            let _ = writer.file.set_len(writer.len);
+            return Err(e.into());
+        }
+        writer.len += len;
+        self.queued.fetch_add(len, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Send the next batch of the log to the inner sink and acknowledge it.
+    ///
+    /// **Returns**
+    /// - `Ok(false)` if there was nothing to send.
+    /// - A retryable `Err(..)` if the batch must be sent again.
+    async fn relay_once(&self) -> Result<bool, SinkError> {
+        let mut position = self.reader.lock().await;
+        let tail = self.lock().segment;
+        let chunk = self.read(*position, tail)?;
+        if chunk.end == *position {
+            return Ok(false);
+        }
+        if !chunk.records.is_empty() {
+            match self.inner.send_batch(&chunk.records).await {
+                Ok(()) => {}
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(e) => self.diagnostics.emit(DiagnosticKind::RecordRejected, || {
+                    format!("durable queue dropped {} record(s) rejected by the sink: {}", chunk.records.len(), e)
+                }),
+            }
+        }
+        self.ack(&mut position, chunk.end)?;
+        Ok(true)
+    }
+
+    /// Read up to `relay_batch` complete records from `from`, moving on to
+    /// the next segment at the end of a segment other than `tail`.
+    fn read(&self, from: Position, tail: u64) -> io::Result<Chunk> {
+        let mut file = File::open(segment_path(&self.config.dir, from.segment))?;
+        file.seek(SeekFrom::Start(from.offset))?;
+        let mut reader = BufReader::new(file);
+        let mut chunk = Chunk { records: Vec::new(), end: from };
+        let mut line = Vec::new();
+        let mut invalid = 0;
+        while chunk.records.len() < self.config.relay_batch.max(1) {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            // A line without its newline is still being written.
+            if read == 0 || line.last() != Some(&b'\n') {
+                break;
+            }
+            chunk.end.offset += read as u64;
+            match self.config.decode(&line) {
+                Some(record) => chunk.records.push(record),
+                None => invalid += 1,
+            }
+        }
+        if invalid > 0 {
+            self.diagnostics.emit(DiagnosticKind::PersistFailed, || {
+                format!("durable queue skipped {} unreadable record(s)", invalid)
+            });
+        }
+        if chunk.end == from && from.segment < tail {
+            chunk.end = Position { segment: from.segment + 1, offset: 0 };
+        }
+        Ok(chunk)
+    }
+
+    /// Record that everything before `end` was delivered.
+    fn ack(&self, position: &mut Position, end: Position) -> io::Result<()> {
+        write_ack(&self.config.dir, end, self.config.sync)?;
+        if end.segment != position.segment {
+            remove(&segment_path(&self.config.dir, position.segment))?;
+        } else {
+            self.queued.fetch_sub(end.offset - position.offset, Ordering::Relaxed);
+        }
+        *position = end;
+        Ok(())
+    }
+
+    /// Relay until the log is empty.
+    async fn drain(&self) -> Result<(), SinkError> {
+        while self.relay_once().await? {}
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, segment: u64) -> PathBuf {
+    dir.join(format!("{:020}.wal", segment))
+}
+
+/// Numbers of the segment files in `dir`, ascending.
+fn segments(dir: &Path) -> io::Result<Vec<u64>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let name = name.to_string_lossy();
+        if let Some(segment) = name.strip_suffix(".wal").and_then(|n| n.parse().ok()) {
+            segments.push(segment);
+        }
+    }
+    segments.sort_unstable();
+    Ok(segments)
+}
+
+fn read_ack(dir: &Path) -> io::Result<Option<Position>> {
+    let text = match fs::read_to_string(dir.join("ack")) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut parts = text.split_whitespace().map(str::parse::<u64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(segment)), Some(Ok(offset))) => Ok(Some(Position { segment, offset })),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "malformed durable queue ack file")),
+    }
+}
+
+/// Atomically replace the `ack` file with `position`.
+fn write_ack(dir: &Path, position: Position, sync: bool) -> io::Result<()> {
+    let tmp = dir.join("ack.tmp");
+    let mut file = File::create(&tmp)?;
+    writeln!(file, "{} {}", position.segment, position.offset)?;
+    if sync {
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, dir.join("ack"))
+}
+
+/// Cut `file` after its last newline, dropping a record torn by a crash.
+///
+/// **Returns**
+/// - The remaining length.
+fn truncate_torn(file: &mut File) -> io::Result<u64> {
+    let len = file.metadata()?.len();
+    let mut end = len;
+    let mut buf = [0u8; 4096];
+    while end > 0 {
+        let start = end.saturating_sub(buf.len() as u64);
+        let chunk = &mut buf[..(end - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        io::Read::read_exact(file, chunk)?;
+        if let Some(newline) = chunk.iter().rposition(|&b| b == b'\n') {
+            end = start + newline as u64 + 1;
+            break;
+        }
+        end = start;
+    }
+    if end != len {
+        file.set_len(end)?;
+    }
+    Ok(end)
+}
+
+fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+#[async_trait]
+impl<S: LogSink + 'static> LogSink for DurableQueueSink<S> {
+    /// Appends `record` to the log.
+    ///
+    /// **Returns**
+    /// - `Err(SinkError::Transient)` if the log is at `max_bytes` or could
+    ///   not be written.
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.start_relay();
+        let line = self.shared.config.encode(record)?;
+        self.shared.append(&line)?;
+        self.shared.appended.notify_one();
+        Ok(())
+    }
+
+    /// Relays the whole log, then flushes the wrapped sink.
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.shared.drain().await?;
+        self.shared.inner.flush().await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.shared.inner.health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        self.shared.inner.schema_drift().await
+    }
+
+    /// Stops the relay, sends what is left of the log and shuts the
+    /// wrapped sink down; records that could not be sent stay in the log
+    /// for the next start.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        if let Some(relay) = self.relay.get() {
+            relay.abort();
+        }
+        let drained = self.shared.drain().await;
+        let shut_down = self.shared.inner.shutdown().await;
+        drained.and(shut_down)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostics::{Diagnostic, DiagnosticsOutput};
+
+    /// Sink that is down: every send fails with a retryable error.
+    struct Down;
+
+    #[async_trait]
+    impl LogSink for Down {
+        async fn send(&self, _record: &LogRecord) -> Result<(), SinkError> {
+            Err(SinkError::transient("down"))
+        }
+    }
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<LogRecord>>);
+
+    #[async_trait]
+    impl LogSink for Collect {
+        async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
+    }
+
+    impl Collect {
+        fn messages(&self) -> Vec<String> {
+            self.0.lock().unwrap().iter().map(|r| r.message.clone().unwrap_or_default()).collect()
+        }
+    }
+
+    fn record(message: &str) -> LogRecord {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": message,
+            "fields": {},
+        }))
+        .unwrap()
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("tracing-log-sink-durable-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Fill the log of `dir` with `messages` while the sink is down.
+    async fn fill(dir: &Path, messages: &[&str]) {
+        let config = DurableQueueConfig {
+            diagnostics: DiagnosticsConfig {
+                output: DiagnosticsOutput::Silent,
+                ..DiagnosticsConfig::default()
+            },
+            ..DurableQueueConfig::new(dir)
+        };
+        let queue = DurableQueueSink::open(Down, config).unwrap();
+        for message in messages {
+            queue.send(&record(message)).await.unwrap();
+        }
+    }
+
+    fn tail(dir: &Path) -> PathBuf {
+        segment_path(dir, *segments(dir).unwrap().last().unwrap())
+    }
+
+    #[tokio::test]
+    async fn reopen_cuts_a_partly_written_record() {
+        let dir = dir("torn");
+        fill(&dir, &["one", "two"]).await;
+        let mut file = OpenOptions::new().append(true).open(tail(&dir)).unwrap();
+        file.write_all(br#"{"timestamp":"2026-01-01T00:0"#).unwrap();
+        drop(file);
+
+        let queue = DurableQueueSink::open(Collect::default(), DurableQueueConfig::new(&dir)).unwrap();
+        queue.send(&record("three")).await.unwrap();
+        queue.flush().await.unwrap();
+        assert_eq!(queue.inner().messages(), ["one", "two", "three"]);
+        assert_eq!(queue.queued_bytes(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn corrupted_tail_record_is_skipped_and_reported() {
+        let dir = dir("corrupted");
+        fill(&dir, &["one", "two"]).await;
+        let path = tail(&dir);
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes[..bytes.len() - 1].iter().rposition(|&b| b == b'\n').unwrap() + 1;
+        bytes[last] = b'#';
+        fs::write(&path, bytes).unwrap();
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let hook = {
+            let reported = reported.clone();
+            Arc::new(move |d: &Diagnostic| reported.lock().unwrap().push(d.kind))
+        };
+        let config = DurableQueueConfig {
+            diagnostics: DiagnosticsConfig {
+                output: DiagnosticsOutput::Callback(hook),
+                ..DiagnosticsConfig::default()
+            },
+            ..DurableQueueConfig::new(&dir)
+        };
+        let queue = DurableQueueSink::open(Collect::default(), config).unwrap();
+        queue.flush().await.unwrap();
+        assert_eq!(queue.inner().messages(), ["one"]);
+        assert_eq!(queue.queued_bytes(), 0);
+        assert_eq!(*reported.lock().unwrap(), [DiagnosticKind::PersistFailed]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn acknowledged_segments_are_removed() {
+        let dir = dir("segments");
+        let config = DurableQueueConfig {
+            segment_bytes: 1,
+            ..DurableQueueConfig::new(&dir)
+        };
+        let queue = DurableQueueSink::open(Collect::default(), config.clone()).unwrap();
+        for message in ["one", "two", "three"] {
+            queue.send(&record(message)).await.unwrap();
+        }
+        assert_eq!(segments(&dir).unwrap(), [1, 2, 3]);
+        queue.flush().await.unwrap();
+        assert_eq!(queue.inner().messages(), ["one", "two", "three"]);
+        assert_eq!(segments(&dir).unwrap(), [3]);
+        assert_eq!(read_ack(&dir).unwrap().map(|p| p.segment), Some(3));
+        drop(queue);
+
+        // A delivered segment left behind by a crash is removed on open.
+        fs::write(segment_path(&dir, 2), b"").unwrap();
+        let queue = DurableQueueSink::open(Collect::default(), config).unwrap();
+        assert_eq!(segments(&dir).unwrap(), [3]);
+        queue.flush().await.unwrap();
+        assert!(queue.inner().messages().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
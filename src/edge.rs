@@ -0,0 +1,226 @@
+//! Error capture for single-threaded edge runtimes (feature `wasm`).
+//!
+//! [`ErrorLogLayer`] needs threads and a Tokio runtime for its background
+//! worker, and `wasm32-unknown-unknown` offers neither. [`EdgeLayer`]
+//! builds the same [`LogRecord`]s but never spawns anything: records wait
+//! in a bounded in-memory buffer until the host awaits
+//! [`EdgeHandle::flush`], typically once per request from
+//! `ctx.wait_until(..)` in a Cloudflare Worker or from
+//! `wasm_bindgen_futures::spawn_local` in the browser. With the
+//! `clickhouse` or `opensearch` feature the built-in HTTP sinks work there
+//! as well, sending through `fetch`.
+//!
+//! The module also builds natively, so edge code can be tested off-target.
+//!
+//! [`ErrorLogLayer`]: crate::layer::ErrorLogLayer
+
+use crate::encoding::SerializedBatch;
+use crate::errors;
+use crate::fields::Fields;
+use crate::host;
+use crate::limits::RecordLimits;
+use crate::record::{LogLevel, LogRecord, SCHEMA_VERSION};
+use crate::resource::ResourceInfo;
+use crate::sink::{LogSink, SinkError};
+use crate::timestamp;
+use crate::trace_context;
+use crate::visitor::FieldVisitor;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use uuid::Uuid;
+
+/// Configuration of [`EdgeLayer`].
+///
+/// **Fields**
+/// - `level`: minimum level of captured events (default
+///   [`LogLevel::Error`]).
+/// - `max_buffered`: records kept until the next
+///   [`EdgeHandle::flush`]; further events are dropped and counted in
+///   [`EdgeHandle::dropped`] (default 1024).
+/// - `resource`: deployment metadata stamped onto every record.
+/// - `limits`: size caps applied to every record.
+#[derive(Debug, Clone)]
+pub struct EdgeConfig {
+    pub level: LogLevel,
+    pub max_buffered: usize,
+    pub resource: ResourceInfo,
+    pub limits: RecordLimits,
+}
+
+impl Default for EdgeConfig {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Error,
+            max_buffered: 1024,
+            resource: ResourceInfo::default(),
+            limits: RecordLimits::default(),
+        }
+    }
+}
+
+/// `tracing_subscriber` layer that buffers records for [`EdgeHandle::flush`].
+///
+/// Captures event fields, the message and W3C trace context carried by
+/// the event itself; span-based features of the threaded layer (span
+/// field inheritance, error-span reports) are not available, and records
+/// carry no hostname or pid.
+pub struct EdgeLayer {
+    buffer: Arc<Mutex<VecDeque<LogRecord>>>,
+    dropped: Arc<AtomicU64>,
+    level: tracing::Level,
+    max_buffered: usize,
+    resource: BTreeMap<String, String>,
+    limits: RecordLimits,
+}
+
+/// Sends the records buffered by an [`EdgeLayer`].
+#[derive(Clone)]
+pub struct EdgeHandle {
+    buffer: Arc<Mutex<VecDeque<LogRecord>>>,
+    dropped: Arc<AtomicU64>,
+    max_buffered: usize,
+    sink: Arc<dyn LogSink>,
+}
+
+impl EdgeLayer {
+    /// Create a layer buffering records for `sink`.
+    ///
+    /// **Returns**
+    /// - The layer to add to a subscriber and the [`EdgeHandle`] that
+    ///   sends its records.
+    pub fn new(sink: Arc<dyn LogSink>, config: &EdgeConfig) -> (Self, EdgeHandle) {
+        let buffer = Arc::new(Mutex::new(VecDeque::new()));
+        let dropped = Arc::new(AtomicU64::new(0));
+        let max_buffered = config.max_buffered.max(1);
+        let layer = EdgeLayer {
+            buffer: Arc::clone(&buffer),
+            dropped: Arc::clone(&dropped),
+            level: config.level.into(),
+            max_buffered,
+            resource: config.resource.to_map(),
+            limits: config.limits.clone(),
+        };
+        (layer, EdgeHandle { buffer, dropped, max_buffered, sink })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for EdgeLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if *meta.level() > self.level {
+            return;
+        }
+
+        let mut fields = Fields::new();
+        let mut message = None;
+        event.record(&mut FieldVisitor { fields: &mut fields, message: &mut message });
+        let trace = trace_context::extract(&mut fields);
+        let (error_kind, error_message) = errors::extract(&mut fields);
+        let (thread_id, thread_name) = host::current_thread();
+
+        let mut record = LogRecord {
+            schema_version: SCHEMA_VERSION,
+            timestamp: timestamp::now(),
+            level: LogLevel::from(*meta.level()),
+            target: Cow::Borrowed(meta.target()),
+            module_path: meta.module_path().map(Cow::Borrowed),
+            file: meta.file().map(Cow::Borrowed),
+            line: meta.line(),
+            fields,
+            message,
+            service_name: None,
+            hostname: None,
+            pid: None,
+            thread_id: Some(thread_id),
+            thread_name,
+            resource: self.resource.clone(),
+            event_id: Some(Uuid::now_v7()),
+            repeat_count: None,
+            fingerprint: None,
+            trace_id: trace.as_ref().map(|t| t.trace_id.clone()),
+            span_id: trace.and_then(|t| t.span_id),
+            span_name: None,
+            duration_ms: None,
+            span_elapsed_ms: None,
+            error_kind,
+            error_message,
+        };
+        self.limits.apply(&mut record);
+
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        if buffer.len() < self.max_buffered {
+            buffer.push_back(record);
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl EdgeHandle {
+    /// Send every buffered record, then flush the sink.
+    ///
+    /// Records are sent one after another on the calling task; there is
+    /// no backoff, as edge runtimes have no timer to wait on.
+    ///
+    /// **Returns**
+    /// - `Ok(n)` with the number of records the sink accepted. Records it
+    ///   rejected permanently are dropped.
+    /// - `Err(..)` on the first retryable failure; that record and the
+    ///   ones after it stay buffered for the next call.
+    pub async fn flush(&self) -> Result<usize, SinkError> {
+        let records: Vec<LogRecord> = self.lock().drain(..).collect();
+        let batch = SerializedBatch::new(&records);
+        let mut sent = 0;
+        let mut failure = None;
+        for index in 0..batch.len() {
+            match self.sink.send_serialized(&batch, index).await {
+                Ok(()) => sent += 1,
+                Err(e) if !e.is_retryable() => {}
+                Err(e) => {
+                    failure = Some((index, e));
+                    break;
+                }
+            }
+        }
+        drop(batch);
+
+        if let Some((index, e)) = failure {
+            self.requeue(records.into_iter().skip(index));
+            return Err(e);
+        }
+        self.sink.flush().await?;
+        Ok(sent)
+    }
+
+    /// Number of records waiting for [`EdgeHandle::flush`].
+    pub fn buffered(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Records dropped because the buffer was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Put unsent records back in front of those captured since, dropping
+    /// the newest ones that no longer fit.
+    fn requeue(&self, unsent: impl DoubleEndedIterator<Item = LogRecord>) {
+        let mut buffer = self.lock();
+        for record in unsent.rev() {
+            buffer.push_front(record);
+        }
+        if buffer.len() > self.max_buffered {
+            let excess = buffer.len() - self.max_buffered;
+            buffer.truncate(self.max_buffered);
+            self.dropped.fetch_add(excess as u64, Ordering::Relaxed);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<LogRecord>> {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,200 @@
+use crate::record::LogRecord;
+use crate::sink::{BoxError, SinkError};
+use std::sync::OnceLock;
+
+/// Wire format of a single [`LogRecord`] for message-oriented sinks such
+/// as [`crate::kafka::KafkaSink`].
+///
+/// JSON, MessagePack and CBOR encode the same serde representation of the
+/// record (a map keyed by field name); Protobuf uses the schema in
+/// `proto/log_record.proto`. A record round-trips through any of them.
+/// The binary formats are typically around 40% smaller than JSON.
+///
+/// - `Json`: UTF-8 JSON object, the default.
+/// - `MessagePack` (feature `msgpack`): MessagePack map.
+/// - `Cbor` (feature `cbor`): CBOR map (RFC 8949).
+/// - `Protobuf` (feature `proto`): `tracing_log_sink.v1.LogRecord` from
+///   `proto/log_record.proto`, see [`crate::proto`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    #[default]
+    Json,
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+    #[cfg(feature = "cbor")]
+    Cbor,
+    #[cfg(feature = "proto")]
+    Protobuf,
+}
+
+/// Error returned by [`PayloadEncoding::decode`].
+#[derive(thiserror::Error, Debug)]
+#[error("failed to decode {encoding:?} log record: {source}")]
+pub struct DecodeError {
+    pub encoding: PayloadEncoding,
+    #[source]
+    pub source: BoxError,
+}
+
+impl PayloadEncoding {
+    /// MIME type of the encoded payload, e.g. for a `content-type` header.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            PayloadEncoding::Json => "application/json",
+            #[cfg(feature = "msgpack")]
+            PayloadEncoding::MessagePack => "application/msgpack",
+            #[cfg(feature = "cbor")]
+            PayloadEncoding::Cbor => "application/cbor",
+            #[cfg(feature = "proto")]
+            PayloadEncoding::Protobuf => "application/x-protobuf",
+        }
+    }
+
+    /// Encode `record` in this format.
+    ///
+    /// **Returns**
+    /// - The payload bytes, or [`SinkError::Serialization`].
+    pub fn encode(&self, record: &LogRecord) -> Result<Vec<u8>, SinkError> {
+        match self {
+            PayloadEncoding::Json => Ok(serde_json::to_vec(record)?),
+            #[cfg(feature = "msgpack")]
+            PayloadEncoding::MessagePack => {
+                rmp_serde::to_vec_named(record).map_err(|e| SinkError::Serialization(Box::new(e)))
+            }
+            #[cfg(feature = "cbor")]
+            PayloadEncoding::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(record, &mut buf)
+                    .map_err(|e| SinkError::Serialization(Box::new(e)))?;
+                Ok(buf)
+            }
+            #[cfg(feature = "proto")]
+            PayloadEncoding::Protobuf => Ok(crate::proto::encode(record)),
+        }
+    }
+
+    /// Decode a payload produced by [`PayloadEncoding::encode`], for Rust
+    /// consumers of the topic.
+    ///
+    /// **Parameters**
+    /// - `payload`: message bytes as read from the broker.
+    ///
+    /// **Returns**
+    /// - The decoded record, or [`DecodeError`] if the payload is not a
+    ///   valid record in this format.
+    pub fn decode(&self, payload: &[u8]) -> Result<LogRecord, DecodeError> {
+        let result: Result<LogRecord, BoxError> = match self {
+            PayloadEncoding::Json => serde_json::from_slice(payload).map_err(Into::into),
+            #[cfg(feature = "msgpack")]
+            PayloadEncoding::MessagePack => rmp_serde::from_slice(payload).map_err(Into::into),
+            #[cfg(feature = "cbor")]
+            PayloadEncoding::Cbor => ciborium::from_reader(payload).map_err(Into::into),
+            #[cfg(feature = "proto")]
+            PayloadEncoding::Protobuf => crate::proto::decode(payload).map_err(Into::into),
+        };
+        result.map_err(|source| DecodeError { encoding: *self, source })
+    }
+}
+
+/// Number of [`PayloadEncoding`] variants across all features.
+const ENCODINGS: usize = 4;
+
+impl PayloadEncoding {
+    /// Cache slot of this encoding in a [`SerializedBatch`].
+    fn slot(&self) -> usize {
+        match self {
+            PayloadEncoding::Json => 0,
+            #[cfg(feature = "msgpack")]
+            PayloadEncoding::MessagePack => 1,
+            #[cfg(feature = "cbor")]
+            PayloadEncoding::Cbor => 2,
+            #[cfg(feature = "proto")]
+            PayloadEncoding::Protobuf => 3,
+        }
+    }
+}
+
+/// A batch of records handed to a sink, with each record's encodings
+/// computed on first use and kept for the rest of the batch.
+///
+/// The worker builds one per batch and passes it to
+/// [`LogSink::send_serialized`], so a record retried after a transient
+/// failure, or read by several sinks, is serialized only once per
+/// encoding.
+///
+/// [`LogSink::send_serialized`]: crate::sink::LogSink::send_serialized
+pub struct SerializedBatch<'a> {
+    records: &'a [LogRecord],
+    encoded: Vec<[OnceLock<Vec<u8>>; ENCODINGS]>,
+    fields: Vec<OnceLock<String>>,
+}
+
+impl<'a> SerializedBatch<'a> {
+    /// Wrap `records`; nothing is serialized until requested.
+    pub fn new(records: &'a [LogRecord]) -> Self {
+        Self {
+            records,
+            encoded: records.iter().map(|_| Default::default()).collect(),
+            fields: records.iter().map(|_| OnceLock::new()).collect(),
+        }
+    }
+
+    /// The records of the batch.
+    pub fn records(&self) -> &'a [LogRecord] {
+        self.records
+    }
+
+    /// Number of records in the batch.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Whether the batch has no records.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Record `index` of the batch.
+    ///
+    /// Panics if `index` is out of bounds, like slice indexing.
+    pub fn record(&self, index: usize) -> &'a LogRecord {
+        &self.records[index]
+    }
+
+    /// Record `index` encoded with `encoding`, as by
+    /// [`PayloadEncoding::encode`].
+    ///
+    /// **Returns**
+    /// - The cached payload, or [`SinkError::Serialization`]; failed
+    ///   encodings are not cached.
+    pub fn encoded(&self, index: usize, encoding: PayloadEncoding) -> Result<&[u8], SinkError> {
+        let slot = &self.encoded[index][encoding.slot()];
+        if let Some(payload) = slot.get() {
+            return Ok(payload);
+        }
+        let payload = encoding.encode(&self.records[index])?;
+        Ok(slot.get_or_init(|| payload))
+    }
+
+    /// [`LogRecord::fields`] of record `index` as a JSON object string, for
+    /// sinks that store the fields in a single text column.
+    pub fn fields_json(&self, index: usize) -> Result<&str, SinkError> {
+        let slot = &self.fields[index];
+        if let Some(json) = slot.get() {
+            return Ok(json);
+        }
+        let json = serde_json::to_string(&self.records[index].fields)?;
+        Ok(slot.get_or_init(|| json))
+    }
+
+    /// The whole batch as JSON lines: one [`PayloadEncoding::Json`] record
+    /// per line, each terminated by `\n`.
+    pub fn json_lines(&self) -> Result<Vec<u8>, SinkError> {
+        let mut body = Vec::new();
+        for index in 0..self.len() {
+            body.extend_from_slice(self.encoded(index, PayloadEncoding::Json)?);
+            body.push(b'\n');
+        }
+        Ok(body)
+    }
+}
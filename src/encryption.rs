@@ -0,0 +1,203 @@
+//! Encryption at rest of records spooled to local disk.
+//!
+//! With a [`SpillKey`] in [`PersistConfig::encryption`] or
+//! [`DurableQueueConfig::encryption`], every record is written as one line
+//! of base64 holding a random 96-bit nonce followed by the AES-256-GCM
+//! ciphertext and tag of its JSON, so payloads never sit in plaintext on
+//! the node's disk. With a key set, only lines that decrypt and
+//! authenticate with it are read: plain JSON lines, lines sealed with
+//! another key and tampered lines count as unreadable and are skipped, so
+//! records cannot be slipped into the spool without the key. Records
+//! spooled before the key was set can be sent with `logrec replay`
+//! without `--key-file`.
+//!
+//! Nonces are random, so a key should be rotated well before 2^32 records
+//! were written with it.
+//!
+//! [`PersistConfig::encryption`]: crate::persist::PersistConfig::encryption
+//! [`DurableQueueConfig::encryption`]: crate::durable::DurableQueueConfig::encryption
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine as _;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Length of a [`SpillKey`] in bytes.
+pub const KEY_LEN: usize = 32;
+
+/// Error returned when a [`SpillKey`] cannot be loaded.
+#[derive(thiserror::Error, Debug)]
+pub enum KeyError {
+    #[error("{0} is not set")]
+    MissingVar(String),
+
+    #[error("cannot read key file {}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("spill key must be {KEY_LEN} bytes, base64-encoded")]
+    Invalid,
+}
+
+/// AES-256-GCM key for records spooled to disk, see the
+/// [module documentation](self).
+///
+/// `Debug` shows only a short fingerprint of the key, e.g.
+/// `SpillKey { id: "3f2a9c01" }`; two keys are equal if their bytes are.
+#[derive(Clone)]
+pub struct SpillKey {
+    key: Arc<LessSafeKey>,
+    fingerprint: [u8; 32],
+}
+
+impl SpillKey {
+    /// Key from its raw bytes.
+    ///
+    /// **Returns**
+    /// - `Err(KeyError::Invalid)` unless `bytes` is [`KEY_LEN`] bytes long.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyError> {
+        if bytes.len() != KEY_LEN {
+            return Err(KeyError::Invalid);
+        }
+        let key = UnboundKey::new(&AES_256_GCM, bytes).map_err(|_| KeyError::Invalid)?;
+        let mut fingerprint = [0u8; 32];
+        fingerprint.copy_from_slice(ring::digest::digest(&ring::digest::SHA256, bytes).as_ref());
+        Ok(Self {
+            key: Arc::new(LessSafeKey::new(key)),
+            fingerprint,
+        })
+    }
+
+    /// Key from base64 text, e.g. the output of `openssl rand -base64 32`;
+    /// surrounding whitespace is ignored.
+    pub fn from_base64(text: &str) -> Result<Self, KeyError> {
+        let bytes = STANDARD.decode(text.trim()).map_err(|_| KeyError::Invalid)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Key from the base64 value of the environment variable `name`.
+    pub fn from_env(name: &str) -> Result<Self, KeyError> {
+        match std::env::var(name) {
+            Ok(text) => Self::from_base64(&text),
+            Err(_) => Err(KeyError::MissingVar(name.to_string())),
+        }
+    }
+
+    /// Key from a file holding either the base64 key or its
+    /// [`KEY_LEN`] raw bytes, e.g. a mounted secret.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|source| KeyError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        match std::str::from_utf8(&bytes).ok().map(Self::from_base64) {
+            Some(Ok(key)) => Ok(key),
+            _ => Self::from_bytes(&bytes),
+        }
+    }
+
+    /// Encrypt the JSON of one record into a line of base64, without the
+    /// trailing newline.
+    pub(crate) fn seal(&self, plaintext: &[u8]) -> io::Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| io::Error::other("no randomness for the spill encryption nonce"))?;
+        let mut ciphertext = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut ciphertext)
+            .map_err(|_| io::Error::other("spill encryption failed"))?;
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(STANDARD.encode(sealed).into_bytes())
+    }
+
+    /// The JSON of the record sealed on `line`.
+    ///
+    /// **Returns**
+    /// - `None` if `line` does not decrypt and authenticate with this key,
+    ///   including plain JSON.
+    pub(crate) fn open(&self, line: &[u8]) -> Option<Vec<u8>> {
+        let mut sealed = STANDARD.decode(line.trim_ascii()).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, ciphertext) = sealed.split_at_mut(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+        let plaintext = self.key.open_in_place(nonce, Aad::empty(), ciphertext).ok()?;
+        Some(plaintext.to_vec())
+    }
+}
+
+impl fmt::Debug for SpillKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let id: String = self.fingerprint[..4].iter().map(|b| format!("{:02x}", b)).collect();
+        f.debug_struct("SpillKey").field("id", &id).finish()
+    }
+}
+
+impl PartialEq for SpillKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl Eq for SpillKey {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &[u8] = br#"{"level":"ERROR","message":"card declined"}"#;
+
+    fn key(byte: u8) -> SpillKey {
+        SpillKey::from_bytes(&[byte; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = key(1);
+        let sealed = key.seal(RECORD).unwrap();
+        assert!(!sealed.windows(4).any(|w| w == b"card"));
+        assert_eq!(key.open(&sealed).as_deref(), Some(RECORD));
+        // The nonce is random, so the same record seals differently.
+        assert_ne!(key.seal(RECORD).unwrap(), sealed);
+    }
+
+    #[test]
+    fn wrong_key_does_not_open() {
+        let sealed = key(1).seal(RECORD).unwrap();
+        assert_eq!(key(2).open(&sealed), None);
+    }
+
+    #[test]
+    fn tampered_line_does_not_open() {
+        let key = key(1);
+        let mut sealed = STANDARD.decode(key.seal(RECORD).unwrap()).unwrap();
+        let last = sealed.len() - 1;
+        sealed[NONCE_LEN] ^= 1;
+        assert_eq!(key.open(STANDARD.encode(&sealed).as_bytes()), None);
+        sealed[NONCE_LEN] ^= 1;
+        sealed[last] ^= 1;
+        assert_eq!(key.open(STANDARD.encode(&sealed).as_bytes()), None);
+        assert_eq!(key.open(&STANDARD.encode(&sealed[..NONCE_LEN - 1]).into_bytes()), None);
+    }
+
+    #[test]
+    fn plain_json_is_rejected() {
+        assert_eq!(key(1).open(RECORD), None);
+    }
+
+    #[test]
+    fn key_from_base64() {
+        let text = format!(" {}\n", STANDARD.encode([7u8; KEY_LEN]));
+        assert_eq!(SpillKey::from_base64(&text).unwrap(), key(7));
+        assert!(matches!(SpillKey::from_base64("c2hvcnQ="), Err(KeyError::Invalid)));
+        assert_eq!(format!("{:?}", key(7)).len(), "SpillKey { id: \"00000000\" }".len());
+    }
+}
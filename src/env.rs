@@ -1,8 +1,54 @@
-/// Environment variable names used by this crate for convenient
-/// configuration of sinks from microservices.
-///
-/// These are purely helpers; the core sink types remain decoupled from
-/// environment access.
+//! Environment variable names used by this crate for convenient
+//! configuration of sinks from microservices.
+//!
+//! These are purely helpers; the core sink types remain decoupled from
+//! environment access. [`from_env`] builds a complete sink and
+//! [`LayerConfig`] from these variables so a service can be configured
+//! entirely by its deployment environment.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::time::Duration;
+
+use crate::backend::{self, BackendBuildError, DsnError};
+use crate::init::LayerConfig;
+use crate::record::LogLevel;
+use crate::resource::ResourceInfo;
+use crate::sink::LogSink;
+
+/// Backend DSN, e.g. `clickhouse://...` or `postgres://...`. Takes
+/// precedence over the backend-specific variables below.
+pub const LOG_SINK_DSN_ENV: &str = "LOG_SINK_DSN";
+
+/// [`LayerConfig::channel_buffer`].
+pub const LOG_SINK_CHANNEL_BUFFER_ENV: &str = "LOG_SINK_CHANNEL_BUFFER";
+
+/// [`LayerConfig::batch_size`].
+pub const LOG_SINK_BATCH_SIZE_ENV: &str = "LOG_SINK_BATCH_SIZE";
+
+/// [`LayerConfig::flush_interval`] in milliseconds.
+pub const LOG_SINK_FLUSH_MS_ENV: &str = "LOG_SINK_FLUSH_MS";
+
+/// [`LayerConfig::level`], e.g. `error` or `warn`.
+pub const LOG_SINK_LEVEL_ENV: &str = "LOG_SINK_LEVEL";
+
+/// [`LayerConfig::enable_stdout`]: `true`/`false`, `1`/`0`, `yes`/`no`.
+pub const LOG_SINK_STDOUT_ENV: &str = "LOG_SINK_STDOUT";
+
+/// [`ResourceInfo::environment`].
+pub const LOG_SINK_ENVIRONMENT_ENV: &str = "LOG_SINK_ENVIRONMENT";
+
+/// [`ResourceInfo::region`].
+pub const LOG_SINK_REGION_ENV: &str = "LOG_SINK_REGION";
+
+/// [`ResourceInfo::version`].
+pub const LOG_SINK_VERSION_ENV: &str = "LOG_SINK_VERSION";
+
+/// [`ResourceInfo::git_sha`].
+pub const LOG_SINK_GIT_SHA_ENV: &str = "LOG_SINK_GIT_SHA";
+
+/// [`ResourceInfo::detect`]: sources to detect, e.g. `docker,ec2`.
+pub const LOG_SINK_DETECT_ENV: &str = "LOG_SINK_DETECT";
 
 /// ClickHouse base HTTP URL, e.g. `http://127.0.0.1:8123`.
 pub const LOG_SINK_CLICKHOUSE_URL_ENV: &str = "LOG_SINK_CLICKHOUSE_URL";
@@ -26,3 +72,125 @@ pub const LOG_SINK_SERVICE_NAME_ENV: &str = "LOG_SINK_SERVICE_NAME";
 pub fn env_or(key: &str, default: &str) -> String {
     std::env::var(key).unwrap_or_else(|_| default.to_string())
 }
+
+/// Error returned by [`from_env`].
+#[derive(thiserror::Error, Debug)]
+pub enum EnvConfigError {
+    #[error("neither {LOG_SINK_DSN_ENV} nor {LOG_SINK_CLICKHOUSE_URL_ENV} is set")]
+    NoBackend,
+
+    #[error("invalid value {value:?} for {key}")]
+    InvalidValue { key: &'static str, value: String },
+
+    #[error(transparent)]
+    Dsn(#[from] DsnError),
+
+    #[error(transparent)]
+    Build(#[from] BackendBuildError),
+}
+
+/// Build a sink and a [`LayerConfig`] from environment variables.
+///
+/// **Behavior**
+/// - The sink comes from [`LOG_SINK_DSN_ENV`] if set, otherwise from the
+///   `LOG_SINK_CLICKHOUSE_*` variables (feature `clickhouse`).
+/// - Layer options not present in the environment keep their
+///   [`LayerConfig::default`] values.
+///
+/// **Returns**
+/// - `Err(..)` if no backend is configured, a variable cannot be parsed,
+///   or the backend cannot be built.
+pub fn from_env() -> Result<(Arc<dyn LogSink>, LayerConfig), EnvConfigError> {
+    let config = layer_config_from_env()?;
+    let sink = sink_from_env()?;
+    Ok((sink, config))
+}
+
+/// Build only the [`LayerConfig`] part of [`from_env`].
+///
+/// Options from the query string of [`LOG_SINK_DSN_ENV`] (see
+/// [`backend::DsnOptions`]) are applied first; the dedicated variables
+/// override them.
+pub fn layer_config_from_env() -> Result<LayerConfig, EnvConfigError> {
+    let mut config = LayerConfig::default();
+
+    if let Some(dsn) = var(LOG_SINK_DSN_ENV) {
+        backend::parse_dsn(&dsn)?.options.apply_to(&mut config);
+    }
+
+    if let Some(buffer) = parse_var(LOG_SINK_CHANNEL_BUFFER_ENV)? {
+        config.channel_buffer = buffer;
+    }
+    if let Some(batch_size) = parse_var(LOG_SINK_BATCH_SIZE_ENV)? {
+        config.batch_size = batch_size;
+    }
+    if let Some(flush_ms) = parse_var::<u64>(LOG_SINK_FLUSH_MS_ENV)? {
+        config.flush_interval = Duration::from_millis(flush_ms);
+    }
+    if let Some(level) = parse_var::<LogLevel>(LOG_SINK_LEVEL_ENV)? {
+        config.level = level;
+    }
+    if let Some(stdout) = var(LOG_SINK_STDOUT_ENV) {
+        config.enable_stdout = parse_bool(LOG_SINK_STDOUT_ENV, &stdout)?;
+    }
+
+    config.resource = ResourceInfo {
+        environment: var(LOG_SINK_ENVIRONMENT_ENV),
+        region: var(LOG_SINK_REGION_ENV),
+        version: var(LOG_SINK_VERSION_ENV),
+        git_sha: var(LOG_SINK_GIT_SHA_ENV),
+        detect: parse_var(LOG_SINK_DETECT_ENV)?.unwrap_or_default(),
+        ..ResourceInfo::default()
+    };
+
+    Ok(config)
+}
+
+fn sink_from_env() -> Result<Arc<dyn LogSink>, EnvConfigError> {
+    if let Some(dsn) = var(LOG_SINK_DSN_ENV) {
+        let backend_cfg = backend::parse_dsn(&dsn)?;
+        return Ok(backend::make_sink_blocking(&backend_cfg)?);
+    }
+
+    #[cfg(feature = "clickhouse")]
+    if let Some(url) = var(LOG_SINK_CLICKHOUSE_URL_ENV) {
+        use crate::clickhouse::{ClickHouseConfig, ClickHouseSink};
+
+        let config = ClickHouseConfig {
+            url,
+            database: env_or(LOG_SINK_CLICKHOUSE_DB_ENV, "default"),
+            table: env_or(LOG_SINK_CLICKHOUSE_TABLE_ENV, "logs"),
+            service_name: var(LOG_SINK_SERVICE_NAME_ENV),
+            user: var(LOG_SINK_CLICKHOUSE_USER_ENV),
+            password: var(LOG_SINK_CLICKHOUSE_PASSWORD_ENV),
+            ..ClickHouseConfig::default()
+        };
+        return Ok(Arc::new(ClickHouseSink::new(config)));
+    }
+
+    Err(EnvConfigError::NoBackend)
+}
+
+/// Read a non-empty environment variable.
+fn var(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|v| !v.is_empty())
+}
+
+fn parse_var<T: FromStr>(key: &'static str) -> Result<Option<T>, EnvConfigError> {
+    match var(key) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| EnvConfigError::InvalidValue { key, value }),
+        None => Ok(None),
+    }
+}
+
+fn parse_bool(key: &'static str, value: &str) -> Result<bool, EnvConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(EnvConfigError::InvalidValue { key, value: value.to_string() }),
+    }
+}
@@ -0,0 +1,132 @@
+use crate::fields::Fields;
+use serde_json::Value;
+use std::error::Error;
+
+/// Field holding the kind of the error an event reports, e.g. `"NotFound"`
+/// for an `io::Error` or `"ParseIntError"`.
+pub const ERROR_KIND_FIELD: &str = "error.kind";
+
+/// Field holding the `Display` output of the error.
+pub const ERROR_MESSAGE_FIELD: &str = "error.message";
+
+/// Field holding the messages of the error's `source()` chain, outermost
+/// first, as an array.
+pub const ERROR_CHAIN_FIELD: &str = "error.chain";
+
+/// Log `err` at `ERROR` level under the `error.*` field convention.
+///
+/// `log_error!(err, "context")` is `tracing::error!` with `err` recorded as
+/// the `error` field, so the event carries [`ERROR_KIND_FIELD`],
+/// [`ERROR_MESSAGE_FIELD`] and [`ERROR_CHAIN_FIELD`]; the layer promotes
+/// the kind and message into [`LogRecord::error_kind`] and
+/// [`LogRecord::error_message`]. Everything after the error is passed to
+/// `error!` as is, so fields and format arguments work as usual:
+/// `log_error!(err, order_id, "charging {} failed", card)`.
+///
+/// `err` is borrowed, and may be any `Error + 'static` value or a value
+/// that dereferences to `dyn Error`, such as `Box<dyn Error + Send + Sync>`
+/// or `anyhow::Error`.
+///
+/// [`LogRecord::error_kind`]: crate::record::LogRecord::error_kind
+/// [`LogRecord::error_message`]: crate::record::LogRecord::error_message
+#[macro_export]
+macro_rules! log_error {
+    ($err:expr $(,)?) => {{
+        use $crate::errors::AsDynError as _;
+        ::tracing::error!(error = ($err).as_dyn_error())
+    }};
+    ($err:expr, $($rest:tt)+) => {{
+        use $crate::errors::AsDynError as _;
+        ::tracing::error!(error = ($err).as_dyn_error(), $($rest)+)
+    }};
+}
+
+/// Borrow a value as `&dyn Error`, so `tracing` records it with
+/// `record_error` rather than as a string; used by [`log_error!`].
+pub trait AsDynError {
+    fn as_dyn_error(&self) -> &(dyn Error + 'static);
+}
+
+impl<E: Error + 'static> AsDynError for E {
+    fn as_dyn_error(&self) -> &(dyn Error + 'static) {
+        self
+    }
+}
+
+impl AsDynError for dyn Error + 'static {
+    fn as_dyn_error(&self) -> &(dyn Error + 'static) {
+        self
+    }
+}
+
+impl AsDynError for dyn Error + Send + 'static {
+    fn as_dyn_error(&self) -> &(dyn Error + 'static) {
+        self
+    }
+}
+
+impl AsDynError for dyn Error + Send + Sync + 'static {
+    fn as_dyn_error(&self) -> &(dyn Error + 'static) {
+        self
+    }
+}
+
+/// Kind of `err`: the [`std::io::ErrorKind`] of an `io::Error`, otherwise
+/// the leading identifier of its `Debug` output (the type or variant
+/// name for derived `Debug`).
+pub fn kind(err: &(dyn Error + 'static)) -> Option<String> {
+    if let Some(io) = err.downcast_ref::<std::io::Error>() {
+        return Some(format!("{:?}", io.kind()));
+    }
+    let debug = format!("{:?}", err);
+    let kind: String = debug
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .collect();
+    (!kind.is_empty()).then_some(kind)
+}
+
+/// Messages of the `source()` chain of `err`, outermost first, not
+/// including `err` itself.
+pub fn chain(err: &(dyn Error + 'static)) -> Vec<String> {
+    let mut chain = Vec::new();
+    let mut source = err.source();
+    while let Some(cause) = source {
+        chain.push(cause.to_string());
+        source = cause.source();
+    }
+    chain
+}
+
+/// Insert `<name>.message`, `<name>.chain` and, if known, `<name>.type`
+/// and `<name>.kind` for `err` into `fields`; how the layer records error
+/// values.
+///
+/// `<name>.type` and `<name>.kind` hold the same value: `.type` is the
+/// original name of the field, `.kind` the one the layer promotes into
+/// [`LogRecord::error_kind`](crate::record::LogRecord::error_kind).
+pub fn insert_fields(fields: &mut Fields, name: &str, err: &(dyn Error + 'static)) {
+    fields.insert(format!("{}.message", name), Value::String(err.to_string()));
+    fields.insert(
+        format!("{}.chain", name),
+        Value::Array(chain(err).into_iter().map(Value::String).collect()),
+    );
+    if let Some(kind) = kind(err) {
+        fields.insert(format!("{}.type", name), Value::String(kind.clone()));
+        fields.insert(format!("{}.kind", name), Value::String(kind));
+    }
+}
+
+/// Take the string [`ERROR_KIND_FIELD`] and [`ERROR_MESSAGE_FIELD`] out of
+/// event `fields` for the dedicated record columns.
+pub(crate) fn extract(fields: &mut Fields) -> (Option<String>, Option<String>) {
+    let mut take = |key: &str| match fields.remove(key) {
+        Some(Value::String(s)) => Some(s),
+        Some(other) => {
+            fields.insert(key.to_string(), other);
+            None
+        }
+        None => None,
+    };
+    (take(ERROR_KIND_FIELD), take(ERROR_MESSAGE_FIELD))
+}
@@ -0,0 +1,200 @@
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::encoding::SerializedBatch;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// When a [`FailoverSink`] switches between its sinks.
+///
+/// **Fields**
+/// - `failure_threshold`: consecutive retryable failures of the primary
+///   after which records go to the secondary.
+/// - `probe_interval`: while failed over, how often the primary's
+///   [`LogSink::health_check`] is tried; probes run as part of sends, so
+///   there are none while nothing is logged.
+/// - `recovery_probes`: consecutive successful probes after which records
+///   go to the primary again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverConfig {
+    pub failure_threshold: u32,
+    pub probe_interval: Duration,
+    pub recovery_probes: u32,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            probe_interval: Duration::from_secs(10),
+            recovery_probes: 2,
+        }
+    }
+}
+
+/// [`LogSink`] that sends to a primary sink and falls back to a secondary
+/// one while the primary keeps failing, e.g. Kafka or a local sink while
+/// ClickHouse is down for maintenance.
+///
+/// Only retryable errors count as failures of the primary; records it
+/// rejects are rejected as usual. The send that reaches
+/// `failure_threshold` already goes to the secondary, so no record waits
+/// for the switch. Records do not move back after recovery: whatever the
+/// secondary accepted stays there.
+pub struct FailoverSink {
+    primary: Arc<dyn LogSink>,
+    secondary: Arc<dyn LogSink>,
+    config: FailoverConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    failed_over: bool,
+    failures: u32,
+    successful_probes: u32,
+    last_probe: Option<Instant>,
+}
+
+impl FailoverSink {
+    /// Send to `primary`, switching to `secondary` as `config` says.
+    pub fn new(primary: Arc<dyn LogSink>, secondary: Arc<dyn LogSink>, config: FailoverConfig) -> Self {
+        Self {
+            primary,
+            secondary,
+            config,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Whether records currently go to the secondary sink.
+    pub fn is_failed_over(&self) -> bool {
+        self.lock().failed_over
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// While failed over, probe the primary if a probe is due.
+    ///
+    /// **Returns**
+    /// - Whether records should go to the primary.
+    async fn use_primary(&self) -> bool {
+        {
+            let mut state = self.lock();
+            if !state.failed_over {
+                return true;
+            }
+            let now = Instant::now();
+            if state.last_probe.is_some_and(|last| now.duration_since(last) < self.config.probe_interval) {
+                return false;
+            }
+            state.last_probe = Some(now);
+        }
+        let healthy = self.primary.health_check().await.is_ok();
+        let mut state = self.lock();
+        if !state.failed_over {
+            return true;
+        }
+        if !healthy {
+            state.successful_probes = 0;
+            return false;
+        }
+        state.successful_probes += 1;
+        if state.successful_probes < self.config.recovery_probes.max(1) {
+            return false;
+        }
+        *state = State::default();
+        tracing::info!(target: DIAGNOSTICS_TARGET, "primary log sink recovered, switching back to it");
+        true
+    }
+
+    /// Record the outcome of a send to the primary.
+    ///
+    /// **Returns**
+    /// - Whether the failure made the sink fail over.
+    fn primary_result(&self, result: &Result<(), SinkError>) -> bool {
+        let mut state = self.lock();
+        match result {
+            Ok(()) => state.failures = 0,
+            Err(e) if e.is_retryable() => {
+                state.failures += 1;
+                if !state.failed_over && state.failures >= self.config.failure_threshold.max(1) {
+                    state.failed_over = true;
+                    state.successful_probes = 0;
+                    state.last_probe = Some(Instant::now());
+                    tracing::warn!(
+                        target: DIAGNOSTICS_TARGET,
+                        failures = state.failures, error = %e,
+                        "primary log sink keeps failing, switching to the secondary"
+                    );
+                    return true;
+                }
+            }
+            Err(_) => {}
+        }
+        false
+    }
+
+    async fn route<'a, F, Fut>(&'a self, send: F) -> Result<(), SinkError>
+    where
+        F: Fn(&'a dyn LogSink) -> Fut,
+        Fut: Future<Output = Result<(), SinkError>>,
+    {
+        if self.use_primary().await {
+            let result = send(&*self.primary).await;
+            if !self.primary_result(&result) {
+                return result;
+            }
+        }
+        send(&*self.secondary).await
+    }
+}
+
+#[async_trait]
+impl LogSink for FailoverSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.route(|sink| sink.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.route(|sink| sink.send_serialized(batch, index)).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.route(|sink| sink.send_batch(records)).await
+    }
+
+    /// Flushes both sinks, returning the first error.
+    async fn flush(&self) -> Result<(), SinkError> {
+        let primary = self.primary.flush().await;
+        let secondary = self.secondary.flush().await;
+        primary.and(secondary)
+    }
+
+    /// Fails only if neither sink is healthy.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        match self.primary.health_check().await {
+            Ok(()) => Ok(()),
+            Err(_) => self.secondary.health_check().await,
+        }
+    }
+
+    /// Checks both sinks, since either may receive records.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        let mut drift = self.primary.schema_drift().await?;
+        drift.extend(self.secondary.schema_drift().await?);
+        Ok(drift)
+    }
+
+    /// Shuts both sinks down, returning the first error.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        let primary = self.primary.shutdown().await;
+        let secondary = self.secondary.shutdown().await;
+        primary.and(secondary)
+    }
+}
@@ -0,0 +1,136 @@
+use crate::limits::{FIELDS_DROPPED_FIELD, TRUNCATED_FIELD};
+use crate::processor::{ProcessOutcome, RecordProcessor};
+use crate::record::LogRecord;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How [`FieldMap`] normalizes field keys.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCase {
+    /// Keep keys as written.
+    #[default]
+    Preserve,
+    /// Lower-case keys: `UserId` → `userid`.
+    Lower,
+    /// Lower-case keys and separate words with `_`:
+    /// `userId`, `User-Id` and `user id` → `user_id`, `HTTPStatus` →
+    /// `http_status`. Dots are kept.
+    Snake,
+}
+
+/// Configuration for [`FieldMap`].
+///
+/// **Fields**
+/// - `case`: normalization applied to every key first.
+/// - `rename`: old key → new key, looked up after normalization, e.g.
+///   `uid` → `user_id`.
+/// - `prefix`: if set, prepended to every key that is neither in `known`
+///   nor a `rename` target, e.g. `"attr."` to keep custom fields apart
+///   from the org-wide schema.
+/// - `known`: keys of the schema that are never prefixed.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FieldMapConfig {
+    pub case: KeyCase,
+    pub rename: BTreeMap<String, String>,
+    pub prefix: Option<String>,
+    pub known: Vec<String>,
+}
+
+/// [`RecordProcessor`] that renames and normalizes field keys, so records
+/// follow one schema whatever individual call sites typed in `error!()`.
+///
+/// If two fields end up with the same key, a field that already had that
+/// key wins over one renamed to it; otherwise the first in key order wins.
+/// The [`TRUNCATED_FIELD`] and [`FIELDS_DROPPED_FIELD`] markers are left
+/// untouched.
+#[derive(Clone, Debug)]
+pub struct FieldMap {
+    case: KeyCase,
+    rename: BTreeMap<String, String>,
+    prefix: Option<String>,
+    known: BTreeSet<String>,
+}
+
+impl FieldMap {
+    /// Build a processor from a [`FieldMapConfig`].
+    pub fn new(config: FieldMapConfig) -> Self {
+        let mut known: BTreeSet<String> = config.known.into_iter().collect();
+        known.extend(config.rename.values().cloned());
+        Self {
+            case: config.case,
+            rename: config.rename,
+            prefix: config.prefix,
+            known,
+        }
+    }
+
+    /// Key under which a field named `key` is shipped.
+    pub fn map_key(&self, key: &str) -> String {
+        if key == TRUNCATED_FIELD || key == FIELDS_DROPPED_FIELD {
+            return key.to_string();
+        }
+        let key = normalize(key, self.case);
+        let key = match self.rename.get(&key) {
+            Some(renamed) => renamed.clone(),
+            None => key,
+        };
+        match &self.prefix {
+            Some(prefix) if !self.known.contains(&key) => format!("{prefix}{key}"),
+            _ => key,
+        }
+    }
+}
+
+impl RecordProcessor for FieldMap {
+    fn process(&self, record: &mut LogRecord) -> ProcessOutcome {
+        let fields = std::mem::take(&mut record.fields);
+        let mut changed = Vec::new();
+        for (key, value) in fields {
+            let mapped = self.map_key(&key);
+            if mapped == key {
+                record.fields.insert(key, value);
+            } else {
+                changed.push((mapped, value));
+            }
+        }
+        for (key, value) in changed {
+            if !record.fields.contains_key(&key) {
+                record.fields.insert(key, value);
+            }
+        }
+        ProcessOutcome::Keep
+    }
+}
+
+fn normalize(key: &str, case: KeyCase) -> String {
+    match case {
+        KeyCase::Preserve => key.to_string(),
+        KeyCase::Lower => key.to_lowercase(),
+        KeyCase::Snake => {
+            let chars: Vec<char> = key.chars().collect();
+            let mut out = String::with_capacity(key.len() + 4);
+            for (i, &c) in chars.iter().enumerate() {
+                if c == '-' || c == '_' || c.is_whitespace() {
+                    if !out.ends_with('_') {
+                        out.push('_');
+                    }
+                    continue;
+                }
+                if c.is_uppercase() && i > 0 {
+                    let prev = chars[i - 1];
+                    let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+                    // `userId` → `user_id`, `HTTPStatus` → `http_status`.
+                    if (prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower))
+                        && !out.ends_with('_')
+                    {
+                        out.push('_');
+                    }
+                }
+                out.extend(c.to_lowercase());
+            }
+            out
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Structured fields of a [`crate::record::LogRecord`].
+//!
+//! [`Fields`] is a map from field name to JSON value with the interface of
+//! the `BTreeMap` it replaces: keys are unique and iterate in ascending
+//! order, so records serialize exactly as before. Entries are kept sorted
+//! in a small vector whose first few slots live inline, so an event with
+//! a handful of fields does not allocate for the map itself.
+
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use smallvec::SmallVec;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Fields stored without a heap allocation; most events carry fewer.
+const INLINE_FIELDS: usize = 4;
+
+/// Field name to value map of a record, ordered by name.
+///
+/// Lookups are binary searches over the sorted entries, which for the
+/// dozen or so fields of a typical record is faster than walking a tree.
+/// [`Fields::clear`] keeps the storage, so records reused by
+/// [`crate::init::LayerConfig::record_pool_size`] keep their capacity.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Fields {
+    entries: SmallVec<[(String, Value); INLINE_FIELDS]>,
+}
+
+impl Fields {
+    /// An empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of fields.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether there are no fields.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Remove every field, keeping the allocated storage.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn find(&self, key: &str) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(k, _)| k.as_str().cmp(key))
+    }
+
+    /// The value of field `key`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.find(key).ok().map(|i| &self.entries[i].1)
+    }
+
+    /// The value of field `key`, for changing it in place.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.find(key).ok().map(|i| &mut self.entries[i].1)
+    }
+
+    /// Whether there is a field `key`.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.find(key).is_ok()
+    }
+
+    /// Set field `key` to `value`.
+    ///
+    /// **Returns**
+    /// - The previous value if the field was already set.
+    pub fn insert(&mut self, key: String, value: Value) -> Option<Value> {
+        match self.find(&key) {
+            Ok(i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            Err(i) => {
+                self.entries.insert(i, (key, value));
+                None
+            }
+        }
+    }
+
+    /// The value of field `key`, set to `default()` first if there is none.
+    pub fn get_or_insert_with(&mut self, key: &str, default: impl FnOnce() -> Value) -> &mut Value {
+        let i = match self.find(key) {
+            Ok(i) => i,
+            Err(i) => {
+                self.entries.insert(i, (key.to_string(), default()));
+                i
+            }
+        };
+        &mut self.entries[i].1
+    }
+
+    /// Remove field `key`, returning its value.
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.find(key).ok().map(|i| self.entries.remove(i).1)
+    }
+
+    /// Remove and return the field with the greatest name.
+    pub fn pop_last(&mut self) -> Option<(String, Value)> {
+        self.entries.pop()
+    }
+
+    /// Keep only the fields for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(&String, &mut Value) -> bool) {
+        self.entries.retain(|(k, v)| keep(k, v));
+    }
+
+    /// Fields in ascending order of name.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&String, &Value)> + ExactSizeIterator {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Like [`Fields::iter`], with mutable values.
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (&String, &mut Value)> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+
+    /// Field names in ascending order.
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &String> + ExactSizeIterator {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    /// Field values in ascending order of name.
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &Value> + ExactSizeIterator {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    /// Like [`Fields::values`], mutable.
+    pub fn values_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut Value> + ExactSizeIterator {
+        self.entries.iter_mut().map(|(_, v)| v)
+    }
+}
+
+impl fmt::Debug for Fields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl std::ops::Index<&str> for Fields {
+    type Output = Value;
+
+    /// The value of field `key`; panics if there is none.
+    fn index(&self, key: &str) -> &Value {
+        self.get(key).expect("no such field")
+    }
+}
+
+impl Extend<(String, Value)> for Fields {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl FromIterator<(String, Value)> for Fields {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        let mut fields = Fields::new();
+        fields.extend(iter);
+        fields
+    }
+}
+
+impl<const N: usize> From<[(String, Value); N]> for Fields {
+    fn from(entries: [(String, Value); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+impl From<BTreeMap<String, Value>> for Fields {
+    fn from(map: BTreeMap<String, Value>) -> Self {
+        // Already sorted and unique.
+        Self { entries: map.into_iter().collect() }
+    }
+}
+
+impl From<Fields> for BTreeMap<String, Value> {
+    fn from(fields: Fields) -> Self {
+        fields.into_iter().collect()
+    }
+}
+
+impl IntoIterator for Fields {
+    type Item = (String, Value);
+    type IntoIter = smallvec::IntoIter<[(String, Value); INLINE_FIELDS]>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Fields {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = std::iter::Map<std::slice::Iter<'a, (String, Value)>, fn(&'a (String, Value)) -> (&'a String, &'a Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Fields {
+    type Item = (&'a String, &'a mut Value);
+    type IntoIter =
+        std::iter::Map<std::slice::IterMut<'a, (String, Value)>, fn(&'a mut (String, Value)) -> (&'a String, &'a mut Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter_mut().map(|(k, v)| (&*k, v))
+    }
+}
+
+impl Serialize for Fields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Accepts any map; a repeated name keeps its last value, as with
+/// `BTreeMap`.
+impl<'de> Deserialize<'de> for Fields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldsVisitor;
+
+        impl<'de> Visitor<'de> for FieldsVisitor {
+            type Value = Fields;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a map of field names to values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Fields, A::Error> {
+                let mut fields = Fields::new();
+                while let Some((key, value)) = access.next_entry::<String, Value>()? {
+                    fields.insert(key, value);
+                }
+                Ok(fields)
+            }
+        }
+
+        deserializer.deserialize_map(FieldsVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_names_sorted_and_unique() {
+        let mut fields = Fields::new();
+        for (key, value) in [("b", 1), ("a", 2), ("c", 3), ("a", 4)] {
+            fields.insert(key.to_string(), json!(value));
+        }
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(fields["a"], json!(4));
+        assert_eq!(fields.remove("b"), Some(json!(1)));
+        assert_eq!(fields.pop_last(), Some(("c".to_string(), json!(3))));
+        assert_eq!(fields.len(), 1);
+        assert!(!fields.entries.spilled());
+    }
+
+    #[test]
+    fn serializes_like_a_btree_map() {
+        let map: BTreeMap<String, Value> =
+            (0..10).map(|i| (format!("field{}", 9 - i), json!({ "n": i }))).collect();
+        let fields = Fields::from(map.clone());
+        assert_eq!(serde_json::to_string(&fields).unwrap(), serde_json::to_string(&map).unwrap());
+        assert_eq!(fields, map.clone().into_iter().rev().collect::<Fields>());
+
+        let parsed: Fields = serde_json::from_str(r#"{"b":1,"a":2,"b":3}"#).unwrap();
+        assert_eq!(parsed, Fields::from([("a".to_string(), json!(2)), ("b".to_string(), json!(3))]));
+    }
+
+    #[test]
+    fn clear_keeps_the_storage() {
+        let mut fields: Fields = (0..10).map(|i| (format!("field{}", i), json!(i))).collect();
+        let capacity = fields.entries.capacity();
+        fields.clear();
+        assert!(fields.is_empty());
+        assert_eq!(fields.entries.capacity(), capacity);
+    }
+}
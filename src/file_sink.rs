@@ -0,0 +1,76 @@
+use crate::encoding::{PayloadEncoding, SerializedBatch};
+use crate::record::LogRecord;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// [`LogSink`] appending records as JSON Lines to a local file, e.g. for
+/// local development, CI artifacts or a copy next to a remote backend.
+///
+/// Each batch is written with a single `write`, so lines of concurrent
+/// batches do not interleave. [`LogSink::flush`] and
+/// [`LogSink::shutdown`] `fsync` the file. The output can be sent on
+/// later with [`crate::replay::replay_file`] (`logrec replay`).
+pub struct FileSink {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Open `path` for appending, creating it and its parent directories
+    /// if needed.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { path: path.to_path_buf(), file: Mutex::new(file) })
+    }
+
+    /// Path of the file written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write(&self, lines: &[u8]) -> Result<(), SinkError> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(lines)?;
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<(), SinkError> {
+        self.file.lock().unwrap_or_else(|e| e.into_inner()).sync_data()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for FileSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.write(&line)
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        let mut line = batch.encoded(index, PayloadEncoding::Json)?.to_vec();
+        line.push(b'\n');
+        self.write(&line)
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.write(&SerializedBatch::new(records).json_lines()?)
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.sync()
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.sync()
+    }
+}
@@ -0,0 +1,205 @@
+use crate::record::LogRecord;
+use serde::Deserialize;
+
+/// Selects which parts of a record make up its [`LogRecord::fingerprint`].
+///
+/// The fingerprint is a stable 64-bit FNV-1a hash rendered as 16 hex
+/// characters. It does not depend on the process, host or Rust version,
+/// so identical errors group together across the whole fleet (Sentry-style
+/// grouping in ClickHouse/OpenSearch dashboards).
+///
+/// **Fields**
+/// - `target`: include the event target.
+/// - `level`: include the level.
+/// - `message`: include the message.
+/// - `normalize_message`: replace the variable parts of the message with
+///   `#` before hashing: UUIDs, hex numbers such as `0x7ffd5a3b` or
+///   `deadbeef42`, and runs of digits elsewhere. So `order 17 failed` and
+///   `order 42 failed` share a fingerprint, as do messages differing only
+///   in a request id, a pointer or an IP address.
+/// - `location`: include source file and line.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FingerprintConfig {
+    pub target: bool,
+    pub level: bool,
+    pub message: bool,
+    pub normalize_message: bool,
+    pub location: bool,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            target: true,
+            level: true,
+            message: true,
+            normalize_message: true,
+            location: true,
+        }
+    }
+}
+
+impl FingerprintConfig {
+    /// Compute the fingerprint of `record` from the configured inputs.
+    pub fn compute(&self, record: &LogRecord) -> String {
+        let mut hash = Fnv1a::new();
+        if self.target {
+            hash.write(record.target.as_bytes());
+        }
+        if self.level {
+            hash.write(record.level.as_str().as_bytes());
+        }
+        if self.message {
+            let message = record.message.as_deref().unwrap_or("");
+            if self.normalize_message {
+                hash.write(normalize_message(message).as_bytes());
+            } else {
+                hash.write(message.as_bytes());
+            }
+        }
+        if self.location {
+            hash.write(record.file.as_deref().unwrap_or("").as_bytes());
+            hash.write(&record.line.unwrap_or(0).to_le_bytes());
+        }
+        format!("{:016x}", hash.finish())
+    }
+}
+
+/// Replace UUIDs, hex numbers and runs of digits in other words with `#`.
+fn normalize_message(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(c) = rest.chars().next() {
+        if !c.is_ascii_alphanumeric() {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        } else if is_uuid(rest) {
+            out.push('#');
+            rest = &rest[UUID_LEN..];
+        } else {
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len());
+            normalize_word(&mut out, &rest[..end]);
+            rest = &rest[end..];
+        }
+    }
+    out
+}
+
+const UUID_LEN: usize = 36;
+
+/// Whether `s` starts with a hyphenated UUID that ends a word.
+fn is_uuid(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() >= UUID_LEN
+        && bytes[..UUID_LEN].iter().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => *b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        })
+        && !bytes.get(UUID_LEN).is_some_and(u8::is_ascii_alphanumeric)
+}
+
+/// Push `word` (ASCII alphanumerics) to `out`: `#` if it is a hex number,
+/// otherwise with every run of digits replaced by `#`.
+fn normalize_word(out: &mut String, word: &str) {
+    let (prefixed, digits) = match word.get(..2) {
+        Some("0x" | "0X") if word.len() > 2 => (true, &word[2..]),
+        _ => (false, word),
+    };
+    // A hex number has at least one digit; `cafe` or `dead` are words.
+    if digits.bytes().all(|b| b.is_ascii_hexdigit()) && (prefixed || digits.bytes().any(|b| b.is_ascii_digit())) {
+        out.push('#');
+        return;
+    }
+    let mut in_digits = false;
+    for c in word.chars() {
+        if c.is_ascii_digit() {
+            if !in_digits {
+                out.push('#');
+            }
+            in_digits = true;
+        } else {
+            out.push(c);
+            in_digits = false;
+        }
+    }
+}
+
+/// 64-bit FNV-1a. Chosen over `DefaultHasher` because its output is
+/// specified and therefore stable across Rust releases.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Field separator, so ("ab", "c") and ("a", "bc") hash differently.
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fingerprint(message: &str) -> String {
+        let record: LogRecord = serde_json::from_value(json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app::orders",
+            "message": message,
+            "file": "src/orders.rs",
+            "line": 42,
+            "fields": {},
+        }))
+        .unwrap();
+        FingerprintConfig::default().compute(&record)
+    }
+
+    #[test]
+    fn normalization_replaces_variable_parts() {
+        let cases = [
+            ("order 17 failed after 3 retries", "order # failed after # retries"),
+            ("request 3f2a9c1e-4b7d-4e2a-9c1e-abcdefabcdef timed out", "request # timed out"),
+            ("null pointer at 0x7ffd5a3b, page 0xdead", "null pointer at #, page #"),
+            ("connect to 10.0.0.12:5432 / [fe80::1ff:fe23]", "connect to #.#.#.#:# / [#::#:#]"),
+            ("commit deadbeef42 by user42 in cafe v2", "commit # by user# in cafe v#"),
+            ("ключ 12 не найден", "ключ # не найден"),
+        ];
+        for (message, normalized) in cases {
+            assert_eq!(normalize_message(message), normalized, "{}", message);
+        }
+    }
+
+    #[test]
+    fn fingerprints_are_stable_across_variable_parts() {
+        let cases = [
+            (
+                "3795544cb5beb950",
+                [
+                    "order 17 of user 3f2a9c1e-4b7d-4e2a-9c1e-abcdefabcdef failed at 0x7ffd5a3b",
+                    "order 90210 of user 00000000-0000-0000-0000-000000000000 failed at 0x1",
+                ],
+            ),
+            ("6d822a2dd54567d3", ["connect to 10.0.0.12:5432 refused", "connect to 192.168.1.1:6432 refused"]),
+        ];
+        for (expected, messages) in cases {
+            for message in messages {
+                assert_eq!(fingerprint(message), expected, "{}", message);
+            }
+        }
+        assert_ne!(fingerprint("connect to 10.0.0.12:5432 refused"), fingerprint("connect to 10.0.0.12:5432 reset"));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::fields::Fields;
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Flattening of nested field values into dot-notation keys, see
+/// [`LayerConfig::flatten_fields`].
+///
+/// `error!(http = %json!({"status": 502, "method": "GET"}))` or a processor
+/// inserting an object produces one field holding a nested value; with
+/// flattening it is shipped as `http.status` and `http.method`, which
+/// OpenSearch mappings and ClickHouse JSON columns handle much better.
+///
+/// **Fields**
+/// - `parse_json_strings`: also flatten string values that contain a
+///   JSON object, such as the output of `?value` for types whose `Debug`
+///   prints JSON (default `true`).
+/// - `separator`: joins the parent and child keys (default `"."`).
+/// - `max_depth`: how many levels are expanded; deeper objects stay
+///   as one value under the joined key (default 8).
+///
+/// Arrays are kept as they are. If a flattened key already exists, the
+/// existing field wins.
+///
+/// [`LayerConfig::flatten_fields`]: crate::init::LayerConfig::flatten_fields
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct FlattenConfig {
+    pub parse_json_strings: bool,
+    pub separator: String,
+    pub max_depth: usize,
+}
+
+impl Default for FlattenConfig {
+    fn default() -> Self {
+        Self {
+            parse_json_strings: true,
+            separator: ".".to_string(),
+            max_depth: 8,
+        }
+    }
+}
+
+impl FlattenConfig {
+    /// Flatten the nested values of `fields` in place.
+    pub fn apply(&self, fields: &mut Fields) {
+        let nested: Vec<String> = fields
+            .iter()
+            .filter(|(_, value)| self.may_be_object(value))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in nested {
+            let Some(value) = fields.remove(&key) else { continue };
+            let mut flat = Vec::new();
+            self.expand(key, value, 1, &mut flat);
+            for (key, value) in flat {
+                if !fields.contains_key(&key) {
+                    fields.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn may_be_object(&self, value: &Value) -> bool {
+        match value {
+            Value::Object(_) => true,
+            Value::String(s) => self.parse_json_strings && s.trim_start().starts_with('{'),
+            _ => false,
+        }
+    }
+
+    /// The object in `value`, parsing it from a string if configured, or
+    /// `value` itself.
+    fn parse_object(&self, value: Value) -> Result<serde_json::Map<String, Value>, Value> {
+        match value {
+            Value::Object(map) => Ok(map),
+            Value::String(s) if self.parse_json_strings && s.trim_start().starts_with('{') => {
+                match serde_json::from_str(&s) {
+                    Ok(Value::Object(map)) => Ok(map),
+                    _ => Err(Value::String(s)),
+                }
+            }
+            other => Err(other),
+        }
+    }
+
+    fn expand(&self, key: String, value: Value, depth: usize, out: &mut Vec<(String, Value)>) {
+        if depth > self.max_depth {
+            return out.push((key, value));
+        }
+        let map = match self.parse_object(value) {
+            Ok(map) if !map.is_empty() => map,
+            Ok(map) => return out.push((key, Value::Object(map))),
+            Err(value) => return out.push((key, value)),
+        };
+        for (child, value) in map {
+            self.expand(format!("{key}{}{child}", self.separator), value, depth + 1, out);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use crate::reload::ConfigHandle;
+use crate::runtime;
+use crate::stats::{LayerStats, StatsSource};
+use crate::worker::Command;
+use std::ops::Deref;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::{JoinError, JoinHandle};
+
+/// Error returned by [`LayerHandle::flush`] and [`LayerHandle::shutdown`].
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleError {
+    #[error("log worker did not finish within {0:?}")]
+    Timeout(Duration),
+
+    #[error("log worker is no longer running")]
+    WorkerGone,
+}
+
+/// Operational handle of an [`crate::layer::ErrorLogLayer`], returned by
+/// `init_tracing*` and [`crate::layer::ErrorLogLayer::handle`].
+///
+/// Cheap to clone. Dropping it does not stop the layer; call
+/// [`LayerHandle::shutdown`] (or at least [`LayerHandle::flush`])
+/// explicitly before the process exits.
+#[derive(Clone, Debug)]
+pub struct LayerHandle {
+    pub(crate) config: ConfigHandle,
+    pub(crate) control: mpsc::UnboundedSender<Command>,
+    pub(crate) stats: StatsSource,
+}
+
+impl LayerHandle {
+    /// Handle for changing the layer's settings at runtime.
+    pub fn config(&self) -> &ConfigHandle {
+        &self.config
+    }
+
+    /// Snapshot of the layer's counters, including drops per target.
+    pub fn stats(&self) -> LayerStats {
+        self.stats.snapshot()
+    }
+
+    /// Send everything the worker has accepted so far and wait for it.
+    ///
+    /// **Behavior**
+    /// - The worker takes the records currently queued in the channel,
+    ///   closes open collapse windows, sends the batch immediately and
+    ///   calls [`crate::sink::LogSink::flush`].
+    /// - Use before a serverless function freezes, at the end of a
+    ///   scheduled job, or in tests before inspecting the sink.
+    ///
+    /// **Returns**
+    /// - `Err(HandleError::Timeout)` if this takes longer than `timeout`;
+    ///   the flush still completes in the background.
+    pub async fn flush(&self, timeout: Duration) -> Result<(), HandleError> {
+        self.request(Command::Flush, timeout).await
+    }
+
+    /// Blocking variant of [`LayerHandle::flush`] for synchronous code.
+    pub fn flush_blocking(&self, timeout: Duration) -> Result<(), HandleError> {
+        runtime::block_on(self.flush(timeout))
+    }
+
+    /// Stop the background worker gracefully.
+    ///
+    /// **Behavior**
+    /// - Closes the channel, drains the records still queued in it and
+    ///   sends them together with the current batch.
+    /// - Calls [`crate::sink::LogSink::flush`] and then
+    ///   [`crate::sink::LogSink::shutdown`].
+    /// - Events recorded afterwards are counted as dropped.
+    ///
+    /// **Returns**
+    /// - `Err(HandleError::Timeout)` if this takes longer than `timeout`;
+    ///   the worker keeps shutting down in the background.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), HandleError> {
+        self.request(Command::Shutdown, timeout).await
+    }
+
+    /// Blocking variant of [`LayerHandle::shutdown`] for synchronous code,
+    /// e.g. the end of a non-async `main()`.
+    pub fn shutdown_blocking(&self, timeout: Duration) -> Result<(), HandleError> {
+        runtime::block_on(self.shutdown(timeout))
+    }
+
+    /// Send a command to the worker and wait for its acknowledgement.
+    async fn request(
+        &self,
+        command: fn(oneshot::Sender<()>) -> Command,
+        timeout: Duration,
+    ) -> Result<(), HandleError> {
+        let (ack_tx, ack_rx) = oneshot::channel();
+        self.control
+            .send(command(ack_tx))
+            .map_err(|_| HandleError::WorkerGone)?;
+        match tokio::time::timeout(timeout, ack_rx).await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(HandleError::WorkerGone),
+            Err(_) => Err(HandleError::Timeout(timeout)),
+        }
+    }
+}
+
+/// What `init_tracing*` returns: the [`LayerHandle`] of the installed
+/// layer together with the `JoinHandle` of its background worker.
+///
+/// Dereferences to [`LayerHandle`], so `recorder.stats()`,
+/// `recorder.config()`, `recorder.flush(..)` and `recorder.shutdown(..)`
+/// work directly; [`LogRecorder::handle`] gives a clonable handle to pass
+/// around. Like the handle, dropping it leaves the layer running.
+#[derive(Debug)]
+pub struct LogRecorder {
+    handle: LayerHandle,
+    worker: JoinHandle<()>,
+}
+
+impl LogRecorder {
+    pub(crate) fn new(handle: LayerHandle, worker: JoinHandle<()>) -> Self {
+        Self { handle, worker }
+    }
+
+    /// Handle of the layer, e.g. to clone into a signal handler.
+    pub fn handle(&self) -> &LayerHandle {
+        &self.handle
+    }
+
+    /// Whether the background worker has exited, normally after
+    /// [`LayerHandle::shutdown`].
+    pub fn is_finished(&self) -> bool {
+        self.worker.is_finished()
+    }
+
+    /// Wait until the background worker has exited.
+    ///
+    /// Call it after [`LayerHandle::shutdown`]: the worker only exits once
+    /// shut down, so this waits forever otherwise.
+    ///
+    /// **Returns**
+    /// - `Err(..)` if the worker task was aborted or panicked outside of
+    ///   its restart loop.
+    pub async fn join(self) -> Result<(), JoinError> {
+        self.worker.await
+    }
+
+    /// Split into the layer handle and the worker's `JoinHandle`.
+    pub fn into_parts(self) -> (LayerHandle, JoinHandle<()>) {
+        (self.handle, self.worker)
+    }
+}
+
+impl Deref for LogRecorder {
+    type Target = LayerHandle;
+
+    fn deref(&self) -> &LayerHandle {
+        &self.handle
+    }
+}
@@ -0,0 +1,62 @@
+use std::thread;
+
+/// Host and process metadata attached to every [`LogRecord`].
+///
+/// Hostname and pid never change for the lifetime of a process, so they
+/// are collected once when the layer is created and then cloned onto each
+/// record. Thread information is captured per event, see [`current_thread`].
+///
+/// [`LogRecord`]: crate::record::LogRecord
+#[derive(Debug, Clone, Default)]
+pub struct HostInfo {
+    /// Hostname of the machine, if it could be determined.
+    pub hostname: Option<String>,
+    /// OS process id of the current process.
+    pub pid: u32,
+}
+
+impl HostInfo {
+    /// Collect host metadata for the current process.
+    ///
+    /// **Returns**
+    /// - [`HostInfo`] with the hostname (when it is valid UTF-8) and the
+    ///   current process id; empty on `wasm32`.
+    pub fn collect() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        return HostInfo {
+            hostname: gethostname::gethostname().into_string().ok(),
+            pid: std::process::id(),
+        };
+        // There is neither a hostname nor a process on `wasm32`.
+        #[cfg(target_arch = "wasm32")]
+        HostInfo::default()
+    }
+}
+
+/// Identifier and optional name of the thread that is emitting an event.
+///
+/// The id is formatted the same way `tracing_subscriber::fmt` prints it
+/// (`ThreadId(N)`), so it can be correlated with console output. Both are
+/// formatted once per thread and cached.
+pub fn current_thread() -> (String, Option<String>) {
+    CURRENT_THREAD.with(Clone::clone)
+}
+
+/// [`current_thread`] written into existing strings, reusing their
+/// capacity.
+pub(crate) fn current_thread_into(id: &mut Option<String>, name: &mut Option<String>) {
+    CURRENT_THREAD.with(|(current_id, current_name)| {
+        match id {
+            Some(id) => id.clone_from(current_id),
+            None => *id = Some(current_id.clone()),
+        }
+        name.clone_from(current_name);
+    })
+}
+
+thread_local! {
+    static CURRENT_THREAD: (String, Option<String>) = {
+        let current = thread::current();
+        (format!("{:?}", current.id()), current.name().map(|s| s.to_string()))
+    };
+}
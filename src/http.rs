@@ -0,0 +1,405 @@
+//! HTTP transport shared by the ClickHouse and OpenSearch sinks, the
+//! SigV4 credential chain and the ClickHouse reader.
+//!
+//! Requests go through reqwest (feature `reqwest`, on by default), or,
+//! with the `hyper-client` feature, through a plain HTTP/1.1 hyper client
+//! with rustls, which compiles a much smaller dependency tree. When both
+//! are enabled the hyper client is used natively; `wasm32` always uses
+//! reqwest, which sends through `fetch` there.
+//!
+//! Bodies are small JSON payloads, so requests and responses are fully
+//! buffered. [`HttpOptions`] tunes the connections of a sink's client,
+//! including the proxy they go through.
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::proxy::ProxyRoute;
+use crate::sink::SinkError;
+use crate::tls::{TlsError, TlsOptions};
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+pub(crate) use hyper::{header, Method, StatusCode};
+#[cfg(not(all(feature = "hyper-client", not(target_arch = "wasm32"))))]
+pub(crate) use reqwest::{header, Method, StatusCode};
+use header::HeaderMap;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "reqwest", all(feature = "hyper-client", not(target_arch = "wasm32")))))]
+compile_error!("the ClickHouse and OpenSearch sinks need an HTTP client: enable the `reqwest` or `hyper-client` feature");
+
+#[cfg(all(
+    feature = "reqwest",
+    not(any(feature = "hyper-client", target_arch = "wasm32")),
+    not(any(feature = "tls-rustls", feature = "tls-native"))
+))]
+compile_error!("reqwest needs a TLS stack: enable the `tls-rustls` or `tls-native` feature");
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+type Connector = hyper_rustls::HttpsConnector<crate::proxy::ProxyConnector>;
+
+/// Connection settings of the HTTP client of a sink, next to its request
+/// `timeout`. Ignored on `wasm32`, where `fetch` manages connections.
+///
+/// **Fields**
+/// - `connect_timeout`: limit for opening a connection; `None` leaves it
+///   to the OS. Default 10 s.
+/// - `pool_idle_timeout`: how long an unused connection is kept for reuse;
+///   `None` keeps it until the server closes it. Default 90 s.
+/// - `pool_max_idle_per_host`: unused connections kept per host; `0`
+///   turns off HTTP keep-alive, so every request opens a new connection.
+///   Default 8.
+/// - `tcp_keepalive`: interval of TCP keep-alive probes, which detect a
+///   backend that went away without closing the connection; `None`
+///   disables them. Default 60 s.
+/// - `proxy`: see [`HttpProxy`]; by default the proxy of the environment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpOptions {
+    pub connect_timeout: Option<Duration>,
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: usize,
+    pub tcp_keepalive: Option<Duration>,
+    pub proxy: HttpProxy,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Some(Duration::from_secs(10)),
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: 8,
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            proxy: HttpProxy::default(),
+        }
+    }
+}
+
+/// Proxy the requests of an HTTP sink go through, for networks whose only
+/// egress is a proxy. Only `http://` proxies are supported; requests to
+/// `https` URLs are tunnelled through them with `CONNECT`.
+///
+/// A no-proxy list holds hosts (`localhost`), domains with their
+/// subdomains (`corp.example` or `.corp.example`), IP addresses and
+/// networks (`10.0.0.0/8`), or `*` for all hosts; ports are ignored.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub enum HttpProxy {
+    /// `HTTPS_PROXY` for `https` URLs and `HTTP_PROXY` for `http` ones,
+    /// falling back to `ALL_PROXY`, except for the comma-separated hosts
+    /// of `NO_PROXY` (each also lowercase); direct if none is set.
+    #[default]
+    FromEnv,
+    /// Connect directly, ignoring the environment.
+    Direct,
+    /// Send every request through `url`, e.g. `http://proxy:3128`, with
+    /// basic auth if `username` is set (or given in `url`), except for
+    /// the hosts of `no_proxy`.
+    Url {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+        no_proxy: Vec<String>,
+    },
+}
+
+impl std::fmt::Debug for HttpProxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpProxy::FromEnv => f.write_str("FromEnv"),
+            HttpProxy::Direct => f.write_str("Direct"),
+            HttpProxy::Url { url, username, password, no_proxy } => f
+                .debug_struct("Url")
+                .field("url", url)
+                .field("username", username)
+                .field("password", &password.as_ref().map(|_| "<redacted>"))
+                .field("no_proxy", no_proxy)
+                .finish(),
+        }
+    }
+}
+
+/// Client of one sink, cheap to clone; held by
+/// [`crate::query::LogReader`], otherwise internal.
+#[derive(Clone)]
+pub struct HttpTransport {
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    client: std::sync::Arc<hyper::Client<Connector, hyper::Body>>,
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    timeout: Option<Duration>,
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    proxy: Option<std::sync::Arc<ProxyRoute>>,
+    #[cfg(not(all(feature = "hyper-client", not(target_arch = "wasm32"))))]
+    client: reqwest::Client,
+}
+
+/// A buffered request.
+#[derive(Debug)]
+pub(crate) struct HttpRequest {
+    pub(crate) method: Method,
+    pub(crate) url: String,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+/// A buffered response.
+#[derive(Debug)]
+pub(crate) struct HttpResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+impl HttpTransport {
+    /// Client for requests to `base_url`.
+    ///
+    /// **Parameters**
+    /// - `timeout`: limit for a whole request, including reading the
+    ///   response; `None` waits indefinitely. Ignored on `wasm32`.
+    /// - `options`, `tls`: see [`HttpOptions`] and [`TlsOptions`]; ignored
+    ///   on `wasm32`.
+    ///
+    /// **Returns**
+    /// - The client and the base URL to send requests to, which differs
+    ///   from `base_url` when reqwest handles [`TlsOptions::server_name`].
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    pub(crate) fn new(
+        base_url: &str,
+        timeout: Option<Duration>,
+        options: &HttpOptions,
+        tls: &TlsOptions,
+    ) -> Result<(Self, String), TlsError> {
+        let proxy = ProxyRoute::new(&options.proxy)?.map(std::sync::Arc::new);
+        let client = hyper_client(options, tls.rustls_config()?, tls.server_name.clone(), proxy.clone());
+        Ok((Self { client: client.into(), timeout, proxy }, base_url.to_string()))
+    }
+
+    /// Client for requests to `base_url`.
+    ///
+    /// **Parameters**
+    /// - `timeout`: limit for a whole request, including reading the
+    ///   response; `None` waits indefinitely. Ignored on `wasm32`.
+    /// - `options`, `tls`: see [`HttpOptions`] and [`TlsOptions`]; ignored
+    ///   on `wasm32`.
+    ///
+    /// **Returns**
+    /// - The client and the base URL to send requests to, which differs
+    ///   from `base_url` when reqwest handles [`TlsOptions::server_name`].
+    #[cfg(not(all(feature = "hyper-client", not(target_arch = "wasm32"))))]
+    #[cfg_attr(target_arch = "wasm32", allow(unused_mut, unused_variables))]
+    pub(crate) fn new(
+        base_url: &str,
+        timeout: Option<Duration>,
+        options: &HttpOptions,
+        tls: &TlsOptions,
+    ) -> Result<(Self, String), TlsError> {
+        let mut builder = reqwest::Client::builder();
+        let mut base_url = base_url.to_string();
+        // `fetch` has no request timeout and does TLS itself.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            builder = reqwest_options(builder, timeout, options)?;
+            (builder, base_url) = tls.apply(builder, &base_url)?;
+        }
+        let client = builder.build().map_err(|e| TlsError::Client(Box::new(e)))?;
+        Ok((Self { client }, base_url))
+    }
+
+    /// Client with the default TLS settings and no proxy, e.g. for
+    /// link-local metadata endpoints.
+    #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+    pub(crate) fn plain(timeout: Option<Duration>) -> Self {
+        let options = HttpOptions { proxy: HttpProxy::Direct, ..HttpOptions::default() };
+        #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+        {
+            let client = hyper_client(&options, crate::tls::default_rustls_config(), None, None);
+            Self { client: client.into(), timeout, proxy: None }
+        }
+        #[cfg(not(all(feature = "hyper-client", not(target_arch = "wasm32"))))]
+        {
+            let builder = reqwest_options(reqwest::Client::builder(), timeout, &options);
+            Self { client: builder.ok().and_then(|b| b.build().ok()).unwrap_or_default() }
+        }
+    }
+
+    /// Send `request` and read the whole response.
+    ///
+    /// **Returns**
+    /// - The response whatever its status; see [`HttpResponse::error`].
+    /// - A transient [`SinkError`] if the server could not be reached or
+    ///   the timeout passed, a permanent one for a malformed request.
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    pub(crate) async fn send(&self, request: HttpRequest) -> Result<HttpResponse, SinkError> {
+        let uri: hyper::Uri = request
+            .url
+            .parse()
+            .map_err(|e| SinkError::permanent(format!("invalid URL {:?}: {e}", request.url)))?;
+        let mut headers = request.headers;
+        // Plain `http` requests go to the proxy as they are, so it sees the
+        // credentials only on each of them; `https` ones send them with
+        // `CONNECT` instead.
+        let proxy = self.proxy.as_ref().filter(|_| uri.scheme_str() != Some("https"));
+        if let Some(server) = proxy.and_then(|route| route.server_for("http", uri.host().unwrap_or_default())) {
+            if let Some(authorization) = server.authorization().and_then(|a| header::HeaderValue::from_str(&a).ok()) {
+                headers.insert(header::PROXY_AUTHORIZATION, authorization);
+            }
+        }
+        let mut builder = hyper::Request::builder().method(request.method).uri(uri);
+        if let Some(builder_headers) = builder.headers_mut() {
+            *builder_headers = headers;
+        }
+        let request = builder
+            .body(hyper::Body::from(request.body))
+            .map_err(|e| SinkError::permanent(format!("invalid HTTP request: {e}")))?;
+        let exchange = async {
+            let response = self.client.request(request).await?;
+            let (parts, body) = response.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            Ok::<_, hyper::Error>(HttpResponse { status: parts.status, headers: parts.headers, body: body.to_vec() })
+        };
+        let result = match self.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange)
+                .await
+                .map_err(|_| SinkError::transient(format!("HTTP request timed out after {timeout:?}")))?,
+            None => exchange.await,
+        };
+        result.map_err(|e| SinkError::Transient(Box::new(e)))
+    }
+
+    /// Send `request` and read the whole response.
+    ///
+    /// **Returns**
+    /// - The response whatever its status; see [`HttpResponse::error`].
+    /// - A transient [`SinkError`] if the server could not be reached or
+    ///   the timeout passed, a permanent one for a malformed request.
+    #[cfg(not(all(feature = "hyper-client", not(target_arch = "wasm32"))))]
+    pub(crate) async fn send(&self, request: HttpRequest) -> Result<HttpResponse, SinkError> {
+        let response = self
+            .client
+            .request(request.method, &request.url)
+            .headers(request.headers)
+            .body(request.body)
+            .send()
+            .await?;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?.to_vec();
+        Ok(HttpResponse { status, headers, body })
+    }
+}
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+fn hyper_client(
+    options: &HttpOptions,
+    tls: rustls::ClientConfig,
+    server_name: Option<String>,
+    proxy: Option<std::sync::Arc<ProxyRoute>>,
+) -> hyper::Client<Connector, hyper::Body> {
+    let mut http = hyper::client::HttpConnector::new();
+    http.enforce_http(false);
+    http.set_connect_timeout(options.connect_timeout);
+    http.set_keepalive(options.tcp_keepalive);
+    let builder = hyper_rustls::HttpsConnectorBuilder::new().with_tls_config(tls).https_or_http();
+    let builder = match server_name {
+        Some(server_name) => builder.with_server_name(server_name),
+        None => builder,
+    };
+    hyper::Client::builder()
+        .pool_idle_timeout(options.pool_idle_timeout)
+        .pool_max_idle_per_host(options.pool_max_idle_per_host)
+        .build(builder.enable_http1().wrap_connector(crate::proxy::ProxyConnector::new(http, proxy)))
+}
+
+#[cfg(not(any(feature = "hyper-client", target_arch = "wasm32")))]
+fn reqwest_options(
+    mut builder: reqwest::ClientBuilder,
+    timeout: Option<Duration>,
+    options: &HttpOptions,
+) -> Result<reqwest::ClientBuilder, TlsError> {
+    // The proxies of the environment are read by `ProxyRoute` too, so
+    // both transports agree on them.
+    builder = builder.no_proxy();
+    if let Some(route) = ProxyRoute::new(&options.proxy)? {
+        let proxy = reqwest::Proxy::custom(move |url| {
+            route.server_for(url.scheme(), url.host_str()?).map(|server| server.url())
+        });
+        builder = builder.proxy(proxy);
+    }
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    Ok(builder
+        .pool_idle_timeout(options.pool_idle_timeout)
+        .pool_max_idle_per_host(options.pool_max_idle_per_host)
+        .tcp_keepalive(options.tcp_keepalive))
+}
+
+impl HttpRequest {
+    pub(crate) fn new(method: Method, url: impl Into<String>) -> Self {
+        Self { method, url: url.into(), headers: HeaderMap::new(), body: Vec::new() }
+    }
+
+    pub(crate) fn get(url: impl Into<String>) -> Self {
+        Self::new(Method::GET, url)
+    }
+
+    pub(crate) fn post(url: impl Into<String>) -> Self {
+        Self::new(Method::POST, url)
+    }
+
+    /// Set the header `name`; fails with a permanent error if `value` is
+    /// not a valid header value.
+    #[cfg(feature = "opensearch")]
+    pub(crate) fn header(mut self, name: header::HeaderName, value: &str) -> Result<Self, SinkError> {
+        let value = header::HeaderValue::from_str(value)
+            .map_err(|e| SinkError::permanent(format!("invalid value of header {name}: {e}")))?;
+        self.headers.insert(name, value);
+        Ok(self)
+    }
+
+    pub(crate) fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+impl HttpResponse {
+    pub(crate) fn is_success(&self) -> bool {
+        self.status.is_success()
+    }
+
+    /// The body as text, with invalid UTF-8 replaced.
+    pub(crate) fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    #[cfg(any(feature = "clickhouse", feature = "opensearch"))]
+    pub(crate) fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, SinkError> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+
+    /// Turn a non-success response into a [`SinkError`], reading the
+    /// `Retry-After` header (in seconds) and the body.
+    pub(crate) fn error(&self, context: &str) -> SinkError {
+        let retry_after = self
+            .headers
+            .get(header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let text = if self.body.is_empty() { "<no body>".to_string() } else { self.text() };
+        SinkError::from_http_status(
+            self.status.as_u16(),
+            retry_after,
+            format!("{} failed with status {}: {}", context, self.status, text),
+        )
+    }
+
+    /// `self` if successful, otherwise its [`HttpResponse::error`].
+    #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+    pub(crate) fn error_for_status(self, context: &str) -> Result<Self, SinkError> {
+        if self.is_success() {
+            Ok(self)
+        } else {
+            Err(self.error(context))
+        }
+    }
+}
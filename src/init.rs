@@ -1,7 +1,29 @@
-use crate::layer::ErrorLogLayer;
-use crate::sink::LogSink;
+use crate::batching::AdaptiveBatching;
+use crate::clock::{Clock, SystemClock};
+use crate::diagnostics::{DiagnosticKind, Diagnostics, DiagnosticsConfig};
+use crate::fingerprint::FingerprintConfig;
+use crate::flatten::FlattenConfig;
+use crate::layer::{DropHook, ErrorLogLayer};
+use crate::level_policy::ShipPolicy;
+use crate::limits::RecordLimits;
+use crate::persist::PersistConfig;
+use crate::record::LogLevel;
+use crate::reload::Route;
+use crate::handle::{LayerHandle, LogRecorder};
+use crate::processor::{RecordFilter, RecordProcessor};
+use crate::resource::ResourceInfo;
+use crate::runtime;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use crate::span_fields::SpanFieldsConfig;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
+use tokio::runtime::Handle;
 use tokio::time::Duration;
+use tracing::instrument::WithSubscriber;
+use tracing::Dispatch;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::Registry;
 
@@ -14,30 +36,379 @@ use tracing_subscriber::Registry;
 /// **Поля**
 /// - `channel_buffer`: максимальное число [`LogRecord`] в очереди до
 ///   начала дропа новых записей.
+/// - `channel_shards`: число шардов канала. Каждый поток пишет в свой
+///   шард, что снижает конкуренцию отправителей при интенсивном
+///   многопоточном логировании; `channel_buffer` делится между шардами.
+/// - `channel_kind`: ограниченный канал (по умолчанию) или неограниченный,
+///   который не дропает записи по числу, а держит их в памяти до жёсткого
+///   лимита в байтах и предупреждает диагностикой `ChannelHighWatermark`
+///   при превышении порога, см. [`ChannelKind`].
+/// - `priority_lanes`: если задано ([`PriorityLanes`]), записи `ERROR`
+///   идут в отдельный канал, который не заполняется потоком `WARN`/`INFO`,
+///   а worker выбирает из него в первую очередь (до `error_weight` ошибок
+///   на одну запись ниже уровнем), так что ошибки не теряются и не ждут за
+///   лавиной предупреждений. Имеет смысл при `level` ниже `Error`.
 /// - `batch_size`: размер батча для отправки в sink.
+/// - `max_batch_bytes`: если задано, батч отправляется также при
+///   достижении этого суммарного размера по оценке
+///   [`LogRecord::estimated_size`], чтобы несколько огромных записей не
+///   превращались в гигантский запрос.
+/// - `max_buffered_bytes`: если задано, ограничивает суммарный размер
+///   записей в очереди (по оценке [`LogRecord::estimated_size`] в момент
+///   постановки). При превышении запись обрабатывается так же, как при
+///   заполненном канале (ожидание `enqueue_timeout`, затем дроп с
+///   [`crate::layer::DropReason::BytesLimit`]), даже если в канале ещё
+///   есть места, — защита от OOM при очень крупных записях. Запись в
+///   пустую очередь принимается всегда.
+/// - `max_in_flight`: сколько батчей может одновременно отправляться в
+///   sink (минимум 1). Пока все слоты заняты, воркер не формирует новые
+///   батчи, и при медленном бэкенде заполняется канал, а не память.
+///   При значении больше 1 порядок доставки между батчами не гарантируется.
+/// - `record_pool_size`: сколько отправленных записей воркер возвращает
+///   слою для повторного использования вместе с буферами батчей; строки
+///   хоста, потока и ресурса таких записей перезаписываются на месте, а
+///   поля событий собираются в их хранилище, а не выделяются заново. По
+///   умолчанию `1024`, как `channel_buffer`: событие с двумя полями
+///   обходится примерно в 7 аллокаций вместо 10, с восемью — в 13 вместо
+///   17 (`record_pool` в `benches/layer.rs`); простаивающий пул занимает
+///   около 1 МБ. `0` выключает пул.
 /// - `flush_interval`: максимальный интервал между flush’ами даже при
 ///   неполном батче.
+/// - `adaptive_batching`: если задано, worker сам подстраивает размер
+///   батча и интервал flush под наблюдаемую задержку sink в пределах
+///   [`AdaptiveBatching`]: растит их, когда бэкенд медленный, и уменьшает,
+///   когда быстрый. `batch_size` и `flush_interval` задают стартовые значения.
+/// - `level`: минимальный уровень событий, которые перехватываются слоем
+///   (по умолчанию [`LogLevel::Error`]).
+/// - `level_policy`: как отправлять события каждого уровня
+///   ([`ShipPolicy`]): все (`Always`, для уровней без записи), одно из `N`
+///   (`Sample(N)`), не больше `N` в секунду (`RateLimit(N)`) или никакие
+///   (`Drop`). Например, `level: LogLevel::Warn` с `Warn => Sample(10)`
+///   добавляет видимость предупреждений, не умножая объём в бэкенде.
+///   Решение принимается в потоке приложения до построения записи;
+///   отброшенные события учитываются в `filtered_events`.
+///   Меняется на лету через [`crate::reload::ConfigHandle::set_level_policy`].
+/// - `routes`: таблица маршрутизации ([`Route`]): записям target’а (вместе
+///   с вложенными модулями) без собственного `service_name` проставляется
+///   сервис первого подходящего маршрута, что через плейсхолдер `{service}`
+///   ([`crate::destination::DestinationTemplate`]) выбирает таблицу или
+///   индекс. Меняется на лету через [`crate::reload::ConfigHandle::set_routes`].
 /// - `enable_stdout`: если `true`, поверх `ErrorLogLayer` добавляется
 ///   `tracing_subscriber::fmt::Layer` и ошибки печатаются в консоль.
-#[derive(Clone, Debug)]
+/// - `resource`: метаданные деплоя ([`ResourceInfo`]: окружение, регион,
+///   версия, git sha, произвольные метки), которые добавляются в каждую
+///   запись. В `detect` включаются источники, из которых при создании слоя
+///   определяются контейнер и облачный инстанс (`container.id`, `host.id`,
+///   ...), см. [`crate::platform::PlatformDetection`].
+/// - `processors`: упорядоченная цепочка [`RecordProcessor`], которые
+///   выполняются в фоновом task и могут дополнять, изменять или
+///   отбрасывать записи до отправки в sink.
+/// - `flatten_fields`: если задано ([`FlattenConfig`]), вложенные объекты
+///   в значениях полей (в том числе строки с JSON‑объектом) в фоновом task
+///   раскладываются в ключи через точку: `http.status`, `http.method`.
+///   Выполняется до `filter` и `processors`, поэтому они видят уже плоские
+///   ключи; после раскладки `limits` применяются повторно.
+/// - `filter`: если задано ([`RecordFilter`]), предикат над записью,
+///   который вызывается в фоновом task перед `processors`: запись с
+///   результатом `false` отбрасывается и учитывается в `filtered_events`.
+///   Проще процессора, когда нужно лишь отсеять известный шум, например
+///   ошибку с определённым кодом.
+/// - `field_allowlist`: если задано, в записи остаются только поля
+///   события с этими ключами (включая унаследованные от span’ов), а
+///   остальные отбрасываются в потоке приложения до постановки в очередь
+///   и сериализации. Сообщение, `error.kind` / `error.message` (их
+///   переносят в колонки до фильтрации) и метаданные записи (время,
+///   уровень, target, хост, ресурс, trace‑контекст) сохраняются всегда;
+///   поля, добавленные `processors`, не фильтруются. Для строгих
+///   требований к минимизации данных.
+/// - `limits`: ограничения [`RecordLimits`] на длину сообщения, размер
+///   значения поля и число полей; применяются до постановки в очередь.
+///   Лишние поля отбрасываются, а их число записывается в поле
+///   `fields_dropped`. По умолчанию ограничений нет; типовые значения
+///   даёт [`RecordLimits::recommended`].
+/// - `capture_log` (feature `log-compat`): если `true`, при инициализации
+///   устанавливается `tracing_log::LogTracer`, и события `log::error!` из
+///   сторонних крейтов тоже попадают в `ErrorLogLayer`.
+/// - `collapse_window`: если задано, одинаковые записи (target, уровень,
+///   сообщение, место в коде) в пределах окна схлопываются: первая
+///   отправляется сразу, остальные подавляются, а по закрытии окна
+///   отправляется одна запись с `repeat_count`.
+/// - `fingerprint`: какие части записи входят в стабильный хеш
+///   `fingerprint` для группировки ошибок; `None` отключает вычисление.
+/// - `clock`: источник времени [`Clock`] для меток времени и окон
+///   схлопывания; в тестах можно подставить [`crate::clock::ManualClock`].
+/// - `runtime`: Tokio runtime, в котором запускается фоновый worker. Если
+///   `None`, используется текущий runtime, а вне runtime — отдельный
+///   фоновый поток, поэтому слой можно инициализировать из синхронного
+///   `main()` до старта runtime.
+/// - `worker_mode`: где выполняется фоновый worker, см. [`WorkerMode`].
+/// - `startup_check`: проверять ли sink через [`LogSink::health_check`]
+///   при инициализации, см. [`StartupCheck`].
+/// - `schema_check`: если `true`, при инициализации таблицы и индексы
+///   sink’а сравниваются с ожидаемой схемой через
+///   [`LogSink::schema_drift`], а отличия (недостающие колонки, другие
+///   типы, лишние колонки) сообщаются одной диагностикой `SchemaDrift`,
+///   а не ошибкой первой вставки. Не мешает старту; по умолчанию `false`.
+/// - `enqueue_timeout`: если задано, при заполненном канале поток
+///   приложения ждёт освобождения места не дольше этого времени и лишь
+///   затем отбрасывает запись. Немного задержки в обмен на гораздо
+///   меньшее число потерь при коротких всплесках; держите значение
+///   маленьким (единицы миллисекунд), так как ожидание блокирует поток.
+/// - `on_drop`: callback, вызываемый в потоке приложения для каждой
+///   записи, потерянной из‑за переполнения канала или после shutdown
+///   (с [`crate::layer::DropReason`]); можно вести свои метрики или писать
+///   в запасной канал. Должен быть быстрым и не логировать через `tracing`.
+/// - `diagnostics`: куда слой сообщает о собственных проблемах
+///   (переполнение канала, повторы и ошибки sink’а) — stderr, `tracing`
+///   target [`crate::diagnostics::DIAGNOSTICS_TARGET`], callback или
+///   никуда; сообщения одного вида не чаще `min_interval`.
+/// - `ignore_targets`: target’ы (вместе с вложенными модулями), события
+///   которых никогда не перехватываются, например `hyper` или `rdkafka`.
+///   События самого крейта (`tracing_log_sink::*`) и события, порождённые
+///   во время работы фонового worker’а, игнорируются всегда, чтобы
+///   исключить петли обратной связи.
+/// - `capture_error_spans`: если `true`, каждый span, внутри которого было
+///   событие уровня `ERROR`, при закрытии отправляется отдельной записью с
+///   `span_name` и `duration_ms` — время обработки запроса до ошибки без
+///   отдельного tracing‑бэкенда.
+/// - `span_fields`: наследование полей span’ов, внутри которых произошло
+///   событие ([`SpanFieldsConfig`]): от всех предков, только от `N`
+///   ближайших или ни от одного (по умолчанию), и чьё значение побеждает
+///   при совпадении ключей — внутреннего span’а или внешнего. Собственные
+///   поля события всегда имеют приоритет.
+/// - `persist_unsent`: если задано ([`PersistConfig`]), при shutdown
+///   батчи в отправке, текущий батч и записи, оставшиеся в канале,
+///   сначала сохраняются в локальный файл и только потом отправляются;
+///   после доставки файл удаляется. Если процесс завершился раньше,
+///   следующий слой с тем же путём отправит сохранённые записи (не
+///   старше `max_age`) перед новыми и удалит файл после их доставки.
+///   Возможны повторы с тем же `event_id`. С feature `encryption`
+///   записи в файле шифруются ключом `PersistConfig::encryption`.
+/// - `stall_timeout`: если задано, отдельный поток следит за heartbeat
+///   фонового worker’а ([`crate::stats::LayerStats::last_heartbeat`]) и
+///   сообщает диагностику `WorkerStalled`, если worker не продвигался
+///   дольше этого времени (например, завис sink). Должно быть больше
+///   `flush_interval`, иначе простаивающий worker сочтётся зависшим.
+#[derive(Clone)]
 pub struct LayerConfig {
     pub channel_buffer: usize,
+    pub channel_shards: usize,
+    pub channel_kind: ChannelKind,
+    pub priority_lanes: Option<PriorityLanes>,
     pub batch_size: usize,
+    pub max_batch_bytes: Option<usize>,
+    pub max_buffered_bytes: Option<usize>,
+    pub max_in_flight: usize,
+    pub record_pool_size: usize,
     pub flush_interval: Duration,
+    pub adaptive_batching: Option<AdaptiveBatching>,
+    pub level: LogLevel,
+    pub level_policy: BTreeMap<LogLevel, ShipPolicy>,
+    pub routes: Vec<Route>,
     pub enable_stdout: bool,
+    pub resource: ResourceInfo,
+    pub processors: Vec<Arc<dyn RecordProcessor>>,
+    pub flatten_fields: Option<FlattenConfig>,
+    pub filter: Option<RecordFilter>,
+    pub field_allowlist: Option<Vec<String>>,
+    pub limits: RecordLimits,
+    pub collapse_window: Option<Duration>,
+    pub fingerprint: Option<FingerprintConfig>,
+    pub clock: Arc<dyn Clock>,
+    pub runtime: Option<Handle>,
+    pub worker_mode: WorkerMode,
+    pub startup_check: StartupCheck,
+    pub schema_check: bool,
+    pub enqueue_timeout: Option<Duration>,
+    pub on_drop: Option<DropHook>,
+    pub diagnostics: DiagnosticsConfig,
+    pub ignore_targets: Vec<String>,
+    pub capture_error_spans: bool,
+    pub span_fields: SpanFieldsConfig,
+    pub persist_unsent: Option<PersistConfig>,
+    pub stall_timeout: Option<Duration>,
+    #[cfg(feature = "log-compat")]
+    pub capture_log: bool,
+}
+
+/// Where the background worker of [`ErrorLogLayer`] runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WorkerMode {
+    /// Spawn a task on [`LayerConfig::runtime`] or the current Tokio
+    /// runtime, falling back to a shared background thread outside of one.
+    #[default]
+    Runtime,
+    /// Run on a dedicated std thread with its own small current-thread
+    /// runtime, so purely synchronous binaries can use the async sinks
+    /// without touching (or having) an application runtime.
+    Thread,
+}
+
+/// Channel between [`ErrorLogLayer`] and its worker, see
+/// [`LayerConfig::channel_kind`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// Holds at most [`LayerConfig::channel_buffer`] records; further ones
+    /// are dropped with [`crate::layer::DropReason::ChannelFull`].
+    #[default]
+    Bounded,
+    /// Never full, for applications that prefer memory over drops.
+    ///
+    /// **Fields**
+    /// - `high_watermark`: queued bytes (by
+    ///   [`LogRecord::estimated_size`]) above which every enqueue reports
+    ///   [`crate::diagnostics::DiagnosticKind::ChannelHighWatermark`],
+    ///   rate limited like all diagnostics.
+    /// - `max_bytes`: hard cap on queued bytes; beyond it records are
+    ///   dropped with [`crate::layer::DropReason::BytesLimit`]. A lower
+    ///   [`LayerConfig::max_buffered_bytes`] wins.
+    Unbounded { high_watermark: usize, max_bytes: usize },
+}
+
+/// A separate lane for `ERROR` records, see [`LayerConfig::priority_lanes`].
+///
+/// **Fields**
+/// - `error_buffer`: capacity of the lane in records (default 1024),
+///   split between [`LayerConfig::channel_shards`] like
+///   [`LayerConfig::channel_buffer`]. The lane is always bounded and does
+///   not count against [`LayerConfig::max_buffered_bytes`] or the byte cap
+///   of [`ChannelKind::Unbounded`], so lower levels cannot fill it.
+/// - `error_weight`: how many `ERROR` records the worker takes before a
+///   lower-level one while both lanes hold records (default 8, minimum 1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityLanes {
+    pub error_buffer: usize,
+    pub error_weight: usize,
+}
+
+impl Default for PriorityLanes {
+    fn default() -> Self {
+        Self { error_buffer: 1024, error_weight: 8 }
+    }
+}
+
+/// What `init_tracing*` does with the result of [`LogSink::health_check`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StartupCheck {
+    /// Do not probe the sink.
+    #[default]
+    Skip,
+    /// Probe the sink and report a diagnostic if it is unhealthy.
+    Warn,
+    /// Probe the sink and panic if it is unhealthy, so a wrong DSN fails
+    /// the deployment instead of surfacing at the first error.
+    FailFast,
+}
+
+impl fmt::Debug for LayerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("LayerConfig");
+        d
+            .field("channel_buffer", &self.channel_buffer)
+            .field("channel_shards", &self.channel_shards)
+            .field("channel_kind", &self.channel_kind)
+            .field("priority_lanes", &self.priority_lanes)
+            .field("batch_size", &self.batch_size)
+            .field("max_batch_bytes", &self.max_batch_bytes)
+            .field("max_buffered_bytes", &self.max_buffered_bytes)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("record_pool_size", &self.record_pool_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("adaptive_batching", &self.adaptive_batching)
+            .field("level", &self.level)
+            .field("level_policy", &self.level_policy)
+            .field("routes", &self.routes)
+            .field("enable_stdout", &self.enable_stdout)
+            .field("resource", &self.resource)
+            .field("processors", &self.processors.len())
+            .field("flatten_fields", &self.flatten_fields)
+            .field("filter", &self.filter.is_some())
+            .field("field_allowlist", &self.field_allowlist)
+            .field("limits", &self.limits)
+            .field("collapse_window", &self.collapse_window)
+            .field("fingerprint", &self.fingerprint)
+            .field("runtime", &self.runtime.is_some())
+            .field("worker_mode", &self.worker_mode)
+            .field("startup_check", &self.startup_check)
+            .field("schema_check", &self.schema_check)
+            .field("enqueue_timeout", &self.enqueue_timeout)
+            .field("on_drop", &self.on_drop.is_some())
+            .field("diagnostics", &self.diagnostics)
+            .field("ignore_targets", &self.ignore_targets)
+            .field("capture_error_spans", &self.capture_error_spans)
+            .field("span_fields", &self.span_fields)
+            .field("persist_unsent", &self.persist_unsent)
+            .field("stall_timeout", &self.stall_timeout);
+        #[cfg(feature = "log-compat")]
+        d.field("capture_log", &self.capture_log);
+        d.finish_non_exhaustive()
+    }
 }
 
 impl Default for LayerConfig {
     fn default() -> Self {
         Self {
             channel_buffer: 1024,
+            channel_shards: 1,
+            channel_kind: ChannelKind::default(),
+            priority_lanes: None,
             batch_size: 128,
+            max_batch_bytes: None,
+            max_buffered_bytes: None,
+            max_in_flight: 1,
+            record_pool_size: 1024,
             flush_interval: Duration::from_secs(1),
+            adaptive_batching: None,
+            level: LogLevel::Error,
+            level_policy: BTreeMap::new(),
+            routes: Vec::new(),
             enable_stdout: true,
+            resource: ResourceInfo::default(),
+            processors: Vec::new(),
+            flatten_fields: None,
+            filter: None,
+            field_allowlist: None,
+            limits: RecordLimits::default(),
+            collapse_window: None,
+            fingerprint: Some(FingerprintConfig::default()),
+            clock: Arc::new(SystemClock),
+            runtime: None,
+            worker_mode: WorkerMode::default(),
+            startup_check: StartupCheck::default(),
+            schema_check: false,
+            enqueue_timeout: None,
+            on_drop: None,
+            diagnostics: DiagnosticsConfig::default(),
+            ignore_targets: Vec::new(),
+            capture_error_spans: false,
+            span_fields: SpanFieldsConfig::default(),
+            persist_unsent: None,
+            stall_timeout: None,
+            #[cfg(feature = "log-compat")]
+            capture_log: true,
         }
     }
 }
 
+/// Error returned by the `try_init_tracing*` functions.
+#[derive(thiserror::Error, Debug)]
+pub enum InitError {
+    /// Another global `tracing` subscriber was installed first, e.g. by an
+    /// earlier `init_tracing*` call in the same test binary.
+    #[error("a global tracing subscriber is already installed")]
+    AlreadyInitialized,
+
+    /// [`LayerConfig::capture_log`] is set but a `log` logger is already
+    /// installed.
+    #[cfg(feature = "log-compat")]
+    #[error("cannot install log tracer: {0}")]
+    LogTracer(#[from] tracing_log::log::SetLoggerError),
+
+    /// The sink failed its probe under [`StartupCheck::FailFast`].
+    #[error("log sink health check failed: {0}")]
+    HealthCheck(SinkError),
+}
+
 /// Initialize global `tracing` subscriber using the provided sink and
 /// [`LayerConfig`].
 ///
@@ -52,26 +423,163 @@ impl Default for LayerConfig {
 /// This installs a [`Registry`] combined with [`ErrorLogLayer`] as the
 /// global default subscriber, so all `tracing` events in the process
 /// are observed by the layer.
-pub fn init_tracing_with_config(sink: Arc<dyn LogSink>, config: LayerConfig) {
-    let (layer, _handle) = ErrorLogLayer::new(
-        sink,
-        config.channel_buffer,
-        config.batch_size,
-        config.flush_interval,
-    );
+///
+/// Unless [`LayerConfig::startup_check`] is [`StartupCheck::Skip`], the
+/// sink is probed first; this blocks until the probe completes and
+/// panics on failure with [`StartupCheck::FailFast`]. With
+/// [`LayerConfig::schema_check`], the sink's schema is compared next,
+/// blocking as well.
+///
+/// Panics as well if a global subscriber is already installed; see
+/// [`try_init_tracing_with_config`] for the fallible variant.
+///
+/// **Returns**
+/// - [`LogRecorder`] for reading stats, changing the captured level at
+///   runtime, shutting the pipeline down before exit and waiting for the
+///   worker.
+pub fn init_tracing_with_config(sink: Arc<dyn LogSink>, config: LayerConfig) -> LogRecorder {
+    try_init_tracing_with_config(sink, config).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`init_tracing_with_config`], but returns an error instead of
+/// panicking, so libraries and tests can cope with an already installed
+/// subscriber.
+///
+/// When a global subscriber is already installed, the worker spawned for
+/// the layer is stopped again before the error is returned.
+///
+/// **Returns**
+/// - `Err(InitError::AlreadyInitialized)` if a global subscriber is
+///   installed.
+/// - `Err(InitError::HealthCheck)` if the probe fails under
+///   [`StartupCheck::FailFast`].
+/// - `Err(InitError::LogTracer)` (feature `log-compat`) if
+///   [`LayerConfig::capture_log`] is set and a `log` logger is installed.
+pub fn try_init_tracing_with_config(sink: Arc<dyn LogSink>, config: LayerConfig) -> Result<LogRecorder, InitError> {
+    if config.startup_check != StartupCheck::Skip {
+        check_health(&config, runtime::block_on(sink.health_check()))?;
+    }
+    if config.schema_check {
+        check_schema(&config, runtime::block_on(sink.schema_drift()));
+    }
+
+    // Checked before anything is installed or the layer reads the
+    // `persist_unsent` backlog, so a failed init leaves no side effects.
+    if tracing::dispatcher::has_been_set() {
+        return Err(InitError::AlreadyInitialized);
+    }
+    #[cfg(feature = "log-compat")]
+    if config.capture_log {
+        tracing_log::LogTracer::init()?;
+    }
+
+    let (layer, worker) = ErrorLogLayer::with_config(sink, &config);
+    let recorder = LogRecorder::new(layer.handle(), worker);
 
     // Всегда подключаем слой, который пишет в внешний sink (БД и т.д.).
     // Дополнительно, при `enable_stdout = true`, подключаем `fmt`‑слой,
     // чтобы видеть события в консоли. Для совместимости типов собираем
     // subscriber в двух вариантах.
-    if config.enable_stdout {
+    let installed = if config.enable_stdout {
         let fmt_layer = tracing_subscriber::fmt::layer();
         let subscriber = Registry::default().with(layer).with(fmt_layer);
-        tracing::subscriber::set_global_default(subscriber).expect("set global subscriber");
+        tracing::subscriber::set_global_default(subscriber)
     } else {
         let subscriber = Registry::default().with(layer);
-        tracing::subscriber::set_global_default(subscriber).expect("set global subscriber");
+        tracing::subscriber::set_global_default(subscriber)
+    };
+    if installed.is_err() {
+        // Lost a race with another init. The layer never saw an event, and
+        // a `persist_unsent` backlog it read stays on disk until it has
+        // been delivered, so its worker can be stopped.
+        recorder.into_parts().1.abort();
+        return Err(InitError::AlreadyInitialized);
+    }
+
+    Ok(recorder)
+}
+
+/// Run `f` with the layer installed as the default subscriber of the
+/// future it returns only, leaving the global dispatcher alone.
+///
+/// Meant for tests that each record into their own sink while running in
+/// parallel, and for applications that must not install a global
+/// subscriber. `f` receives the [`LayerHandle`] of the layer, e.g. to
+/// flush it and check its stats.
+///
+/// **Behavior**
+/// - The subscriber is set around every poll of the future, so it follows
+///   the future across threads; tasks spawned inside do not inherit it
+///   unless instrumented with
+///   `tracing::instrument::WithSubscriber::with_current_subscriber`.
+/// - [`LayerConfig::startup_check`] applies as in
+///   [`init_tracing_with_config`], panicking under
+///   [`StartupCheck::FailFast`]; `capture_log` is ignored, since a `log`
+///   logger is always global. So does [`LayerConfig::schema_check`].
+/// - Once the future completes, the layer is shut down, waiting up to
+///   [`SCOPED_SHUTDOWN_TIMEOUT`], so the sink holds every record when this
+///   returns.
+///
+/// ```ignore
+/// let stats = with_scoped_recorder(sink, LayerConfig::default(), |handle| async move {
+///     tracing::error!("boom");
+///     handle.stats()
+/// })
+/// .await;
+/// assert_eq!(stats.total_events, 1);
+/// ```
+pub async fn with_scoped_recorder<F, Fut>(sink: Arc<dyn LogSink>, config: LayerConfig, f: F) -> Fut::Output
+where
+    F: FnOnce(LayerHandle) -> Fut,
+    Fut: Future,
+{
+    if config.startup_check != StartupCheck::Skip {
+        check_health(&config, sink.health_check().await).unwrap_or_else(|e| panic!("{}", e));
+    }
+    if config.schema_check {
+        check_schema(&config, sink.schema_drift().await);
+    }
+
+    let (layer, _worker) = ErrorLogLayer::with_config(sink, &config);
+    let handle = layer.handle();
+    let dispatch = if config.enable_stdout {
+        Dispatch::new(Registry::default().with(layer).with(tracing_subscriber::fmt::layer()))
+    } else {
+        Dispatch::new(Registry::default().with(layer))
+    };
+
+    let output = f(handle.clone()).with_subscriber(dispatch).await;
+    let _ = handle.shutdown(SCOPED_SHUTDOWN_TIMEOUT).await;
+    output
+}
+
+/// How long [`with_scoped_recorder`] waits for the layer to shut down.
+pub const SCOPED_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Apply [`LayerConfig::startup_check`] to the outcome of the sink's
+/// [`LogSink::health_check`].
+fn check_health(config: &LayerConfig, result: Result<(), SinkError>) -> Result<(), InitError> {
+    if let Err(e) = result {
+        match config.startup_check {
+            StartupCheck::FailFast => return Err(InitError::HealthCheck(e)),
+            _ => Diagnostics::new(&config.diagnostics).emit(DiagnosticKind::HealthCheckFailed, || {
+                format!("log sink health check failed: {}", e)
+            }),
+        }
     }
+    Ok(())
+}
+
+/// Report the outcome of the sink's [`LogSink::schema_drift`] for
+/// [`LayerConfig::schema_check`], one line per differing destination in a
+/// single diagnostic, so rate limiting keeps all of them.
+fn check_schema(config: &LayerConfig, result: Result<Vec<SchemaDrift>, SinkError>) {
+    let message = match result {
+        Ok(drift) if drift.is_empty() => return,
+        Ok(drift) => drift.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"),
+        Err(e) => format!("log sink schema check failed: {}", e),
+    };
+    Diagnostics::new(&config.diagnostics).emit(DiagnosticKind::SchemaDrift, || message);
 }
 
 /// Initialize tracing with sensible defaults.
@@ -85,6 +593,26 @@ pub fn init_tracing_with_config(sink: Arc<dyn LogSink>, config: LayerConfig) {
 /// Equivalent to calling [`init_tracing_with_config`] with
 /// [`LayerConfig::default`]. This is the recommended entrypoint for
 /// typical microservices.
-pub fn init_tracing(sink: Arc<dyn LogSink>) {
-    init_tracing_with_config(sink, LayerConfig::default());
+pub fn init_tracing(sink: Arc<dyn LogSink>) -> LogRecorder {
+    init_tracing_with_config(sink, LayerConfig::default())
+}
+
+/// Like [`init_tracing`], but returns an error instead of panicking, see
+/// [`try_init_tracing_with_config`].
+pub fn try_init_tracing(sink: Arc<dyn LogSink>) -> Result<LogRecorder, InitError> {
+    try_init_tracing_with_config(sink, LayerConfig::default())
+}
+
+/// Initialize tracing entirely from environment variables.
+///
+/// **Behavior**
+///
+/// Builds the sink and [`LayerConfig`] with [`crate::env::from_env`] and
+/// passes them to [`init_tracing_with_config`].
+///
+/// **Returns**
+/// - `Err(..)` if the environment does not describe a usable backend.
+pub fn init_tracing_from_env() -> Result<LogRecorder, crate::env::EnvConfigError> {
+    let (sink, config) = crate::env::from_env()?;
+    Ok(init_tracing_with_config(sink, config))
 }
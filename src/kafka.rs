@@ -1,47 +1,741 @@
-use crate::{record::LogRecord, sink::LogSink};
+use crate::backend::{decode, DsnError};
+use crate::destination::DestinationTemplate;
+use crate::encoding::{PayloadEncoding, SerializedBatch};
+use crate::record::LogRecord;
+use crate::sink::{LogSink, SinkError};
 use async_trait::async_trait;
 use rdkafka::config::ClientConfig;
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::error::{KafkaError, RDKafkaErrorCode};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use uuid::Uuid;
 
-/// Kafka sink that publishes each log record as a JSON message to
-/// a configured topic.
+/// Configuration for [`KafkaSink`].
+#[derive(Clone, Debug)]
+pub struct KafkaConfig {
+    /// Comma-separated list of broker addresses.
+    pub brokers: String,
+    /// Target topic; placeholders such as `{service}` are filled per
+    /// record, see [`DestinationTemplate`].
+    pub topic: String,
+    /// Message payload format.
+    pub encoding: PayloadEncoding,
+    /// SASL authentication; `None` connects without it.
+    pub sasl: Option<KafkaSasl>,
+    /// TLS towards the brokers; `None` connects in plain text.
+    ///
+    /// Needs librdkafka built with TLS: enable the `ssl` (or
+    /// `ssl-vendored`) feature of `rdkafka` in the application.
+    pub tls: Option<KafkaTls>,
+    /// How messages are assigned to partitions.
+    pub partitioner: KafkaPartitioner,
+    /// Acknowledgements, timeouts and retries of the producer.
+    pub delivery: KafkaDelivery,
+    /// Further librdkafka properties, e.g. `"acks"`, applied after the
+    /// typed settings.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self {
+            brokers: "localhost:9092".to_string(),
+            topic: "logs".to_string(),
+            encoding: PayloadEncoding::Json,
+            sasl: None,
+            tls: None,
+            partitioner: KafkaPartitioner::default(),
+            delivery: KafkaDelivery::default(),
+            properties: BTreeMap::new(),
+        }
+    }
+}
+
+/// Partitioning strategy of a [`KafkaSink`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum KafkaPartitioner {
+    /// Messages have no key and librdkafka fills one partition for a while
+    /// before moving to the next, which gives the largest batches.
+    #[default]
+    Sticky,
+    /// Messages are keyed by a template such as `"{service}"` or
+    /// `"{fields.tenant_id}"` (see [`DestinationTemplate`]) and assigned by
+    /// murmur2 of the key, like the Java client does. All records with the
+    /// same key land in one partition, so consumers see them in order.
+    /// Placeholder values are normalized as in destination names.
+    Murmur2 { key: String },
+    /// The partition is the number in the named event field (a number or
+    /// a numeric string); records without one fall back to the sticky
+    /// partitioner, and records naming a partition the topic does not
+    /// have are rejected.
+    Field(String),
+}
+
+/// Acknowledgements a [`KafkaSink`] waits for (`acks`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KafkaAcks {
+    /// Do not wait; messages lost by the broker are not noticed.
+    None,
+    /// Wait for the partition leader.
+    Leader,
+    /// Wait for all in-sync replicas.
+    #[default]
+    All,
+}
+
+/// What a [`KafkaSink`] does with a record whose delivery timed out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeliveryTimeoutPolicy {
+    /// Report it as [`SinkError::Transient`], so the layer keeps retrying.
+    #[default]
+    Retry,
+    /// Retry, but reject the record with [`SinkError::Permanent`] once its
+    /// delivery timed out this many times (at least once). Records are
+    /// told apart by `event_id`; those without one are always retried.
+    DropAfter(u32),
+}
+
+/// Delivery guarantees of a [`KafkaSink`], trading durability against
+/// bounded latency.
+///
+/// **Fields**
+/// - `acks`: acknowledgements to wait for.
+/// - `message_timeout`: how long librdkafka tries to deliver a message,
+///   retries included, before reporting a timeout (`message.timeout.ms`).
+/// - `retries`: retries of a failed produce request within
+///   `message_timeout` (`retries`).
+/// - `idempotent`: let brokers drop duplicates of librdkafka's own
+///   retries and keep their order (`enable.idempotence`); needs
+///   [`KafkaAcks::All`].
+/// - `on_timeout`: what a timed-out delivery means for the layer.
+///
+/// The defaults are librdkafka's: all replicas, five minutes of retries.
+/// For bounded latency, lower `message_timeout` and use
+/// [`DeliveryTimeoutPolicy::DropAfter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KafkaDelivery {
+    pub acks: KafkaAcks,
+    pub message_timeout: Duration,
+    pub retries: u32,
+    pub idempotent: bool,
+    pub on_timeout: DeliveryTimeoutPolicy,
+}
+
+impl Default for KafkaDelivery {
+    fn default() -> Self {
+        Self {
+            acks: KafkaAcks::All,
+            message_timeout: Duration::from_secs(300),
+            retries: i32::MAX as u32,
+            idempotent: false,
+            on_timeout: DeliveryTimeoutPolicy::Retry,
+        }
+    }
+}
+
+/// SASL mechanism and credentials of a [`KafkaConfig`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum KafkaSasl {
+    /// `PLAIN`, e.g. Confluent Cloud API keys.
+    Plain { username: String, password: String },
+    /// `SCRAM-SHA-256`.
+    ScramSha256 { username: String, password: String },
+    /// `SCRAM-SHA-512`, e.g. Amazon MSK with SASL/SCRAM.
+    ScramSha512 { username: String, password: String },
+    /// `OAUTHBEARER` with tokens fetched from an OIDC provider.
+    OAuthBearer(OAuthBearerConfig),
+}
+
+/// OIDC client credentials for [`KafkaSasl::OAuthBearer`].
+///
+/// **Fields**
+/// - `token_endpoint`: URL tokens are requested from.
+/// - `client_id` / `client_secret`: credentials of the client.
+/// - `scope`: requested scope, if the provider needs one.
+/// - `extensions`: SASL extensions as `key=value` pairs separated by `,`,
+///   e.g. `logicalCluster=lkc-123,identityPoolId=pool-abc` for Confluent
+///   Cloud.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct OAuthBearerConfig {
+    pub token_endpoint: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: Option<String>,
+    pub extensions: Option<String>,
+}
+
+impl fmt::Debug for KafkaSasl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mechanism, username) = match self {
+            KafkaSasl::Plain { username, .. } => ("Plain", username),
+            KafkaSasl::ScramSha256 { username, .. } => ("ScramSha256", username),
+            KafkaSasl::ScramSha512 { username, .. } => ("ScramSha512", username),
+            KafkaSasl::OAuthBearer(config) => return f.debug_tuple("OAuthBearer").field(config).finish(),
+        };
+        f.debug_struct(mechanism)
+            .field("username", username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+impl fmt::Debug for OAuthBearerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuthBearerConfig")
+            .field("token_endpoint", &self.token_endpoint)
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<redacted>")
+            .field("scope", &self.scope)
+            .field("extensions", &self.extensions)
+            .finish()
+    }
+}
+
+/// TLS settings of a [`KafkaConfig`].
+///
+/// The default verifies the brokers against the system's root
+/// certificates and presents no client certificate, which is what
+/// managed services such as MSK and Confluent Cloud expect.
+///
+/// **Fields**
+/// - `ca_bundle`: PEM file with the certificates to trust instead.
+/// - `client_cert`: PEM file with the client certificate, for mTLS.
+/// - `client_key`: PEM file with the client's private key.
+/// - `key_password`: password of `client_key`, if it is encrypted.
+/// - `insecure`: accept any broker certificate. Only for tests.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct KafkaTls {
+    pub ca_bundle: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub key_password: Option<String>,
+    pub insecure: bool,
+}
+
+impl fmt::Debug for KafkaTls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KafkaTls")
+            .field("ca_bundle", &self.ca_bundle)
+            .field("client_cert", &self.client_cert)
+            .field("client_key", &self.client_key)
+            .field("key_password", &self.key_password.as_ref().map(|_| "<redacted>"))
+            .field("insecure", &self.insecure)
+            .finish()
+    }
+}
+
+impl KafkaConfig {
+    /// Parse a `kafka://` DSN:
+    /// `kafka://[user:password@]broker1,broker2/topic?key=value&..`.
+    ///
+    /// Credentials in the DSN enable SASL (`PLAIN` unless
+    /// `sasl_mechanism` says otherwise), and SASL or any `ssl_*` key
+    /// enables TLS unless `tls=false`. Query keys:
+    /// - `sasl_mechanism`: `plain`, `scram-sha-256`, `scram-sha-512` or
+    ///   `oauthbearer`.
+    /// - `oauth_token_endpoint`, `oauth_client_id`, `oauth_client_secret`,
+    ///   `oauth_scope`, `oauth_extensions`: see [`OAuthBearerConfig`].
+    /// - `key`: key template, selecting [`KafkaPartitioner::Murmur2`].
+    /// - `partition_field`: selects [`KafkaPartitioner::Field`].
+    /// - `acks` (`0`, `1` or `all`), `message_timeout_ms`, `retries`,
+    ///   `idempotent`, `drop_after_timeouts`: see [`KafkaDelivery`].
+    /// - `tls`: `true` or `false`.
+    /// - `ssl_ca`, `ssl_cert`, `ssl_key`, `ssl_key_password`,
+    ///   `tls_insecure`: see [`KafkaTls`].
+    /// - Keys containing a dot, e.g. `linger.ms`, are passed to librdkafka
+    ///   as properties.
+    ///
+    /// **Returns**
+    /// - `Err(DsnError::InvalidOption)` for other keys and invalid values.
+    pub fn from_dsn(dsn: &str) -> Result<Self, DsnError> {
+        let without_scheme = dsn.get("kafka://".len()..).unwrap_or("");
+        let (location, query) = without_scheme.split_once('?').unwrap_or((without_scheme, ""));
+        let (userinfo, location) = match location.rsplit_once('@') {
+            Some((userinfo, location)) => (Some(userinfo), location),
+            None => (None, location),
+        };
+        let (brokers, topic) = location.split_once('/').unwrap_or((location, ""));
+
+        let mut config = KafkaConfig {
+            brokers: brokers.to_string(),
+            topic: Some(topic).filter(|t| !t.is_empty()).unwrap_or("logs").to_string(),
+            ..KafkaConfig::default()
+        };
+        let mut mechanism = None;
+        let mut oauth = OAuthBearerConfig::default();
+        let mut tls = KafkaTls::default();
+        let mut tls_enabled = None;
+        let mut tls_configured = false;
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, raw) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = decode(raw);
+            let invalid = || DsnError::InvalidOption { key: key.to_string(), value: value.clone() };
+            let flag = || match value.as_str() {
+                "true" | "1" => Ok(true),
+                "false" | "0" => Ok(false),
+                _ => Err(invalid()),
+            };
+            match key {
+                "sasl_mechanism" => mechanism = Some(value.to_ascii_lowercase()),
+                "oauth_token_endpoint" => oauth.token_endpoint = value,
+                "oauth_client_id" => oauth.client_id = value,
+                "oauth_client_secret" => oauth.client_secret = value,
+                "oauth_scope" => oauth.scope = Some(value),
+                "oauth_extensions" => oauth.extensions = Some(value),
+                "key" if !value.is_empty() => config.partitioner = KafkaPartitioner::Murmur2 { key: value },
+                "partition_field" if !value.is_empty() => config.partitioner = KafkaPartitioner::Field(value),
+                "acks" => {
+                    config.delivery.acks = match value.as_str() {
+                        "0" => KafkaAcks::None,
+                        "1" => KafkaAcks::Leader,
+                        "all" | "-1" => KafkaAcks::All,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "message_timeout_ms" => {
+                    config.delivery.message_timeout = Duration::from_millis(value.parse().map_err(|_| invalid())?)
+                }
+                "retries" => config.delivery.retries = value.parse().map_err(|_| invalid())?,
+                "idempotent" => config.delivery.idempotent = flag()?,
+                "drop_after_timeouts" => {
+                    config.delivery.on_timeout = DeliveryTimeoutPolicy::DropAfter(value.parse().map_err(|_| invalid())?)
+                }
+                "tls" => tls_enabled = Some(flag()?),
+                "ssl_ca" => tls.ca_bundle = Some(value.into()),
+                "ssl_cert" => tls.client_cert = Some(value.into()),
+                "ssl_key" => tls.client_key = Some(value.into()),
+                "ssl_key_password" => tls.key_password = Some(value),
+                "tls_insecure" => tls.insecure = flag()?,
+                key if key.contains('.') => {
+                    config.properties.insert(key.to_string(), value);
+                }
+                _ => return Err(invalid()),
+            }
+            tls_configured |= key.starts_with("ssl_") || key == "tls_insecure";
+        }
+
+        let (username, password) = match userinfo.map(|u| u.split_once(':').unwrap_or((u, ""))) {
+            Some((username, password)) => (decode(username), decode(password)),
+            None => (String::new(), String::new()),
+        };
+        config.sasl = match mechanism.as_deref() {
+            None if userinfo.is_none() => None,
+            None | Some("plain") => Some(KafkaSasl::Plain { username, password }),
+            Some("scram-sha-256") => Some(KafkaSasl::ScramSha256 { username, password }),
+            Some("scram-sha-512") => Some(KafkaSasl::ScramSha512 { username, password }),
+            Some("oauthbearer") => Some(KafkaSasl::OAuthBearer(oauth)),
+            Some(other) => {
+                return Err(DsnError::InvalidOption {
+                    key: "sasl_mechanism".to_string(),
+                    value: other.to_string(),
+                })
+            }
+        };
+        if tls_enabled.unwrap_or(config.sasl.is_some() || tls_configured) {
+            config.tls = Some(tls);
+        }
+        Ok(config)
+    }
+
+    /// librdkafka client configuration for these settings.
+    fn client_config(&self) -> ClientConfig {
+        let mut client = ClientConfig::new();
+        client.set("bootstrap.servers", &self.brokers);
+        let protocol = match (&self.sasl, &self.tls) {
+            (None, None) => "plaintext",
+            (None, Some(_)) => "ssl",
+            (Some(_), None) => "sasl_plaintext",
+            (Some(_), Some(_)) => "sasl_ssl",
+        };
+        client.set("security.protocol", protocol);
+
+        match &self.sasl {
+            Some(KafkaSasl::Plain { username, password }) => set_credentials(&mut client, "PLAIN", username, password),
+            Some(KafkaSasl::ScramSha256 { username, password }) => {
+                set_credentials(&mut client, "SCRAM-SHA-256", username, password)
+            }
+            Some(KafkaSasl::ScramSha512 { username, password }) => {
+                set_credentials(&mut client, "SCRAM-SHA-512", username, password)
+            }
+            Some(KafkaSasl::OAuthBearer(oauth)) => {
+                client
+                    .set("sasl.mechanism", "OAUTHBEARER")
+                    .set("sasl.oauthbearer.method", "oidc")
+                    .set("sasl.oauthbearer.token.endpoint.url", &oauth.token_endpoint)
+                    .set("sasl.oauthbearer.client.id", &oauth.client_id)
+                    .set("sasl.oauthbearer.client.secret", &oauth.client_secret);
+                if let Some(scope) = &oauth.scope {
+                    client.set("sasl.oauthbearer.scope", scope);
+                }
+                if let Some(extensions) = &oauth.extensions {
+                    client.set("sasl.oauthbearer.extensions", extensions);
+                }
+            }
+            None => {}
+        }
+
+        if let Some(tls) = &self.tls {
+            let path = |p: &PathBuf| p.to_string_lossy().into_owned();
+            if let Some(ca) = &tls.ca_bundle {
+                client.set("ssl.ca.location", path(ca));
+            }
+            if let Some(cert) = &tls.client_cert {
+                client.set("ssl.certificate.location", path(cert));
+            }
+            if let Some(key) = &tls.client_key {
+                client.set("ssl.key.location", path(key));
+            }
+            if let Some(password) = &tls.key_password {
+                client.set("ssl.key.password", password);
+            }
+            if tls.insecure {
+                client
+                    .set("enable.ssl.certificate.verification", "false")
+                    .set("ssl.endpoint.identification.algorithm", "none");
+            }
+        }
+
+        let delivery = &self.delivery;
+        let acks = match delivery.acks {
+            KafkaAcks::None => "0",
+            KafkaAcks::Leader => "1",
+            KafkaAcks::All => "all",
+        };
+        client
+            .set("acks", acks)
+            .set("message.timeout.ms", delivery.message_timeout.as_millis().to_string())
+            .set("retries", delivery.retries.min(i32::MAX as u32).to_string())
+            .set("enable.idempotence", delivery.idempotent.to_string());
+
+        if let KafkaPartitioner::Murmur2 { .. } = self.partitioner {
+            client.set("partitioner", "murmur2_random");
+        }
+
+        for (key, value) in &self.properties {
+            client.set(key, value);
+        }
+        client
+    }
+}
+
+fn set_credentials(client: &mut ClientConfig, mechanism: &str, username: &str, password: &str) {
+    client
+        .set("sasl.mechanism", mechanism)
+        .set("sasl.username", username)
+        .set("sasl.password", password);
+}
+
+/// Kafka sink that publishes each log record as a message to a configured
+/// topic, encoded as JSON unless another [`PayloadEncoding`] is chosen.
 #[derive(Clone)]
 pub struct KafkaSink {
     producer: FutureProducer,
-    topic: String,
+    topic: DestinationTemplate,
+    encoding: PayloadEncoding,
+    key: Option<DestinationTemplate>,
+    partition_field: Option<String>,
+    on_timeout: DeliveryTimeoutPolicy,
+    /// Delivery timeouts so far per record, for
+    /// [`DeliveryTimeoutPolicy::DropAfter`].
+    timeouts: Arc<Mutex<HashMap<Uuid, u32>>>,
 }
 
 impl KafkaSink {
     /// Create a new Kafka sink.
     ///
     /// `brokers` is a comma-separated list of broker addresses.
-    /// `topic` is the target Kafka topic.
+    /// `topic` is the target Kafka topic; placeholders such as `{service}`
+    /// are filled per record, see [`DestinationTemplate`].
     pub fn new(brokers: &str, topic: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
-        let producer: FutureProducer = ClientConfig::new()
-            .set("bootstrap.servers", brokers)
-            .create()?;
+        Self::with_encoding(brokers, topic, PayloadEncoding::Json)
+    }
+
+    /// Create a new Kafka sink that encodes records with `encoding`.
+    ///
+    /// Consumers can decode the messages with [`PayloadEncoding::decode`].
+    pub fn with_encoding(
+        brokers: &str,
+        topic: &str,
+        encoding: PayloadEncoding,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Self::from_config(KafkaConfig {
+            brokers: brokers.to_string(),
+            topic: topic.to_string(),
+            encoding,
+            ..KafkaConfig::default()
+        })
+    }
+
+    /// Create a sink from a full [`KafkaConfig`], e.g. with SASL and TLS
+    /// for a managed cluster.
+    ///
+    /// **Returns**
+    /// - `Err(..)` if librdkafka rejects the configuration, for example a
+    ///   TLS setting when it was built without TLS support.
+    pub fn from_config(config: KafkaConfig) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let producer: FutureProducer = config.client_config().create()?;
 
         Ok(KafkaSink {
             producer,
-            topic: topic.to_string(),
+            topic: DestinationTemplate::new(&config.topic),
+            encoding: config.encoding,
+            key: match &config.partitioner {
+                KafkaPartitioner::Murmur2 { key } => Some(DestinationTemplate::new(key)),
+                _ => None,
+            },
+            partition_field: match config.partitioner {
+                KafkaPartitioner::Field(field) => Some(field),
+                _ => None,
+            },
+            on_timeout: config.delivery.on_timeout,
+            timeouts: Arc::default(),
         })
     }
+
+    /// Publish one encoded record, waiting for its delivery report.
+    async fn publish(&self, record: &LogRecord, payload: &[u8]) -> Result<(), SinkError> {
+        let topic = self.topic.render(record);
+        let event_id = record.event_id;
+        let key = self.key.as_ref().map(|key| key.render(record));
+        let partition = self
+            .partition_field
+            .as_ref()
+            .and_then(|field| record.fields.get(field))
+            .and_then(|value| value.as_u64().or_else(|| value.as_str()?.parse().ok()))
+            .and_then(|n| i32::try_from(n).ok());
+        let mut record = FutureRecord::<str, _>::to(&topic).payload(payload);
+        if let Some(key) = &key {
+            record = record.key(key.as_ref());
+        }
+        if let Some(partition) = partition {
+            record = record.partition(partition);
+        }
+        // Wait at most 5s for room in the producer queue, then for the
+        // delivery report.
+        let result = self.producer.send(record, Duration::from_secs(5)).await;
+        let DeliveryTimeoutPolicy::DropAfter(limit) = self.on_timeout else {
+            return result.map(drop).map_err(|(e, _)| classify_delivery(e, partition.is_some()));
+        };
+        let limit = limit.max(1);
+        let Some(event_id) = event_id else {
+            return result.map(drop).map_err(|(e, _)| classify_delivery(e, partition.is_some()));
+        };
+        match result {
+            Err((e, _)) if e.rdkafka_error_code() == Some(RDKafkaErrorCode::MessageTimedOut) => {
+                let mut timeouts = self.lock_timeouts();
+                let count = timeouts.entry(event_id).or_insert(0);
+                *count += 1;
+                if *count < limit {
+                    return Err(classify(e));
+                }
+                timeouts.remove(&event_id);
+                Err(SinkError::permanent(format!("kafka delivery timed out {} times: {}", limit, e)))
+            }
+            result => {
+                let result = result.map(drop).map_err(|(e, _)| classify_delivery(e, partition.is_some()));
+                // Delivered or given up on: the record is not sent again.
+                if !result.as_ref().is_err_and(SinkError::is_retryable) {
+                    self.lock_timeouts().remove(&event_id);
+                }
+                result
+            }
+        }
+    }
+
+    fn lock_timeouts(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, u32>> {
+        self.timeouts.lock().unwrap_or_else(|e| e.into_inner())
+    }
 }
 
 #[async_trait]
 impl LogSink for KafkaSink {
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
-        let payload = serde_json::to_vec(record)?;
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.publish(record, &self.encoding.encode(record)?).await
+    }
+
+    /// Reuses the payload encoded for an earlier attempt at the record.
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.publish(batch.record(index), batch.encoded(index, self.encoding)?).await
+    }
 
-        let record = FutureRecord::to(&self.topic).payload(&payload);
-        // Wait for the delivery report with a bounded timeout.
-        self.producer
-            .send(record, Duration::from_secs(5))
+    /// Waits for messages still queued in the producer to be delivered.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        let producer = self.producer.clone();
+        tokio::task::spawn_blocking(move || producer.flush(Duration::from_secs(10)))
             .await
-            .map_err(|(e, _)| -> Box<dyn Error + Send + Sync> { Box::new(e) })?;
+            .map_err(SinkError::transient)?
+            .map_err(classify)
+    }
+
+    /// Fetches the metadata of the target topic from the brokers, or of
+    /// all topics if the topic name is a template.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        let producer = self.producer.clone();
+        let topic = (!self.topic.is_templated()).then(|| self.topic.as_str().to_string());
+        // `fetch_metadata` is a blocking librdkafka call.
+        let metadata = tokio::task::spawn_blocking(move || {
+            producer.client().fetch_metadata(topic.as_deref(), Duration::from_secs(5))
+        })
+        .await
+        .map_err(SinkError::transient)?
+        .map_err(classify)?;
+
+        if self.topic.is_templated() {
+            return Ok(());
+        }
+        match metadata.topics().first().and_then(|t| t.error()) {
+            Some(err) => Err(SinkError::transient(format!(
+                "kafka topic {} is unavailable: {:?}",
+                self.topic.as_str(),
+                RDKafkaErrorCode::from(err)
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Like [`classify`], but rejects records naming a partition the topic
+/// does not have.
+fn classify_delivery(err: KafkaError, explicit_partition: bool) -> SinkError {
+    match err.rdkafka_error_code() {
+        Some(RDKafkaErrorCode::UnknownPartition) if explicit_partition => SinkError::Permanent(Box::new(err)),
+        _ => classify(err),
+    }
+}
+
+/// Map a delivery failure onto [`SinkError`].
+fn classify(err: KafkaError) -> SinkError {
+    match err.rdkafka_error_code() {
+        Some(
+            RDKafkaErrorCode::MessageSizeTooLarge
+            | RDKafkaErrorCode::InvalidMessage
+            | RDKafkaErrorCode::InvalidMessageSize,
+        ) => SinkError::Permanent(Box::new(err)),
+        Some(
+            RDKafkaErrorCode::TopicAuthorizationFailed
+            | RDKafkaErrorCode::SaslAuthenticationFailed
+            | RDKafkaErrorCode::Authentication,
+        ) => SinkError::Auth(Box::new(err)),
+        _ => SinkError::Transient(Box::new(err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dsn_brokers_and_topic() {
+        let cases = [
+            ("kafka://broker1:9092,broker2:9092/errors", "broker1:9092,broker2:9092", "errors"),
+            ("kafka://localhost:9092", "localhost:9092", "logs"),
+            ("kafka://localhost:9092/?acks=1", "localhost:9092", "logs"),
+            ("kafka://user:pw@b1,b2/app-logs?tls=false", "b1,b2", "app-logs"),
+        ];
+        for (dsn, brokers, topic) in cases {
+            let config = KafkaConfig::from_dsn(dsn).unwrap();
+            assert_eq!((config.brokers.as_str(), config.topic.as_str()), (brokers, topic), "{}", dsn);
+        }
+    }
+
+    #[test]
+    fn dsn_security_options() {
+        let plain = |username: &str, password: &str| KafkaSasl::Plain {
+            username: username.to_string(),
+            password: password.to_string(),
+        };
+        let ca = KafkaTls { ca_bundle: Some("/etc/ca.pem".into()), ..KafkaTls::default() };
+        let cases = [
+            ("kafka://b/t", None, None, "plaintext"),
+            ("kafka://u:p%40ss@b/t", Some(plain("u", "p@ss")), Some(KafkaTls::default()), "sasl_ssl"),
+            ("kafka://u:p@b/t?tls=false", Some(plain("u", "p")), None, "sasl_plaintext"),
+            (
+                "kafka://u:p@b/t?sasl_mechanism=SCRAM-SHA-512",
+                Some(KafkaSasl::ScramSha512 { username: "u".into(), password: "p".into() }),
+                Some(KafkaTls::default()),
+                "sasl_ssl",
+            ),
+            ("kafka://b/t?ssl_ca=/etc/ca.pem", None, Some(ca), "ssl"),
+            ("kafka://b/t?tls=true", None, Some(KafkaTls::default()), "ssl"),
+        ];
+        for (dsn, sasl, tls, protocol) in cases {
+            let config = KafkaConfig::from_dsn(dsn).unwrap();
+            assert_eq!(config.sasl, sasl, "{}", dsn);
+            assert_eq!(config.tls, tls, "{}", dsn);
+            assert_eq!(config.client_config().get("security.protocol"), Some(protocol), "{}", dsn);
+        }
+
+        let config = KafkaConfig::from_dsn(
+            "kafka://b/t?sasl_mechanism=oauthbearer&oauth_token_endpoint=https://idp/token\
+             &oauth_client_id=app&oauth_client_secret=s&ssl_cert=/c.pem&ssl_key=/k.pem&tls_insecure=1",
+        )
+        .unwrap();
+        let client = config.client_config();
+        assert_eq!(client.get("sasl.mechanism"), Some("OAUTHBEARER"));
+        assert_eq!(client.get("sasl.oauthbearer.token.endpoint.url"), Some("https://idp/token"));
+        assert_eq!(client.get("ssl.certificate.location"), Some("/c.pem"));
+        assert_eq!(client.get("ssl.key.location"), Some("/k.pem"));
+        assert_eq!(client.get("enable.ssl.certificate.verification"), Some("false"));
+
+        for dsn in ["kafka://u:p@b/t?sasl_mechanism=gssapi", "kafka://b/t?tls=yes", "kafka://b/t?bogus=1"] {
+            assert!(matches!(KafkaConfig::from_dsn(dsn), Err(DsnError::InvalidOption { .. })), "{}", dsn);
+        }
+    }
+
+    /// A record that timed out once and is then rejected for another
+    /// reason must not stay in the timeout counts.
+    #[tokio::test]
+    async fn timeout_counts_are_cleared_on_every_final_outcome() {
+        let config = KafkaConfig::from_dsn(
+            "kafka://127.0.0.1:1/t?drop_after_timeouts=2&message_timeout_ms=100&message.max.bytes=1000",
+        )
+        .unwrap();
+        let sink = KafkaSink::from_config(config).unwrap();
+        let mut record: LogRecord = serde_json::from_value(serde_json::json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": "m",
+            "fields": {},
+        }))
+        .unwrap();
+        record.event_id = Some(Uuid::now_v7());
+
+        let first = sink.send(&record).await.unwrap_err();
+        assert!(first.is_retryable(), "{}", first);
+        assert_eq!(sink.lock_timeouts().len(), 1);
+        let second = sink.send(&record).await.unwrap_err();
+        assert!(!second.is_retryable(), "{}", second);
+        assert!(sink.lock_timeouts().is_empty());
+
+        sink.send(&record).await.unwrap_err();
+        assert_eq!(sink.lock_timeouts().len(), 1);
+        record.message = Some("x".repeat(2_000));
+        let rejected = sink.send(&record).await.unwrap_err();
+        assert!(!rejected.is_retryable() && !rejected.to_string().contains("timed out"), "{}", rejected);
+        assert!(sink.lock_timeouts().is_empty());
+    }
 
-        Ok(())
+    #[test]
+    fn dsn_partitioner() {
+        let cases = [
+            ("kafka://b/t", KafkaPartitioner::Sticky, None),
+            (
+                "kafka://b/t?key=%7Bservice%7D",
+                KafkaPartitioner::Murmur2 { key: "{service}".to_string() },
+                Some("murmur2_random"),
+            ),
+            ("kafka://b/t?partition_field=shard", KafkaPartitioner::Field("shard".to_string()), None),
+        ];
+        for (dsn, partitioner, property) in cases {
+            let config = KafkaConfig::from_dsn(dsn).unwrap();
+            assert_eq!(config.partitioner, partitioner, "{}", dsn);
+            assert_eq!(config.client_config().get("partitioner"), property, "{}", dsn);
+        }
     }
 }
@@ -1,30 +1,116 @@
-use crate::record::LogRecord;
+use crate::backpressure::{ByteBudget, CapacitySignal};
+use crate::clock::Clock;
+use crate::collapse::Collapser;
+use crate::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::errors;
+#[cfg(doc)]
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::fields::Fields;
+use crate::handle::LayerHandle;
+use crate::host::{self, HostInfo};
+use crate::init::{ChannelKind, LayerConfig};
+use crate::limits::RecordLimits;
+use crate::persist;
+use crate::pool::RecordPool;
+use crate::record::{LogLevel, LogRecord, SCHEMA_VERSION};
+use crate::reload::{ConfigHandle, DynamicConfig};
 use crate::sink::LogSink;
-use chrono::Utc;
-use std::collections::BTreeMap;
-use std::error::Error;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
-use tokio::sync::mpsc;
-use tokio::task::JoinHandle;
-use tokio::time::{sleep, Duration};
-use tracing::{Event, Level, Subscriber};
+use crate::span_fields::{SpanFields, SpanFieldsConfig};
+use crate::stats::{LayerStats, StatsSource};
+use crate::timestamp::Timestamp;
+use crate::trace_context::{self, SpanTraceFields, TraceContext};
+use crate::worker::{self, Command, Worker};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::BuildHasher;
+use std::sync::{Arc, atomic::{AtomicU64, AtomicUsize, Ordering}};
+use std::time::Instant;
+use crate::channel::{self, RecordSender};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::Semaphore;
+use uuid::{Builder, Uuid};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Duration;
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
 use tracing_subscriber::layer::{Context, Layer};
-use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::registry::{LookupSpan, SpanRef};
+
+pub use crate::visitor::FieldVisitor;
+
+/// Why a record was dropped before reaching the worker, see
+/// [`LayerConfig::on_drop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// The channel shard had no free capacity.
+    ChannelFull,
+    /// The records already queued exceeded
+    /// [`LayerConfig::max_buffered_bytes`].
+    BytesLimit,
+    /// The worker has shut down.
+    Closed,
+}
+
+/// Callback invoked on the application thread for every dropped record.
+pub type DropHook = Arc<dyn Fn(&LogRecord, DropReason) + Send + Sync>;
 
 /// `tracing_subscriber` layer that observes events and forwards them to
 /// an asynchronous [`LogSink`] via a bounded channel and background task.
 ///
 /// By default this layer only captures events with level `ERROR` and above
-/// and turns them into [`LogRecord`]s. Network I/O is fully decoupled from
+/// (see [`LayerConfig::level`]) and turns them into [`LogRecord`]s. Network I/O is fully decoupled from
 /// application threads to minimize impact on request latency.
 pub struct ErrorLogLayer {
-    sender: mpsc::Sender<LogRecord>,
+    /// One sender per channel shard, see [`LayerConfig::channel_shards`].
+    senders: Vec<RecordSender>,
+    /// Shards of the `ERROR` lane, see [`LayerConfig::priority_lanes`];
+    /// empty when disabled.
+    error_senders: Vec<RecordSender>,
     /// Total events seen by the layer (before filtering by level).
     pub total_events: Arc<AtomicU64>,
     /// Successfully enqueued into channel.
     pub enqueued_events: Arc<AtomicU64>,
     /// Dropped because the channel was full.
     pub dropped_events: Arc<AtomicU64>,
+    /// Discarded by [`LayerConfig::level_policy`], [`LayerConfig::filter`]
+    /// or one of the configured record processors.
+    pub filtered_events: Arc<AtomicU64>,
+    /// Host and process metadata stamped onto every record.
+    host: HostInfo,
+    /// Flattened [`crate::resource::ResourceInfo`] stamped onto every record.
+    resource: BTreeMap<String, String>,
+    /// Size caps applied before a record is enqueued.
+    limits: RecordLimits,
+    /// See [`LayerConfig::field_allowlist`].
+    field_allowlist: Option<BTreeSet<String>>,
+    /// Time source for record timestamps.
+    clock: Arc<dyn Clock>,
+    /// Runtime-reloadable settings such as the captured level.
+    reload: ConfigHandle,
+    /// Control channel of the background worker.
+    control: mpsc::UnboundedSender<Command>,
+    /// See [`LayerConfig::on_drop`].
+    on_drop: Option<DropHook>,
+    /// Set when [`LayerConfig::enqueue_timeout`] is configured.
+    capacity: Option<(Arc<CapacitySignal>, Duration)>,
+    /// See [`LayerConfig::max_buffered_bytes`]; shared with the worker.
+    buffered_bytes: Option<Arc<ByteBudget>>,
+    /// See [`ChannelKind::Unbounded`].
+    high_watermark: Option<usize>,
+    /// The counters above plus per-target drop accounting.
+    stats: StatsSource,
+    /// Sink for the layer's own problems, shared with the worker.
+    diagnostics: Arc<Diagnostics>,
+    /// See [`LayerConfig::ignore_targets`].
+    ignore_targets: Vec<String>,
+    /// See [`LayerConfig::capture_error_spans`].
+    capture_error_spans: bool,
+    /// See [`LayerConfig::span_fields`].
+    span_fields: SpanFieldsConfig,
+    /// See [`LayerConfig::record_pool_size`]; shared with the worker.
+    pool: Option<Arc<RecordPool>>,
 }
 
 impl ErrorLogLayer {
@@ -32,6 +118,11 @@ impl ErrorLogLayer {
     /// [`LogRecord`]s from a bounded channel and sends them to the
     /// provided [`LogSink`].
     ///
+    /// The task runs on [`LayerConfig::runtime`] if set, otherwise on the
+    /// current Tokio runtime; outside of a runtime a dedicated background
+    /// thread is started instead of panicking. See also
+    /// [`LayerConfig::worker_mode`].
+    ///
     /// Minimal thresholds are enforced for `buffer`, `batch_size` and
     /// `flush_interval` to avoid degenerate configurations.
     pub fn new(
@@ -40,84 +131,341 @@ impl ErrorLogLayer {
         batch_size: usize,
         flush_interval: Duration,
     ) -> (Self, JoinHandle<()>) {
+        let config = LayerConfig {
+            channel_buffer: buffer,
+            batch_size,
+            flush_interval,
+            ..LayerConfig::default()
+        };
+        Self::with_config(sink, &config)
+    }
+
+    /// Create a new layer from a full [`LayerConfig`].
+    ///
+    /// Behaves like [`ErrorLogLayer::new`] but also honors the remaining
+    /// config options such as [`LayerConfig::resource`].
+    pub fn with_config(sink: Arc<dyn LogSink>, config: &LayerConfig) -> (Self, JoinHandle<()>) {
         // Enforce minimal thresholds to avoid degenerate configs.
-        let buffer = buffer.max(16);
-        let batch_size = batch_size.max(1);
-        let flush_interval = if flush_interval < Duration::from_millis(10) {
+        let buffer = config.channel_buffer.max(16);
+        let batch_size = config.batch_size.max(1);
+        let flush_interval = if config.flush_interval < Duration::from_millis(10) {
             Duration::from_millis(10)
         } else {
-            flush_interval
-        };
-
-        let (tx, mut rx) = mpsc::channel::<LogRecord>(buffer);
-
-        let total_events = Arc::new(AtomicU64::new(0));
-        let enqueued_events = Arc::new(AtomicU64::new(0));
-        let dropped_events = Arc::new(AtomicU64::new(0));
-
-        let _total_events_bg = Arc::clone(&total_events);
-        let enqueued_events_bg = Arc::clone(&enqueued_events);
-        let _dropped_events_bg = Arc::clone(&dropped_events);
-
-        let handle = tokio::spawn(async move {
-            let mut batch = Vec::with_capacity(batch_size);
-            let backoff = Duration::from_millis(100);
-            let max_backoff = Duration::from_secs(10);
-
-            loop {
-                tokio::select! {
-                    Some(record) = rx.recv() => {
-                        batch.push(record);
-                        enqueued_events_bg.fetch_add(1, Ordering::Relaxed);
-                        if batch.len() >= batch_size {
-                            if let Err(e) = send_batch(&*sink, &mut batch, backoff, max_backoff).await {
-                                eprintln!("error sending log batch: {}", e);
-                            }
-                        }
-                    }
-                    _ = sleep(flush_interval) => {
-                        if !batch.is_empty() {
-                            if let Err(e) = send_batch(&*sink, &mut batch, backoff, max_backoff).await {
-                                eprintln!("error flushing log batch: {}", e);
-                            }
-                        }
-                    }
-                }
+            config.flush_interval
+        };
+        let (batch_size, flush_interval) = match &config.adaptive_batching {
+            Some(adaptive) => adaptive.clamp(batch_size, flush_interval),
+            None => (batch_size, flush_interval),
+        };
+
+        // Split the total buffer between shards; each thread always
+        // enqueues into the same shard, so producers on different threads
+        // do not contend on a single channel.
+        let shards = config.channel_shards.max(1);
+        let shard_buffer = match config.channel_kind {
+            ChannelKind::Bounded => Some(buffer.div_ceil(shards).max(16)),
+            ChannelKind::Unbounded { .. } => None,
+        };
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..shards).map(|_| channel::channel(shard_buffer)).unzip();
+        let (error_senders, error_receivers): (Vec<_>, Vec<_>) = match &config.priority_lanes {
+            Some(lanes) => {
+                let buffer = Some(lanes.error_buffer.max(16).div_ceil(shards).max(16));
+                (0..shards).map(|_| channel::channel(buffer)).unzip()
             }
-        });
+            None => (Vec::new(), Vec::new()),
+        };
+
+        let stats = StatsSource::default();
+        let diagnostics = Arc::new(Diagnostics::new(&config.diagnostics));
+
+        let (control, control_rx) = mpsc::unbounded_channel();
+        let capacity = config
+            .enqueue_timeout
+            .map(|timeout| (Arc::new(CapacitySignal::default()), timeout));
+
+        // An unbounded channel is only held back by its byte cap.
+        let (max_buffered_bytes, high_watermark) = match config.channel_kind {
+            ChannelKind::Bounded => (config.max_buffered_bytes, None),
+            ChannelKind::Unbounded { high_watermark, max_bytes } => {
+                let max = config.max_buffered_bytes.map_or(max_bytes, |max| max.min(max_bytes));
+                (Some(max), Some(high_watermark))
+            }
+        };
+        let buffered_bytes = max_buffered_bytes.map(|max| Arc::new(ByteBudget::new(max)));
+
+        let replay = match &config.persist_unsent {
+            Some(persist) => load_unsent(persist, config.clock.now(), &diagnostics),
+            None => Vec::new(),
+        };
+
+        let pool = (config.record_pool_size > 0).then(|| Arc::new(RecordPool::new(config.record_pool_size)));
+
+        let reload = ConfigHandle::new(
+            DynamicConfig {
+                level: config.level,
+                level_policy: config.level_policy.clone(),
+                routes: config.routes.clone(),
+            },
+            config.clock.instant(),
+        );
+
+        let worker = Worker {
+            sink,
+            receivers,
+            error_receivers,
+            error_weight: config.priority_lanes.map_or(1, |lanes| lanes.error_weight.max(1)),
+            control: control_rx,
+            capacity: capacity.as_ref().map(|(signal, _)| Arc::clone(signal)),
+            buffered_bytes: buffered_bytes.clone(),
+            diagnostics: Arc::clone(&diagnostics),
+            batch_size,
+            max_batch_bytes: config.max_batch_bytes,
+            batch_bytes: 0,
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            sends: JoinSet::new(),
+            flush_interval,
+            adaptive: config.adaptive_batching.clone(),
+            fingerprint: config.fingerprint.clone(),
+            processors: config.processors.clone(),
+            flatten: config.flatten_fields.clone(),
+            limits: config.limits.clone(),
+            filter: config.filter.clone(),
+            collapser: config.collapse_window.map(Collapser::new),
+            dynamic: reload.subscribe(),
+            routes: config.routes.clone(),
+            clock: Arc::clone(&config.clock),
+            enqueued_events: Arc::clone(&stats.enqueued_events),
+            filtered_events: Arc::clone(&stats.filtered_events),
+            worker_restarts: Arc::clone(&stats.worker_restarts),
+            stopping: false,
+            heartbeat: Arc::clone(&stats.heartbeat),
+            stall_timeout: config.stall_timeout,
+            pool: pool.clone(),
+            persist: config.persist_unsent.clone(),
+            replay,
+            unacked: HashMap::new(),
+            backlog: HashSet::new(),
+            spooling: false,
+        };
+        let handle = worker.spawn(config.worker_mode, config.runtime.as_ref());
 
         (Self {
-            sender: tx,
-            total_events,
-            enqueued_events,
-            dropped_events,
+            senders,
+            error_senders,
+            total_events: Arc::clone(&stats.total_events),
+            enqueued_events: Arc::clone(&stats.enqueued_events),
+            dropped_events: Arc::clone(&stats.dropped_events),
+            filtered_events: Arc::clone(&stats.filtered_events),
+            stats,
+            host: HostInfo::collect(),
+            resource: config.resource.collect(),
+            limits: config.limits.clone(),
+            field_allowlist: config.field_allowlist.as_ref().map(|keys| keys.iter().cloned().collect()),
+            clock: Arc::clone(&config.clock),
+            reload,
+            control,
+            on_drop: config.on_drop.clone(),
+            capacity,
+            buffered_bytes,
+            high_watermark,
+            diagnostics,
+            ignore_targets: config.ignore_targets.clone(),
+            capture_error_spans: config.capture_error_spans,
+            span_fields: config.span_fields.clone(),
+            pool,
         }, handle)
     }
-}
 
-async fn send_batch(
-    sink: &dyn LogSink,
-    batch: &mut Vec<LogRecord>,
-    mut backoff: Duration,
-    max_backoff: Duration,
-) -> Result<(), Box<dyn Error + Send + Sync>> {
-    loop {
-        let mut last_err: Option<Box<dyn Error + Send + Sync>> = None;
-        for record in batch.iter() {
-            if let Err(e) = sink.send(record).await {
-                last_err = Some(e);
-                break;
+    /// Handle for changing the layer's settings at runtime.
+    pub fn config_handle(&self) -> ConfigHandle {
+        self.reload.clone()
+    }
+
+    /// Operational handle for reloading settings and shutting the worker
+    /// down.
+    pub fn handle(&self) -> LayerHandle {
+        LayerHandle {
+            config: self.reload.clone(),
+            control: self.control.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+
+    /// Snapshot of the layer's counters.
+    pub fn stats(&self) -> LayerStats {
+        self.stats.snapshot()
+    }
+
+    /// Whether events of `target` must not be captured: the crate's own
+    /// targets (such as [`DIAGNOSTICS_TARGET`]) and
+    /// [`LayerConfig::ignore_targets`].
+    fn is_ignored(&self, target: &str) -> bool {
+        is_target_or_child(target, CRATE_TARGET)
+            || self.ignore_targets.iter().any(|prefix| is_target_or_child(target, prefix))
+    }
+
+    /// Copy fields of `spans` (nearest first) into `fields` as configured by
+    /// [`LayerConfig::span_fields`]; fields already present are kept.
+    fn inherit_span_fields<'a, S>(
+        &self,
+        fields: &mut Fields,
+        spans: impl Iterator<Item = SpanRef<'a, S>>,
+    ) where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let depth = self.span_fields.depth();
+        if depth == 0 {
+            return;
+        }
+        let mut spans: Vec<_> = spans.take(depth).collect();
+        if !self.span_fields.child_overrides {
+            spans.reverse();
+        }
+        for span in spans {
+            if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                for (key, value) in &span_fields.fields {
+                    fields.get_or_insert_with(key, || value.clone());
+                }
             }
         }
+    }
+
+    /// A record returned by the worker to build the next one in (see
+    /// [`LayerConfig::record_pool_size`]), and its emptied field storage
+    /// to collect the event's fields into; without one, new ones.
+    fn recycled(&self) -> (Option<LogRecord>, Fields) {
+        let mut recycled = self.pool.as_deref().and_then(RecordPool::take);
+        let fields = recycled.as_mut().map(|record| std::mem::take(&mut record.fields)).unwrap_or_default();
+        (recycled, fields)
+    }
 
-        if last_err.is_none() {
-            batch.clear();
-            return Ok(());
+    /// Build a record for `callsite` in `recycled`, if given, stamped with
+    /// host, resource and a fresh event id, with the `error.kind` and `error.message` fields
+    /// promoted to their columns and [`LayerConfig::field_allowlist`] and
+    /// [`LayerConfig::limits`] applied.
+    ///
+    /// The error fields are taken out before the allowlist applies, so
+    /// the columns are filled whether or not it lists them.
+    fn new_record(
+        &self,
+        recycled: Option<LogRecord>,
+        callsite: Callsite,
+        mut fields: Fields,
+        message: Option<String>,
+        trace: Option<TraceContext>,
+    ) -> LogRecord {
+        let (error_kind, error_message) = errors::extract(&mut fields);
+        if let Some(allowed) = &self.field_allowlist {
+            fields.retain(|key, _| allowed.contains(key));
+        }
+        // Reuse the strings of a recycled record where possible.
+        let (mut hostname, mut thread_id, mut thread_name, mut resource) = match recycled {
+            Some(recycled) => (recycled.hostname, recycled.thread_id, recycled.thread_name, recycled.resource),
+            None => (None, None, None, BTreeMap::new()),
+        };
+        hostname.clone_from(&self.host.hostname);
+        host::current_thread_into(&mut thread_id, &mut thread_name);
+        if resource != self.resource {
+            resource.clone_from(&self.resource);
+        }
+        let timestamp = self.clock.now();
+        let mut record = LogRecord {
+            schema_version: SCHEMA_VERSION,
+            timestamp,
+            level: callsite.level,
+            target: callsite.target,
+            module_path: callsite.module_path,
+            file: callsite.file,
+            line: callsite.line,
+            fields,
+            message,
+            service_name: None,
+            hostname,
+            pid: Some(self.host.pid),
+            thread_id,
+            thread_name,
+            resource,
+            event_id: Some(event_id(timestamp)),
+            repeat_count: None,
+            fingerprint: None,
+            trace_id: trace.as_ref().map(|t| t.trace_id.clone()),
+            span_id: trace.and_then(|t| t.span_id),
+            span_name: None,
+            duration_ms: None,
+            span_elapsed_ms: None,
+            error_kind,
+            error_message,
+        };
+        self.limits.apply(&mut record);
+        record
+    }
+
+    /// Hand `record` to the worker, accounting for and reporting drops.
+    fn enqueue(&self, record: LogRecord) {
+        // The `ERROR` lane is bounded by its own capacity only, so a flood
+        // of lower levels filling the byte budget cannot drop errors.
+        let error_lane = !self.error_senders.is_empty() && record.level == LogLevel::Error;
+        let (senders, buffered_bytes) = if error_lane {
+            (&self.error_senders, None)
+        } else {
+            (&self.senders, self.buffered_bytes.as_ref())
+        };
+        let size = match buffered_bytes {
+            Some(budget) => {
+                let size = record.estimated_size();
+                let reserved = budget.try_reserve(size)
+                    || self
+                        .capacity
+                        .as_ref()
+                        .is_some_and(|(capacity, timeout)| capacity.reserve_timeout(budget, size, *timeout));
+                if !reserved {
+                    self.drop_record(record, DropReason::BytesLimit);
+                    return;
+                }
+                size
+            }
+            None => 0,
+        };
+        let sender = &senders[shard_index() % senders.len()];
+        let result = match (sender.try_send(record), &self.capacity) {
+            (Err(TrySendError::Full(record)), Some((capacity, timeout))) => {
+                capacity.send_timeout(sender, record, *timeout)
+            }
+            (result, _) => result,
+        };
+        if let Err(e) = result {
+            if let Some(budget) = buffered_bytes {
+                budget.release(size);
+            }
+            match e {
+                TrySendError::Full(record) => self.drop_record(record, DropReason::ChannelFull),
+                TrySendError::Closed(record) => self.drop_record(record, DropReason::Closed),
+            }
+        } else if let (Some(budget), Some(mark)) = (buffered_bytes, self.high_watermark) {
+            let used = budget.used();
+            if used > mark {
+                self.diagnostics.emit(DiagnosticKind::ChannelHighWatermark, || {
+                    format!("log channel holds {} bytes, above its high watermark of {}", used, mark)
+                });
+            }
         }
+    }
 
-        eprintln!("log sink send failed, retrying in {:?}", backoff);
-        sleep(backoff).await;
-        backoff = std::cmp::min(backoff * 2, max_backoff);
+    /// Account for and report a record that never reached the worker.
+    fn drop_record(&self, record: LogRecord, reason: DropReason) {
+        self.stats.record_drop(&record.target);
+        if let Some(on_drop) = &self.on_drop {
+            on_drop(&record, reason);
+        }
+        match reason {
+            DropReason::ChannelFull => self.diagnostics.emit(DiagnosticKind::ChannelFull, || {
+                format!("log channel full, dropping log record from {}", record.target)
+            }),
+            DropReason::BytesLimit => self.diagnostics.emit(DiagnosticKind::ChannelFull, || {
+                format!("log channel over max_buffered_bytes, dropping log record from {}", record.target)
+            }),
+            DropReason::Closed => {}
+        }
     }
 }
 
@@ -125,67 +473,350 @@ impl<S> Layer<S> for ErrorLogLayer
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
-    fn on_event(&self, event: &Event, _ctx: Context<'_, S>) {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut trace = SpanTraceFields::default();
+        attrs.record(&mut trace);
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if !trace.is_empty() {
+            extensions.insert(trace);
+        }
+        extensions.insert(SpanTiming { started: self.clock.instant(), errored: false });
+        if self.span_fields.depth() > 0 {
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields.visitor());
+            extensions.insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut fields.visitor());
+        }
+        match extensions.get_mut::<SpanTraceFields>() {
+            Some(trace) => values.record(trace),
+            None => {
+                let mut trace = SpanTraceFields::default();
+                values.record(&mut trace);
+                if !trace.is_empty() {
+                    extensions.insert(trace);
+                }
+            }
+        }
+    }
+
+    /// With [`LayerConfig::capture_error_spans`], report a span that saw an
+    /// `ERROR` event as its own record once it closes.
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if !self.capture_error_spans || worker::in_worker() {
+            return;
+        }
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let elapsed = match span.extensions().get::<SpanTiming>() {
+            Some(timing) if timing.errored => self.clock.instant().saturating_duration_since(timing.started),
+            _ => return,
+        };
+        let meta = span.metadata();
+        if self.is_ignored(meta.target()) {
+            return;
+        }
+        let (recycled, mut fields) = self.recycled();
+        self.inherit_span_fields(&mut fields, span.scope());
+        let trace = trace_context::extract(&mut fields).or_else(|| {
+            span.scope()
+                .find_map(|span| span.extensions().get::<SpanTraceFields>().and_then(SpanTraceFields::context))
+        });
+        let message = format!("span {} closed with errors", meta.name());
+        let mut record = self.new_record(recycled, Callsite::from(meta), fields, Some(message), trace);
+        record.level = LogLevel::Error;
+        record.span_name = Some(meta.name().to_string());
+        record.duration_ms = Some(millis(elapsed));
+        self.enqueue(record);
+    }
+
+    fn on_event(&self, event: &Event, ctx: Context<'_, S>) {
+        // Events emitted while the worker itself is running (e.g. by the
+        // sink's HTTP client) would feed straight back into the sink.
+        if worker::in_worker() {
+            return;
+        }
         self.total_events.fetch_add(1, Ordering::Relaxed);
-        if *event.metadata().level() > Level::ERROR {
+        if *event.metadata().level() > Level::from(self.reload.level()) {
+            return;
+        }
+
+        // Events bridged from the `log` crate carry their real metadata in
+        // `log.*` fields; restore it and drop the helper fields.
+        #[cfg(feature = "log-compat")]
+        let normalized = tracing_log::NormalizeEvent::normalized_metadata(event);
+        #[cfg(feature = "log-compat")]
+        let meta = normalized.as_ref().unwrap_or(event.metadata());
+        #[cfg(not(feature = "log-compat"))]
+        let meta = event.metadata();
+        // Decide on everything that can drop the event before allocating.
+        if self.is_ignored(meta.target()) {
             return;
         }
+        if !self.reload.admit(LogLevel::from(*meta.level()), self.clock.instant()) {
+            self.filtered_events.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        #[cfg(feature = "log-compat")]
+        let callsite = match normalized.as_ref() {
+            Some(meta) => Callsite::interned(meta),
+            None => Callsite::from(event.metadata()),
+        };
+        #[cfg(not(feature = "log-compat"))]
+        let callsite = Callsite::from(meta);
 
-        let mut fields = BTreeMap::new();
+        let (recycled, mut fields) = self.recycled();
         let mut message: Option<String> = None;
 
         let mut visitor = crate::layer::FieldVisitor { fields: &mut fields, message: &mut message };
         event.record(&mut visitor);
+        #[cfg(feature = "log-compat")]
+        if normalized.is_some() {
+            fields.retain(|key, _| !key.starts_with("log."));
+        }
+        if self.capture_error_spans && callsite.level == LogLevel::Error {
+            mark_error_spans(event, &ctx);
+        }
+        if let Some(scope) = ctx.event_scope(event) {
+            self.inherit_span_fields(&mut fields, scope);
+        }
+        let trace = trace_context::extract(&mut fields).or_else(|| span_trace_context(event, &ctx));
+        let mut record = self.new_record(recycled, callsite, fields, message, trace);
+        record.span_elapsed_ms = ctx
+            .event_scope(event)
+            .and_then(|mut scope| scope.next())
+            .and_then(|span| {
+                let started = span.extensions().get::<SpanTiming>()?.started;
+                Some(millis(self.clock.instant().saturating_duration_since(started)))
+            });
+        self.enqueue(record);
+    }
+}
 
-        let meta = event.metadata();
-        let record = LogRecord {
-            timestamp: Utc::now(),
-            level: meta.level().to_string(),
-            target: meta.target().to_string(),
-            module_path: meta.module_path().map(|s| s.to_string()),
-            file: meta.file().map(|s| s.to_string()),
+/// Source location and level of a record.
+///
+/// Borrows the strings of `'static` callsite metadata, so building a
+/// record for a regular event or span copies no strings.
+struct Callsite {
+    level: LogLevel,
+    target: Cow<'static, str>,
+    module_path: Option<Cow<'static, str>>,
+    file: Option<Cow<'static, str>>,
+    line: Option<u32>,
+}
+
+impl Callsite {
+    /// Intern the strings of metadata that does not live for `'static`,
+    /// such as the metadata `tracing-log` reconstructs for `log` records.
+    #[cfg(feature = "log-compat")]
+    fn interned(meta: &Metadata<'_>) -> Self {
+        Callsite {
+            level: LogLevel::from(*meta.level()),
+            target: crate::record::intern(meta.target()),
+            module_path: meta.module_path().map(crate::record::intern),
+            file: meta.file().map(crate::record::intern),
             line: meta.line(),
-            fields,
-            message,
-            service_name: None,
-        };
+        }
+    }
+}
 
-        if let Err(_e) = self.sender.try_send(record) {
-            self.dropped_events.fetch_add(1, Ordering::Relaxed);
-            eprintln!("log channel full, dropping log record");
+impl From<&'static Metadata<'static>> for Callsite {
+    fn from(meta: &'static Metadata<'static>) -> Self {
+        Callsite {
+            level: LogLevel::from(*meta.level()),
+            target: Cow::Borrowed(meta.target()),
+            module_path: meta.module_path().map(Cow::Borrowed),
+            file: meta.file().map(Cow::Borrowed),
+            line: meta.line(),
         }
     }
 }
 
-use tracing::field::{Field, Visit};
+/// Read the records a previous process left in
+/// [`LayerConfig::persist_unsent`], reporting problems as diagnostics.
+fn load_unsent(config: &persist::PersistConfig, now: Timestamp, diagnostics: &Diagnostics) -> Vec<LogRecord> {
+    match persist::load(config, now) {
+        Ok(replay) => {
+            if replay.expired > 0 || replay.invalid > 0 {
+                diagnostics.emit(DiagnosticKind::PersistFailed, || {
+                    format!(
+                        "discarded {} expired and {} unreadable unsent log records from {}",
+                        replay.expired,
+                        replay.invalid,
+                        config.path.display()
+                    )
+                });
+            }
+            replay.records
+        }
+        Err(e) => {
+            diagnostics.emit(DiagnosticKind::PersistFailed, || {
+                format!("error reading unsent log records from {}: {}", config.path.display(), e)
+            });
+            Vec::new()
+        }
+    }
+}
 
-pub struct FieldVisitor<'a> {
-    pub fields: &'a mut BTreeMap<String, serde_json::Value>,
-    pub message: &'a mut Option<String>,
+/// UUIDv7 for a record stamped at `timestamp`.
+///
+/// Cheaper than [`Uuid::now_v7`], which reads the clock again and
+/// synchronizes on a process-wide counter: the random bits come from a
+/// per-thread SplitMix64 generator seeded from the std hasher keys. Ids
+/// are ordered by millisecond, but not within one.
+fn event_id(timestamp: Timestamp) -> Uuid {
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(RandomState::new().hash_one(0u8));
+    }
+    let mut random = [0u8; 10];
+    STATE.with(|state| {
+        let next = || {
+            let mut z = state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+            state.set(z);
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        random[..8].copy_from_slice(&next().to_le_bytes());
+        random[8..].copy_from_slice(&next().to_le_bytes()[..2]);
+    });
+    let millis = u64::try_from(timestamp.timestamp_millis()).unwrap_or(0);
+    Builder::from_unix_timestamp_millis(millis, &random).into_uuid()
 }
 
-impl<'a> Visit for FieldVisitor<'a> {
-    fn record_str(&mut self, field: &Field, value: &str) {
-        if field.name() == "message" {
-            *self.message = Some(value.to_string());
-        } else {
-            self.fields.insert(field.name().to_string(), serde_json::Value::String(value.to_string()));
+/// `duration` in whole milliseconds, saturating at `u64::MAX`.
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Creation time of every span, plus whether an `ERROR` event was emitted
+/// inside it when [`LayerConfig::capture_error_spans`] is enabled.
+struct SpanTiming {
+    started: Instant,
+    errored: bool,
+}
+
+/// Flag every span around `event` as having seen an error.
+fn mark_error_spans<S>(event: &Event, ctx: &Context<'_, S>)
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(scope) = ctx.event_scope(event) else {
+        return;
+    };
+    for span in scope {
+        if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+            timing.errored = true;
         }
     }
+}
+
+/// Trace context of the nearest span around `event` that carries one.
+fn span_trace_context<S>(event: &Event, ctx: &Context<'_, S>) -> Option<TraceContext>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    ctx.event_scope(event)?
+        .find_map(|span| span.extensions().get::<SpanTraceFields>().and_then(SpanTraceFields::context))
+}
+
+/// Root target of every event emitted by this crate.
+const CRATE_TARGET: &str = "tracing_log_sink";
+
+/// `target` equals `prefix` or is a module below it (`prefix::...`).
+pub(crate) fn is_target_or_child(target: &str, prefix: &str) -> bool {
+    target
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
 
-    fn record_i64(&mut self, field: &Field, value: i64) {
-        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+/// Stable per-thread shard index, assigned round-robin on first use.
+fn shard_index() -> usize {
+    static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed);
     }
+    SHARD.with(|shard| *shard)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::init::{with_scoped_recorder, LayerConfig};
+    use crate::level_policy::ShipPolicy;
+    use crate::record::{LogLevel, LogRecord};
+    use crate::reload::Route;
+    use crate::sink::{LogSink, SinkError};
+    use async_trait::async_trait;
+    use std::error::Error;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Collect(Mutex<Vec<LogRecord>>);
 
-    fn record_u64(&mut self, field: &Field, value: u64) {
-        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+    #[async_trait]
+    impl LogSink for Collect {
+        async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+            self.0.lock().unwrap().push(record.clone());
+            Ok(())
+        }
     }
 
-    fn record_bool(&mut self, field: &Field, value: bool) {
-        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+    #[tokio::test]
+    async fn field_allowlist_keeps_error_columns() {
+        let sink = Arc::new(Collect::default());
+        let config = LayerConfig {
+            field_allowlist: Some(vec!["user_id".to_string()]),
+            ..LayerConfig::default()
+        };
+        with_scoped_recorder(sink.clone(), config, |_| async {
+            let err = "x".parse::<i32>().unwrap_err();
+            tracing::error!(target: "app", user_id = 7, other = 1, error = &err as &(dyn Error + 'static), "failed");
+        })
+        .await;
+
+        let records = sink.0.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].error_kind.as_deref(), Some("ParseIntError"));
+        assert_eq!(records[0].error_message.as_deref(), Some("invalid digit found in string"));
+        assert_eq!(records[0].fields.keys().collect::<Vec<_>>(), ["user_id"]);
     }
 
-    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
-        self.fields.insert(field.name().to_string(), serde_json::Value::String(format!("{:?}", value)));
+    #[tokio::test]
+    async fn dynamic_settings_apply_at_runtime() {
+        let sink = Arc::new(Collect::default());
+        with_scoped_recorder(sink.clone(), LayerConfig::default(), |handle| async move {
+            let flush = || handle.flush(tokio::time::Duration::from_secs(5));
+            tracing::warn!(target: "app", "before");
+            handle.config().set_level(LogLevel::Warn);
+            tracing::warn!(target: "app", "warn");
+            flush().await.unwrap();
+
+            handle.config().set_level_policy([(LogLevel::Error, ShipPolicy::Drop)].into());
+            tracing::error!(target: "app", "dropped");
+            handle.config().set_level_policy(Default::default());
+            flush().await.unwrap();
+
+            handle.config().set_routes(vec![Route { target: "app::billing".into(), service: "billing".into() }]);
+            tracing::error!(target: "app::billing::invoice", "routed");
+            tracing::error!(target: "app", "unrouted");
+            flush().await.unwrap();
+        })
+        .await;
+
+        let records = sink.0.lock().unwrap();
+        let shipped: Vec<_> = records.iter().map(|r| (r.message.as_deref().unwrap_or_default(), r.service_name.as_deref())).collect();
+        assert_eq!(shipped, [("warn", None), ("routed", Some("billing")), ("unrouted", None)]);
     }
 }
@@ -0,0 +1,115 @@
+use crate::record::LogLevel;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// How events of one level are shipped, see
+/// [`LayerConfig::level_policy`].
+///
+/// In config files: `"always"`, `"drop"`, `{ sample = 10 }` or
+/// `{ rate_limit = 100 }`.
+///
+/// [`LayerConfig::level_policy`]: crate::init::LayerConfig::level_policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShipPolicy {
+    /// Ship every event.
+    Always,
+    /// Ship one of every `N` events; `0` and `1` ship all of them.
+    Sample(u32),
+    /// Ship at most `N` events per second, dropping the rest until the
+    /// next second starts.
+    RateLimit(u32),
+    /// Ship nothing.
+    Drop,
+}
+
+/// Runtime state of the configured policies, checked on the application
+/// thread before a record is built.
+///
+/// Policies are stored as atomics so [`crate::reload::ConfigHandle`] can
+/// replace them while events are being checked.
+#[derive(Debug)]
+pub(crate) struct LevelGate {
+    /// Per level: the policy, encoded by [`encode`].
+    policies: [AtomicU64; 5],
+    /// Per level: events seen for [`ShipPolicy::Sample`], or the current
+    /// second (high 32 bits) and the events shipped in it for
+    /// [`ShipPolicy::RateLimit`].
+    state: [AtomicU64; 5],
+    origin: Instant,
+}
+
+impl LevelGate {
+    /// Gate for `policies`; levels without an entry are shipped.
+    pub(crate) fn new(policies: &BTreeMap<LogLevel, ShipPolicy>, origin: Instant) -> Self {
+        let gate = Self {
+            policies: Default::default(),
+            state: Default::default(),
+            origin,
+        };
+        gate.set(policies);
+        gate
+    }
+
+    /// Replace the policies, restarting the counters of every level whose
+    /// policy changed.
+    pub(crate) fn set(&self, policies: &BTreeMap<LogLevel, ShipPolicy>) {
+        let mut wanted = [ShipPolicy::Always; 5];
+        for (level, policy) in policies {
+            wanted[*level as usize] = *policy;
+        }
+        for ((slot, state), policy) in self.policies.iter().zip(&self.state).zip(wanted) {
+            if slot.swap(encode(policy), Ordering::Relaxed) != encode(policy) {
+                state.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Whether an event of `level` seen at `now` should be shipped.
+    pub(crate) fn admit(&self, level: LogLevel, now: Instant) -> bool {
+        let state = &self.state[level as usize];
+        match decode(self.policies[level as usize].load(Ordering::Relaxed)) {
+            ShipPolicy::Always => true,
+            ShipPolicy::Drop => false,
+            ShipPolicy::Sample(n) => n <= 1 || state.fetch_add(1, Ordering::Relaxed).is_multiple_of(u64::from(n)),
+            ShipPolicy::RateLimit(n) => {
+                let second = now.saturating_duration_since(self.origin).as_secs() & u64::from(u32::MAX);
+                let mut current = state.load(Ordering::Relaxed);
+                loop {
+                    let count = if current >> 32 == second { current & u64::from(u32::MAX) } else { 0 };
+                    if count >= u64::from(n) {
+                        return false;
+                    }
+                    let next = (second << 32) | (count + 1);
+                    match state.compare_exchange_weak(current, next, Ordering::Relaxed, Ordering::Relaxed) {
+                        Ok(_) => return true,
+                        Err(actual) => current = actual,
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Policy kind in the high 32 bits, its `N` in the low ones.
+fn encode(policy: ShipPolicy) -> u64 {
+    let (kind, n) = match policy {
+        ShipPolicy::Always => (0, 0),
+        ShipPolicy::Drop => (1, 0),
+        ShipPolicy::Sample(n) => (2, n),
+        ShipPolicy::RateLimit(n) => (3, n),
+    };
+    (kind << 32) | u64::from(n)
+}
+
+fn decode(value: u64) -> ShipPolicy {
+    let n = value as u32;
+    match value >> 32 {
+        1 => ShipPolicy::Drop,
+        2 => ShipPolicy::Sample(n),
+        3 => ShipPolicy::RateLimit(n),
+        _ => ShipPolicy::Always,
+    }
+}
@@ -1,9 +1,142 @@
+// Crate-internal helpers of the threaded layer are unused on `wasm32`.
+#![cfg_attr(target_arch = "wasm32", allow(dead_code))]
+
 pub mod record;
+pub mod fields;
 pub mod sink;
+#[cfg(not(target_arch = "wasm32"))]
 pub mod layer;
+pub mod host;
+pub mod resource;
+pub mod platform;
+pub mod processor;
+pub mod redaction;
+pub mod field_map;
+pub mod flatten;
+pub mod limits;
+pub mod level_policy;
+pub mod timestamp;
+#[cfg(not(feature = "chrono"))]
+mod std_timestamp;
+pub mod fingerprint;
+pub mod clock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod reload;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod handle;
+pub mod stats;
+pub mod diagnostics;
+pub mod batching;
+pub mod encoding;
+pub mod errors;
+pub mod destination;
+pub mod schema;
+pub mod tenant;
+pub mod trace_context;
+pub mod span_fields;
+
+#[cfg(any(feature = "clickhouse", feature = "opensearch"))]
+pub mod tls;
+#[cfg(any(feature = "clickhouse", feature = "opensearch"))]
+pub mod http;
+#[cfg(any(feature = "clickhouse", feature = "opensearch"))]
+mod proxy;
 
 #[cfg(feature = "clickhouse")]
 pub mod clickhouse;
 
+#[cfg(feature = "postgres")]
+pub mod postgres;
+#[cfg(all(feature = "postgres", feature = "tls-rustls"))]
+mod postgres_tls;
+
+#[cfg(feature = "kafka")]
+pub mod kafka;
+
+#[cfg(feature = "opensearch")]
+pub mod opensearch;
+
+#[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+pub mod aws_auth;
+
+#[cfg(feature = "otel")]
+pub mod otel;
+
+#[cfg(all(feature = "encryption", not(target_arch = "wasm32")))]
+pub mod encryption;
+
+#[cfg(feature = "proto")]
+pub mod proto;
+
+#[cfg(feature = "parquet")]
+pub mod parquet;
+
+#[cfg(all(feature = "chaos", not(target_arch = "wasm32")))]
+pub mod chaos;
+
+#[cfg(all(feature = "socket", not(target_arch = "wasm32")))]
+pub mod socket;
+
+#[cfg(feature = "tower")]
+pub mod tower;
+
+#[cfg(feature = "actix")]
+pub mod actix;
+
+#[cfg(feature = "tonic")]
+pub mod tonic;
+
+#[cfg(feature = "config")]
+pub mod config;
+
+#[cfg(feature = "wasm")]
+pub mod edge;
+
+// The background worker and everything built on it need threads and a
+// Tokio runtime; on `wasm32` only the edge layer is available.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod backend;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod decorators;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod buffered;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod failover;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tee;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod durable;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod replay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod task;
+#[cfg(all(any(feature = "clickhouse", feature = "postgres"), not(target_arch = "wasm32")))]
+pub mod query;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod env;
+
+#[cfg(not(target_arch = "wasm32"))]
 pub mod init;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod persist;
 pub mod noop_sink;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod file_sink;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod stdout_sink;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backpressure;
+#[cfg(not(target_arch = "wasm32"))]
+mod channel;
+#[cfg(not(target_arch = "wasm32"))]
+mod collapse;
+#[cfg(not(target_arch = "wasm32"))]
+mod pool;
+#[cfg(all(any(feature = "clickhouse", feature = "postgres"), not(target_arch = "wasm32")))]
+mod probe;
+#[cfg(not(target_arch = "wasm32"))]
+mod runtime;
+mod visitor;
+#[cfg(not(target_arch = "wasm32"))]
+mod worker;
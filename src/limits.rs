@@ -0,0 +1,191 @@
+use crate::record::LogRecord;
+use serde::Deserialize;
+
+/// Name of the marker field added to records that were truncated.
+pub const TRUNCATED_FIELD: &str = "truncated";
+
+/// Name of the field holding how many fields [`RecordLimits::max_fields`]
+/// dropped from a record.
+pub const FIELDS_DROPPED_FIELD: &str = "fields_dropped";
+
+/// Size caps applied to every record before it is enqueued.
+///
+/// Protects batches and backend rows from accidental huge payloads (for
+/// example a `?struct` debug dump of a multi-megabyte value). Oversized
+/// values are cut at a UTF-8 character boundary and the record gets a
+/// `truncated = true` marker field. `None` disables the respective cap;
+/// the default has no caps, [`RecordLimits::recommended`] sets all three.
+///
+/// **Fields**
+/// - `max_message_bytes`: maximum length of the formatted message.
+/// - `max_field_bytes`: maximum length of a single field value; non-string
+///   values are measured by their JSON encoding and replaced with a
+///   truncated string when they exceed it. Also caps `error_message`.
+/// - `max_fields`: maximum number of structured fields; the first ones in
+///   key order are kept, and the number of dropped ones is recorded in a
+///   [`FIELDS_DROPPED_FIELD`] field, so a caller attaching thousands of
+///   keys cannot blow up the column count of the backend. The marker
+///   fields are not counted.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct RecordLimits {
+    pub max_message_bytes: Option<usize>,
+    pub max_field_bytes: Option<usize>,
+    pub max_fields: Option<usize>,
+}
+
+impl RecordLimits {
+    /// Limits that never truncate anything, the same as the default.
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Caps generous enough for ordinary events: 32 KiB messages, 16 KiB
+    /// field values and 256 fields.
+    pub fn recommended() -> Self {
+        Self {
+            max_message_bytes: Some(32 * 1024),
+            max_field_bytes: Some(16 * 1024),
+            max_fields: Some(256),
+        }
+    }
+
+    /// Apply the caps to `record` in place.
+    ///
+    /// **Returns**
+    /// - `true` if anything was truncated or dropped; in that case the
+    ///   [`TRUNCATED_FIELD`] marker has been added to `record.fields`, and
+    ///   [`FIELDS_DROPPED_FIELD`] if fields were dropped.
+    pub fn apply(&self, record: &mut LogRecord) -> bool {
+        let mut truncated = false;
+
+        if let (Some(max), Some(message)) = (self.max_message_bytes, record.message.as_mut()) {
+            truncated |= truncate_str(message, max);
+        }
+
+        // Markers of an earlier pass do not count against `max_fields`.
+        let marked = record.fields.remove(TRUNCATED_FIELD);
+        let mut dropped = match record.fields.remove(FIELDS_DROPPED_FIELD) {
+            Some(serde_json::Value::Number(n)) if n.is_u64() => n.as_u64().unwrap_or(0),
+            Some(other) => {
+                record.fields.insert(FIELDS_DROPPED_FIELD.to_string(), other);
+                0
+            }
+            None => 0,
+        };
+        if let Some(max) = self.max_fields {
+            while record.fields.len() > max {
+                record.fields.pop_last();
+                dropped += 1;
+                truncated = true;
+            }
+        }
+        if dropped > 0 {
+            record
+                .fields
+                .insert(FIELDS_DROPPED_FIELD.to_string(), serde_json::Value::from(dropped));
+        }
+
+        if let Some(max) = self.max_field_bytes {
+            if let Some(message) = record.error_message.as_mut() {
+                truncated |= truncate_str(message, max);
+            }
+            for value in record.fields.values_mut() {
+                truncated |= truncate_value(value, max);
+            }
+        }
+
+        if truncated {
+            record
+                .fields
+                .insert(TRUNCATED_FIELD.to_string(), serde_json::Value::Bool(true));
+        } else if let Some(marked) = marked {
+            record.fields.insert(TRUNCATED_FIELD.to_string(), marked);
+        }
+        truncated
+    }
+}
+
+fn truncate_value(value: &mut serde_json::Value, max: usize) -> bool {
+    match value {
+        serde_json::Value::String(s) => truncate_str(s, max),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            let mut encoded = value.to_string();
+            if truncate_str(&mut encoded, max) {
+                *value = serde_json::Value::String(encoded);
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Truncate `s` to at most `max` bytes without splitting a character.
+fn truncate_str(s: &mut String, max: usize) -> bool {
+    if s.len() <= max {
+        return false;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    s.truncate(end);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn record(message: &str, fields: serde_json::Value) -> LogRecord {
+        serde_json::from_value(json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": message,
+            "fields": fields,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn default_limits_change_nothing() {
+        let mut record = record(&"x".repeat(100_000), json!({ "big": "y".repeat(100_000) }));
+        let before = serde_json::to_value(&record).unwrap();
+        assert!(!RecordLimits::default().apply(&mut record));
+        assert_eq!(serde_json::to_value(&record).unwrap(), before);
+    }
+
+    #[test]
+    fn truncation_stops_at_a_character_boundary() {
+        let limits = RecordLimits { max_message_bytes: Some(4), max_field_bytes: Some(5), ..RecordLimits::default() };
+        // "é" and "ж" take two bytes, "€" three.
+        let mut record = record("aéé", json!({ "name": "жжж", "sign": "a€€", "n": 123456789, "list": [1, 2, 3] }));
+        assert!(limits.apply(&mut record));
+        assert_eq!(record.message.as_deref(), Some("aé"));
+        assert_eq!(record.fields["name"], json!("жж"));
+        assert_eq!(record.fields["sign"], json!("a€"));
+        assert_eq!(record.fields["n"], json!(123456789));
+        assert_eq!(record.fields["list"], json!("[1,2,"));
+        assert_eq!(record.fields[TRUNCATED_FIELD], json!(true));
+    }
+
+    #[test]
+    fn fields_over_the_cap_are_dropped_and_counted() {
+        let limits = RecordLimits { max_fields: Some(2), ..RecordLimits::default() };
+        let mut record = record("m", json!({ "d": 4, "a": 1, "c": 3, "b": 2 }));
+        assert!(limits.apply(&mut record));
+        assert_eq!(
+            serde_json::to_value(&record.fields).unwrap(),
+            json!({ "a": 1, "b": 2, FIELDS_DROPPED_FIELD: 2, TRUNCATED_FIELD: true })
+        );
+
+        // Applying again keeps the markers out of the count.
+        assert!(!limits.apply(&mut record));
+        assert_eq!(record.fields[FIELDS_DROPPED_FIELD], json!(2));
+        assert_eq!(record.fields.len(), 4);
+    }
+}
@@ -1,7 +1,6 @@
 use crate::record::LogRecord;
-use crate::sink::LogSink;
+use crate::sink::{LogSink, SinkError};
 use async_trait::async_trait;
-use std::error::Error;
 
 /// A sink that simply drops all records.
 ///
@@ -10,9 +9,10 @@ use std::error::Error;
 #[derive(Clone, Default)]
 pub struct NoopSink;
 
-#[async_trait]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 impl LogSink for NoopSink {
-    async fn send(&self, _record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
+    async fn send(&self, _record: &LogRecord) -> Result<(), SinkError> {
         Ok(())
     }
 }
@@ -1,51 +1,532 @@
-use crate::{record::LogRecord, sink::LogSink};
+#[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+use crate::aws_auth::{SigV4Config, Signer};
+use crate::destination::DestinationTemplate;
+use crate::http::{header, HttpOptions, HttpRequest, HttpResponse, HttpTransport};
+use crate::timestamp::TimestampFormat;
+use crate::record::{LogRecord, LogRecordRef};
+use crate::schema::{self, ColumnType, SchemaBackend, SchemaDrift, SchemaOptions};
+use crate::sink::{LogSink, SinkError};
+use crate::tls::{TlsError, TlsOptions};
 use async_trait::async_trait;
-use reqwest::Client;
-use std::error::Error;
+#[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Configuration for [`OpenSearchSink`].
+#[derive(Clone, Debug)]
+pub struct OpenSearchConfig {
+    /// Base URL of the OpenSearch cluster, e.g. "http://localhost:9200".
+    pub base_url: String,
+    /// Target index name; placeholders such as `{date}` are filled per
+    /// record, see [`DestinationTemplate`].
+    pub index: String,
+    /// How the `timestamp` field of each document is written.
+    pub timestamp_format: TimestampFormat,
+    /// Per-request timeout (default 30 s); `None` waits indefinitely.
+    /// Ignored on `wasm32`.
+    pub timeout: Option<Duration>,
+    /// Connect timeout, pooling and keep-alive of the connections.
+    pub http: HttpOptions,
+    /// Private CA, client certificate (mTLS) and SNI override for
+    /// `https` URLs.
+    pub tls: TlsOptions,
+    /// Basic auth credentials sent with every request, e.g. from the
+    /// `user:pass@` part of an `opensearch://` DSN; ignored when requests
+    /// are signed with `aws_sigv4`.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sign requests with AWS SigV4 for Amazon OpenSearch Service
+    /// (feature `aws-auth`); `None` sends them unsigned.
+    #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+    pub aws_sigv4: Option<SigV4Config>,
+}
+
+impl Default for OpenSearchConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "http://localhost:9200".to_string(),
+            index: "logs".to_string(),
+            timestamp_format: TimestampFormat::default(),
+            timeout: Some(Duration::from_secs(30)),
+            http: HttpOptions::default(),
+            tls: TlsOptions::default(),
+            username: None,
+            password: None,
+            #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+            aws_sigv4: None,
+        }
+    }
+}
 
 /// OpenSearch sink that sends log records via HTTP bulk API.
 #[derive(Clone)]
 pub struct OpenSearchSink {
-    client: Client,
-    /// Base URL of the OpenSearch cluster, e.g. "http://localhost:9200".
-    base_url: String,
-    /// Target index name.
-    index: String,
+    client: HttpTransport,
+    index: DestinationTemplate,
+    config: OpenSearchConfig,
+    #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+    signer: Option<Arc<Signer>>,
 }
 
 impl OpenSearchSink {
     pub fn new(base_url: String, index: String) -> Self {
-        OpenSearchSink {
-            client: Client::new(),
+        Self::from_config(OpenSearchConfig {
             base_url,
             index,
+            ..OpenSearchConfig::default()
+        })
+    }
+
+    /// Construct a sink from a full [`OpenSearchConfig`].
+    ///
+    /// Panics if the HTTP client cannot be built, e.g. because a file of
+    /// [`OpenSearchConfig::tls`] is missing; see
+    /// [`OpenSearchSink::try_from_config`].
+    pub fn from_config(config: OpenSearchConfig) -> Self {
+        Self::try_from_config(config).expect("build OpenSearch HTTP client")
+    }
+
+    /// Like [`OpenSearchSink::from_config`], but returns an error instead
+    /// of panicking when the HTTP client cannot be built.
+    pub fn try_from_config(mut config: OpenSearchConfig) -> Result<Self, TlsError> {
+        let (client, base_url) = HttpTransport::new(&config.base_url, config.timeout, &config.http, &config.tls)?;
+        config.base_url = base_url;
+        Ok(OpenSearchSink {
+            client,
+            index: DestinationTemplate::new(&config.index),
+            #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+            signer: config.aws_sigv4.clone().map(|c| Arc::new(Signer::new(c))),
+            config,
+        })
+    }
+
+    /// Send `request`, signed first if `OpenSearchConfig::aws_sigv4` is
+    /// set, otherwise with basic auth if `OpenSearchConfig::username` is.
+    async fn execute(&self, mut request: HttpRequest) -> Result<HttpResponse, SinkError> {
+        #[cfg(all(feature = "aws-auth", not(target_arch = "wasm32")))]
+        if let Some(signer) = &self.signer {
+            signer.sign(&mut request).await?;
+            return self.client.send(request).await;
         }
+        if let Some(username) = &self.config.username {
+            use base64::Engine;
+
+            let credentials = format!("{}:{}", username, self.config.password.as_deref().unwrap_or(""));
+            let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+            request = request.header(header::AUTHORIZATION, &format!("Basic {}", encoded))?;
+        }
+        self.client.send(request).await
     }
-}
 
-#[async_trait]
-impl LogSink for OpenSearchSink {
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>> {
-        // Minimal bulk body with a single operation.
-        let action = format!("{{\"index\":{{\"_index\":\"{}\"}}}}\n", self.index);
-        let doc = serde_json::to_string(record)? + "\n";
-        let body = format!("{}{}", action, doc);
+    /// Send `records` in one `_bulk` request.
+    ///
+    /// **Returns**
+    /// - The failed items with the position of their record in
+    ///   `records`; empty if every document was stored.
+    /// - A transient error if a response with failures does not have
+    ///   one item per document.
+    async fn bulk(&self, records: &[&LogRecord]) -> Result<Vec<(usize, BulkItemError)>, SinkError> {
+        // The event id doubles as the document id, so a resent record
+        // overwrites itself instead of creating a duplicate.
+        let mut body = String::new();
+        for record in records {
+            let index = self.index.render(record);
+            let action = match record.event_id {
+                Some(id) => serde_json::json!({ "index": { "_index": index, "_id": id } }),
+                None => serde_json::json!({ "index": { "_index": index } }),
+            };
+            body.push_str(&action.to_string());
+            body.push('\n');
+            body.push_str(&serde_json::to_string(
+                &LogRecordRef::from(*record).with_timestamp(self.config.timestamp_format),
+            )?);
+            body.push('\n');
+        }
 
-        let url = format!("{}/_bulk", self.base_url.trim_end_matches('/'));
+        let url = format!("{}/_bulk", self.config.base_url.trim_end_matches('/'));
         let resp = self
-            .client
-            .post(&url)
-            .header("Content-Type", "application/x-ndjson")
-            .body(body)
-            .send()
+            .execute(
+                HttpRequest::post(url)
+                    .header(header::CONTENT_TYPE, "application/x-ndjson")?
+                    .body(body),
+            )
             .await?;
+        if !resp.is_success() {
+            return Err(resp.error("OpenSearch bulk insert"));
+        }
+
+        // A 200 response can still carry failures of single documents.
+        let response: serde_json::Value = resp.json()?;
+        if response.get("errors").and_then(serde_json::Value::as_bool) != Some(true) {
+            return Ok(Vec::new());
+        }
+        // Failed items are matched to records by position, which only
+        // holds if there is one item per document.
+        let items = response.get("items").and_then(serde_json::Value::as_array);
+        let count = items.map_or(0, Vec::len);
+        if count != records.len() {
+            return Err(SinkError::transient(format!(
+                "OpenSearch bulk response has {} item(s) for {} document(s)",
+                count,
+                records.len()
+            )));
+        }
+        Ok(items
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .filter_map(|(position, item)| BulkItemError::from_item(item).map(|e| (position, e)))
+            .collect())
+    }
+}
+
+/// A document of a `_bulk` request that OpenSearch did not store.
+///
+/// **Fields**
+/// - `index`, `id`: where the document was to be written.
+/// - `status`: HTTP status of the item, e.g. 400 for a mapping conflict
+///   or 429 for a rejection by a busy node.
+/// - `kind`: `error.type`, e.g. `mapper_parsing_exception`.
+/// - `reason`: `error.reason`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkItemError {
+    pub index: String,
+    pub id: Option<String>,
+    pub status: u16,
+    pub kind: String,
+    pub reason: String,
+}
+
+impl BulkItemError {
+    /// The error of one entry of the `items` array, if it failed.
+    fn from_item(item: &serde_json::Value) -> Option<Self> {
+        let result = item.as_object()?.values().next()?;
+        let status = result.get("status").and_then(serde_json::Value::as_u64).unwrap_or(0) as u16;
+        let error = result.get("error")?;
+        let text = |value: Option<&serde_json::Value>| value.and_then(serde_json::Value::as_str).unwrap_or("").to_string();
+        Some(Self {
+            index: text(result.get("_index")),
+            id: result.get("_id").and_then(serde_json::Value::as_str).map(str::to_string),
+            status,
+            kind: text(error.get("type")),
+            reason: match error {
+                serde_json::Value::String(reason) => reason.clone(),
+                _ => text(error.get("reason")),
+            },
+        })
+    }
+
+    /// Whether storing the document again may succeed: rejections by busy
+    /// nodes (429) and server-side failures (5xx).
+    pub fn is_retryable(&self) -> bool {
+        self.status == 429 || self.status >= 500
+    }
+}
+
+impl std::fmt::Display for BulkItemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "document")?;
+        if let Some(id) = &self.id {
+            write!(f, " {}", id)?;
+        }
+        write!(f, " in {} failed with status {}: {}: {}", self.index, self.status, self.kind, self.reason)
+    }
+}
+
+impl std::error::Error for BulkItemError {}
+
+/// Documents of a `_bulk` request that OpenSearch did not store, carried
+/// by the [`SinkError`] of the failed send; downcast the boxed error of
+/// its `Transient` or `Permanent` variant to inspect them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BulkError {
+    pub failed: Vec<BulkItemError>,
+}
+
+impl std::fmt::Display for BulkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpenSearch bulk insert: {} document(s) failed", self.failed.len())?;
+        if let Some(first) = self.failed.first() {
+            write!(f, ", first: {}", first)?;
+        }
+        Ok(())
+    }
+}
 
-        if resp.status().is_success() {
+impl std::error::Error for BulkError {}
+
+impl BulkError {
+    /// [`SinkError`] for these failures: [`SinkError::Transient`] if any of
+    /// them is retryable, otherwise [`SinkError::Permanent`].
+    fn into_sink_error(self) -> SinkError {
+        if self.failed.iter().any(BulkItemError::is_retryable) {
+            SinkError::Transient(Box::new(self))
+        } else {
+            SinkError::Permanent(Box::new(self))
+        }
+    }
+}
+
+/// Passes of [`LogSink::send_batch`] over documents that failed with a
+/// retryable item error, after the first request.
+const BULK_ITEM_RETRIES: u32 = 3;
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl LogSink for OpenSearchSink {
+    /// Writes the record as a single-document bulk request; a failed item
+    /// is reported as a [`BulkError`].
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let failed = self.bulk(&[record]).await?;
+        if failed.is_empty() {
+            return Ok(());
+        }
+        Err(BulkError { failed: failed.into_iter().map(|(_, e)| e).collect() }.into_sink_error())
+    }
+
+    /// Writes all records in one bulk request, then resends only the
+    /// documents that failed with a retryable item error, up to three
+    /// more times with backoff (no backoff on `wasm32`).
+    ///
+    /// **Returns**
+    /// - A [`BulkError`] with the documents not stored: transient if any
+    ///   of them is still retryable, permanent if all were rejected.
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        let mut pending: Vec<&LogRecord> = records.iter().collect();
+        let mut rejected = Vec::new();
+        let mut attempt = 0;
+        #[cfg_attr(target_arch = "wasm32", allow(unused_variables, unused_mut))]
+        let mut backoff = Duration::from_millis(100);
+        loop {
+            let failed = self.bulk(&pending).await?;
+            let mut retry = Vec::new();
+            let mut retryable = Vec::new();
+            for (position, error) in failed {
+                if error.is_retryable() {
+                    let record = pending.get(position).ok_or_else(|| {
+                        SinkError::transient("OpenSearch bulk response refers to an unknown document")
+                    })?;
+                    retry.push(*record);
+                    retryable.push(error);
+                } else {
+                    rejected.push(error);
+                }
+            }
+            if retry.is_empty() {
+                break;
+            }
+            attempt += 1;
+            if attempt > BULK_ITEM_RETRIES || cfg!(target_arch = "wasm32") {
+                rejected.extend(retryable);
+                break;
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            pending = retry;
+        }
+        if rejected.is_empty() {
             Ok(())
         } else {
-            let status = resp.status();
-            let text = resp.text().await.unwrap_or_else(|_| "<no body>".to_string());
-            Err(format!("OpenSearch bulk insert failed with status {}: {}", status, text).into())
+            Err(BulkError { failed: rejected }.into_sink_error())
+        }
+    }
+
+    /// Queries `_cluster/health` and fails if the cluster is `red`.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        let url = format!("{}/_cluster/health", self.config.base_url.trim_end_matches('/'));
+        let resp = self.execute(HttpRequest::get(url)).await?;
+        if !resp.is_success() {
+            return Err(resp.error("OpenSearch health check"));
+        }
+
+        let health: serde_json::Value = resp.json()?;
+        match health.get("status").and_then(|s| s.as_str()) {
+            Some("red") => Err(SinkError::transient("OpenSearch cluster status is red")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Compares the top-level fields of the mapping of the target index,
+    /// or of every existing index matching a templated name, with those
+    /// of [`schema::expected_columns`] for `timestamp_format`; only the
+    /// mapping type (`keyword`, `date`, ...) is compared. An index that
+    /// does not exist yet is not checked.
+    ///
+    /// With dynamic mapping, fields appear once a document has them and
+    /// strings map to `text`; set up the template of `logrec schema
+    /// opensearch` to avoid both.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        let url = format!(
+            "{}/{}/_mapping?allow_no_indices=true&ignore_unavailable=true",
+            self.config.base_url.trim_end_matches('/'),
+            self.index.pattern()
+        );
+        let resp = self.execute(HttpRequest::get(url)).await?;
+        if resp.status.as_u16() == 404 {
+            return Ok(Vec::new());
+        }
+        if !resp.is_success() {
+            return Err(resp.error("OpenSearch schema check"));
+        }
+        let options = SchemaOptions { timestamp_format: self.config.timestamp_format, ..SchemaOptions::default() };
+        let expected = schema::expected_columns(SchemaBackend::OpenSearch, &options).map_err(SinkError::permanent)?;
+        let indices: serde_json::Map<String, serde_json::Value> = resp.json()?;
+        let mut drift = Vec::new();
+        for (index, mapping) in indices {
+            let actual: Vec<ColumnType> = mapping["mappings"]["properties"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(name, field)| ColumnType::new(name, field["type"].as_str().unwrap_or("object")))
+                .collect();
+            let index_drift = schema::drift(SchemaBackend::OpenSearch, index, &expected, &actual);
+            if !index_drift.is_empty() {
+                drift.push(index_drift);
+            }
         }
+        Ok(drift)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    fn record(message: &str) -> LogRecord {
+        let mut record: LogRecord = serde_json::from_value(json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": message,
+            "fields": {},
+        }))
+        .unwrap();
+        record.event_id = Some(uuid::Uuid::now_v7());
+        record
+    }
+
+    /// Answer one request per connection with the next of `responses`,
+    /// returning the request bodies once all were sent.
+    fn serve(responses: Vec<serde_json::Value>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(String::from_utf8(body).unwrap());
+
+                let response = response.to_string();
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, server)
+    }
+
+    /// `(action, document)` pairs of a `_bulk` body.
+    fn documents(body: &str) -> Vec<(serde_json::Value, serde_json::Value)> {
+        let lines: Vec<serde_json::Value> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        lines.chunks(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect()
+    }
+
+    #[test]
+    fn bulk_item_errors() {
+        let created = json!({ "index": { "_index": "logs", "_id": "a", "status": 201 } });
+        assert_eq!(BulkItemError::from_item(&created), None);
+
+        let busy = json!({ "index": { "_index": "logs", "_id": "b", "status": 429, "error": {
+            "type": "es_rejected_execution_exception", "reason": "rejected execution"
+        } } });
+        let error = BulkItemError::from_item(&busy).unwrap();
+        assert_eq!(
+            error,
+            BulkItemError {
+                index: "logs".to_string(),
+                id: Some("b".to_string()),
+                status: 429,
+                kind: "es_rejected_execution_exception".to_string(),
+                reason: "rejected execution".to_string(),
+            }
+        );
+        assert!(error.is_retryable());
+
+        let mapping = json!({ "create": { "_index": "logs", "status": 400, "error": {
+            "type": "mapper_parsing_exception", "reason": "failed to parse field [user_id]"
+        } } });
+        let error = BulkItemError::from_item(&mapping).unwrap();
+        assert_eq!((error.id.as_deref(), error.status, error.kind.as_str()), (None, 400, "mapper_parsing_exception"));
+        assert!(!error.is_retryable());
+
+        let legacy = json!({ "index": { "_index": "logs", "status": 503, "error": "node unavailable" } });
+        let error = BulkItemError::from_item(&legacy).unwrap();
+        assert_eq!((error.reason.as_str(), error.is_retryable()), ("node unavailable", true));
+    }
+
+    #[tokio::test]
+    async fn action_lines_escape_the_index() {
+        let (url, server) = serve(vec![json!({ "errors": false, "items": [{}] })]);
+        let sink = OpenSearchSink::new(url, "logs-\"quoted\"\\".to_string());
+        sink.send(&record("m")).await.unwrap();
+
+        let bodies = server.join().unwrap();
+        let (action, document) = documents(&bodies[0]).remove(0);
+        assert_eq!(action["index"]["_index"], json!("logs-\"quoted\"\\"));
+        assert_eq!(document["message"], json!("m"));
+    }
+
+    #[tokio::test]
+    async fn send_batch_retries_only_retryable_items() {
+        let item = |status: u16, error: Option<&str>| match error {
+            Some(kind) => json!({ "index": { "_index": "logs", "status": status, "error": { "type": kind, "reason": kind } } }),
+            None => json!({ "index": { "_index": "logs", "status": status } }),
+        };
+        let (url, server) = serve(vec![
+            json!({ "errors": true, "items": [item(429, Some("busy")), item(400, Some("mapping")), item(201, None)] }),
+            json!({ "errors": false, "items": [item(201, None)] }),
+        ]);
+        let sink = OpenSearchSink::new(url, "logs".to_string());
+        let records = [record("busy"), record("bad"), record("ok")];
+
+        let Err(SinkError::Permanent(err)) = sink.send_batch(&records).await else {
+            panic!("expected a permanent error");
+        };
+        let bulk = err.downcast_ref::<BulkError>().unwrap();
+        assert_eq!(bulk.failed.iter().map(|e| (e.status, e.kind.as_str())).collect::<Vec<_>>(), [(400, "mapping")]);
+
+        let bodies = server.join().unwrap();
+        let ids = |body: &str| -> Vec<serde_json::Value> {
+            documents(body).into_iter().map(|(action, _)| action["index"]["_id"].clone()).collect()
+        };
+        let id = |record: &LogRecord| json!(record.event_id.unwrap());
+        assert_eq!(ids(&bodies[0]), records.iter().map(id).collect::<Vec<_>>());
+        assert_eq!(ids(&bodies[1]), [id(&records[0])]);
     }
 }
@@ -0,0 +1,175 @@
+//! Export through an OpenTelemetry Logs pipeline (feature `otel`).
+//!
+//! [`OtelSink`] hands every record to a `Logger` of an OpenTelemetry
+//! `LoggerProvider`, typically `opentelemetry_sdk::logs::LoggerProvider`
+//! with the OTLP exporter of the application. The layer's filtering,
+//! redaction, batching and retries stay in front of it; processing and
+//! export are then up to the SDK and the collector.
+
+use crate::record::{LogLevel, LogRecord};
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use opentelemetry::logs::{AnyValue, LogRecord as _, Logger, LoggerProvider, Severity};
+use opentelemetry::trace::{SpanId, TraceId};
+use opentelemetry::{InstrumentationScope, Key};
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+type FlushHook = Box<dyn Fn() + Send + Sync>;
+
+/// [`LogSink`] emitting records as OpenTelemetry log records.
+///
+/// Each [`LogRecord`] becomes one log record of the logger:
+/// - `timestamp` is the timestamp, the time of `send` the observed one;
+/// - `level` is the severity (`TRACE` = 1 ... `ERROR` = 17) and its text;
+/// - `message` is the body, `target` the target;
+/// - `trace_id` and `span_id` are the trace context, so the SDK
+///   correlates the record with the trace;
+/// - `fields` are attributes, with objects and arrays as maps and lists;
+/// - the remaining metadata are attributes named after the semantic
+///   conventions where one exists (`code.namespace`, `code.filepath`,
+///   `code.lineno`, `service.name`, `host.name`, `process.pid`,
+///   `thread.id`, `thread.name`, `log.record.uid`, `exception.type`,
+///   `exception.message`), otherwise after the record field
+///   (`fingerprint`, `repeat_count`, `span_name`, `duration_ms`,
+///   `span_elapsed_ms`), plus the `resource` labels under their own keys.
+///
+/// `Logger::emit` only queues the record in the SDK's processor, so
+/// `send` never fails: export errors go to the SDK's error handling, not
+/// to the layer's retries. The SDK exports on its own schedule; give
+/// [`OtelSink::with_flush`] a hook calling the provider's `force_flush`
+/// to have [`LogSink::flush`] and [`LogSink::shutdown`] wait for it.
+pub struct OtelSink<L> {
+    logger: L,
+    flush: Option<FlushHook>,
+}
+
+impl<L: Logger> OtelSink<L> {
+    /// Emit through a logger of `provider` whose instrumentation scope
+    /// is this crate.
+    pub fn new<P: LoggerProvider<Logger = L>>(provider: &P) -> Self {
+        let scope = InstrumentationScope::builder(env!("CARGO_PKG_NAME"))
+            .with_version(env!("CARGO_PKG_VERSION"))
+            .build();
+        Self::from_logger(provider.logger_with_scope(scope))
+    }
+
+    /// Emit through `logger`.
+    pub fn from_logger(logger: L) -> Self {
+        Self { logger, flush: None }
+    }
+
+    /// Call `flush` from [`LogSink::flush`] and [`LogSink::shutdown`],
+    /// e.g. `move || { provider.force_flush(); }`.
+    pub fn with_flush(mut self, flush: impl Fn() + Send + Sync + 'static) -> Self {
+        self.flush = Some(Box::new(flush));
+        self
+    }
+
+    fn emit(&self, record: &LogRecord) {
+        let mut otel = self.logger.create_log_record();
+        otel.set_timestamp(SystemTime::from(record.timestamp));
+        otel.set_observed_timestamp(SystemTime::from(crate::timestamp::now()));
+        otel.set_severity_number(severity(record.level));
+        otel.set_severity_text(record.level.as_str());
+        otel.set_target(record.target.clone());
+        if let Some(message) = &record.message {
+            otel.set_body(AnyValue::from(message.clone()));
+        }
+        let trace_id = record.trace_id.as_deref().and_then(|id| TraceId::from_hex(id).ok());
+        if let Some(trace_id) = trace_id {
+            let span_id = record.span_id.as_deref().and_then(|id| SpanId::from_hex(id).ok());
+            otel.set_trace_context(trace_id, span_id.unwrap_or(SpanId::INVALID), None);
+        }
+
+        let text = |key: &'static str, value: &Option<String>| value.clone().map(|v| (key, AnyValue::from(v)));
+        let int = |key: &'static str, value: Option<i64>| value.map(|v| (key, AnyValue::Int(v)));
+        let metadata = [
+            record.module_path.clone().map(|v| ("code.namespace", AnyValue::from(v))),
+            record.file.clone().map(|v| ("code.filepath", AnyValue::from(v))),
+            int("code.lineno", record.line.map(i64::from)),
+            text("service.name", &record.service_name),
+            text("host.name", &record.hostname),
+            int("process.pid", record.pid.map(i64::from)),
+            text("thread.id", &record.thread_id),
+            text("thread.name", &record.thread_name),
+            record.event_id.map(|id| ("log.record.uid", AnyValue::from(id.to_string()))),
+            text("exception.type", &record.error_kind),
+            text("exception.message", &record.error_message),
+            text("fingerprint", &record.fingerprint),
+            int("repeat_count", record.repeat_count.map(saturating_i64)),
+            text("span_name", &record.span_name),
+            int("duration_ms", record.duration_ms.map(saturating_i64)),
+            int("span_elapsed_ms", record.span_elapsed_ms.map(saturating_i64)),
+        ];
+        otel.add_attributes(metadata.into_iter().flatten());
+        otel.add_attributes(record.resource.iter().map(|(k, v)| (k.clone(), AnyValue::from(v.clone()))));
+        otel.add_attributes(
+            record.fields.iter().filter_map(|(k, v)| any_value(v).map(|v| (k.clone(), v))),
+        );
+
+        self.logger.emit(otel);
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<L: Logger + Send + Sync> LogSink for OtelSink<L> {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.emit(record);
+        Ok(())
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        records.iter().for_each(|record| self.emit(record));
+        Ok(())
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        if let Some(flush) = &self.flush {
+            flush();
+        }
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.flush().await
+    }
+}
+
+fn severity(level: LogLevel) -> Severity {
+    match level {
+        LogLevel::Trace => Severity::Trace,
+        LogLevel::Debug => Severity::Debug,
+        LogLevel::Info => Severity::Info,
+        LogLevel::Warn => Severity::Warn,
+        LogLevel::Error => Severity::Error,
+    }
+}
+
+fn saturating_i64(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+/// `value` as an attribute value; `None` for `null`, which OpenTelemetry
+/// cannot represent. Integers beyond `i64` become doubles.
+fn any_value(value: &serde_json::Value) -> Option<AnyValue> {
+    use serde_json::Value;
+
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => AnyValue::Boolean(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => AnyValue::Int(i),
+            None => AnyValue::Double(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Value::String(s) => AnyValue::from(s.clone()),
+        Value::Array(items) => AnyValue::ListAny(Box::new(items.iter().filter_map(any_value).collect())),
+        Value::Object(entries) => AnyValue::Map(Box::new(
+            entries
+                .iter()
+                .filter_map(|(k, v)| any_value(v).map(|v| (Key::from(k.clone()), v)))
+                .collect::<HashMap<_, _>>(),
+        )),
+    })
+}
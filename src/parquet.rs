@@ -0,0 +1,233 @@
+//! Columnar Parquet encoding of [`LogRecord`] batches (feature `parquet`).
+//!
+//! Meant for archival sinks and standalone jobs that move logs to object
+//! storage. Every record field becomes a typed column; `fields` and
+//! `resource` are stored as JSON strings so the schema does not depend on
+//! the keys in use.
+//!
+//! | column | type |
+//! |---|---|
+//! | `schema_version`, `line`, `pid` | `UInt32` |
+//! | `timestamp` | `Timestamp(Microsecond, "UTC")` |
+//! | `repeat_count`, `duration_ms`, `span_elapsed_ms` | `UInt64` |
+//! | `fields`, `resource` | `Utf8` (JSON object) |
+//! | every other field | `Utf8` |
+//!
+//! Optional record fields are nullable columns.
+
+use crate::record::LogRecord;
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::errors::ParquetError;
+use ::parquet::file::properties::WriterProperties;
+use arrow_array::builder::{StringBuilder, TimestampMicrosecondBuilder, UInt32Builder, UInt64Builder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use std::io::Write;
+use std::sync::{Arc, OnceLock};
+
+/// Arrow schema of the batches produced by [`record_batch`].
+pub fn schema() -> SchemaRef {
+    static SCHEMA: OnceLock<SchemaRef> = OnceLock::new();
+    SCHEMA
+        .get_or_init(|| {
+            let text = |name: &str, nullable: bool| Field::new(name, DataType::Utf8, nullable);
+            Arc::new(Schema::new(vec![
+                Field::new("schema_version", DataType::UInt32, false),
+                Field::new(
+                    "timestamp",
+                    DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+                    false,
+                ),
+                text("level", false),
+                text("target", false),
+                text("module_path", true),
+                text("file", true),
+                Field::new("line", DataType::UInt32, true),
+                text("fields", false),
+                text("message", true),
+                text("service_name", true),
+                text("hostname", true),
+                Field::new("pid", DataType::UInt32, true),
+                text("thread_id", true),
+                text("thread_name", true),
+                text("resource", false),
+                text("event_id", true),
+                Field::new("repeat_count", DataType::UInt64, true),
+                text("fingerprint", true),
+                text("trace_id", true),
+                text("span_id", true),
+                text("span_name", true),
+                Field::new("duration_ms", DataType::UInt64, true),
+                Field::new("span_elapsed_ms", DataType::UInt64, true),
+                text("error_kind", true),
+                text("error_message", true),
+            ]))
+        })
+        .clone()
+}
+
+/// Convert `records` into one Arrow [`RecordBatch`] with [`schema`].
+pub fn record_batch(records: &[LogRecord]) -> Result<RecordBatch, ArrowError> {
+    let n = records.len();
+    let mut schema_version = UInt32Builder::with_capacity(n);
+    let mut timestamp = TimestampMicrosecondBuilder::with_capacity(n).with_timezone("UTC");
+    let mut level = StringBuilder::new();
+    let mut target = StringBuilder::new();
+    let mut module_path = StringBuilder::new();
+    let mut file = StringBuilder::new();
+    let mut line = UInt32Builder::with_capacity(n);
+    let mut fields = StringBuilder::new();
+    let mut message = StringBuilder::new();
+    let mut service_name = StringBuilder::new();
+    let mut hostname = StringBuilder::new();
+    let mut pid = UInt32Builder::with_capacity(n);
+    let mut thread_id = StringBuilder::new();
+    let mut thread_name = StringBuilder::new();
+    let mut resource = StringBuilder::new();
+    let mut event_id = StringBuilder::new();
+    let mut repeat_count = UInt64Builder::with_capacity(n);
+    let mut fingerprint = StringBuilder::new();
+    let mut trace_id = StringBuilder::new();
+    let mut span_id = StringBuilder::new();
+    let mut span_name = StringBuilder::new();
+    let mut duration_ms = UInt64Builder::with_capacity(n);
+    let mut span_elapsed_ms = UInt64Builder::with_capacity(n);
+    let mut error_kind = StringBuilder::new();
+    let mut error_message = StringBuilder::new();
+
+    for record in records {
+        // Exhaustive destructuring, as in `LogRecord::to_json_for_version`:
+        // a new `LogRecord` field must also get a column here.
+        let LogRecord {
+            schema_version: r_schema_version,
+            timestamp: r_timestamp,
+            level: r_level,
+            target: r_target,
+            module_path: r_module_path,
+            file: r_file,
+            line: r_line,
+            fields: r_fields,
+            message: r_message,
+            service_name: r_service_name,
+            hostname: r_hostname,
+            pid: r_pid,
+            thread_id: r_thread_id,
+            thread_name: r_thread_name,
+            resource: r_resource,
+            event_id: r_event_id,
+            repeat_count: r_repeat_count,
+            fingerprint: r_fingerprint,
+            trace_id: r_trace_id,
+            span_id: r_span_id,
+            span_name: r_span_name,
+            duration_ms: r_duration_ms,
+            span_elapsed_ms: r_span_elapsed_ms,
+            error_kind: r_error_kind,
+            error_message: r_error_message,
+        } = record;
+
+        schema_version.append_value(*r_schema_version);
+        timestamp.append_value(r_timestamp.timestamp_micros());
+        level.append_value(r_level.as_str());
+        target.append_value(r_target.as_ref());
+        module_path.append_option(r_module_path.as_deref());
+        file.append_option(r_file.as_deref());
+        line.append_option(*r_line);
+        fields.append_value(to_json(r_fields)?);
+        message.append_option(r_message.as_deref());
+        service_name.append_option(r_service_name.as_deref());
+        hostname.append_option(r_hostname.as_deref());
+        pid.append_option(*r_pid);
+        thread_id.append_option(r_thread_id.as_deref());
+        thread_name.append_option(r_thread_name.as_deref());
+        resource.append_value(to_json(r_resource)?);
+        event_id.append_option(r_event_id.map(|id| id.to_string()));
+        repeat_count.append_option(*r_repeat_count);
+        fingerprint.append_option(r_fingerprint.as_deref());
+        trace_id.append_option(r_trace_id.as_deref());
+        span_id.append_option(r_span_id.as_deref());
+        span_name.append_option(r_span_name.as_deref());
+        duration_ms.append_option(*r_duration_ms);
+        span_elapsed_ms.append_option(*r_span_elapsed_ms);
+        error_kind.append_option(r_error_kind.as_deref());
+        error_message.append_option(r_error_message.as_deref());
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(schema_version.finish()),
+        Arc::new(timestamp.finish()),
+        Arc::new(level.finish()),
+        Arc::new(target.finish()),
+        Arc::new(module_path.finish()),
+        Arc::new(file.finish()),
+        Arc::new(line.finish()),
+        Arc::new(fields.finish()),
+        Arc::new(message.finish()),
+        Arc::new(service_name.finish()),
+        Arc::new(hostname.finish()),
+        Arc::new(pid.finish()),
+        Arc::new(thread_id.finish()),
+        Arc::new(thread_name.finish()),
+        Arc::new(resource.finish()),
+        Arc::new(event_id.finish()),
+        Arc::new(repeat_count.finish()),
+        Arc::new(fingerprint.finish()),
+        Arc::new(trace_id.finish()),
+        Arc::new(span_id.finish()),
+        Arc::new(span_name.finish()),
+        Arc::new(duration_ms.finish()),
+        Arc::new(span_elapsed_ms.finish()),
+        Arc::new(error_kind.finish()),
+        Arc::new(error_message.finish()),
+    ];
+    RecordBatch::try_new(schema(), columns)
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, ArrowError> {
+    serde_json::to_string(value).map_err(|e| ArrowError::ExternalError(Box::new(e)))
+}
+
+/// Streaming Parquet writer for [`LogRecord`] batches.
+///
+/// Each call to [`ParquetBatchWriter::write`] appends the records to the
+/// file; row groups are flushed by the underlying [`ArrowWriter`] as they
+/// fill up. Columns are Snappy-compressed.
+pub struct ParquetBatchWriter<W: Write + Send> {
+    inner: ArrowWriter<W>,
+}
+
+impl<W: Write + Send> ParquetBatchWriter<W> {
+    /// Start a Parquet file on `writer`.
+    pub fn new(writer: W) -> Result<Self, ParquetError> {
+        let props = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        Ok(Self {
+            inner: ArrowWriter::try_new(writer, schema(), Some(props))?,
+        })
+    }
+
+    /// Append `records` to the file.
+    pub fn write(&mut self, records: &[LogRecord]) -> Result<(), ParquetError> {
+        self.inner.write(&record_batch(records)?)
+    }
+
+    /// Write the footer and return the underlying writer.
+    pub fn finish(self) -> Result<W, ParquetError> {
+        self.inner.into_inner()
+    }
+}
+
+/// Encode `records` as a complete, in-memory Parquet file.
+///
+/// **Parameters**
+/// - `records`: the batch to encode; may be empty.
+///
+/// **Returns**
+/// - The Parquet file bytes, ready to upload as a single object.
+pub fn to_parquet(records: &[LogRecord]) -> Result<Vec<u8>, ParquetError> {
+    let mut writer = ParquetBatchWriter::new(Vec::new())?;
+    writer.write(records)?;
+    writer.finish()
+}
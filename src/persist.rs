@@ -0,0 +1,138 @@
+#[cfg(feature = "encryption")]
+use crate::encryption::SpillKey;
+use crate::record::LogRecord;
+use crate::timestamp::Timestamp;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Keep records that were not delivered by shutdown in a local file and
+/// send them after the next start, see [`LayerConfig::persist_unsent`].
+///
+/// On [`LayerHandle::shutdown`] the worker writes the batches it is
+/// still sending, its current batch, the records still queued in the
+/// channel and open collapse summaries to `path` before sending them, and
+/// removes the file once they have all been delivered. If the process dies
+/// first (the sink is down, the shutdown timeout fires, the orchestrator
+/// kills the container), the next layer built with the same `path` reads
+/// the file, drops records older than `max_age` and sends the rest ahead
+/// of new events, removing the file only once they were delivered.
+///
+/// Delivery is at-least-once: records the sink accepted just before the
+/// process died are sent again, with the same `event_id`.
+///
+/// **Fields**
+/// - `path`: JSON Lines file holding the backlog; its directory must
+///   exist. Use a distinct path per process.
+/// - `max_age`: records older than this are discarded on replay
+///   (default 24 hours).
+/// - `encryption` (feature `encryption`): encrypt the records in the file
+///   with this key, see `encryption::SpillKey`. Default `None`.
+///
+/// [`LayerConfig::persist_unsent`]: crate::init::LayerConfig::persist_unsent
+/// [`LayerHandle::shutdown`]: crate::handle::LayerHandle::shutdown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersistConfig {
+    pub path: PathBuf,
+    pub max_age: Duration,
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<SpillKey>,
+}
+
+impl PersistConfig {
+    /// Persist to `path`, keeping records for the default 24 hours.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_age: Duration::from_secs(24 * 60 * 60),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        }
+    }
+
+    /// One line of the backlog for `record`, with its newline.
+    fn encode(&self, record: &LogRecord) -> io::Result<Vec<u8>> {
+        let mut line = serde_json::to_vec(record)?;
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption {
+            line = key.seal(&line)?;
+        }
+        line.push(b'\n');
+        Ok(line)
+    }
+
+    /// The record on a line of the backlog, `None` if it is unreadable.
+    fn decode(&self, line: &[u8]) -> Option<LogRecord> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption {
+            return serde_json::from_slice(&key.open(line)?).ok();
+        }
+        serde_json::from_slice(line).ok()
+    }
+}
+
+/// Records read back by [`load`].
+#[derive(Debug, Default)]
+pub(crate) struct Replay {
+    pub(crate) records: Vec<LogRecord>,
+    /// Records older than [`PersistConfig::max_age`].
+    pub(crate) expired: usize,
+    /// Lines that did not parse as a [`LogRecord`].
+    pub(crate) invalid: usize,
+}
+
+/// Atomically replace the backlog file with `records`, one JSON object
+/// (or its ciphertext) per line.
+pub(crate) fn save<'a>(config: &PersistConfig, records: impl IntoIterator<Item = &'a LogRecord>) -> io::Result<()> {
+    let tmp = config.path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&tmp)?);
+    for record in records {
+        writer.write_all(&config.encode(record)?)?;
+    }
+    writer.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+    fs::rename(&tmp, &config.path)
+}
+
+/// Read the backlog file left by a previous process.
+///
+/// The file stays until the worker has delivered its records, so a crash
+/// during the replay leaves them for the next start; it is removed here
+/// only if none of them is left to send.
+///
+/// **Returns**
+/// - An empty [`Replay`] if there is no file.
+pub(crate) fn load(config: &PersistConfig, now: Timestamp) -> io::Result<Replay> {
+    let file = match File::open(&config.path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Replay::default()),
+        Err(e) => return Err(e),
+    };
+    let max_age = i64::try_from(config.max_age.as_millis()).unwrap_or(i64::MAX);
+    let mut replay = Replay::default();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match config.decode(line.as_bytes()) {
+            Some(record) if now.timestamp_millis().saturating_sub(record.timestamp.timestamp_millis()) > max_age => {
+                replay.expired += 1;
+            }
+            Some(record) => replay.records.push(record),
+            None => replay.invalid += 1,
+        }
+    }
+    if replay.records.is_empty() {
+        remove(&config.path)?;
+    }
+    Ok(replay)
+}
+
+/// Delete the backlog file once its records were delivered.
+pub(crate) fn remove(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,322 @@
+//! Detection of the container and cloud instance a process runs in.
+//!
+//! [`detect`] runs the sources enabled in [`PlatformDetection`] once, when
+//! the layer is created (see [`crate::resource::ResourceInfo::detect`]),
+//! and returns resource attributes named after the OpenTelemetry semantic
+//! conventions, which the layer stamps onto every record. Metadata
+//! endpoints are queried in parallel with a short timeout each, so on a
+//! machine without them detection delays startup by about one timeout.
+//! Sources that find nothing add nothing.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::{self, Read, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+/// Which sources [`detect`] queries; all are off by default, since the
+/// endpoint sources cost up to `timeout_ms` of startup time when absent.
+///
+/// **Fields**
+/// - `docker`: container id from `/proc/self/cgroup` or, with cgroup v2,
+///   `/proc/self/mountinfo` (Docker, containerd, CRI-O, Kubernetes pods):
+///   `container.id`.
+/// - `ecs`: ECS task metadata endpoint v4 (or v3) from
+///   `ECS_CONTAINER_METADATA_URI_V4`: `aws.ecs.task.arn`,
+///   `aws.ecs.cluster.arn`, `aws.ecs.launchtype` and
+///   `cloud.availability_zone`, with `cloud.platform` `aws_ecs`.
+/// - `ec2`: EC2 instance metadata (IMDSv2, falling back to v1):
+///   `host.id` (the instance id) and `cloud.availability_zone`, with
+///   `cloud.platform` `aws_ec2`.
+/// - `gce`: Compute Engine metadata server: `host.id` and
+///   `cloud.availability_zone`, with `cloud.platform`
+///   `gcp_compute_engine`.
+/// - `timeout_ms`: bound on connecting to an endpoint and on each read
+///   from it. Default 200.
+///
+/// The cloud sources also set `cloud.provider` (`aws` or `gcp`); with both
+/// `ecs` and `ec2` on an EC2-backed ECS task, `cloud.platform` is `aws_ecs`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlatformDetection {
+    pub docker: bool,
+    pub ecs: bool,
+    pub ec2: bool,
+    pub gce: bool,
+    pub timeout_ms: u64,
+}
+
+impl Default for PlatformDetection {
+    fn default() -> Self {
+        Self {
+            docker: false,
+            ecs: false,
+            ec2: false,
+            gce: false,
+            timeout_ms: 200,
+        }
+    }
+}
+
+impl PlatformDetection {
+    /// Whether any source is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.docker || self.ecs || self.ec2 || self.gce
+    }
+}
+
+/// Error returned when parsing an unknown detection source.
+#[derive(thiserror::Error, Debug)]
+#[error("unknown platform detection source: {0}")]
+pub struct ParseSourceError(String);
+
+/// Parses from a comma-separated list of the sources to enable, e.g.
+/// `docker,ec2`, keeping the default timeout; `none` or an empty list
+/// enables none.
+impl FromStr for PlatformDetection {
+    type Err = ParseSourceError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut detection = PlatformDetection::default();
+        for source in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match source.to_ascii_lowercase().as_str() {
+                "docker" => detection.docker = true,
+                "ecs" => detection.ecs = true,
+                "ec2" => detection.ec2 = true,
+                "gce" => detection.gce = true,
+                "none" => {}
+                _ => return Err(ParseSourceError(source.to_string())),
+            }
+        }
+        Ok(detection)
+    }
+}
+
+type Attributes = BTreeMap<String, String>;
+
+/// Query the sources enabled in `config`, blocking until all of them
+/// have answered or timed out.
+///
+/// **Returns**
+/// - The detected attributes; empty if nothing was found, no source is
+///   enabled, or on `wasm32`.
+pub fn detect(config: &PlatformDetection) -> Attributes {
+    #[cfg(not(target_arch = "wasm32"))]
+    if config.is_enabled() {
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        return std::thread::scope(|scope| {
+            // Later sources win on shared keys, so ECS overrides the
+            // `cloud.platform` of the EC2 instance it runs on.
+            let sources = [
+                config.ec2.then(|| scope.spawn(move || ec2(timeout))),
+                config.gce.then(|| scope.spawn(move || gce(timeout))),
+                config.ecs.then(|| scope.spawn(move || ecs(timeout))),
+                config.docker.then(|| scope.spawn(docker)),
+            ];
+            let mut attributes = Attributes::new();
+            for source in sources.into_iter().flatten() {
+                attributes.extend(source.join().unwrap_or_default());
+            }
+            attributes
+        });
+    }
+    #[cfg(target_arch = "wasm32")]
+    let _ = config;
+    Attributes::new()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn docker() -> Attributes {
+    let id = std::fs::read_to_string("/proc/self/cgroup")
+        .ok()
+        .and_then(|cgroup| cgroup.lines().find_map(cgroup_container_id).map(str::to_string))
+        .or_else(|| {
+            let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+            mountinfo.lines().find_map(mount_container_id).map(str::to_string)
+        });
+    id.map(|id| ("container.id".to_string(), id)).into_iter().collect()
+}
+
+/// Container id in a line of `/proc/self/cgroup`, e.g.
+/// `0::/system.slice/docker-<id>.scope` or `12:memory:/kubepods/<pod>/<id>`.
+#[cfg(not(target_arch = "wasm32"))]
+fn cgroup_container_id(line: &str) -> Option<&str> {
+    line.split(['/', ':', '-', '.']).find(|part| is_container_id(part))
+}
+
+/// Container id in a line of `/proc/self/mountinfo`, which with cgroup v2
+/// mounts files such as `/etc/hostname` from
+/// `.../containers/<id>/hostname`; layer directories of the storage
+/// driver have ids of the same shape, so only those paths count.
+#[cfg(not(target_arch = "wasm32"))]
+fn mount_container_id(line: &str) -> Option<&str> {
+    line.split(' ').find_map(|path| {
+        let (_, rest) = path.split_once("/containers/")?;
+        rest.split('/').next().filter(|id| is_container_id(id))
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn is_container_id(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ecs(timeout: Duration) -> Attributes {
+    let mut attributes = Attributes::new();
+    let Some(uri) = ["ECS_CONTAINER_METADATA_URI_V4", "ECS_CONTAINER_METADATA_URI"]
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|v| !v.is_empty()))
+    else {
+        return attributes;
+    };
+    let Some(task) = http_get(&format!("{}/task", uri.trim_end_matches('/')), &[], timeout) else {
+        return attributes;
+    };
+    let Ok(task) = serde_json::from_str::<serde_json::Value>(&task) else {
+        return attributes;
+    };
+    let Some(arn) = task["TaskARN"].as_str() else {
+        return attributes;
+    };
+    attributes.insert("cloud.provider".to_string(), "aws".to_string());
+    attributes.insert("cloud.platform".to_string(), "aws_ecs".to_string());
+    attributes.insert("aws.ecs.task.arn".to_string(), arn.to_string());
+    if let Some(cluster) = task["Cluster"].as_str() {
+        attributes.insert("aws.ecs.cluster.arn".to_string(), cluster.to_string());
+    }
+    if let Some(launch_type) = task["LaunchType"].as_str() {
+        attributes.insert("aws.ecs.launchtype".to_string(), launch_type.to_ascii_lowercase());
+    }
+    if let Some(zone) = task["AvailabilityZone"].as_str() {
+        attributes.insert("cloud.availability_zone".to_string(), zone.to_string());
+    }
+    attributes
+}
+
+/// Link-local address of the EC2 and Compute Engine metadata services.
+#[cfg(not(target_arch = "wasm32"))]
+const METADATA_HOST: &str = "http://169.254.169.254";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn ec2(timeout: Duration) -> Attributes {
+    let mut attributes = Attributes::new();
+    // Give up at once if the service is unreachable, so detection off EC2
+    // costs a single timeout.
+    let Ok(token) = http(
+        "PUT",
+        &format!("{METADATA_HOST}/latest/api/token"),
+        &[("X-aws-ec2-metadata-token-ttl-seconds", "60")],
+        timeout,
+    ) else {
+        return attributes;
+    };
+    let headers: Vec<(&str, &str)> = token.iter().map(|t| ("X-aws-ec2-metadata-token", t.as_str())).collect();
+    let get = |path: &str| http_get(&format!("{METADATA_HOST}/latest/meta-data/{path}"), &headers, timeout);
+    let Some(id) = get("instance-id") else {
+        return attributes;
+    };
+    attributes.insert("cloud.provider".to_string(), "aws".to_string());
+    attributes.insert("cloud.platform".to_string(), "aws_ec2".to_string());
+    attributes.insert("host.id".to_string(), id);
+    if let Some(zone) = get("placement/availability-zone") {
+        attributes.insert("cloud.availability_zone".to_string(), zone);
+    }
+    attributes
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn gce(timeout: Duration) -> Attributes {
+    let mut attributes = Attributes::new();
+    let headers = [("Metadata-Flavor", "Google")];
+    let get = |path: &str| http_get(&format!("{METADATA_HOST}/computeMetadata/v1/instance/{path}"), &headers, timeout);
+    let Some(id) = get("id") else {
+        return attributes;
+    };
+    attributes.insert("cloud.provider".to_string(), "gcp".to_string());
+    attributes.insert("cloud.platform".to_string(), "gcp_compute_engine".to_string());
+    attributes.insert("host.id".to_string(), id);
+    // `projects/<number>/zones/<zone>`
+    if let Some(zone) = get("zone").and_then(|zone| zone.rsplit('/').next().map(str::to_string)) {
+        attributes.insert("cloud.availability_zone".to_string(), zone);
+    }
+    attributes
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn http_get(url: &str, headers: &[(&str, &str)], timeout: Duration) -> Option<String> {
+    http("GET", url, headers, timeout).ok().flatten()
+}
+
+/// Largest metadata response read.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_RESPONSE_BYTES: u64 = 64 * 1024;
+
+/// Minimal blocking HTTP/1.1 request to a plain `http://` URL, as the
+/// metadata endpoints are link-local and unencrypted.
+///
+/// **Returns**
+/// - The trimmed body of a `200` response; `Ok(None)` for any other
+///   status or a malformed response.
+/// - `Err(..)` if the endpoint could not be reached or timed out.
+#[cfg(not(target_arch = "wasm32"))]
+fn http(method: &str, url: &str, headers: &[(&str, &str)], timeout: Duration) -> io::Result<Option<String>> {
+    let Some(rest) = url.strip_prefix("http://") else { return Ok(None) };
+    let (authority, path) = rest.find('/').map_or((rest, "/"), |i| (&rest[..i], &rest[i..]));
+    let address = if authority.contains(':') { authority.to_string() } else { format!("{authority}:80") };
+    let Some(address) = address.to_socket_addrs()?.next() else { return Ok(None) };
+    let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut request = format!("{method} {path} HTTP/1.1\r\nHost: {authority}\r\nConnection: close\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    if method != "GET" {
+        request.push_str("Content-Length: 0\r\n");
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes())?;
+
+    let mut response = Vec::new();
+    stream.take(MAX_RESPONSE_BYTES).read_to_end(&mut response)?;
+    Ok(response_body(&response))
+}
+
+/// Body of a `200` response.
+#[cfg(not(target_arch = "wasm32"))]
+fn response_body(response: &[u8]) -> Option<String> {
+    let response = std::str::from_utf8(response).ok()?;
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    let mut lines = head.lines();
+    if lines.next()?.split(' ').nth(1)? != "200" {
+        return None;
+    }
+    let chunked = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.eq_ignore_ascii_case("transfer-encoding") && value.trim().eq_ignore_ascii_case("chunked")
+        })
+    });
+    let body = if chunked { dechunk(body)? } else { body.to_string() };
+    Some(body.trim().to_string())
+}
+
+/// Body of a `Transfer-Encoding: chunked` response.
+#[cfg(not(target_arch = "wasm32"))]
+fn dechunk(mut body: &str) -> Option<String> {
+    let mut out = String::new();
+    loop {
+        let (size, rest) = body.split_once("\r\n")?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(out);
+        }
+        out.push_str(rest.get(..size)?);
+        body = rest.get(size..)?.strip_prefix("\r\n")?;
+    }
+}
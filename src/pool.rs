@@ -0,0 +1,80 @@
+use crate::record::LogRecord;
+use std::borrow::Cow;
+use std::sync::Mutex;
+
+/// Batch buffers kept for reuse; one per in-flight batch is the common
+/// case, a few more absorb bursts.
+const MAX_POOLED_BATCHES: usize = 8;
+
+/// Recycles sent records and batch buffers from the worker back to the
+/// layer, see [`crate::init::LayerConfig::record_pool_size`].
+///
+/// After a batch is sent the worker returns it here: the buffer is kept
+/// for the next batch and up to `capacity` records are kept with their
+/// host, thread and resource strings, which the layer overwrites in place
+/// for the next event instead of allocating new ones, and their emptied
+/// field storage, which the layer collects the next event's fields into.
+/// Everything else a record owns (message, trace ids, ...) is freed on
+/// return; `target`, `module_path` and `file` borrow `'static` strings
+/// and own nothing to reuse.
+#[derive(Debug)]
+pub(crate) struct RecordPool {
+    capacity: usize,
+    records: Mutex<Vec<LogRecord>>,
+    batches: Mutex<Vec<Vec<LogRecord>>>,
+}
+
+impl RecordPool {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(Vec::with_capacity(capacity)),
+            batches: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// A recycled record, if one is available without waiting: producers
+    /// never block on the pool and allocate a fresh record instead.
+    pub(crate) fn take(&self) -> Option<LogRecord> {
+        self.records.try_lock().ok()?.pop()
+    }
+
+    /// An empty batch buffer with room for at least `len` records.
+    pub(crate) fn batch(&self, len: usize) -> Vec<LogRecord> {
+        let mut batch = self.batches.lock().ok().and_then(|mut batches| batches.pop()).unwrap_or_default();
+        batch.reserve(len);
+        batch
+    }
+
+    /// Return the records of a sent batch and its buffer to the pool.
+    pub(crate) fn recycle(&self, mut batch: Vec<LogRecord>) {
+        // Free the records' contents before taking the lock that
+        // producers try to acquire, and take it only once, so concurrent
+        // recycles cannot grow the pool past `capacity`.
+        batch.iter_mut().for_each(reset);
+        if let Ok(mut records) = self.records.lock() {
+            let room = self.capacity.saturating_sub(records.len()).min(batch.len());
+            records.extend(batch.drain(..room));
+        }
+        batch.clear();
+        if let Ok(mut batches) = self.batches.lock() {
+            if batches.len() < MAX_POOLED_BATCHES {
+                batches.push(batch);
+            }
+        }
+    }
+}
+
+/// Drop everything of `record` that is not reused by the layer.
+fn reset(record: &mut LogRecord) {
+    record.target = Cow::Borrowed("");
+    record.module_path = None;
+    record.file = None;
+    record.fields.clear();
+    record.message = None;
+    record.service_name = None;
+    record.fingerprint = None;
+    record.trace_id = None;
+    record.span_id = None;
+    record.span_name = None;
+}
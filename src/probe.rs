@@ -0,0 +1,50 @@
+//! Periodic health probes of database sinks.
+//!
+//! A probe runs only while the sink's connection has been idle for a full
+//! interval, so busy sinks pay nothing and an idle one notices a broken
+//! connection before the next record does.
+
+use crate::worker::WorkerScope;
+use std::future::Future;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+/// Time since the sink's connection was last used.
+pub(crate) struct Idle(Mutex<Instant>);
+
+impl Idle {
+    pub(crate) fn new() -> Self {
+        Self(Mutex::new(Instant::now()))
+    }
+
+    /// Mark the connection as used now.
+    pub(crate) fn touch(&self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).elapsed()
+    }
+}
+
+/// Run `probe` with `target` every `interval` on the current Tokio
+/// runtime, until `target` is dropped or `probe` returns `false`.
+pub(crate) fn spawn<T, F, Fut>(target: Weak<T>, interval: Duration, probe: F) -> tokio::task::JoinHandle<()>
+where
+    T: Send + Sync + 'static,
+    F: Fn(Arc<T>) -> Fut + Send + 'static,
+    Fut: Future<Output = bool> + Send,
+{
+    tokio::spawn(WorkerScope::new(async move {
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        ticks.tick().await;
+        loop {
+            ticks.tick().await;
+            let Some(target) = target.upgrade() else { return };
+            if !probe(target).await {
+                return;
+            }
+        }
+    }))
+}
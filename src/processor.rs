@@ -0,0 +1,49 @@
+use crate::record::LogRecord;
+use std::sync::Arc;
+
+/// Result of running a [`RecordProcessor`] over a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessOutcome {
+    /// Pass the (possibly modified) record on to the next processor.
+    Keep,
+    /// Discard the record; later processors and the sink never see it.
+    Drop,
+}
+
+/// Predicate over records for [`LayerConfig::filter`]: `true` keeps the
+/// record, `false` drops it.
+///
+/// [`LayerConfig::filter`]: crate::init::LayerConfig::filter
+pub type RecordFilter = Arc<dyn Fn(&LogRecord) -> bool + Send + Sync>;
+
+/// Hook that can enrich, mutate or drop records before they reach the sink.
+///
+/// Processors are configured as an ordered chain via
+/// [`LayerConfig::processors`] and run inside the background worker, so
+/// they never add latency to the thread that emitted the event. This is the
+/// extension point for custom per-company logic such as tagging, scrubbing
+/// or filtering.
+///
+/// [`LayerConfig::processors`]: crate::init::LayerConfig::processors
+pub trait RecordProcessor: Send + Sync {
+    /// Inspect and optionally modify a single record.
+    ///
+    /// **Parameters**
+    /// - `record`: mutable record produced by the layer.
+    ///
+    /// **Returns**
+    /// - [`ProcessOutcome::Keep`] to continue processing.
+    /// - [`ProcessOutcome::Drop`] to discard the record.
+    fn process(&self, record: &mut LogRecord) -> ProcessOutcome;
+}
+
+/// Run `record` through `processors` in order, stopping at the first
+/// processor that drops it.
+pub fn apply_chain(processors: &[Arc<dyn RecordProcessor>], record: &mut LogRecord) -> ProcessOutcome {
+    for processor in processors {
+        if processor.process(record) == ProcessOutcome::Drop {
+            return ProcessOutcome::Drop;
+        }
+    }
+    ProcessOutcome::Keep
+}
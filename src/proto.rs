@@ -0,0 +1,263 @@
+//! Protobuf representation of [`LogRecord`] (feature `proto`).
+//!
+//! [`v1`] holds the message types for `proto/log_record.proto`, the
+//! canonical contract for non-Rust consumers. They are checked in, rather
+//! than generated by a build script, so building the crate does not need
+//! `protoc`; regenerate them with `prost-build` whenever the `.proto`
+//! changes.
+//!
+//! Sinks use the conversions below, or [`PayloadEncoding::Protobuf`].
+//!
+//! [`PayloadEncoding::Protobuf`]: crate::encoding::PayloadEncoding::Protobuf
+
+use crate::record::{LogLevel, LogRecord};
+use crate::timestamp::Timestamp;
+use prost::Message;
+use uuid::Uuid;
+
+/// Types generated from `proto/log_record.proto` (package
+/// `tracing_log_sink.v1`).
+pub mod v1 {
+    /// Log level, numbered like OpenTelemetry `SeverityNumber`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Level {
+        Unspecified = 0,
+        Trace = 1,
+        Debug = 5,
+        Info = 9,
+        Warn = 13,
+        Error = 17,
+    }
+
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct LogRecord {
+        #[prost(uint32, tag = "1")]
+        pub schema_version: u32,
+        /// UTC timestamp as nanoseconds since the Unix epoch.
+        #[prost(int64, tag = "2")]
+        pub timestamp_unix_nanos: i64,
+        #[prost(enumeration = "Level", tag = "3")]
+        pub level: i32,
+        #[prost(string, tag = "4")]
+        pub target: ::prost::alloc::string::String,
+        #[prost(string, optional, tag = "5")]
+        pub module_path: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "6")]
+        pub file: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(uint32, optional, tag = "7")]
+        pub line: ::core::option::Option<u32>,
+        /// Structured fields; each value is its JSON encoding.
+        #[prost(btree_map = "string, string", tag = "8")]
+        pub fields: ::prost::alloc::collections::BTreeMap<
+            ::prost::alloc::string::String,
+            ::prost::alloc::string::String,
+        >,
+        #[prost(string, optional, tag = "9")]
+        pub message: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "10")]
+        pub service_name: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "11")]
+        pub hostname: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(uint32, optional, tag = "12")]
+        pub pid: ::core::option::Option<u32>,
+        #[prost(string, optional, tag = "13")]
+        pub thread_id: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "14")]
+        pub thread_name: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(btree_map = "string, string", tag = "15")]
+        pub resource: ::prost::alloc::collections::BTreeMap<
+            ::prost::alloc::string::String,
+            ::prost::alloc::string::String,
+        >,
+        /// UUIDv7 as its 16 raw bytes.
+        #[prost(bytes = "vec", optional, tag = "16")]
+        pub event_id: ::core::option::Option<::prost::alloc::vec::Vec<u8>>,
+        #[prost(uint64, optional, tag = "17")]
+        pub repeat_count: ::core::option::Option<u64>,
+        #[prost(string, optional, tag = "18")]
+        pub fingerprint: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "19")]
+        pub trace_id: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "20")]
+        pub span_id: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "21")]
+        pub span_name: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(uint64, optional, tag = "22")]
+        pub duration_ms: ::core::option::Option<u64>,
+        #[prost(uint64, optional, tag = "23")]
+        pub span_elapsed_ms: ::core::option::Option<u64>,
+        #[prost(string, optional, tag = "24")]
+        pub error_kind: ::core::option::Option<::prost::alloc::string::String>,
+        #[prost(string, optional, tag = "25")]
+        pub error_message: ::core::option::Option<::prost::alloc::string::String>,
+    }
+}
+
+/// Error converting a protobuf message back into a [`LogRecord`].
+#[derive(thiserror::Error, Debug)]
+pub enum ProtoError {
+    #[error("failed to decode protobuf log record: {0}")]
+    Decode(#[from] prost::DecodeError),
+
+    #[error("unknown log level {0}")]
+    InvalidLevel(i32),
+
+    #[error("event_id must be 16 bytes, got {0}")]
+    InvalidEventId(usize),
+
+    #[error("field {key} is not valid JSON: {source}")]
+    InvalidField {
+        key: String,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+impl From<LogLevel> for v1::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => v1::Level::Trace,
+            LogLevel::Debug => v1::Level::Debug,
+            LogLevel::Info => v1::Level::Info,
+            LogLevel::Warn => v1::Level::Warn,
+            LogLevel::Error => v1::Level::Error,
+        }
+    }
+}
+
+impl From<&LogRecord> for v1::LogRecord {
+    /// Timestamps outside the range representable in `i64` nanoseconds
+    /// (years 1677..2262) saturate at the respective bound.
+    fn from(record: &LogRecord) -> Self {
+        // Exhaustive destructuring, as in `LogRecord::to_json_for_version`:
+        // a new `LogRecord` field must also be added to the `.proto`.
+        let LogRecord {
+            schema_version,
+            timestamp,
+            level,
+            target,
+            module_path,
+            file,
+            line,
+            fields,
+            message,
+            service_name,
+            hostname,
+            pid,
+            thread_id,
+            thread_name,
+            resource,
+            event_id,
+            repeat_count,
+            fingerprint,
+            trace_id,
+            span_id,
+            span_name,
+            duration_ms,
+            span_elapsed_ms,
+            error_kind,
+            error_message,
+        } = record;
+
+        let timestamp_unix_nanos = timestamp.timestamp_nanos_opt().unwrap_or(if timestamp.timestamp() < 0 {
+            i64::MIN
+        } else {
+            i64::MAX
+        });
+
+        v1::LogRecord {
+            schema_version: *schema_version,
+            timestamp_unix_nanos,
+            level: v1::Level::from(*level) as i32,
+            target: target.to_string(),
+            module_path: module_path.as_deref().map(str::to_string),
+            file: file.as_deref().map(str::to_string),
+            line: *line,
+            fields: fields.iter().map(|(k, v)| (k.clone(), v.to_string())).collect(),
+            message: message.clone(),
+            service_name: service_name.clone(),
+            hostname: hostname.clone(),
+            pid: *pid,
+            thread_id: thread_id.clone(),
+            thread_name: thread_name.clone(),
+            resource: resource.clone(),
+            event_id: event_id.map(|id| id.as_bytes().to_vec()),
+            repeat_count: *repeat_count,
+            fingerprint: fingerprint.clone(),
+            trace_id: trace_id.clone(),
+            span_id: span_id.clone(),
+            span_name: span_name.clone(),
+            duration_ms: *duration_ms,
+            span_elapsed_ms: *span_elapsed_ms,
+            error_kind: error_kind.clone(),
+            error_message: error_message.clone(),
+        }
+    }
+}
+
+impl TryFrom<v1::LogRecord> for LogRecord {
+    type Error = ProtoError;
+
+    fn try_from(proto: v1::LogRecord) -> Result<Self, ProtoError> {
+        let level = match v1::Level::try_from(proto.level) {
+            Ok(v1::Level::Trace) => LogLevel::Trace,
+            Ok(v1::Level::Debug) => LogLevel::Debug,
+            Ok(v1::Level::Info) => LogLevel::Info,
+            Ok(v1::Level::Warn) => LogLevel::Warn,
+            Ok(v1::Level::Error) => LogLevel::Error,
+            Ok(v1::Level::Unspecified) | Err(_) => return Err(ProtoError::InvalidLevel(proto.level)),
+        };
+        let event_id = proto
+            .event_id
+            .map(|bytes| Uuid::from_slice(&bytes).map_err(|_| ProtoError::InvalidEventId(bytes.len())))
+            .transpose()?;
+        let fields = proto
+            .fields
+            .into_iter()
+            .map(|(key, value)| match serde_json::from_str(&value) {
+                Ok(value) => Ok((key, value)),
+                Err(source) => Err(ProtoError::InvalidField { key, source }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(LogRecord {
+            // Messages from producers that predate versioning leave it at 0.
+            schema_version: proto.schema_version.max(1),
+            timestamp: Timestamp::from_timestamp_nanos(proto.timestamp_unix_nanos),
+            level,
+            target: proto.target.into(),
+            module_path: proto.module_path.map(Into::into),
+            file: proto.file.map(Into::into),
+            line: proto.line,
+            fields,
+            message: proto.message,
+            service_name: proto.service_name,
+            hostname: proto.hostname,
+            pid: proto.pid,
+            thread_id: proto.thread_id,
+            thread_name: proto.thread_name,
+            resource: proto.resource,
+            event_id,
+            repeat_count: proto.repeat_count,
+            fingerprint: proto.fingerprint,
+            trace_id: proto.trace_id,
+            span_id: proto.span_id,
+            span_name: proto.span_name,
+            duration_ms: proto.duration_ms,
+            span_elapsed_ms: proto.span_elapsed_ms,
+            error_kind: proto.error_kind,
+            error_message: proto.error_message,
+        })
+    }
+}
+
+/// Encode `record` as a `tracing_log_sink.v1.LogRecord` message.
+pub fn encode(record: &LogRecord) -> Vec<u8> {
+    v1::LogRecord::from(record).encode_to_vec()
+}
+
+/// Decode a `tracing_log_sink.v1.LogRecord` message produced by [`encode`].
+pub fn decode(payload: &[u8]) -> Result<LogRecord, ProtoError> {
+    LogRecord::try_from(v1::LogRecord::decode(payload)?)
+}
@@ -0,0 +1,353 @@
+//! Routing of HTTP sink requests through the proxy of [`HttpProxy`], and,
+//! with `hyper-client`, the connector that talks to it.
+
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::http::HttpProxy;
+use crate::tls::TlsError;
+use std::net::IpAddr;
+
+/// Where the requests of one client go.
+#[derive(Debug)]
+pub(crate) struct ProxyRoute {
+    http: Option<ProxyServer>,
+    https: Option<ProxyServer>,
+    no_proxy: NoProxy,
+}
+
+/// An `http://` proxy.
+#[derive(Debug, Clone)]
+pub(crate) struct ProxyServer {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl ProxyRoute {
+    /// The route of `proxy`; `None` if every request goes directly.
+    pub(crate) fn new(proxy: &HttpProxy) -> Result<Option<Self>, TlsError> {
+        match proxy {
+            HttpProxy::Direct => Ok(None),
+            HttpProxy::FromEnv => Ok(Self::from_env()),
+            HttpProxy::Url { url, username, password, no_proxy } => {
+                let server = ProxyServer::parse(url, username.as_deref(), password.as_deref())?;
+                Ok(Some(Self {
+                    http: Some(server.clone()),
+                    https: Some(server),
+                    no_proxy: NoProxy::parse(no_proxy.iter().map(String::as_str)),
+                }))
+            }
+        }
+    }
+
+    /// `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY` and `NO_PROXY`, lowercase
+    /// names first. Invalid values are reported and ignored, as a proxy
+    /// set for other programs must not stop the sink from starting.
+    fn from_env() -> Option<Self> {
+        let var = |name: &str| {
+            std::env::var(name.to_ascii_lowercase())
+                .or_else(|_| std::env::var(name))
+                .ok()
+                .filter(|value| !value.trim().is_empty())
+        };
+        let server = |name: &str| {
+            let value = var(name).or_else(|| var("ALL_PROXY"))?;
+            ProxyServer::parse(value.trim(), None, None)
+                .inspect_err(|e| tracing::warn!(target: DIAGNOSTICS_TARGET, error = %e, "ignoring {}", name))
+                .ok()
+        };
+        let (http, https) = (server("HTTP_PROXY"), server("HTTPS_PROXY"));
+        if http.is_none() && https.is_none() {
+            return None;
+        }
+        let no_proxy = var("NO_PROXY").unwrap_or_default();
+        Some(Self { http, https, no_proxy: NoProxy::parse(no_proxy.split(',')) })
+    }
+
+    /// The proxy for a request to `host` over `scheme`, if any.
+    pub(crate) fn server_for(&self, scheme: &str, host: &str) -> Option<&ProxyServer> {
+        let server = if scheme.eq_ignore_ascii_case("https") { &self.https } else { &self.http };
+        server.as_ref().filter(|_| !self.no_proxy.matches(host))
+    }
+}
+
+impl ProxyServer {
+    /// Parse `http://[user[:password]@]host[:port]`; the scheme may be
+    /// omitted. `username` and `password` take precedence over the URL's.
+    fn parse(url: &str, username: Option<&str>, password: Option<&str>) -> Result<Self, TlsError> {
+        let error = |reason: String| TlsError::Proxy { url: without_userinfo(url), reason };
+        let rest = match url.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("http") => rest,
+            Some((scheme, _)) => return Err(error(format!("unsupported scheme {scheme:?}, expected http"))),
+            None => url,
+        };
+        let authority = rest.split('/').next().unwrap_or_default();
+        let (userinfo, host_port) = match authority.rsplit_once('@') {
+            Some((userinfo, host_port)) => (Some(userinfo), host_port),
+            None => (None, authority),
+        };
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => {
+                (host, port.parse().map_err(|_| error(format!("invalid port {port:?}")))?)
+            }
+            _ => (host_port, 80),
+        };
+        if host.is_empty() {
+            return Err(error("no host".to_string()));
+        }
+
+        let decode = |s: &str| urlencoding::decode(s).map(|d| d.into_owned()).unwrap_or_else(|_| s.to_string());
+        let (url_username, url_password) = match userinfo.map(|u| u.split_once(':').unwrap_or((u, ""))) {
+            Some((username, password)) => (Some(decode(username)), Some(decode(password))),
+            None => (None, None),
+        };
+        let credentials = username.map(str::to_string).or(url_username).map(|username| {
+            let password = password.map(str::to_string).or(url_password).unwrap_or_default();
+            (username, password)
+        });
+        Ok(Self { host: host.to_string(), port, credentials })
+    }
+
+    /// `http://[user:password@]host:port`, the form reqwest takes.
+    #[cfg(not(any(feature = "hyper-client", target_arch = "wasm32")))]
+    pub(crate) fn url(&self) -> String {
+        match &self.credentials {
+            Some((username, password)) => format!(
+                "http://{}:{}@{}:{}",
+                urlencoding::encode(username),
+                urlencoding::encode(password),
+                self.host,
+                self.port
+            ),
+            None => format!("http://{}:{}", self.host, self.port),
+        }
+    }
+
+    /// Value of the `Proxy-Authorization` header.
+    #[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+    pub(crate) fn authorization(&self) -> Option<String> {
+        use base64::Engine;
+
+        let (username, password) = self.credentials.as_ref()?;
+        let encoded = base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        Some(format!("Basic {encoded}"))
+    }
+}
+
+/// `url` without `user:password@`, for error messages.
+fn without_userinfo(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").map_or(("", url), |(scheme, rest)| (scheme, rest));
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let rest = match rest[..authority_end].rfind('@') {
+        Some(at) => &rest[at + 1..],
+        None => rest,
+    };
+    if scheme.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{scheme}://{rest}")
+    }
+}
+
+/// Hosts reached without the proxy.
+#[derive(Debug, Default)]
+struct NoProxy {
+    all: bool,
+    domains: Vec<String>,
+    networks: Vec<(IpAddr, u8)>,
+}
+
+impl NoProxy {
+    fn parse<'a>(entries: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut no_proxy = Self::default();
+        for entry in entries.into_iter().map(str::trim).filter(|e| !e.is_empty()) {
+            if entry == "*" {
+                no_proxy.all = true;
+            } else if let Some((ip, bits)) = entry.split_once('/') {
+                match (parse_ip(ip), bits.parse::<u8>()) {
+                    (Some(ip), Ok(bits)) => no_proxy.networks.push((ip, bits)),
+                    _ => tracing::warn!(target: DIAGNOSTICS_TARGET, "ignoring invalid no-proxy entry {:?}", entry),
+                }
+            } else if let Some(ip) = parse_ip(entry) {
+                no_proxy.networks.push((ip, if ip.is_ipv4() { 32 } else { 128 }));
+            } else {
+                let domain = entry.rsplit_once(':').map_or(entry, |(domain, _)| domain);
+                let domain = domain.trim_start_matches("*.").trim_start_matches('.');
+                no_proxy.domains.push(domain.to_ascii_lowercase());
+            }
+        }
+        no_proxy
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        if self.all {
+            return true;
+        }
+        if let Some(ip) = parse_ip(host) {
+            return self.networks.iter().any(|(network, bits)| contains(*network, *bits, ip));
+        }
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.domains.iter().any(|domain| {
+            host == *domain || (host.len() > domain.len() && host.ends_with(domain.as_str()) && {
+                host.as_bytes()[host.len() - domain.len() - 1] == b'.'
+            })
+        })
+    }
+}
+
+/// An IP address, possibly in brackets or with a port.
+fn parse_ip(s: &str) -> Option<IpAddr> {
+    if let Ok(ip) = s.trim_start_matches('[').trim_end_matches(']').parse() {
+        return Some(ip);
+    }
+    let (host, _port) = s.rsplit_once(':')?;
+    host.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+fn contains(network: IpAddr, bits: u8, ip: IpAddr) -> bool {
+    match (network, ip) {
+        (IpAddr::V4(network), IpAddr::V4(ip)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(bits.min(32))).unwrap_or(0);
+            u32::from(network) & mask == u32::from(ip) & mask
+        }
+        (IpAddr::V6(network), IpAddr::V6(ip)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(bits.min(128))).unwrap_or(0);
+            u128::from(network) & mask == u128::from(ip) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+pub(crate) use connector::ProxyConnector;
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+mod connector {
+    use super::{ProxyRoute, ProxyServer};
+    use hyper::client::connect::{Connected, Connection, HttpConnector};
+    use hyper::service::Service;
+    use hyper::Uri;
+    use std::future::Future;
+    use std::io;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+    use tokio::net::TcpStream;
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    /// Longest `CONNECT` response header accepted from the proxy.
+    const MAX_CONNECT_RESPONSE: usize = 16 * 1024;
+
+    /// TCP connector that opens connections to proxied hosts at the proxy:
+    /// a `CONNECT` tunnel for `https`, which the TLS connector wrapping
+    /// this one then secures end to end, and a connection marked as
+    /// proxied for `http`, to which hyper sends absolute-form requests.
+    #[derive(Clone)]
+    pub(crate) struct ProxyConnector {
+        http: HttpConnector,
+        route: Option<Arc<ProxyRoute>>,
+    }
+
+    impl ProxyConnector {
+        pub(crate) fn new(http: HttpConnector, route: Option<Arc<ProxyRoute>>) -> Self {
+            Self { http, route }
+        }
+    }
+
+    impl Service<Uri> for ProxyConnector {
+        type Response = ProxyStream;
+        type Error = BoxError;
+        type Future = Pin<Box<dyn Future<Output = Result<ProxyStream, BoxError>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+            self.http.poll_ready(cx).map_err(Into::into)
+        }
+
+        fn call(&mut self, dst: Uri) -> Self::Future {
+            let https = dst.scheme_str() == Some("https");
+            let server = self
+                .route
+                .as_ref()
+                .and_then(|route| route.server_for(dst.scheme_str().unwrap_or("http"), dst.host().unwrap_or_default()))
+                .cloned();
+            let mut http = self.http.clone();
+            Box::pin(async move {
+                let Some(server) = server else {
+                    return Ok(ProxyStream { stream: http.call(dst).await?, proxied: false });
+                };
+                let proxy: Uri = format!("http://{}:{}", server.host, server.port).parse()?;
+                let mut stream = http.call(proxy).await?;
+                if https {
+                    tunnel(&mut stream, &dst, &server).await?;
+                }
+                Ok(ProxyStream { stream, proxied: !https })
+            })
+        }
+    }
+
+    /// Ask the proxy on `stream` for a tunnel to the host of `dst`.
+    async fn tunnel(stream: &mut TcpStream, dst: &Uri, server: &ProxyServer) -> Result<(), BoxError> {
+        let authority = format!("{}:{}", dst.host().unwrap_or_default(), dst.port_u16().unwrap_or(443));
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some(authorization) = server.authorization() {
+            request.push_str(&format!("Proxy-Authorization: {authorization}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+
+        // The server sends nothing after the header before the TLS
+        // handshake starts, so reading past it loses no data.
+        let mut response = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !response.windows(4).any(|w| w == b"\r\n\r\n") {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                return Err(format!("proxy closed the connection on CONNECT {authority}").into());
+            }
+            response.extend_from_slice(&buf[..n]);
+            if response.len() > MAX_CONNECT_RESPONSE {
+                return Err(format!("proxy response to CONNECT {authority} is too large").into());
+            }
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines().next().unwrap_or_default();
+        let status = status_line.split_whitespace().nth(1).and_then(|s| s.parse::<u16>().ok());
+        match status {
+            Some(200..=299) => Ok(()),
+            _ => Err(format!("proxy refused CONNECT {authority}: {status_line}").into()),
+        }
+    }
+
+    /// Connection opened by [`ProxyConnector`].
+    pub(crate) struct ProxyStream {
+        stream: TcpStream,
+        proxied: bool,
+    }
+
+    impl Connection for ProxyStream {
+        fn connected(&self) -> Connected {
+            self.stream.connected().proxy(self.proxied)
+        }
+    }
+
+    impl AsyncRead for ProxyStream {
+        fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for ProxyStream {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.stream).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_shutdown(cx)
+        }
+    }
+}
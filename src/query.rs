@@ -0,0 +1,234 @@
+//! Reading shipped records back from ClickHouse or Postgres.
+//!
+//! [`LogReader`] runs simple filtered queries against the table a
+//! DSN-built sink writes to; the `logrec` CLI (feature `cli`) is built on
+//! it.
+
+use crate::backend::{self, BackendKind, DsnError};
+use crate::record::{LogLevel, LogRecord};
+use crate::timestamp::Timestamp;
+use std::error::Error;
+
+/// Filter of [`LogReader::query`].
+///
+/// **Fields**
+/// - `service`: exact `service_name`.
+/// - `min_level`: least severe level included, e.g. [`LogLevel::Warn`]
+///   for warnings and errors.
+/// - `since`: records at or after this time.
+/// - `target`: `target` prefix, e.g. `"my_app::db"`.
+/// - `limit`: the newest this many matching records are returned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogQuery {
+    pub service: Option<String>,
+    pub min_level: Option<LogLevel>,
+    pub since: Option<Timestamp>,
+    pub target: Option<String>,
+    pub limit: usize,
+}
+
+impl Default for LogQuery {
+    fn default() -> Self {
+        Self {
+            service: None,
+            min_level: None,
+            since: None,
+            target: None,
+            limit: 100,
+        }
+    }
+}
+
+impl LogQuery {
+    /// Names of the levels `min_level` admits.
+    fn levels(&self) -> Option<Vec<&'static str>> {
+        let min = self.min_level?;
+        let all = [LogLevel::Trace, LogLevel::Debug, LogLevel::Info, LogLevel::Warn, LogLevel::Error];
+        Some(all.iter().filter(|level| **level >= min).map(LogLevel::as_str).collect())
+    }
+}
+
+/// Error returned by [`LogReader`].
+#[derive(thiserror::Error, Debug)]
+pub enum QueryError {
+    #[error(transparent)]
+    Dsn(#[from] DsnError),
+
+    #[error("querying {0:?} backends is not supported")]
+    Unsupported(BackendKind),
+
+    #[error("cannot query templated table {0:?}")]
+    TemplatedTable(String),
+
+    #[error("query failed: {0}")]
+    Request(Box<dyn Error + Send + Sync>),
+
+    #[error("unreadable row: {0}")]
+    Row(#[from] serde_json::Error),
+}
+
+/// Reads records from the table of a `clickhouse://` or `postgres://`
+/// DSN, as written by the sink built from the same DSN.
+///
+/// ClickHouse tables are read with their columns as in
+/// `migrations/clickhouse`; `service` filters need the `service_name`
+/// column of shared tables. Postgres tables are read from the `record`
+/// JSONB column.
+pub enum LogReader {
+    #[cfg(feature = "clickhouse")]
+    ClickHouse {
+        client: crate::http::HttpTransport,
+        config: Box<crate::clickhouse::ClickHouseConfig>,
+    },
+    #[cfg(feature = "postgres")]
+    Postgres { client: tokio_postgres::Client, table: String },
+}
+
+impl LogReader {
+    /// Connect to the backend of `dsn`.
+    pub async fn connect(dsn: &str) -> Result<Self, QueryError> {
+        let config = backend::parse_dsn(dsn)?;
+        match config.kind {
+            #[cfg(feature = "clickhouse")]
+            BackendKind::Clickhouse => {
+                let mut config = backend::clickhouse_config(&config);
+                if crate::destination::DestinationTemplate::new(&config.table).is_templated() {
+                    return Err(QueryError::TemplatedTable(config.table));
+                }
+                let (client, url) = crate::http::HttpTransport::new(&config.url, config.timeout, &config.http, &config.tls)
+                    .map_err(|e| QueryError::Request(Box::new(e)))?;
+                config.url = url;
+                Ok(LogReader::ClickHouse { client, config: Box::new(config) })
+            }
+            #[cfg(feature = "postgres")]
+            BackendKind::Postgres => {
+                let client = crate::postgres::open(&config.dsn)
+                    .await
+                    .map_err(|e| QueryError::Request(Box::new(e)))?;
+                let table = config.options.table.unwrap_or_else(|| "logs".to_string());
+                Ok(LogReader::Postgres { client, table })
+            }
+            kind => Err(QueryError::Unsupported(kind)),
+        }
+    }
+
+    /// Run `query`.
+    ///
+    /// **Returns**
+    /// - The newest `query.limit` matching records, oldest first.
+    pub async fn query(&self, query: &LogQuery) -> Result<Vec<LogRecord>, QueryError> {
+        match self {
+            #[cfg(feature = "clickhouse")]
+            LogReader::ClickHouse { client, config } => query_clickhouse(client, config, query).await,
+            #[cfg(feature = "postgres")]
+            LogReader::Postgres { client, table } => query_postgres(client, table, query).await,
+        }
+    }
+}
+
+#[cfg(feature = "clickhouse")]
+async fn query_clickhouse(
+    client: &crate::http::HttpTransport,
+    config: &crate::clickhouse::ClickHouseConfig,
+    query: &LogQuery,
+) -> Result<Vec<LogRecord>, QueryError> {
+    // `timestamp` may be an RFC 3339 string or a DateTime64 column.
+    const TIME: &str = "parseDateTime64BestEffortOrZero(toString(timestamp), 6, 'UTC')";
+    let mut params = vec![
+        ("database", config.database.clone()),
+        ("output_format_json_quote_64bit_integers", "0".to_string()),
+        ("date_time_output_format", "iso".to_string()),
+    ];
+    if let Some(user) = &config.user {
+        params.push(("user", user.clone()));
+    }
+    if let Some(password) = &config.password {
+        params.push(("password", password.clone()));
+    }
+    let mut sql = format!("SELECT * FROM {}.{} WHERE 1", config.database, config.table);
+    if let Some(service) = &query.service {
+        sql.push_str(" AND service_name = {service:String}");
+        params.push(("param_service", service.clone()));
+    }
+    if let Some(levels) = query.levels() {
+        sql.push_str(" AND level IN {levels:Array(String)}");
+        let quoted: Vec<String> = levels.iter().map(|level| format!("'{}'", level)).collect();
+        params.push(("param_levels", format!("[{}]", quoted.join(","))));
+    }
+    if let Some(since) = &query.since {
+        sql.push_str(&format!(" AND {} >= parseDateTime64BestEffort({{since:String}}, 6, 'UTC')", TIME));
+        params.push(("param_since", since.to_rfc3339()));
+    }
+    if let Some(target) = &query.target {
+        sql.push_str(" AND startsWith(target, {target:String})");
+        params.push(("param_target", target.clone()));
+    }
+    sql.push_str(&format!(" ORDER BY {} DESC LIMIT {} FORMAT JSONEachRow", TIME, query.limit));
+    params.push(("query", sql));
+
+    let query_string: Vec<String> = params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, urlencoding::encode(value)))
+        .collect();
+    let url = format!("{}/?{}", config.url, query_string.join("&"));
+    let resp = client
+        .send(crate::http::HttpRequest::get(url))
+        .await
+        .map_err(|e| QueryError::Request(Box::new(e)))?;
+    let body = resp.text();
+    if !resp.is_success() {
+        return Err(QueryError::Request(format!("ClickHouse query failed with status {}: {}", resp.status, body).into()));
+    }
+    let mut records = Vec::new();
+    for line in body.lines().filter(|l| !l.trim().is_empty()) {
+        let mut row: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)?;
+        // `fields` is stored as a JSON string.
+        if let Some(serde_json::Value::String(fields)) = row.get("fields") {
+            let fields = serde_json::from_str(fields)?;
+            row.insert("fields".to_string(), fields);
+        }
+        records.push(serde_json::from_value(serde_json::Value::Object(row))?);
+    }
+    records.reverse();
+    Ok(records)
+}
+
+#[cfg(feature = "postgres")]
+async fn query_postgres(
+    client: &tokio_postgres::Client,
+    table: &str,
+    query: &LogQuery,
+) -> Result<Vec<LogRecord>, QueryError> {
+    use tokio_postgres::types::ToSql;
+
+    const TIME: &str = "(record->>'timestamp')::timestamptz";
+    let levels = query.levels();
+    let since = query.since.map(std::time::SystemTime::from);
+    let mut sql = format!("SELECT record FROM {} WHERE true", table);
+    let mut params: Vec<&(dyn ToSql + Sync)> = Vec::new();
+    if let Some(service) = &query.service {
+        params.push(service);
+        sql.push_str(&format!(" AND record->>'service_name' = ${}", params.len()));
+    }
+    if let Some(levels) = &levels {
+        params.push(levels);
+        sql.push_str(&format!(" AND record->>'level' = ANY(${})", params.len()));
+    }
+    if let Some(since) = &since {
+        params.push(since);
+        sql.push_str(&format!(" AND {} >= ${}", TIME, params.len()));
+    }
+    if let Some(target) = &query.target {
+        params.push(target);
+        sql.push_str(&format!(" AND starts_with(record->>'target', ${})", params.len()));
+    }
+    sql.push_str(&format!(" ORDER BY {} DESC LIMIT {}", TIME, query.limit));
+
+    let rows = client.query(&sql, &params).await.map_err(|e| QueryError::Request(Box::new(e)))?;
+    let mut records = rows
+        .iter()
+        .map(|row| serde_json::from_value(row.get::<_, serde_json::Value>(0)))
+        .collect::<Result<Vec<LogRecord>, _>>()?;
+    records.reverse();
+    Ok(records)
+}
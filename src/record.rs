@@ -1,30 +1,701 @@
-use chrono::{DateTime, Utc};
-use serde::Serialize;
-use std::collections::BTreeMap;
+use crate::fields::Fields;
+use crate::timestamp::{Timestamp, TimestampFormat};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use uuid::Uuid;
+
+/// Current version of the [`LogRecord`] schema.
+///
+/// Evolution policy, enforced by [`SCHEMA_FIELDS`] and the checks below:
+/// - fields are only ever added, never renamed or removed;
+/// - new fields must be optional (`Option<_>` or an empty collection) and
+///   deserialize with a default, so records from older producers still
+///   parse in newer consumers; unknown fields are ignored, so newer
+///   records parse in older consumers;
+/// - every change that adds fields bumps `SCHEMA_VERSION` and registers
+///   the new fields in [`SCHEMA_FIELDS`] with that version, plus a
+///   matching migration under `migrations/`.
+///
+/// Sinks with a fixed table layout (ClickHouse) use this table to write
+/// only the columns that exist at the version the table was created with.
+pub const SCHEMA_VERSION: u32 = 9;
+
+/// Every serialized [`LogRecord`] field together with the schema version
+/// that introduced it.
+pub const SCHEMA_FIELDS: &[(&str, u32)] = &[
+    ("timestamp", 1),
+    ("level", 1),
+    ("target", 1),
+    ("module_path", 1),
+    ("file", 1),
+    ("line", 1),
+    ("fields", 1),
+    ("message", 1),
+    ("service_name", 1),
+    ("schema_version", 2),
+    ("hostname", 2),
+    ("pid", 2),
+    ("thread_id", 2),
+    ("thread_name", 2),
+    ("resource", 2),
+    ("event_id", 3),
+    ("repeat_count", 4),
+    ("fingerprint", 5),
+    ("trace_id", 6),
+    ("span_id", 6),
+    ("span_name", 7),
+    ("duration_ms", 7),
+    ("span_elapsed_ms", 8),
+    ("error_kind", 9),
+    ("error_message", 9),
+];
+
+// `SCHEMA_VERSION` must be bumped together with `SCHEMA_FIELDS`.
+const _: () = {
+    let mut max = 0;
+    let mut i = 0;
+    while i < SCHEMA_FIELDS.len() {
+        if SCHEMA_FIELDS[i].1 > max {
+            max = SCHEMA_FIELDS[i].1;
+        }
+        i += 1;
+    }
+    assert!(max == SCHEMA_VERSION, "SCHEMA_VERSION does not match SCHEMA_FIELDS");
+};
+
+/// Schema version that introduced the field `name`, if it is part of the
+/// schema at all.
+pub fn field_since(name: &str) -> Option<u32> {
+    SCHEMA_FIELDS.iter().find(|(n, _)| *n == name).map(|(_, v)| *v)
+}
+
+/// Severity of a [`LogRecord`].
+///
+/// Serializes to the same upper-case strings that `tracing::Level`
+/// displays as (`"ERROR"`, `"WARN"`, ...), so existing tables and
+/// consumers see no difference from the former `String` field.
+/// Deserialization accepts any casing.
+///
+/// Variants are ordered by severity: `LogLevel::Error > LogLevel::Warn`.
+/// Note that this is the reverse of `tracing::Level`, where more verbose
+/// levels compare greater.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// Upper-case name of the level, e.g. `"ERROR"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    /// Numeric severity following the OpenTelemetry `SeverityNumber`
+    /// convention (TRACE=1, DEBUG=5, INFO=9, WARN=13, ERROR=17), handy for
+    /// range queries in backends.
+    pub fn severity(&self) -> u8 {
+        match self {
+            LogLevel::Trace => 1,
+            LogLevel::Debug => 5,
+            LogLevel::Info => 9,
+            LogLevel::Warn => 13,
+            LogLevel::Error => 17,
+        }
+    }
+}
+
+impl From<tracing::Level> for LogLevel {
+    fn from(level: tracing::Level) -> Self {
+        match level {
+            tracing::Level::TRACE => LogLevel::Trace,
+            tracing::Level::DEBUG => LogLevel::Debug,
+            tracing::Level::INFO => LogLevel::Info,
+            tracing::Level::WARN => LogLevel::Warn,
+            tracing::Level::ERROR => LogLevel::Error,
+        }
+    }
+}
+
+impl From<LogLevel> for tracing::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => tracing::Level::TRACE,
+            LogLevel::Debug => tracing::Level::DEBUG,
+            LogLevel::Info => tracing::Level::INFO,
+            LogLevel::Warn => tracing::Level::WARN,
+            LogLevel::Error => tracing::Level::ERROR,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when parsing an unknown level name.
+#[derive(thiserror::Error, Debug)]
+#[error("unknown log level: {0}")]
+pub struct ParseLevelError(String);
+
+impl FromStr for LogLevel {
+    type Err = ParseLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "TRACE" => Ok(LogLevel::Trace),
+            "DEBUG" => Ok(LogLevel::Debug),
+            "INFO" => Ok(LogLevel::Info),
+            "WARN" | "WARNING" => Ok(LogLevel::Warn),
+            "ERROR" => Ok(LogLevel::Error),
+            _ => Err(ParseLevelError(s.to_string())),
+        }
+    }
+}
+
+impl Serialize for LogLevel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for LogLevel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
 
 /// Normalized representation of a `tracing` event that is ready to be
 /// shipped to an external logging backend.
 ///
 /// This struct is backend-agnostic and captures both the event metadata
 /// (level, target, module, file, line) and all structured fields.
-#[derive(Debug, Clone, Serialize)]
+///
+/// Records round-trip through `serde`, so Rust consumers (Kafka
+/// consumers, replay tools) can parse what this crate produces with e.g.
+/// `serde_json::from_str::<LogRecord>`. Records written before a field
+/// existed deserialize with that field's default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogRecord {
+    /// Version of the record schema, see [`SCHEMA_VERSION`]. Records
+    /// without it predate versioning and are treated as version 1.
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     /// UTC timestamp when the event was observed by the layer.
-    pub timestamp: DateTime<Utc>,
-    /// Stringified log level (e.g. "ERROR").
-    pub level: String,
+    pub timestamp: Timestamp,
+    /// Log level; serialized as its upper-case name (e.g. "ERROR").
+    pub level: LogLevel,
     /// Event target from `tracing` metadata.
-    pub target: String,
+    ///
+    /// Borrowed from the callsite's `'static` metadata for regular events,
+    /// so capturing one does not allocate. Records bridged from `log` or
+    /// deserialized borrow it from [`intern`]; records built by
+    /// processors may own theirs.
+    #[serde(deserialize_with = "deserialize_interned")]
+    pub target: Cow<'static, str>,
     /// Optional Rust module path where the event originated.
-    pub module_path: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_interned_option")]
+    pub module_path: Option<Cow<'static, str>>,
     /// Optional source file path.
-    pub file: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_interned_option")]
+    pub file: Option<Cow<'static, str>>,
     /// Optional source line number.
     pub line: Option<u32>,
     /// All structured fields attached to the event, including custom keys.
-    pub fields: BTreeMap<String, serde_json::Value>,
+    pub fields: Fields,
     /// Optional formatted log message, if present.
     pub message: Option<String>,
     /// Optional logical service name, populated by sinks or callers.
     pub service_name: Option<String>,
+    /// Hostname of the machine that produced the event.
+    pub hostname: Option<String>,
+    /// OS process id of the producing process.
+    pub pid: Option<u32>,
+    /// Identifier of the thread that emitted the event.
+    pub thread_id: Option<String>,
+    /// Name of the thread that emitted the event, if it has one.
+    pub thread_name: Option<String>,
+    /// Deployment metadata (environment, region, version, labels)
+    /// configured at init time.
+    #[serde(default)]
+    pub resource: BTreeMap<String, String>,
+    /// Unique, time-ordered (UUIDv7) id assigned when the event is
+    /// captured. Retries and replays resend the same id, so downstream
+    /// stores can deduplicate at-least-once delivery.
+    pub event_id: Option<Uuid>,
+    /// Number of identical records that were collapsed into this one by
+    /// [`LayerConfig::collapse_window`]; `None` for regular records.
+    ///
+    /// [`LayerConfig::collapse_window`]: crate::init::LayerConfig::collapse_window
+    pub repeat_count: Option<u64>,
+    /// Stable hash grouping records of the same kind, see
+    /// [`crate::fingerprint::FingerprintConfig`].
+    pub fingerprint: Option<String>,
+    /// W3C trace id (32 lowercase hex digits) promoted from a
+    /// `traceparent` or `trace_id` field, see [`crate::trace_context`].
+    pub trace_id: Option<String>,
+    /// W3C span (parent) id (16 lowercase hex digits) accompanying
+    /// [`LogRecord::trace_id`].
+    pub span_id: Option<String>,
+    /// Name of the span this record describes; set on span-close records,
+    /// see [`LayerConfig::capture_error_spans`].
+    ///
+    /// [`LayerConfig::capture_error_spans`]: crate::init::LayerConfig::capture_error_spans
+    pub span_name: Option<String>,
+    /// Time between the span being created and closed, in milliseconds;
+    /// set together with [`LogRecord::span_name`].
+    pub duration_ms: Option<u64>,
+    /// For events emitted inside a span: milliseconds between the creation
+    /// of the innermost span and the event.
+    pub span_elapsed_ms: Option<u64>,
+    /// Kind of the error the event reports, promoted from the `error.kind`
+    /// field (see [`crate::errors`]), e.g. `"Io"` or `"ParseIntError"`.
+    pub error_kind: Option<String>,
+    /// `Display` output of the error the event reports, promoted from the
+    /// `error.message` field.
+    pub error_message: Option<String>,
+}
+
+fn default_schema_version() -> u32 {
+    1
+}
+
+/// Number of distinct strings [`intern`] keeps; later ones are copied.
+const MAX_INTERNED: usize = 4096;
+
+/// Longest string [`intern`] keeps.
+const MAX_INTERNED_LEN: usize = 256;
+
+/// A `'static` copy of `s`, shared by every call with the same string.
+///
+/// Targets, module paths and file names repeat across records, so records
+/// that do not get them from `'static` callsite metadata (deserialized
+/// ones, events bridged from `log`) borrow them from here instead of
+/// allocating each time. The strings are never freed; once
+/// `MAX_INTERNED` of them are kept, or for strings longer than
+/// `MAX_INTERNED_LEN`, `s` is copied into an owned string instead.
+pub fn intern(s: &str) -> Cow<'static, str> {
+    static INTERNED: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    if s.len() > MAX_INTERNED_LEN {
+        return Cow::Owned(s.to_string());
+    }
+    let mut interned = INTERNED.get_or_init(Mutex::default).lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(kept) = interned.get(s) {
+        return Cow::Borrowed(kept);
+    }
+    if interned.len() >= MAX_INTERNED {
+        return Cow::Owned(s.to_string());
+    }
+    let kept: &'static str = Box::leak(s.into());
+    interned.insert(kept);
+    Cow::Borrowed(kept)
+}
+
+/// String deserialized through [`intern`].
+struct Interned(Cow<'static, str>);
+
+impl<'de> Deserialize<'de> for Interned {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct InternVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for InternVisitor {
+            type Value = Interned;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            // Borrowed or transient, the input is only read, so neither
+            // path allocates for a string that is already interned.
+            fn visit_str<E: serde::de::Error>(self, s: &str) -> Result<Interned, E> {
+                Ok(Interned(intern(s)))
+            }
+        }
+
+        deserializer.deserialize_str(InternVisitor)
+    }
+}
+
+fn deserialize_interned<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'static, str>, D::Error> {
+    Interned::deserialize(deserializer).map(|s| s.0)
+}
+
+fn deserialize_interned_option<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Cow<'static, str>>, D::Error> {
+    Option::<Interned>::deserialize(deserializer).map(|s| s.map(|s| s.0))
+}
+
+impl LogRecord {
+    /// Serialize the record into a JSON object restricted to the fields
+    /// that exist in schema `version`.
+    ///
+    /// **Parameters**
+    /// - `version`: target schema version, usually the version the
+    ///   destination table or index was created with.
+    ///
+    /// **Returns**
+    /// - A JSON object without fields introduced after `version`.
+    pub fn to_json_for_version(&self, version: u32) -> serde_json::Result<serde_json::Value> {
+        // Exhaustive destructuring: adding a field to `LogRecord` stops
+        // compiling here, as a reminder to register it in `SCHEMA_FIELDS`.
+        let LogRecord {
+            schema_version: _,
+            timestamp: _,
+            level: _,
+            target: _,
+            module_path: _,
+            file: _,
+            line: _,
+            fields: _,
+            message: _,
+            service_name: _,
+            hostname: _,
+            pid: _,
+            thread_id: _,
+            thread_name: _,
+            resource: _,
+            event_id: _,
+            repeat_count: _,
+            fingerprint: _,
+            trace_id: _,
+            span_id: _,
+            span_name: _,
+            duration_ms: _,
+            span_elapsed_ms: _,
+            error_kind: _,
+            error_message: _,
+        } = self;
+
+        serde_json::to_value(LogRecordRef::from(self).for_version(version))
+    }
+
+    /// Cheap estimate of the record's serialized JSON size in bytes.
+    ///
+    /// Sums the lengths of the string contents plus a fixed allowance for
+    /// keys, numbers and punctuation, without serializing anything. Used
+    /// for byte-based batching; exact for neither JSON nor other encodings.
+    pub fn estimated_size(&self) -> usize {
+        const OVERHEAD: usize = 320;
+        let opt = |s: &Option<String>| s.as_ref().map_or(0, String::len);
+        let cow = |s: &Option<Cow<'static, str>>| s.as_ref().map_or(0, |s| s.len());
+        let fields: usize = self
+            .fields
+            .iter()
+            .map(|(key, value)| key.len() + json_value_size(value) + 4)
+            .sum();
+        let resource: usize = self.resource.iter().map(|(k, v)| k.len() + v.len() + 6).sum();
+
+        OVERHEAD
+            + self.target.len()
+            + cow(&self.module_path)
+            + cow(&self.file)
+            + opt(&self.message)
+            + opt(&self.service_name)
+            + opt(&self.hostname)
+            + opt(&self.thread_id)
+            + opt(&self.thread_name)
+            + opt(&self.fingerprint)
+            + opt(&self.trace_id)
+            + opt(&self.span_id)
+            + opt(&self.span_name)
+            + opt(&self.error_kind)
+            + opt(&self.error_message)
+            + fields
+            + resource
+    }
+
+    /// Serialize the record into a JSON object with `timestamp` rendered
+    /// in the given [`TimestampFormat`].
+    pub fn to_json_with_timestamp(&self, format: TimestampFormat) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(LogRecordRef::from(self).with_timestamp(format))
+    }
+}
+
+/// Borrowed view of a [`LogRecord`], serialized exactly like the record.
+///
+/// Lets code on the send path adjust what gets written without cloning
+/// the record: filling in `service_name` for one destination of a
+/// fan-out, restricting the output to an older schema version
+/// ([`LogRecordRef::for_version`]) or rendering the timestamp in a
+/// [`TimestampFormat`] ([`LogRecordRef::with_timestamp`]) all serialize
+/// straight from the borrowed strings and maps.
+#[derive(Debug, Clone, Copy)]
+pub struct LogRecordRef<'a> {
+    pub schema_version: u32,
+    pub timestamp: &'a Timestamp,
+    pub level: LogLevel,
+    pub target: &'a str,
+    pub module_path: Option<&'a str>,
+    pub file: Option<&'a str>,
+    pub line: Option<u32>,
+    pub fields: &'a Fields,
+    pub message: Option<&'a str>,
+    pub service_name: Option<&'a str>,
+    pub hostname: Option<&'a str>,
+    pub pid: Option<u32>,
+    pub thread_id: Option<&'a str>,
+    pub thread_name: Option<&'a str>,
+    pub resource: &'a BTreeMap<String, String>,
+    pub event_id: Option<Uuid>,
+    pub repeat_count: Option<u64>,
+    pub fingerprint: Option<&'a str>,
+    pub trace_id: Option<&'a str>,
+    pub span_id: Option<&'a str>,
+    pub span_name: Option<&'a str>,
+    pub duration_ms: Option<u64>,
+    pub span_elapsed_ms: Option<u64>,
+    pub error_kind: Option<&'a str>,
+    pub error_message: Option<&'a str>,
+    /// Only fields that exist in this schema version are serialized;
+    /// [`SCHEMA_VERSION`] (everything) by default.
+    pub version: u32,
+    /// How `timestamp` is serialized; `None` keeps the RFC 3339 form of
+    /// [`LogRecord`].
+    pub timestamp_format: Option<TimestampFormat>,
+}
+
+impl<'a> LogRecordRef<'a> {
+    /// Serialize only the fields that exist in schema `version`, as
+    /// [`LogRecord::to_json_for_version`] does.
+    pub fn for_version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
+    /// Serialize `timestamp` in `format`, as
+    /// [`LogRecord::to_json_with_timestamp`] does.
+    pub fn with_timestamp(self, format: TimestampFormat) -> Self {
+        Self { timestamp_format: Some(format), ..self }
+    }
+
+    /// Copy the view into an owned [`LogRecord`].
+    pub fn to_record(&self) -> LogRecord {
+        let LogRecordRef {
+            schema_version,
+            timestamp,
+            level,
+            target,
+            module_path,
+            file,
+            line,
+            fields,
+            message,
+            service_name,
+            hostname,
+            pid,
+            thread_id,
+            thread_name,
+            resource,
+            event_id,
+            repeat_count,
+            fingerprint,
+            trace_id,
+            span_id,
+            span_name,
+            duration_ms,
+            span_elapsed_ms,
+            error_kind,
+            error_message,
+            version: _,
+            timestamp_format: _,
+        } = self;
+        LogRecord {
+            schema_version: *schema_version,
+            timestamp: **timestamp,
+            level: *level,
+            target: Cow::Owned(target.to_string()),
+            module_path: module_path.map(|s| Cow::Owned(s.to_string())),
+            file: file.map(|s| Cow::Owned(s.to_string())),
+            line: *line,
+            fields: (*fields).clone(),
+            message: message.map(str::to_string),
+            service_name: service_name.map(str::to_string),
+            hostname: hostname.map(str::to_string),
+            pid: *pid,
+            thread_id: thread_id.map(str::to_string),
+            thread_name: thread_name.map(str::to_string),
+            resource: (*resource).clone(),
+            event_id: *event_id,
+            repeat_count: *repeat_count,
+            fingerprint: fingerprint.map(str::to_string),
+            trace_id: trace_id.map(str::to_string),
+            span_id: span_id.map(str::to_string),
+            span_name: span_name.map(str::to_string),
+            duration_ms: *duration_ms,
+            span_elapsed_ms: *span_elapsed_ms,
+            error_kind: error_kind.map(str::to_string),
+            error_message: error_message.map(str::to_string),
+        }
+    }
+}
+
+impl<'a> From<&'a LogRecord> for LogRecordRef<'a> {
+    fn from(record: &'a LogRecord) -> Self {
+        // Exhaustive destructuring, as in `LogRecord::to_json_for_version`.
+        let LogRecord {
+            schema_version,
+            timestamp,
+            level,
+            target,
+            module_path,
+            file,
+            line,
+            fields,
+            message,
+            service_name,
+            hostname,
+            pid,
+            thread_id,
+            thread_name,
+            resource,
+            event_id,
+            repeat_count,
+            fingerprint,
+            trace_id,
+            span_id,
+            span_name,
+            duration_ms,
+            span_elapsed_ms,
+            error_kind,
+            error_message,
+        } = record;
+        LogRecordRef {
+            schema_version: *schema_version,
+            timestamp,
+            level: *level,
+            target: target.as_ref(),
+            module_path: module_path.as_deref(),
+            file: file.as_deref(),
+            line: *line,
+            fields,
+            message: message.as_deref(),
+            service_name: service_name.as_deref(),
+            hostname: hostname.as_deref(),
+            pid: *pid,
+            thread_id: thread_id.as_deref(),
+            thread_name: thread_name.as_deref(),
+            resource,
+            event_id: *event_id,
+            repeat_count: *repeat_count,
+            fingerprint: fingerprint.as_deref(),
+            trace_id: trace_id.as_deref(),
+            span_id: span_id.as_deref(),
+            span_name: span_name.as_deref(),
+            duration_ms: *duration_ms,
+            span_elapsed_ms: *span_elapsed_ms,
+            error_kind: error_kind.as_deref(),
+            error_message: error_message.as_deref(),
+            version: SCHEMA_VERSION,
+            timestamp_format: None,
+        }
+    }
+}
+
+impl Serialize for LogRecordRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let LogRecordRef {
+            schema_version,
+            timestamp,
+            level,
+            target,
+            module_path,
+            file,
+            line,
+            fields,
+            message,
+            service_name,
+            hostname,
+            pid,
+            thread_id,
+            thread_name,
+            resource,
+            event_id,
+            repeat_count,
+            fingerprint,
+            trace_id,
+            span_id,
+            span_name,
+            duration_ms,
+            span_elapsed_ms,
+            error_kind,
+            error_message,
+            version,
+            timestamp_format,
+        } = self;
+        let included = |key: &str| field_since(key).is_some_and(|since| since <= *version);
+        let mut map = serializer.serialize_map(None)?;
+        macro_rules! entries {
+            ($($name:ident),*) => {$(
+                if included(stringify!($name)) {
+                    map.serialize_entry(stringify!($name), $name)?;
+                }
+            )*};
+        }
+        entries!(schema_version);
+        if included("timestamp") {
+            match timestamp_format {
+                Some(format) => map.serialize_entry("timestamp", &format.format(timestamp))?,
+                None => map.serialize_entry("timestamp", timestamp)?,
+            }
+        }
+        entries!(
+            level, target, module_path, file, line, fields, message, service_name,
+            hostname, pid, thread_id, thread_name, resource, event_id, repeat_count,
+            fingerprint, trace_id, span_id, span_name, duration_ms, span_elapsed_ms,
+            error_kind, error_message
+        );
+        map.end()
+    }
+}
+
+/// Approximate serialized size of a JSON value.
+fn json_value_size(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::Null | serde_json::Value::Bool(_) => 5,
+        serde_json::Value::Number(_) => 12,
+        serde_json::Value::String(s) => s.len() + 2,
+        serde_json::Value::Array(items) => 2 + items.iter().map(|v| json_value_size(v) + 1).sum::<usize>(),
+        serde_json::Value::Object(map) => {
+            2 + map.iter().map(|(k, v)| k.len() + json_value_size(v) + 4).sum::<usize>()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialized_records_share_interned_strings() {
+        let line = r#"{"timestamp":"2026-01-01T00:00:00Z","level":"ERROR","target":"app::db","file":"src/db.rs","fields":{}}"#;
+        let first: LogRecord = serde_json::from_str(line).unwrap();
+        let second: LogRecord = serde_json::from_reader(line.as_bytes()).unwrap();
+        for record in [&first, &second] {
+            assert!(matches!(record.target, Cow::Borrowed("app::db")));
+            assert!(matches!(record.file, Some(Cow::Borrowed("src/db.rs"))));
+            assert_eq!(record.module_path, None);
+        }
+        assert!(std::ptr::eq(first.target.as_ref(), second.target.as_ref()));
+
+        let long = "x".repeat(MAX_INTERNED_LEN + 1);
+        assert!(matches!(intern(&long), Cow::Owned(_)));
+    }
 }
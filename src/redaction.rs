@@ -0,0 +1,231 @@
+use crate::processor::{ProcessOutcome, RecordProcessor};
+use crate::record::LogRecord;
+use regex::Regex;
+use serde::Deserialize;
+
+/// Placeholder written in place of redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Configuration for [`RedactionProcessor`].
+///
+/// **Fields**
+/// - `field_patterns`: case-insensitive substrings matched against field
+///   names; the whole value of a matching field is replaced.
+/// - `value_patterns`: regular expressions applied to the message, the
+///   error message and all string field values; every match is replaced.
+/// - `card_numbers`: also replace payment card numbers in those strings:
+///   13-19 digits, optionally grouped by spaces or dashes, that pass the
+///   Luhn check, so order ids and timestamps of the same length are kept.
+/// - `replacement`: text written instead of redacted data.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+    pub field_patterns: Vec<String>,
+    pub value_patterns: Vec<String>,
+    pub card_numbers: bool,
+    pub replacement: String,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            field_patterns: ["password", "passwd", "secret", "token", "authorization", "api_key", "cookie"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            value_patterns: vec![
+                // Bearer tokens in header-like strings.
+                r"(?i)bearer\s+[a-z0-9._~+/=-]+".to_string(),
+            ],
+            card_numbers: true,
+            replacement: REDACTED.to_string(),
+        }
+    }
+}
+
+/// Candidate payment card numbers, confirmed by [`luhn_valid`].
+const CARD_NUMBER: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+/// Whether the digits of `candidate` have a valid Luhn check digit.
+fn luhn_valid(candidate: &str) -> bool {
+    let sum: u32 = candidate
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .rev()
+        .enumerate()
+        .map(|(i, b)| {
+            let digit = u32::from(b - b'0');
+            match i % 2 {
+                0 => digit,
+                _ if digit > 4 => digit * 2 - 9,
+                _ => digit * 2,
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// [`RecordProcessor`] that scrubs sensitive data before records leave the
+/// process.
+///
+/// Add it to [`LayerConfig::processors`] (usually first, so later
+/// processors never observe raw secrets).
+///
+/// [`LayerConfig::processors`]: crate::init::LayerConfig::processors
+#[derive(Clone, Debug)]
+pub struct RedactionProcessor {
+    field_patterns: Vec<String>,
+    value_patterns: Vec<Regex>,
+    card_numbers: Option<Regex>,
+    replacement: String,
+}
+
+impl RedactionProcessor {
+    /// Build a processor from a [`RedactionConfig`].
+    ///
+    /// **Returns**
+    /// - `Err(..)` if one of `value_patterns` is not a valid regex.
+    pub fn new(config: RedactionConfig) -> Result<Self, regex::Error> {
+        let value_patterns = config
+            .value_patterns
+            .iter()
+            .map(|p| Regex::new(p))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            field_patterns: config.field_patterns.iter().map(|p| p.to_ascii_lowercase()).collect(),
+            value_patterns,
+            card_numbers: config.card_numbers.then(|| Regex::new(CARD_NUMBER).expect("valid card number pattern")),
+            replacement: config.replacement,
+        })
+    }
+
+    fn is_sensitive_field(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.field_patterns.iter().any(|p| name.contains(p.as_str()))
+    }
+
+    fn redact_str(&self, value: &mut String) {
+        for re in &self.value_patterns {
+            if re.is_match(value) {
+                *value = re.replace_all(value, self.replacement.as_str()).into_owned();
+            }
+        }
+        if let Some(re) = &self.card_numbers {
+            if re.find_iter(value).any(|m| luhn_valid(m.as_str())) {
+                *value = re
+                    .replace_all(value, |caps: &regex::Captures<'_>| {
+                        let candidate = &caps[0];
+                        if luhn_valid(candidate) {
+                            self.replacement.clone()
+                        } else {
+                            candidate.to_string()
+                        }
+                    })
+                    .into_owned();
+            }
+        }
+    }
+
+    fn redact_value(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => self.redact_str(s),
+            serde_json::Value::Array(items) => items.iter_mut().for_each(|v| self.redact_value(v)),
+            serde_json::Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if self.is_sensitive_field(key) {
+                        *v = serde_json::Value::String(self.replacement.clone());
+                    } else {
+                        self.redact_value(v);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl RecordProcessor for RedactionProcessor {
+    fn process(&self, record: &mut LogRecord) -> ProcessOutcome {
+        if let Some(message) = record.message.as_mut() {
+            self.redact_str(message);
+        }
+        if let Some(message) = record.error_message.as_mut() {
+            self.redact_str(message);
+        }
+
+        for (name, value) in record.fields.iter_mut() {
+            if self.is_sensitive_field(name) {
+                *value = serde_json::Value::String(self.replacement.clone());
+            } else {
+                self.redact_value(value);
+            }
+        }
+
+        ProcessOutcome::Keep
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn redact(fields: serde_json::Value, message: &str) -> LogRecord {
+        let mut record: LogRecord = serde_json::from_value(json!({
+            "timestamp": "2026-01-01T00:00:00Z",
+            "level": "ERROR",
+            "target": "app",
+            "message": message,
+            "fields": fields,
+        }))
+        .unwrap();
+        let processor = RedactionProcessor::new(RedactionConfig::default()).unwrap();
+        assert_eq!(processor.process(&mut record), ProcessOutcome::Keep);
+        record
+    }
+
+    #[test]
+    fn sensitive_field_names_are_replaced_at_any_depth() {
+        let record = redact(
+            json!({
+                "user_password": "hunter2",
+                "Authorization": "Basic abc",
+                "request": { "headers": { "X-Api_Key": "k", "accept": "json" }, "items": [{ "token": 1 }] },
+                "user_id": 7,
+            }),
+            "login failed",
+        );
+        assert_eq!(
+            serde_json::to_value(&record.fields).unwrap(),
+            json!({
+                "user_password": REDACTED,
+                "Authorization": REDACTED,
+                "request": { "headers": { "X-Api_Key": REDACTED, "accept": "json" }, "items": [{ "token": REDACTED }] },
+                "user_id": 7,
+            })
+        );
+    }
+
+    #[test]
+    fn value_patterns_replace_matches_in_strings() {
+        let record = redact(
+            json!({ "header": "Bearer eyJhbGciOi.x-y", "note": ["sent bearer abc123 upstream"] }),
+            "call with bearer abc123 failed",
+        );
+        assert_eq!(record.message.as_deref(), Some("call with [REDACTED] failed"));
+        assert_eq!(record.fields["header"], json!(REDACTED));
+        assert_eq!(record.fields["note"], json!(["sent [REDACTED] upstream"]));
+    }
+
+    #[test]
+    fn only_luhn_valid_digit_runs_are_card_numbers() {
+        let record = redact(
+            json!({ "card": "4111 1111 1111 1111", "order": "order 1234567890123 shipped" }),
+            "charged 5500-0000-0000-0004, ts 1767225600000",
+        );
+        assert_eq!(record.fields["card"], json!(REDACTED));
+        assert_eq!(record.fields["order"], json!("order 1234567890123 shipped"));
+        assert_eq!(record.message.as_deref(), Some("charged [REDACTED], ts 1767225600000"));
+    }
+}
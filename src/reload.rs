@@ -0,0 +1,126 @@
+use crate::level_policy::{LevelGate, ShipPolicy};
+use crate::record::LogLevel;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// Settings that can be changed on a live service through a
+/// [`ConfigHandle`], e.g. to raise verbosity during an incident.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicConfig {
+    /// Minimal level captured by the layer, see [`crate::init::LayerConfig::level`].
+    pub level: LogLevel,
+    /// How events of each level are shipped, see
+    /// [`crate::init::LayerConfig::level_policy`].
+    pub level_policy: BTreeMap<LogLevel, ShipPolicy>,
+    /// Routing table, see [`crate::init::LayerConfig::routes`].
+    pub routes: Vec<Route>,
+}
+
+/// Entry of the routing table: records of a target are attributed to a
+/// service, which selects their destination through the `{service}`
+/// placeholder of [`crate::destination::DestinationTemplate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    /// Target prefix, matched like [`crate::init::LayerConfig::ignore_targets`].
+    pub target: String,
+    /// Set as [`crate::record::LogRecord::service_name`] on matching records
+    /// that do not name a service themselves.
+    pub service: String,
+}
+
+/// The service of the first route matching `target`.
+pub(crate) fn route<'a>(routes: &'a [Route], target: &str) -> Option<&'a str> {
+    routes
+        .iter()
+        .find(|route| crate::layer::is_target_or_child(target, &route.target))
+        .map(|route| route.service.as_str())
+}
+
+/// Handle for reloading [`DynamicConfig`] at runtime.
+///
+/// Available from [`crate::handle::LayerHandle::config`] and
+/// [`crate::layer::ErrorLogLayer::config_handle`].
+/// Cheap to clone. The level and the level policy are mirrored into atomics
+/// so the layer's hot path never takes a lock; other settings, like the
+/// routes read by the worker, are distributed through a `tokio::sync::watch`
+/// channel that components can [`subscribe`] to.
+///
+/// [`subscribe`]: ConfigHandle::subscribe
+#[derive(Clone, Debug)]
+pub struct ConfigHandle {
+    tx: Arc<watch::Sender<DynamicConfig>>,
+    level: Arc<AtomicU8>,
+    gate: Arc<LevelGate>,
+}
+
+impl ConfigHandle {
+    /// Handle starting from `initial`; rate limits count seconds from
+    /// `origin`.
+    pub(crate) fn new(initial: DynamicConfig, origin: Instant) -> Self {
+        let level = Arc::new(AtomicU8::new(level_to_u8(initial.level)));
+        let gate = Arc::new(LevelGate::new(&initial.level_policy, origin));
+        let (tx, _rx) = watch::channel(initial);
+        ConfigHandle { tx: Arc::new(tx), level, gate }
+    }
+
+    /// Snapshot of the current settings.
+    pub fn current(&self) -> DynamicConfig {
+        self.tx.borrow().clone()
+    }
+
+    /// Modify the settings in place and notify subscribers.
+    pub fn update(&self, f: impl FnOnce(&mut DynamicConfig)) {
+        self.tx.send_modify(|config| {
+            f(config);
+            self.level.store(level_to_u8(config.level), Ordering::Relaxed);
+            self.gate.set(&config.level_policy);
+        });
+    }
+
+    /// Change the minimal captured level.
+    pub fn set_level(&self, level: LogLevel) {
+        self.update(|config| config.level = level);
+    }
+
+    /// Replace the level policy.
+    pub fn set_level_policy(&self, level_policy: BTreeMap<LogLevel, ShipPolicy>) {
+        self.update(|config| config.level_policy = level_policy);
+    }
+
+    /// Replace the routing table.
+    pub fn set_routes(&self, routes: Vec<Route>) {
+        self.update(|config| config.routes = routes);
+    }
+
+    /// Receive a notification whenever the settings change.
+    pub fn subscribe(&self) -> watch::Receiver<DynamicConfig> {
+        self.tx.subscribe()
+    }
+
+    /// Current level, read without locking.
+    pub(crate) fn level(&self) -> LogLevel {
+        level_from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    /// Whether the level policy ships an event of `level` seen at `now`.
+    pub(crate) fn admit(&self, level: LogLevel, now: Instant) -> bool {
+        self.gate.admit(level, now)
+    }
+}
+
+fn level_to_u8(level: LogLevel) -> u8 {
+    level as u8
+}
+
+fn level_from_u8(value: u8) -> LogLevel {
+    match value {
+        0 => LogLevel::Trace,
+        1 => LogLevel::Debug,
+        2 => LogLevel::Info,
+        3 => LogLevel::Warn,
+        _ => LogLevel::Error,
+    }
+}
@@ -0,0 +1,196 @@
+//! Re-sending records saved as JSON Lines.
+//!
+//! [`replay_file`] reads a file of one JSON record per line — a
+//! [`persist`](crate::persist) backlog, a [`durable`](crate::durable) log
+//! segment or any other NDJSON dump of [`LogRecord`]s — and sends it to a
+//! sink in batches, reporting progress as it goes. `logrec replay`
+//! (feature `cli`) runs it against the backend of a DSN.
+
+use crate::decorators::RetryPolicy;
+#[cfg(feature = "encryption")]
+use crate::encryption::SpillKey;
+use crate::record::LogRecord;
+use crate::sink::{LogSink, SinkError};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// How [`replay_file`] sends.
+///
+/// **Fields**
+/// - `batch_size`: records per [`LogSink::send_batch`] (default 500).
+/// - `retry`: retries of a batch failing with a retryable error; once
+///   they are used up the replay stops with [`ReplayError::Sink`].
+///   Default: 10 retries with a backoff from 100 ms up to 10 s.
+/// - `encryption` (feature `encryption`): key of a file written with
+///   `encryption::SpillKey`; with a key, plain JSON lines are skipped as
+///   unreadable.
+///   Default `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayConfig {
+    pub batch_size: usize,
+    pub retry: RetryPolicy,
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<SpillKey>,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 500,
+            retry: RetryPolicy {
+                max_retries: 10,
+                initial_backoff: std::time::Duration::from_millis(100),
+                max_backoff: std::time::Duration::from_secs(10),
+            },
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        }
+    }
+}
+
+impl ReplayConfig {
+    /// The record on `line`, `None` if it is unreadable.
+    fn decode(&self, line: &[u8]) -> Option<LogRecord> {
+        #[cfg(feature = "encryption")]
+        if let Some(key) = &self.encryption {
+            return serde_json::from_slice(&key.open(line)?).ok();
+        }
+        serde_json::from_slice(line).ok()
+    }
+}
+
+/// Counters of a running or finished replay.
+///
+/// **Fields**
+/// - `bytes_read`, `total_bytes`: position in and size of the file.
+/// - `lines`: lines of the file handled, including empty and unreadable
+///   ones; a replay that stopped can be resumed by skipping this many
+///   lines (`logrec replay --skip`).
+/// - `sent`: records the sink accepted.
+/// - `rejected`: records the sink rejected permanently; they are skipped.
+/// - `invalid`: non-empty lines that are not a [`LogRecord`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayProgress {
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    pub lines: u64,
+    pub sent: u64,
+    pub rejected: u64,
+    pub invalid: u64,
+}
+
+/// Error returned by [`replay_file`].
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    #[error("cannot read replay file: {0}")]
+    Io(#[from] io::Error),
+
+    /// A batch kept failing; everything before `progress.lines` was
+    /// handled.
+    #[error("sink failed after {} sent records: {source}", progress.sent)]
+    Sink { source: SinkError, progress: ReplayProgress },
+}
+
+/// Send the records of the JSON Lines file `path` to `sink`.
+///
+/// Batches the sink rejects permanently are sent again record by record,
+/// so only the offending records are skipped. Unreadable lines, such as
+/// a torn last line, are counted and skipped.
+///
+/// **Parameters**
+/// - `skip`: lines at the start of the file left out, see
+///   [`ReplayProgress::lines`].
+/// - `on_progress`: called after every batch.
+///
+/// **Returns**
+/// - The final counters.
+pub async fn replay_file(
+    path: &Path,
+    sink: &dyn LogSink,
+    config: &ReplayConfig,
+    skip: u64,
+    mut on_progress: impl FnMut(&ReplayProgress),
+) -> Result<ReplayProgress, ReplayError> {
+    let file = File::open(path)?;
+    let mut progress = ReplayProgress { total_bytes: file.metadata()?.len(), ..ReplayProgress::default() };
+    let mut reader = BufReader::new(file);
+    let mut line = Vec::new();
+    let batch_size = config.batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut lines = 0;
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read > 0 {
+            progress.bytes_read += read as u64;
+            lines += 1;
+            if lines > skip && !line.trim_ascii().is_empty() {
+                match config.decode(&line) {
+                    Some(record) => batch.push(record),
+                    None => progress.invalid += 1,
+                }
+            }
+        }
+        if batch.len() >= batch_size || (read == 0 && !batch.is_empty()) {
+            if let Err(source) = send(sink, &batch, config, &mut progress).await {
+                return Err(ReplayError::Sink { source, progress });
+            }
+            batch.clear();
+            progress.lines = lines;
+            on_progress(&progress);
+        }
+        if read == 0 {
+            progress.lines = lines;
+            return Ok(progress);
+        }
+    }
+}
+
+/// Send `batch`, retrying as `config.retry` says and isolating records
+/// the sink rejects.
+async fn send(
+    sink: &dyn LogSink,
+    batch: &[LogRecord],
+    config: &ReplayConfig,
+    progress: &mut ReplayProgress,
+) -> Result<(), SinkError> {
+    match retry(&config.retry, || sink.send_batch(batch)).await {
+        Ok(()) => progress.sent += batch.len() as u64,
+        Err(e) if e.is_retryable() => return Err(e),
+        Err(_) if batch.len() == 1 => progress.rejected += 1,
+        Err(_) => {
+            for record in batch {
+                match retry(&config.retry, || sink.send(record)).await {
+                    Ok(()) => progress.sent += 1,
+                    Err(e) if e.is_retryable() => return Err(e),
+                    Err(_) => progress.rejected += 1,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn retry<F, Fut>(policy: &RetryPolicy, mut attempt: F) -> Result<(), SinkError>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(), SinkError>>,
+{
+    let mut backoff = policy.initial_backoff;
+    let mut retries = 0;
+    loop {
+        match attempt().await {
+            Err(e) if e.is_retryable() && retries < policy.max_retries => {
+                let delay = match e {
+                    SinkError::RateLimited { retry_after: Some(retry_after) } => retry_after,
+                    _ => backoff,
+                };
+                tokio::time::sleep(delay).await;
+                backoff = std::cmp::min(backoff * 2, policy.max_backoff);
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
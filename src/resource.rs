@@ -0,0 +1,65 @@
+use crate::platform::{self, PlatformDetection};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Deployment metadata describing where a service is running.
+///
+/// Set once via [`LayerConfig::resource`] at `init_tracing` time and
+/// merged into the `resource` map of every [`LogRecord`], so shared tables
+/// can be filtered by environment, region or release.
+///
+/// [`LayerConfig::resource`]: crate::init::LayerConfig::resource
+/// [`LogRecord`]: crate::record::LogRecord
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ResourceInfo {
+    /// Deployment environment, e.g. "prod" or "staging".
+    pub environment: Option<String>,
+    /// Region or datacenter, e.g. "eu-west-1".
+    pub region: Option<String>,
+    /// Service version, e.g. "1.4.2".
+    pub version: Option<String>,
+    /// Git commit the binary was built from.
+    pub git_sha: Option<String>,
+    /// Arbitrary additional key/value labels.
+    pub labels: BTreeMap<String, String>,
+    /// Container and cloud instance attributes to detect when the layer
+    /// is created, e.g. `container.id` or `host.id`; none by default.
+    pub detect: PlatformDetection,
+}
+
+impl ResourceInfo {
+    /// Flatten the resource into the key/value map stored on records.
+    ///
+    /// **Returns**
+    /// - A map containing all `labels` plus the well-known keys
+    ///   `environment`, `region`, `version` and `git_sha` when set. The
+    ///   well-known keys take precedence over labels with the same name.
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        let mut map = self.labels.clone();
+        let known = [
+            ("environment", &self.environment),
+            ("region", &self.region),
+            ("version", &self.version),
+            ("git_sha", &self.git_sha),
+        ];
+        for (key, value) in known {
+            if let Some(value) = value {
+                map.insert(key.to_string(), value.clone());
+            }
+        }
+        map
+    }
+
+    /// [`ResourceInfo::to_map`] plus the attributes found by the sources
+    /// of `detect`; called once by the layer.
+    ///
+    /// Blocks while detecting, for up to about `detect.timeout_ms` per
+    /// endpoint source. Keys set explicitly take precedence over
+    /// detected ones.
+    pub fn collect(&self) -> BTreeMap<String, String> {
+        let mut map = platform::detect(&self.detect);
+        map.extend(self.to_map());
+        map
+    }
+}
@@ -0,0 +1,44 @@
+//! Shared background Tokio runtime used when the crate has to run async
+//! code without an application runtime to borrow.
+
+use std::future::{self, Future};
+use std::sync::OnceLock;
+use std::thread;
+use tokio::runtime::{Builder, Handle};
+
+/// Lazily started current-thread runtime driven by a dedicated thread.
+pub(crate) fn background() -> &'static Handle {
+    static RUNTIME: OnceLock<Handle> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build log sink runtime");
+        let handle = runtime.handle().clone();
+        thread::Builder::new()
+            .name("log-sink-runtime".to_string())
+            .spawn(move || runtime.block_on(future::pending::<()>()))
+            .expect("spawn log sink runtime thread");
+        handle
+    })
+}
+
+/// Drive `future` to completion from synchronous code.
+///
+/// The future runs on a scoped helper thread against the [`background`]
+/// runtime, so this works both outside of and inside an async context,
+/// and tasks spawned by the future (e.g. a database connection driver)
+/// outlive the call.
+pub(crate) fn block_on<F>(future: F) -> F::Output
+where
+    F: Future + Send,
+    F::Output: Send,
+{
+    let handle = background();
+    thread::scope(|scope| {
+        scope
+            .spawn(|| handle.block_on(future))
+            .join()
+            .expect("log sink background task panicked")
+    })
+}
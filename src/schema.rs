@@ -0,0 +1,685 @@
+//! Canonical DDL for the tables and indices the sinks write to.
+//!
+//! [`ddl`] derives the statement from [`SCHEMA_FIELDS`]: a `CREATE TABLE`
+//! for ClickHouse, Postgres or MySQL, or an OpenSearch index template.
+//! ClickHouse and Postgres columns have the types of `migrations/`, in
+//! the order of [`SCHEMA_FIELDS`]; [`ColumnMapping`]s add typed columns
+//! computed from event fields. `logrec schema` prints the same statements.
+//!
+//! [`expected_columns`] lists the same columns with their types, and
+//! [`drift`] compares them with those of an existing table, for
+//! [`crate::sink::LogSink::schema_drift`].
+
+use crate::destination::DestinationTemplate;
+use crate::record::{SCHEMA_FIELDS, SCHEMA_VERSION};
+use crate::timestamp::TimestampFormat;
+use std::fmt;
+use std::str::FromStr;
+
+/// Backend a statement of [`ddl`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaBackend {
+    ClickHouse,
+    Postgres,
+    /// MySQL 8, for sinks of the application; no built-in sink writes to
+    /// it.
+    MySql,
+    /// A composable index template, the body of
+    /// `PUT _index_template/<name>`.
+    OpenSearch,
+}
+
+impl FromStr for SchemaBackend {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "clickhouse" => Ok(SchemaBackend::ClickHouse),
+            "postgres" | "postgresql" => Ok(SchemaBackend::Postgres),
+            "mysql" => Ok(SchemaBackend::MySql),
+            "opensearch" => Ok(SchemaBackend::OpenSearch),
+            _ => Err(SchemaError::UnknownBackend(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for SchemaBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SchemaBackend::ClickHouse => "clickhouse",
+            SchemaBackend::Postgres => "postgres",
+            SchemaBackend::MySql => "mysql",
+            SchemaBackend::OpenSearch => "opensearch",
+        })
+    }
+}
+
+/// Type of a [`ColumnMapping`] column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnKind {
+    String,
+    Int,
+    Float,
+    Bool,
+}
+
+impl FromStr for ColumnKind {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "string" => Ok(ColumnKind::String),
+            "int" => Ok(ColumnKind::Int),
+            "float" => Ok(ColumnKind::Float),
+            "bool" => Ok(ColumnKind::Bool),
+            _ => Err(SchemaError::UnknownKind(s.to_string())),
+        }
+    }
+}
+
+/// A column holding event field `field` of every record, computed from
+/// the `fields` column (`MATERIALIZED` in ClickHouse, a stored generated
+/// column in Postgres and MySQL), so it can be indexed and queried
+/// without parsing JSON. For OpenSearch it maps `fields.<field>`, and
+/// `column` is ignored.
+///
+/// Values of another JSON type are `NULL` (ClickHouse, Postgres, MySQL
+/// for numbers and booleans); `String` columns take numbers and booleans
+/// as text.
+///
+/// Parses from `column=field:kind`, or `field:kind` for a column named
+/// like the field, e.g. `user_id=user.id:string` or `status:int`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    pub column: String,
+    pub field: String,
+    pub kind: ColumnKind,
+}
+
+impl FromStr for ColumnMapping {
+    type Err = SchemaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (mapping, kind) = s.rsplit_once(':').ok_or_else(|| SchemaError::InvalidMapping(s.to_string()))?;
+        let (column, field) = mapping.split_once('=').unwrap_or((mapping, mapping));
+        if column.is_empty() || field.is_empty() {
+            return Err(SchemaError::InvalidMapping(s.to_string()));
+        }
+        Ok(Self { column: column.to_string(), field: field.to_string(), kind: kind.parse()? })
+    }
+}
+
+/// Options of [`ddl`].
+///
+/// **Fields**
+/// - `table`: table (optionally `database.table`) or OpenSearch index;
+///   placeholders of an index, e.g. `logs-{date}`, become `*` in the
+///   template's pattern. Default `logs`.
+/// - `version`: [`LogRecord`] schema version of the table; fields added
+///   later are left out. Default [`SCHEMA_VERSION`].
+/// - `shared`: ClickHouse only: a table shared by several services, with
+///   a non-null `service_name` leading the sort key, as in
+///   `migrations/clickhouse/shared_table.sql`; without it there is no
+///   `service_name` column, as in `per_service.sql`. Default `true`.
+/// - `timestamp_format`: how the sink writes timestamps, which sets the
+///   ClickHouse column type and the OpenSearch date format; see
+///   [`TimestampFormat`]. Postgres and MySQL always use a timestamp type.
+/// - `columns`: additional columns, see [`ColumnMapping`].
+///
+/// [`LogRecord`]: crate::record::LogRecord
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaOptions {
+    pub table: String,
+    pub version: u32,
+    pub shared: bool,
+    pub timestamp_format: TimestampFormat,
+    pub columns: Vec<ColumnMapping>,
+}
+
+impl Default for SchemaOptions {
+    fn default() -> Self {
+        Self {
+            table: "logs".to_string(),
+            version: SCHEMA_VERSION,
+            shared: true,
+            timestamp_format: TimestampFormat::default(),
+            columns: Vec::new(),
+        }
+    }
+}
+
+/// Error returned by [`ddl`] and when parsing its options.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    #[error("unknown schema backend {0:?}, expected clickhouse, postgres, mysql or opensearch")]
+    UnknownBackend(String),
+
+    #[error("unknown column kind {0:?}, expected string, int, float or bool")]
+    UnknownKind(String),
+
+    #[error("invalid column mapping {0:?}, expected column=field:kind")]
+    InvalidMapping(String),
+
+    #[error("unsupported schema version {0}, expected 1 to {SCHEMA_VERSION}")]
+    UnsupportedVersion(u32),
+
+    #[error("invalid name {0:?}, expected letters, digits and underscores")]
+    InvalidName(String),
+
+    #[error("invalid field {0:?}: quotes and backslashes are not supported")]
+    InvalidField(String),
+
+    #[error("column {0:?} is defined twice")]
+    DuplicateColumn(String),
+}
+
+/// A column of a table, or a top-level field of an index mapping, and
+/// its type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnType {
+    pub name: String,
+    pub ty: String,
+}
+
+impl ColumnType {
+    pub fn new(name: impl Into<String>, ty: impl Into<String>) -> Self {
+        Self { name: name.into(), ty: ty.into() }
+    }
+}
+
+/// A column whose type differs from the expected one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeMismatch {
+    pub name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// How a table or index differs from the schema a sink expects, see
+/// [`drift`] and [`crate::sink::LogSink::schema_drift`].
+///
+/// **Fields**
+/// - `destination`: table (`database.table` for ClickHouse) or index.
+/// - `missing`: expected columns the destination lacks, with the
+///   expected type; the sink cannot store these record fields.
+/// - `mismatched`: columns of another type; inserts may fail or convert.
+/// - `extra`: columns the sink does not write, with their type; they
+///   need a default, or inserts fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaDrift {
+    pub backend: SchemaBackend,
+    pub destination: String,
+    pub missing: Vec<ColumnType>,
+    pub mismatched: Vec<TypeMismatch>,
+    pub extra: Vec<ColumnType>,
+}
+
+impl SchemaDrift {
+    /// Whether the destination matches the expected schema.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.mismatched.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// One line, e.g. `clickhouse default.logs differs from the expected
+/// schema: missing columns: error_kind Nullable(String); type mismatches:
+/// line is Int64, expected Nullable(UInt32); extra columns: host String`.
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} differs from the expected schema", self.backend, self.destination)?;
+        let columns = |columns: &[ColumnType]| {
+            columns.iter().map(|c| format!("{} {}", c.name, c.ty)).collect::<Vec<_>>().join(", ")
+        };
+        let mut separator = ": ";
+        if !self.missing.is_empty() {
+            write!(f, "{separator}missing columns: {}", columns(&self.missing))?;
+            separator = "; ";
+        }
+        if !self.mismatched.is_empty() {
+            let mismatched = self
+                .mismatched
+                .iter()
+                .map(|m| format!("{} is {}, expected {}", m.name, m.actual, m.expected))
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "{separator}type mismatches: {mismatched}")?;
+            separator = "; ";
+        }
+        if !self.extra.is_empty() {
+            write!(f, "{separator}extra columns: {}", columns(&self.extra))?;
+        }
+        Ok(())
+    }
+}
+
+/// Type of a [`LogRecord`] field, mapped to a column type per backend.
+///
+/// [`LogRecord`]: crate::record::LogRecord
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Timestamp,
+    Level,
+    Text,
+    UInt32,
+    UInt64,
+    Json,
+    Map,
+    Uuid,
+}
+
+fn field_kind(name: &str) -> Kind {
+    match name {
+        "timestamp" => Kind::Timestamp,
+        "level" => Kind::Level,
+        "line" | "schema_version" | "pid" => Kind::UInt32,
+        "repeat_count" | "duration_ms" | "span_elapsed_ms" => Kind::UInt64,
+        "fields" => Kind::Json,
+        "resource" => Kind::Map,
+        "event_id" => Kind::Uuid,
+        _ => Kind::Text,
+    }
+}
+
+/// A column of the canonical schema.
+struct Column {
+    name: &'static str,
+    kind: Kind,
+    required: bool,
+}
+
+/// The statement creating the table or index template of `backend`.
+///
+/// **Returns**
+/// - The statement, terminated by `;` for SQL backends, or the template
+///   as pretty-printed JSON for OpenSearch.
+/// - `Err(..)` for an unsupported `version`, a table or column name that
+///   is not a plain identifier, or a column defined twice.
+pub fn ddl(backend: SchemaBackend, options: &SchemaOptions) -> Result<String, SchemaError> {
+    let columns = checked_columns(backend, options)?;
+    match backend {
+        SchemaBackend::OpenSearch => Ok(opensearch_template(&columns, options)),
+        _ => {
+            options.table.split('.').try_for_each(check_identifier)?;
+            Ok(create_table(backend, &columns, options))
+        }
+    }
+}
+
+/// The columns a statement of [`ddl`] creates, with their types as
+/// `backend` spells them: e.g. `Nullable(UInt32)` in ClickHouse, `INT4`
+/// in Postgres, without `NOT NULL` or the expression of a computed
+/// column. For OpenSearch, the top-level fields of the mapping with their
+/// mapping type, e.g. `keyword`. `options.table` is not used.
+///
+/// **Returns**
+/// - `Err(..)` like [`ddl`].
+pub fn expected_columns(backend: SchemaBackend, options: &SchemaOptions) -> Result<Vec<ColumnType>, SchemaError> {
+    let columns = checked_columns(backend, options)?;
+    if backend == SchemaBackend::OpenSearch {
+        return Ok(columns
+            .iter()
+            .map(|column| {
+                let mapping = opensearch_mapping(column, options);
+                ColumnType::new(column.name, mapping["type"].as_str().unwrap_or("object"))
+            })
+            .collect());
+    }
+    let mut expected: Vec<ColumnType> = columns
+        .iter()
+        .map(|column| ColumnType::new(column_name(backend, column.name), base_type(backend, column, options)))
+        .collect();
+    expected.extend(
+        options
+            .columns
+            .iter()
+            .map(|mapping| ColumnType::new(&mapping.column, mapped_type(backend, mapping.kind))),
+    );
+    Ok(expected)
+}
+
+/// Compare the `actual` columns of `destination` with the `expected`
+/// ones, e.g. of [`expected_columns`].
+///
+/// Types are compared as `backend` spells them, ignoring case for the SQL
+/// backends and differences that do not change what a sink can write:
+/// `LowCardinality` and the time zone of `DateTime64` in ClickHouse;
+/// `timestamptz`/`timestamp`, `int4`/`int8`, `json`/`jsonb`,
+/// `text`/`varchar` and the SQL names of these types in Postgres; spaces
+/// and `BOOLEAN`/`tinyint(1)` in MySQL.
+pub fn drift(
+    backend: SchemaBackend,
+    destination: impl Into<String>,
+    expected: &[ColumnType],
+    actual: &[ColumnType],
+) -> SchemaDrift {
+    let mut drift = SchemaDrift {
+        backend,
+        destination: destination.into(),
+        missing: Vec::new(),
+        mismatched: Vec::new(),
+        extra: Vec::new(),
+    };
+    for column in expected {
+        match actual.iter().find(|a| a.name == column.name) {
+            None => drift.missing.push(column.clone()),
+            Some(a) if !same_type(backend, &column.ty, &a.ty) => drift.mismatched.push(TypeMismatch {
+                name: column.name.clone(),
+                expected: column.ty.clone(),
+                actual: a.ty.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    drift.extra = actual
+        .iter()
+        .filter(|a| !expected.iter().any(|c| c.name == a.name))
+        .cloned()
+        .collect();
+    drift
+}
+
+fn same_type(backend: SchemaBackend, expected: &str, actual: &str) -> bool {
+    match backend {
+        SchemaBackend::ClickHouse => clickhouse_type(expected) == clickhouse_type(actual),
+        SchemaBackend::Postgres => postgres_type(expected) == postgres_type(actual),
+        SchemaBackend::MySql => mysql_type(expected) == mysql_type(actual),
+        SchemaBackend::OpenSearch => expected == actual,
+    }
+}
+
+fn clickhouse_type(ty: &str) -> String {
+    let mut ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+    const LOW_CARDINALITY: &str = "LowCardinality(";
+    while let Some(start) = ty.find(LOW_CARDINALITY) {
+        let inner = start + LOW_CARDINALITY.len();
+        let mut depth = 1;
+        let Some(end) = ty[inner..].find(|c| {
+            depth += match c {
+                '(' => 1,
+                ')' => -1,
+                _ => 0,
+            };
+            depth == 0
+        }) else {
+            break;
+        };
+        ty.remove(inner + end);
+        ty.replace_range(start..inner, "");
+    }
+    let mut from = 0;
+    while let Some(start) = ty[from..].find("DateTime64(").map(|i| from + i) {
+        from = start + "DateTime64(".len();
+        if let (Some(comma), Some(end)) = (ty[from..].find(','), ty[from..].find(')')) {
+            if comma < end {
+                ty.replace_range(from + comma..from + end, "");
+            }
+        }
+    }
+    ty
+}
+
+fn postgres_type(ty: &str) -> String {
+    let ty = ty.to_ascii_lowercase();
+    match ty.as_str() {
+        "timestamptz" | "timestamp" | "timestamp with time zone" | "timestamp without time zone" => "timestamp",
+        "int4" | "int8" | "int" | "integer" | "bigint" => "integer",
+        "json" | "jsonb" => "json",
+        "text" | "varchar" | "character varying" => "text",
+        "float8" | "double precision" => "float8",
+        "bool" | "boolean" => "boolean",
+        _ => return ty,
+    }
+    .to_string()
+}
+
+fn mysql_type(ty: &str) -> String {
+    let ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+    match ty.as_str() {
+        "bool" | "boolean" => "tinyint(1)".to_string(),
+        _ => ty,
+    }
+}
+
+/// The canonical columns for `options`, after checking `options` except
+/// for the table name.
+fn checked_columns(backend: SchemaBackend, options: &SchemaOptions) -> Result<Vec<Column>, SchemaError> {
+    if options.version == 0 || options.version > SCHEMA_VERSION {
+        return Err(SchemaError::UnsupportedVersion(options.version));
+    }
+    let columns = canonical_columns(backend, options);
+    for (i, mapping) in options.columns.iter().enumerate() {
+        if mapping.field.contains(['\'', '"', '\\']) {
+            return Err(SchemaError::InvalidField(mapping.field.clone()));
+        }
+        if backend == SchemaBackend::OpenSearch {
+            continue;
+        }
+        check_identifier(&mapping.column)?;
+        let name = column_name(backend, &mapping.column);
+        let taken = columns.iter().any(|c| column_name(backend, c.name) == name)
+            || options.columns[..i].iter().any(|m| m.column == mapping.column);
+        if taken {
+            return Err(SchemaError::DuplicateColumn(mapping.column.clone()));
+        }
+    }
+    Ok(columns)
+}
+
+fn canonical_columns(backend: SchemaBackend, options: &SchemaOptions) -> Vec<Column> {
+    let clickhouse = backend == SchemaBackend::ClickHouse;
+    SCHEMA_FIELDS
+        .iter()
+        .filter(|(_, since)| *since <= options.version)
+        .filter(|(name, _)| !(clickhouse && *name == "service_name" && !options.shared))
+        .map(|(name, _)| Column {
+            name,
+            kind: field_kind(name),
+            required: matches!(*name, "timestamp" | "level" | "target" | "fields")
+                || (clickhouse && *name == "service_name"),
+        })
+        .collect()
+}
+
+/// Postgres and MySQL keep the timestamp in `ts`, as the Postgres sink
+/// expects.
+fn column_name(backend: SchemaBackend, name: &str) -> &str {
+    match (backend, name) {
+        (SchemaBackend::Postgres | SchemaBackend::MySql, "timestamp") => "ts",
+        _ => name,
+    }
+}
+
+fn check_identifier(name: &str) -> Result<(), SchemaError> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(SchemaError::InvalidName(name.to_string()))
+    }
+}
+
+fn create_table(backend: SchemaBackend, columns: &[Column], options: &SchemaOptions) -> String {
+    let mut lines: Vec<(String, String)> = columns
+        .iter()
+        .map(|column| (column_name(backend, column.name).to_string(), column_type(backend, column, options)))
+        .collect();
+    lines.extend(
+        options
+            .columns
+            .iter()
+            .map(|mapping| (mapping.column.clone(), mapped_column(backend, mapping))),
+    );
+    let width = lines.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let body = lines
+        .iter()
+        .map(|(name, ty)| format!("    {name:width$} {ty}"))
+        .collect::<Vec<_>>()
+        .join(",\n");
+
+    let suffix = match backend {
+        SchemaBackend::ClickHouse => {
+            let key = if columns.iter().any(|c| c.name == "service_name") {
+                "service_name, timestamp, level, target"
+            } else {
+                "timestamp, level, target"
+            };
+            format!(" ENGINE = MergeTree\nORDER BY ({key})")
+        }
+        SchemaBackend::MySql => " ENGINE = InnoDB DEFAULT CHARSET = utf8mb4".to_string(),
+        _ => String::new(),
+    };
+    format!("CREATE TABLE IF NOT EXISTS {} (\n{body}\n){suffix};\n", options.table)
+}
+
+fn column_type(backend: SchemaBackend, column: &Column, options: &SchemaOptions) -> String {
+    let ty = base_type(backend, column, options);
+    match backend {
+        SchemaBackend::ClickHouse => ty,
+        _ if column.required => format!("{ty} NOT NULL"),
+        _ => ty,
+    }
+}
+
+/// Type of `column`, with `Nullable` for ClickHouse but without `NOT NULL`.
+fn base_type(backend: SchemaBackend, column: &Column, options: &SchemaOptions) -> String {
+    let ty = match backend {
+        SchemaBackend::ClickHouse => match column.kind {
+            Kind::Timestamp => match options.timestamp_format {
+                TimestampFormat::DateTime64Millis => "DateTime64(3)",
+                TimestampFormat::EpochMillis | TimestampFormat::EpochMicros => "Int64",
+                TimestampFormat::Rfc3339 | TimestampFormat::Rfc3339Millis => "String",
+            },
+            Kind::Level | Kind::Text | Kind::Json => "String",
+            Kind::UInt32 => "UInt32",
+            Kind::UInt64 => "UInt64",
+            Kind::Map => "Map(String, String)",
+            Kind::Uuid => "UUID",
+        },
+        SchemaBackend::Postgres => match column.kind {
+            Kind::Timestamp => "TIMESTAMPTZ",
+            Kind::Level | Kind::Text => "TEXT",
+            Kind::UInt32 => "INT4",
+            Kind::UInt64 => "INT8",
+            Kind::Json | Kind::Map => "JSONB",
+            Kind::Uuid => "UUID",
+        },
+        SchemaBackend::MySql => match (column.kind, column.name) {
+            (Kind::Timestamp, _) => "DATETIME(6)",
+            (Kind::Level, _) => "VARCHAR(8)",
+            (Kind::Text, "trace_id") => "CHAR(32)",
+            (Kind::Text, "span_id") => "CHAR(16)",
+            (Kind::Text, "file" | "message" | "error_message") => "TEXT",
+            (Kind::Text, _) => "VARCHAR(255)",
+            (Kind::UInt32, _) => "INT UNSIGNED",
+            (Kind::UInt64, _) => "BIGINT UNSIGNED",
+            (Kind::Json | Kind::Map, _) => "JSON",
+            (Kind::Uuid, _) => "CHAR(36)",
+        },
+        SchemaBackend::OpenSearch => unreachable!("OpenSearch has no columns"),
+    };
+    match backend {
+        // ClickHouse has no `Nullable(Map(..))`; an empty map stands for none.
+        SchemaBackend::ClickHouse if !column.required && column.kind != Kind::Map => format!("Nullable({ty})"),
+        _ => ty.to_string(),
+    }
+}
+
+/// Type of a [`ColumnMapping`] column of `kind`.
+fn mapped_type(backend: SchemaBackend, kind: ColumnKind) -> &'static str {
+    match (backend, kind) {
+        (SchemaBackend::ClickHouse, ColumnKind::String) => "Nullable(String)",
+        (SchemaBackend::ClickHouse, ColumnKind::Int) => "Nullable(Int64)",
+        (SchemaBackend::ClickHouse, ColumnKind::Float) => "Nullable(Float64)",
+        (SchemaBackend::ClickHouse, ColumnKind::Bool) => "Nullable(Bool)",
+        (SchemaBackend::Postgres, ColumnKind::String) => "TEXT",
+        (SchemaBackend::Postgres, ColumnKind::Int) => "INT8",
+        (SchemaBackend::Postgres, ColumnKind::Float) => "FLOAT8",
+        (SchemaBackend::Postgres, ColumnKind::Bool) => "BOOLEAN",
+        (SchemaBackend::MySql, ColumnKind::String) => "VARCHAR(255)",
+        (SchemaBackend::MySql, ColumnKind::Int) => "BIGINT",
+        (SchemaBackend::MySql, ColumnKind::Float) => "DOUBLE",
+        (SchemaBackend::MySql, ColumnKind::Bool) => "BOOLEAN",
+        (SchemaBackend::OpenSearch, ColumnKind::String) => "keyword",
+        (SchemaBackend::OpenSearch, ColumnKind::Int) => "long",
+        (SchemaBackend::OpenSearch, ColumnKind::Float) => "double",
+        (SchemaBackend::OpenSearch, ColumnKind::Bool) => "boolean",
+    }
+}
+
+fn mapped_column(backend: SchemaBackend, mapping: &ColumnMapping) -> String {
+    let field = &mapping.field;
+    let ty = mapped_type(backend, mapping.kind);
+    match backend {
+        SchemaBackend::ClickHouse => format!("{ty} MATERIALIZED JSONExtract(fields, '{field}', '{ty}')"),
+        SchemaBackend::Postgres => {
+            let typed = |json_type: &str, value: &str| {
+                format!("{ty} GENERATED ALWAYS AS (CASE WHEN jsonb_typeof(fields->'{field}') = '{json_type}' THEN {value} END) STORED")
+            };
+            match mapping.kind {
+                ColumnKind::String => format!("{ty} GENERATED ALWAYS AS (fields->>'{field}') STORED"),
+                ColumnKind::Int => typed("number", &format!("(fields->>'{field}')::NUMERIC::INT8")),
+                ColumnKind::Float => typed("number", &format!("(fields->>'{field}')::FLOAT8")),
+                ColumnKind::Bool => typed("boolean", &format!("(fields->'{field}')::BOOLEAN")),
+            }
+        }
+        SchemaBackend::MySql => {
+            let value = format!("JSON_EXTRACT(fields, '$.\"{field}\"')");
+            let typed = |json_types: &str, converted: &str| {
+                format!("{ty} GENERATED ALWAYS AS (IF(JSON_TYPE({value}) IN ({json_types}), {converted}, NULL)) STORED")
+            };
+            match mapping.kind {
+                ColumnKind::String => format!("{ty} GENERATED ALWAYS AS (JSON_UNQUOTE({value})) STORED"),
+                ColumnKind::Int => typed("'INTEGER', 'UNSIGNED INTEGER'", &value),
+                ColumnKind::Float => typed("'INTEGER', 'UNSIGNED INTEGER', 'DOUBLE', 'DECIMAL'", &value),
+                ColumnKind::Bool => typed("'BOOLEAN'", &format!("JSON_UNQUOTE({value}) = 'true'")),
+            }
+        }
+        SchemaBackend::OpenSearch => unreachable!("OpenSearch has no columns"),
+    }
+}
+
+fn opensearch_template(columns: &[Column], options: &SchemaOptions) -> String {
+    let properties: serde_json::Map<String, serde_json::Value> = columns
+        .iter()
+        .map(|column| (column.name.to_string(), opensearch_mapping(column, options)))
+        .collect();
+    let template = serde_json::json!({
+        "index_patterns": [DestinationTemplate::new(&options.table).pattern()],
+        "template": { "mappings": { "properties": properties } },
+    });
+    let mut out = serde_json::to_string_pretty(&template).unwrap_or_default();
+    out.push('\n');
+    out
+}
+
+fn opensearch_mapping(column: &Column, options: &SchemaOptions) -> serde_json::Value {
+    use serde_json::{json, Map, Value};
+
+    match (column.kind, column.name) {
+        (Kind::Timestamp, _) => match options.timestamp_format {
+            TimestampFormat::Rfc3339 => json!({ "type": "date_nanos" }),
+            TimestampFormat::Rfc3339Millis => json!({ "type": "date", "format": "strict_date_optional_time" }),
+            TimestampFormat::EpochMillis => json!({ "type": "date", "format": "epoch_millis" }),
+            TimestampFormat::EpochMicros => json!({ "type": "long" }),
+            TimestampFormat::DateTime64Millis => json!({ "type": "date", "format": "yyyy-MM-dd HH:mm:ss.SSS" }),
+        },
+        (Kind::Text, "message" | "error_message") => json!({ "type": "text" }),
+        (Kind::Level | Kind::Text | Kind::Uuid, _) => json!({ "type": "keyword" }),
+        (Kind::UInt32, _) => json!({ "type": "integer" }),
+        (Kind::UInt64, _) => json!({ "type": "long" }),
+        (Kind::Json, _) => {
+            let fields: Map<String, Value> = options
+                .columns
+                .iter()
+                .map(|mapping| {
+                    let ty = mapped_type(SchemaBackend::OpenSearch, mapping.kind);
+                    (mapping.field.clone(), json!({ "type": ty }))
+                })
+                .collect();
+            json!({ "type": "object", "properties": fields })
+        }
+        (Kind::Map, _) => json!({ "type": "object" }),
+    }
+}
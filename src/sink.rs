@@ -1,13 +1,142 @@
+use crate::encoding::SerializedBatch;
 use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
 use async_trait::async_trait;
 use std::error::Error;
+use std::time::Duration;
+
+/// Boxed error carried inside [`SinkError`] variants.
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Failure reported by a [`LogSink`].
+///
+/// The variant tells the layer's retry loop what to do with the failed
+/// record: retryable failures are retried with backoff, poison records
+/// are dropped so they do not block the rest of the batch.
+#[derive(thiserror::Error, Debug)]
+pub enum SinkError {
+    /// Network error, timeout or server-side failure; retried with
+    /// exponential backoff.
+    #[error("transient sink failure: {0}")]
+    Transient(BoxError),
+
+    /// The backend rejected the record itself (e.g. HTTP 400, constraint
+    /// violation); retrying cannot succeed, so the record is dropped.
+    #[error("record rejected by sink: {0}")]
+    Permanent(BoxError),
+
+    /// The record could not be encoded; dropped like [`SinkError::Permanent`].
+    #[error("failed to serialize record: {0}")]
+    Serialization(BoxError),
+
+    /// Credentials were rejected; retried with backoff since they are
+    /// usually fixed by rotating secrets rather than by dropping data.
+    #[error("sink authentication failed: {0}")]
+    Auth(BoxError),
+
+    /// The backend asked to slow down; retried after `retry_after` if
+    /// the server provided it, otherwise after the regular backoff.
+    #[error("rate limited by sink")]
+    RateLimited { retry_after: Option<Duration> },
+}
+
+impl SinkError {
+    /// Wrap any error as [`SinkError::Transient`].
+    pub fn transient(err: impl Into<BoxError>) -> Self {
+        SinkError::Transient(err.into())
+    }
+
+    /// Wrap any error as [`SinkError::Permanent`].
+    pub fn permanent(err: impl Into<BoxError>) -> Self {
+        SinkError::Permanent(err.into())
+    }
+
+    /// Whether sending the same record again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        !matches!(self, SinkError::Permanent(_) | SinkError::Serialization(_))
+    }
+
+    /// Classify a non-success HTTP response.
+    ///
+    /// **Parameters**
+    /// - `status`: HTTP status code.
+    /// - `retry_after`: parsed `Retry-After` header, if any.
+    /// - `message`: human-readable description, e.g. the response body.
+    ///
+    /// **Returns**
+    /// - `Auth` for 401/403, `RateLimited` for 429, `Transient` for 408
+    ///   and 5xx, `Permanent` for the remaining statuses.
+    pub fn from_http_status(status: u16, retry_after: Option<Duration>, message: String) -> Self {
+        match status {
+            401 | 403 => SinkError::Auth(message.into()),
+            429 => SinkError::RateLimited { retry_after },
+            408 | 500..=599 => SinkError::Transient(message.into()),
+            _ => SinkError::Permanent(message.into()),
+        }
+    }
+}
+
+impl From<BoxError> for SinkError {
+    fn from(err: BoxError) -> Self {
+        SinkError::Transient(err)
+    }
+}
+
+impl From<serde_json::Error> for SinkError {
+    fn from(err: serde_json::Error) -> Self {
+        SinkError::Serialization(Box::new(err))
+    }
+}
+
+impl From<std::io::Error> for SinkError {
+    fn from(err: std::io::Error) -> Self {
+        SinkError::Transient(Box::new(err))
+    }
+}
+
+#[cfg(feature = "reqwest")]
+impl From<reqwest::Error> for SinkError {
+    fn from(err: reqwest::Error) -> Self {
+        match err.status() {
+            Some(status) => SinkError::from_http_status(status.as_u16(), None, err.to_string()),
+            None if err.is_builder() => SinkError::Permanent(Box::new(err)),
+            None => SinkError::Transient(Box::new(err)),
+        }
+    }
+}
 
 /// Asynchronous destination for [`LogRecord`]s produced by the logging layer.
 ///
 /// Implementations are responsible for transporting records to a concrete
 /// backend (ClickHouse, Loki, stdout, etc). The layer calls `send` from a
 /// background task and never awaits it on the application thread.
-#[async_trait]
+///
+/// **Delivery guarantees**
+///
+/// The layer's worker delivers records at least once:
+/// - A record is done only when the sink returns `Ok(())` for it or a
+///   non-retryable error (a panic counts as one). Every retryable error,
+///   timeouts included, means "not known to be stored" and the record is
+///   sent again, so a backend that stored it before failing receives it
+///   twice with the same [`LogRecord::event_id`]; deduplicate on it.
+/// - A batch is released only after each of its records is done; later
+///   records of the batch wait for a failing one.
+/// - A batch goes to [`LogSink::send_batch`] once; after a retryable
+///   error its records are sent, and retried, one by one, so records the
+///   failed batch stored may arrive twice.
+/// - Return `Ok(())` only once the record is stored, or held by the sink
+///   in a buffer it retries from itself.
+///
+/// Records are lost when dropped before reaching the worker (see
+/// [`crate::layer::DropReason`]), when the sink rejects them, or when the
+/// process exits with records still queued or in flight; see
+/// [`crate::init::LayerConfig::persist_unsent`] for the last case.
+///
+/// On `wasm32` the trait is declared with `#[async_trait(?Send)]`, since
+/// `fetch`-based futures are not `Send`; implement it with the same
+/// attribute there.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
 pub trait LogSink: Send + Sync {
     /// Send a single log record to the underlying backend.
     ///
@@ -16,14 +145,61 @@ pub trait LogSink: Send + Sync {
     ///
     /// **Returns**
     /// - `Ok(())` if the record was accepted by the backend.
-    /// - `Err(..)` if the backend failed (network error, serialization
-    ///   error, HTTP status, etc.). The layer will treat this as a
-    ///   transient failure and retry the batch with backoff.
+    /// - `Err(..)` if the backend failed. Retryable [`SinkError`]s make
+    ///   the layer retry the record with backoff (honoring
+    ///   [`SinkError::RateLimited::retry_after`]); permanent ones drop it.
     ///
     /// This method is called from a Tokio task that owns the batching
     /// loop. Implementations should strive to be non-blocking and use
     /// async I/O under the hood.
-    async fn send(&self, record: &LogRecord) -> Result<(), Box<dyn Error + Send + Sync>>;
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError>;
+
+    /// Send record `index` of `batch`; this is what the layer's worker
+    /// calls.
+    ///
+    /// Sinks that serialize records override it to take the payload from
+    /// [`SerializedBatch`], which caches it across retries of the record.
+    /// Results are interpreted like those of [`LogSink::send`].
+    ///
+    /// Default implementation calls [`LogSink::send`] with the record.
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.send(batch.record(index)).await
+    }
+
+    /// Send several records at once.
+    ///
+    /// The layer's worker calls it with each batch of more than one
+    /// record. If it panics or returns a retryable error, the worker
+    /// falls back to [`LogSink::send_serialized`] for each record of the
+    /// batch, retrying them one by one; a non-retryable error ends the
+    /// batch. [`crate::buffered::BufferedSink`] calls it with the records
+    /// it accumulated.
+    ///
+    /// Backends with a bulk API override it to write the batch in one
+    /// request.
+    ///
+    /// **Returns**
+    /// - `Ok(())` if every record was accepted.
+    /// - A retryable `Err(..)` if the batch should be sent again; records
+    ///   accepted before the failure may then arrive twice.
+    /// - A non-retryable `Err(..)` if records were rejected and the batch
+    ///   must not be retried.
+    ///
+    /// Default implementation calls [`LogSink::send`] for each record in
+    /// order, stopping at the first retryable error; records rejected
+    /// with a non-retryable one are skipped, and the last such error is
+    /// returned once the others are sent.
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        let mut rejected = None;
+        for record in records {
+            match self.send(record).await {
+                Ok(()) => {}
+                Err(e) if e.is_retryable() => return Err(e),
+                Err(e) => rejected = Some(e),
+            }
+        }
+        rejected.map_or(Ok(()), Err)
+    }
 
     /// Flush any buffered records, if the backend implements buffering.
     ///
@@ -32,7 +208,79 @@ pub trait LogSink: Send + Sync {
     /// - `Err(..)` if the backend reported an error during flush.
     ///
     /// Default implementation is a no-op.
-    async fn flush(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+    async fn flush(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Check that the backend is reachable and accepts our credentials,
+    /// e.g. for readiness probes or [`crate::init::LayerConfig::startup_check`].
+    ///
+    /// **Returns**
+    /// - `Ok(())` if the backend answered a cheap probe request.
+    /// - `Err(..)` describing why the backend is not usable.
+    ///
+    /// Default implementation always succeeds.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Compare the tables or indices the sink writes to with the schema
+    /// it expects, e.g. for [`crate::init::LayerConfig::schema_check`].
+    ///
+    /// **Returns**
+    /// - One [`SchemaDrift`] per destination that differs; empty if all
+    ///   match or the backend has no schema to compare.
+    /// - `Err(..)` if the schema could not be read, e.g. because the
+    ///   table does not exist.
+    ///
+    /// Default implementation reports no drift.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        Ok(Vec::new())
+    }
+
+    /// Release backend resources: flush producer queues, close
+    /// connections and finish in-flight requests.
+    ///
+    /// Called once by [`crate::handle::LayerHandle::shutdown`] after the
+    /// final batch and [`LogSink::flush`]; `send` is not called afterwards.
+    ///
+    /// Default implementation is a no-op.
+    async fn shutdown(&self) -> Result<(), SinkError> {
         Ok(())
     }
 }
+
+/// Forwards to the shared sink, so a sink built as `Arc<dyn LogSink>`
+/// (e.g. by [`crate::backend::make_sink_from_config_async`]) can be
+/// wrapped by decorators generic over their inner sink.
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl<S: LogSink + ?Sized> LogSink for std::sync::Arc<S> {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        (**self).send(record).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        (**self).send_serialized(batch, index).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        (**self).send_batch(records).await
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        (**self).flush().await
+    }
+
+    async fn health_check(&self) -> Result<(), SinkError> {
+        (**self).health_check().await
+    }
+
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        (**self).schema_drift().await
+    }
+
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        (**self).shutdown().await
+    }
+}
@@ -0,0 +1,136 @@
+//! Newline-delimited JSON over a local socket.
+//!
+//! [`SocketSink`] writes each record as one JSON line to a TCP or Unix
+//! socket, typically that of `log-recorder-agent` (feature `agent`),
+//! which forwards the records of all processes on a host to the backend.
+
+use crate::record::LogRecord;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+/// Address of a [`SocketSink`] or of the agent's listener:
+/// `tcp://host:port` or, on Unix, `unix:///path/to/socket`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddress {
+    Tcp(String),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Error returned when parsing a [`SocketAddress`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid socket address {0:?}, expected tcp://host:port or unix:///path")]
+pub struct SocketAddressError(pub String);
+
+impl FromStr for SocketAddress {
+    type Err = SocketAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SocketAddressError(s.to_string());
+        match s.split_once("://") {
+            Some(("tcp", addr)) if !addr.is_empty() => Ok(SocketAddress::Tcp(addr.to_string())),
+            #[cfg(unix)]
+            Some(("unix", path)) if !path.is_empty() => Ok(SocketAddress::Unix(PathBuf::from(path))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for SocketAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketAddress::Tcp(addr) => write!(f, "tcp://{}", addr),
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+type Stream = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// [`LogSink`] writing records as JSON lines to a socket.
+///
+/// The connection is opened on the first send and again after a write
+/// failed. `Ok(())` means the record was written to the socket, not that
+/// the receiver stored it: records in flight when the receiver goes away
+/// are lost, and a record whose write failed midway may reach it as a
+/// torn line, which the agent skips before the record is sent again.
+pub struct SocketSink {
+    address: SocketAddress,
+    stream: Mutex<Option<Stream>>,
+}
+
+impl SocketSink {
+    /// Write to `address`; nothing is connected until the first send.
+    pub fn new(address: SocketAddress) -> Self {
+        Self { address, stream: Mutex::new(None) }
+    }
+
+    async fn connect(&self) -> std::io::Result<Stream> {
+        Ok(match &self.address {
+            SocketAddress::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr.as_str()).await?;
+                stream.set_nodelay(true)?;
+                Box::new(stream)
+            }
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => Box::new(tokio::net::UnixStream::connect(path).await?),
+        })
+    }
+
+    /// Write `data` to the socket, connecting first if needed; a failed
+    /// write closes the connection.
+    async fn write(&self, data: &[u8]) -> Result<(), SinkError> {
+        let mut stream = self.stream.lock().await;
+        let connected = match stream.as_mut() {
+            Some(connected) => connected,
+            None => stream.insert(self.connect().await?),
+        };
+        if let Err(e) = connected.write_all(data).await {
+            *stream = None;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for SocketSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.write(&line).await
+    }
+
+    /// Writes all records with one write.
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        let mut data = Vec::new();
+        for record in records {
+            serde_json::to_writer(&mut data, record)?;
+            data.push(b'\n');
+        }
+        self.write(&data).await
+    }
+
+    /// Connects if not connected yet.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        let mut stream = self.stream.lock().await;
+        if stream.is_none() {
+            *stream = Some(self.connect().await?);
+        }
+        Ok(())
+    }
+
+    /// Closes the connection.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        if let Some(mut stream) = self.stream.lock().await.take() {
+            stream.shutdown().await?;
+        }
+        Ok(())
+    }
+}
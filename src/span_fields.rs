@@ -0,0 +1,70 @@
+use crate::fields::Fields;
+use crate::visitor::FieldVisitor;
+
+/// Which enclosing spans contribute their fields to a record, see
+/// [`SpanFieldsConfig`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpanFieldInheritance {
+    /// Records only carry the event's own fields. This is the default.
+    #[default]
+    None,
+    /// Fields of every span from the current one up to the root.
+    All,
+    /// Fields of the `n` spans closest to the event.
+    Nearest(usize),
+}
+
+/// How fields of the spans an event is emitted in are copied onto its
+/// record.
+///
+/// The event's own fields always take precedence over span fields; among
+/// spans, `child_overrides` decides whether the span closer to the event
+/// wins (the default) or the outer one does. Inherited fields count
+/// against [`crate::limits::RecordLimits`] like any other field.
+///
+/// **Fields**
+/// - `inherit`: which spans contribute, see [`SpanFieldInheritance`].
+/// - `child_overrides`: when two spans set the same field, keep the value
+///   of the inner span (`true`) or of the outer one (`false`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpanFieldsConfig {
+    pub inherit: SpanFieldInheritance,
+    pub child_overrides: bool,
+}
+
+impl Default for SpanFieldsConfig {
+    fn default() -> Self {
+        Self {
+            inherit: SpanFieldInheritance::None,
+            child_overrides: true,
+        }
+    }
+}
+
+impl SpanFieldsConfig {
+    /// Number of enclosing spans, nearest first, whose fields are
+    /// inherited; `0` when inheritance is off.
+    pub(crate) fn depth(&self) -> usize {
+        match self.inherit {
+            SpanFieldInheritance::None => 0,
+            SpanFieldInheritance::All => usize::MAX,
+            SpanFieldInheritance::Nearest(n) => n,
+        }
+    }
+}
+
+/// Recorded fields of a span, stored in its extensions while span field
+/// inheritance is enabled.
+#[derive(Debug, Default)]
+pub(crate) struct SpanFields {
+    pub(crate) fields: Fields,
+    /// A span's `message` field, which is not inherited.
+    message: Option<String>,
+}
+
+impl SpanFields {
+    /// Visitor recording span values into these fields.
+    pub(crate) fn visitor(&mut self) -> FieldVisitor<'_> {
+        FieldVisitor { fields: &mut self.fields, message: &mut self.message }
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::timestamp::Timestamp;
+use std::sync::atomic::AtomicI64;
+use std::sync::{Arc, Mutex};
+
+/// Maximum number of distinct targets tracked in
+/// [`LayerStats::dropped_by_target`]; drops from further targets are
+/// accounted under [`OTHER_TARGETS`].
+pub const MAX_TRACKED_TARGETS: usize = 128;
+
+/// Key aggregating drops of targets beyond [`MAX_TRACKED_TARGETS`].
+pub const OTHER_TARGETS: &str = "<other>";
+
+/// Point-in-time copy of the layer's counters.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LayerStats {
+    /// Events seen by the layer, before level filtering.
+    pub total_events: u64,
+    /// Records taken off the channel by the worker.
+    pub enqueued_events: u64,
+    /// Records lost before reaching the worker.
+    pub dropped_events: u64,
+    /// Records discarded by the level policy, the filter or a record
+    /// processor.
+    pub filtered_events: u64,
+    /// `dropped_events` broken down by `target`, so the noisy subsystem
+    /// starving the channel can be identified.
+    pub dropped_by_target: BTreeMap<String, u64>,
+    /// Times the worker panicked, outside of the sink's sends, and was
+    /// restarted on the same channel.
+    pub worker_restarts: u64,
+    /// When the worker last went through its loop: on every received
+    /// record, flush tick and command. An old value means it is stuck,
+    /// typically waiting on a wedged sink; `None` before it started.
+    pub last_heartbeat: Option<Timestamp>,
+}
+
+/// Shared counters backing [`LayerStats`].
+#[derive(Clone, Default)]
+pub(crate) struct StatsSource {
+    pub(crate) total_events: Arc<AtomicU64>,
+    pub(crate) enqueued_events: Arc<AtomicU64>,
+    pub(crate) dropped_events: Arc<AtomicU64>,
+    pub(crate) filtered_events: Arc<AtomicU64>,
+    pub(crate) worker_restarts: Arc<AtomicU64>,
+    /// Unix milliseconds of [`LayerStats::last_heartbeat`], `0` before the
+    /// first one.
+    pub(crate) heartbeat: Arc<AtomicI64>,
+    dropped_by_target: Arc<Mutex<HashMap<String, u64>>>,
+}
+
+impl StatsSource {
+    /// Count one dropped record of `target`.
+    ///
+    /// Only called on the (already slow) drop path, so a mutex is fine.
+    pub(crate) fn record_drop(&self, target: &str) {
+        self.dropped_events.fetch_add(1, Ordering::Relaxed);
+        let mut map = self.dropped_by_target.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = map.get_mut(target) {
+            *count += 1;
+        } else if map.len() < MAX_TRACKED_TARGETS {
+            map.insert(target.to_string(), 1);
+        } else {
+            *map.entry(OTHER_TARGETS.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> LayerStats {
+        let dropped_by_target = self
+            .dropped_by_target
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(target, count)| (target.clone(), *count))
+            .collect();
+        LayerStats {
+            total_events: self.total_events.load(Ordering::Relaxed),
+            enqueued_events: self.enqueued_events.load(Ordering::Relaxed),
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            filtered_events: self.filtered_events.load(Ordering::Relaxed),
+            dropped_by_target,
+            worker_restarts: self.worker_restarts.load(Ordering::Relaxed),
+            last_heartbeat: match self.heartbeat.load(Ordering::Relaxed) {
+                0 => None,
+                millis => Timestamp::from_timestamp_millis(millis),
+            },
+        }
+    }
+}
+
+impl std::fmt::Debug for StatsSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
@@ -0,0 +1,333 @@
+//! `std::time` based [`Timestamp`], used when the `chrono` feature is off.
+//!
+//! Mirrors the part of `chrono::DateTime<Utc>` the crate relies on, with
+//! the same method names, so code written against one compiles against
+//! the other. Serialized forms are identical to chrono's:
+//! `2024-05-01T12:30:00.123456Z` through `serde`, `+00:00` from
+//! [`Timestamp::to_rfc3339`], with 0, 3, 6 or 9 fractional digits.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{self, Write};
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+const SECS_PER_DAY: i64 = 86_400;
+
+/// UTC point in time with nanosecond precision.
+///
+/// Stored as whole seconds since the Unix epoch (negative before it) plus
+/// the nanoseconds into that second.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Timestamp {
+    secs: i64,
+    nanos: u32,
+}
+
+/// Error parsing an RFC 3339 date-time into a [`Timestamp`].
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid RFC 3339 timestamp")]
+pub struct ParseTimestampError;
+
+impl Timestamp {
+    /// The Unix epoch, `1970-01-01T00:00:00Z`.
+    pub const UNIX_EPOCH: Timestamp = Timestamp { secs: 0, nanos: 0 };
+
+    /// Current system time.
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    /// Build a timestamp from seconds since the epoch and the nanoseconds
+    /// into that second.
+    ///
+    /// **Returns**
+    /// - `None` if `nanos` is not below one second.
+    pub fn from_timestamp(secs: i64, nanos: u32) -> Option<Self> {
+        (i64::from(nanos) < NANOS_PER_SEC).then_some(Timestamp { secs, nanos })
+    }
+
+    /// Build a timestamp from milliseconds since the epoch.
+    pub fn from_timestamp_millis(millis: i64) -> Option<Self> {
+        Self::from_timestamp(millis.div_euclid(1000), (millis.rem_euclid(1000) * 1_000_000) as u32)
+    }
+
+    /// Build a timestamp from nanoseconds since the epoch.
+    pub fn from_timestamp_nanos(nanos: i64) -> Self {
+        Timestamp {
+            secs: nanos.div_euclid(NANOS_PER_SEC),
+            nanos: nanos.rem_euclid(NANOS_PER_SEC) as u32,
+        }
+    }
+
+    /// Whole seconds since the epoch, rounded towards negative infinity.
+    pub fn timestamp(&self) -> i64 {
+        self.secs
+    }
+
+    /// Nanoseconds into the current second.
+    pub fn timestamp_subsec_nanos(&self) -> u32 {
+        self.nanos
+    }
+
+    /// Milliseconds since the epoch.
+    pub fn timestamp_millis(&self) -> i64 {
+        self.secs * 1000 + i64::from(self.nanos / 1_000_000)
+    }
+
+    /// Microseconds since the epoch.
+    pub fn timestamp_micros(&self) -> i64 {
+        self.secs * 1_000_000 + i64::from(self.nanos / 1000)
+    }
+
+    /// Nanoseconds since the epoch.
+    ///
+    /// **Returns**
+    /// - `None` outside the range representable in `i64` (years
+    ///   1677..2262).
+    pub fn timestamp_nanos_opt(&self) -> Option<i64> {
+        self.secs.checked_mul(NANOS_PER_SEC)?.checked_add(i64::from(self.nanos))
+    }
+
+    /// RFC 3339 with as many fractional digits as needed and a `+00:00`
+    /// offset, e.g. `2024-05-01T12:30:00.123456789+00:00`.
+    pub fn to_rfc3339(&self) -> String {
+        let mut out = String::with_capacity(32);
+        self.write_to(&mut out, 'T', Fraction::Auto, "+00:00");
+        out
+    }
+
+    /// Write the date and time, `separator` between them, the fractional
+    /// seconds as `fraction` asks and a final `suffix`.
+    pub(crate) fn write_to(&self, out: &mut String, separator: char, fraction: Fraction, suffix: &str) {
+        let (year, month, day) = civil_from_days(self.secs.div_euclid(SECS_PER_DAY));
+        let secs_of_day = self.secs.rem_euclid(SECS_PER_DAY);
+        let (hour, minute, second) = (secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60);
+
+        // As chrono, years outside 0..=9999 carry an explicit sign.
+        if (0..=9999).contains(&year) {
+            let _ = write!(out, "{year:04}");
+        } else {
+            let _ = write!(out, "{year:+05}");
+        }
+        let _ = write!(out, "-{month:02}-{day:02}{separator}{hour:02}:{minute:02}:{second:02}");
+        match fraction {
+            Fraction::Millis => {
+                let _ = write!(out, ".{:03}", self.nanos / 1_000_000);
+            }
+            Fraction::Auto if self.nanos == 0 => {}
+            Fraction::Auto if self.nanos.is_multiple_of(1_000_000) => {
+                let _ = write!(out, ".{:03}", self.nanos / 1_000_000);
+            }
+            Fraction::Auto if self.nanos.is_multiple_of(1000) => {
+                let _ = write!(out, ".{:06}", self.nanos / 1000);
+            }
+            Fraction::Auto => {
+                let _ = write!(out, ".{:09}", self.nanos);
+            }
+        }
+        out.push_str(suffix);
+    }
+}
+
+/// Fractional seconds written by [`Timestamp::write_to`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Fraction {
+    /// 0, 3, 6 or 9 digits, the fewest that keep full precision.
+    Auto,
+    /// Always 3 digits.
+    Millis,
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => Timestamp::UNIX_EPOCH + since,
+            Err(e) => {
+                let before = e.duration();
+                let secs = -(before.as_secs() as i64);
+                match before.subsec_nanos() {
+                    0 => Timestamp { secs, nanos: 0 },
+                    nanos => Timestamp { secs: secs - 1, nanos: NANOS_PER_SEC as u32 - nanos },
+                }
+            }
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(ts: Timestamp) -> Self {
+        if ts.secs >= 0 {
+            UNIX_EPOCH + Duration::new(ts.secs as u64, ts.nanos)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(ts.secs.unsigned_abs()) + Duration::from_nanos(u64::from(ts.nanos))
+        }
+    }
+}
+
+impl Add<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    /// Panics if the result does not fit the timestamp range.
+    fn add(self, rhs: Duration) -> Timestamp {
+        let nanos = self.nanos + rhs.subsec_nanos();
+        let carry = i64::from(nanos >= NANOS_PER_SEC as u32);
+        let secs = i64::try_from(rhs.as_secs())
+            .ok()
+            .and_then(|secs| self.secs.checked_add(secs))
+            .and_then(|secs| secs.checked_add(carry))
+            .expect("timestamp overflow");
+        Timestamp { secs, nanos: nanos % NANOS_PER_SEC as u32 }
+    }
+}
+
+impl AddAssign<Duration> for Timestamp {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self = *self + rhs;
+    }
+}
+
+/// `2024-05-01T12:30:00.123Z`, as chrono's `Debug`.
+impl fmt::Debug for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::with_capacity(32);
+        self.write_to(&mut out, 'T', Fraction::Auto, "Z");
+        f.write_str(&out)
+    }
+}
+
+/// `2024-05-01 12:30:00.123 UTC`, as chrono's `Display`.
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::with_capacity(32);
+        self.write_to(&mut out, ' ', Fraction::Auto, " UTC");
+        f.write_str(&out)
+    }
+}
+
+/// Parses RFC 3339 date-times with any UTC offset, e.g.
+/// `2024-05-01T12:30:00Z` or `2024-05-01 15:30:00.5+03:00`. Leap seconds
+/// are not supported.
+impl FromStr for Timestamp {
+    type Err = ParseTimestampError;
+
+    fn from_str(s: &str) -> Result<Self, ParseTimestampError> {
+        parse_rfc3339(s.as_bytes()).ok_or(ParseTimestampError)
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut out = String::with_capacity(32);
+        self.write_to(&mut out, 'T', Fraction::Auto, "Z");
+        serializer.serialize_str(&out)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an RFC 3339 formatted date and time string")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Timestamp, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+fn parse_rfc3339(s: &[u8]) -> Option<Timestamp> {
+    fn digits(s: &[u8], range: std::ops::Range<usize>) -> Option<i64> {
+        s.get(range)?
+            .iter()
+            .try_fold(0i64, |acc, &b| b.is_ascii_digit().then(|| acc * 10 + i64::from(b - b'0')))
+    }
+    let expect = |i: usize, b: u8| s.get(i).is_some_and(|c| c.eq_ignore_ascii_case(&b));
+
+    if !(expect(4, b'-') && expect(7, b'-') && (expect(10, b'T') || expect(10, b' ')) && expect(13, b':') && expect(16, b':')) {
+        return None;
+    }
+    let (year, month, day) = (digits(s, 0..4)?, digits(s, 5..7)?, digits(s, 8..10)?);
+    let (hour, minute, second) = (digits(s, 11..13)?, digits(s, 14..16)?, digits(s, 17..19)?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+
+    let mut i = 19;
+    let mut nanos = 0;
+    if s.get(i) == Some(&b'.') {
+        let start = i + 1;
+        i = start;
+        while s.get(i).is_some_and(u8::is_ascii_digit) {
+            i += 1;
+        }
+        if i == start {
+            return None;
+        }
+        // Digits past nanosecond precision are ignored.
+        let significant = (i - start).min(9);
+        nanos = digits(s, start..start + significant)? * 10i64.pow((9 - significant) as u32);
+    }
+
+    let offset = match s.get(i..)? {
+        [b'Z' | b'z'] => 0,
+        [sign @ (b'+' | b'-'), _, _, b':', _, _] => {
+            let (hours, minutes) = (digits(s, i + 1..i + 3)?, digits(s, i + 4..i + 6)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return None,
+    };
+
+    let secs = days_from_civil(year, month, day) * SECS_PER_DAY + hour * 3600 + minute * 60 + second - offset;
+    Some(Timestamp { secs, nanos: nanos as u32 })
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the epoch of a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of [`days_from_civil`]: `(year, month, day)` of a day count.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
@@ -0,0 +1,47 @@
+use crate::encoding::{PayloadEncoding, SerializedBatch};
+use crate::record::LogRecord;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::io::{self, Write};
+
+/// [`LogSink`] printing records as JSON Lines to stdout, for local
+/// development and CI logs, or for a collector reading the container's
+/// output.
+///
+/// Unlike [`crate::init::LayerConfig::enable_stdout`], which prints
+/// through a `fmt` layer for humans, the lines are complete
+/// [`LogRecord`]s, exactly as other sinks receive them. Each batch is
+/// written under one lock of stdout, so its lines stay together.
+#[derive(Debug, Clone, Default)]
+pub struct StdoutSink;
+
+impl StdoutSink {
+    fn write(&self, lines: &[u8]) -> Result<(), SinkError> {
+        io::stdout().lock().write_all(lines)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LogSink for StdoutSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.write(&line)
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        let mut line = batch.encoded(index, PayloadEncoding::Json)?.to_vec();
+        line.push(b'\n');
+        self.write(&line)
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.write(&SerializedBatch::new(records).json_lines()?)
+    }
+
+    async fn flush(&self) -> Result<(), SinkError> {
+        io::stdout().flush()?;
+        Ok(())
+    }
+}
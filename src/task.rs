@@ -0,0 +1,128 @@
+//! Recording tokio tasks that panic or are cancelled.
+//!
+//! A panic in a spawned task only reaches whoever awaits its
+//! `JoinHandle`, and a background task is often never awaited, so its
+//! failure leaves no record. [`spawn_logged`] spawns a task that emits an
+//! `ERROR` event when it panics or is dropped before completing;
+//! [`log_join_error`] does the same for the `JoinError` of a task spawned
+//! otherwise.
+//!
+//! The events have target [`TASK_TARGET`] and the fields `task` (the
+//! given name), `error.kind` (`panic` or `cancelled`) and, for panics,
+//! `error.message` with the panic payload, which the layer moves to
+//! [`LogRecord::error_kind`] and [`LogRecord::error_message`].
+//!
+//! [`LogRecord::error_kind`]: crate::record::LogRecord::error_kind
+//! [`LogRecord::error_message`]: crate::record::LogRecord::error_message
+
+use std::any::Any;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::task::{JoinError, JoinHandle};
+
+/// Target of the events emitted for failed tasks.
+pub const TASK_TARGET: &str = "task";
+
+/// Spawn `future` on the current runtime like `tokio::spawn`, recording
+/// it if it panics or is cancelled.
+///
+/// The panic still propagates to the returned handle, so awaiting it
+/// gives the usual `JoinError`. A task counts as cancelled when it is
+/// dropped unfinished: aborted through its handle, or still running when
+/// the runtime shuts down. Like `tokio::spawn`, panics outside a
+/// runtime.
+///
+/// **Parameters**
+/// - `name`: the `task` field of the event.
+pub fn spawn_logged<F>(name: impl Into<Arc<str>>, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(Logged {
+        name: name.into(),
+        future: Box::pin(future),
+        finished: false,
+    })
+}
+
+/// Record a failed task from its `JoinError`, for tasks not spawned with
+/// [`spawn_logged`].
+///
+/// ```ignore
+/// if let Err(e) = handle.await {
+///     log_join_error("refresh-cache", &e);
+/// }
+/// ```
+pub fn log_join_error(name: &str, err: &JoinError) {
+    if err.is_cancelled() {
+        cancelled(name);
+    } else {
+        tracing::error!(
+            target: TASK_TARGET,
+            task = name,
+            error.kind = "panic",
+            error.message = %err,
+            "task {} panicked",
+            name
+        );
+    }
+}
+
+struct Logged<F: Future> {
+    name: Arc<str>,
+    future: Pin<Box<F>>,
+    finished: bool,
+}
+
+impl<F: Future> Future for Logged<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(output)) => {
+                self.finished = true;
+                Poll::Ready(output)
+            }
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => {
+                self.finished = true;
+                let message = payload_str(&*payload);
+                tracing::error!(
+                    target: TASK_TARGET,
+                    task = &*self.name,
+                    error.kind = "panic",
+                    error.message = message,
+                    "task {} panicked",
+                    self.name
+                );
+                panic::resume_unwind(payload)
+            }
+        }
+    }
+}
+
+impl<F: Future> Drop for Logged<F> {
+    fn drop(&mut self) {
+        if !self.finished {
+            cancelled(&self.name);
+        }
+    }
+}
+
+fn cancelled(name: &str) {
+    tracing::error!(target: TASK_TARGET, task = name, error.kind = "cancelled", "task {} was cancelled", name);
+}
+
+fn payload_str(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&'static str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "<non-string panic payload>"
+    }
+}
@@ -0,0 +1,100 @@
+use crate::diagnostics::DIAGNOSTICS_TARGET;
+use crate::encoding::SerializedBatch;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::Arc;
+
+/// [`LogSink`] that sends every record to all of its sinks in order, e.g.
+/// ClickHouse plus a local copy.
+///
+/// A send succeeds once any sink has accepted it, so a failing copy does
+/// not make the layer retry, and duplicate, the records the others
+/// already took; its errors are only logged to the diagnostics target.
+/// When every sink fails, the error of the first one is returned.
+pub struct TeeSink {
+    sinks: Vec<Arc<dyn LogSink>>,
+}
+
+impl TeeSink {
+    /// Send to each of `sinks`.
+    pub fn new(sinks: Vec<Arc<dyn LogSink>>) -> Self {
+        Self { sinks }
+    }
+
+    async fn each<'a, F, Fut>(&'a self, send: F) -> Result<(), SinkError>
+    where
+        F: Fn(&'a dyn LogSink) -> Fut,
+        Fut: Future<Output = Result<(), SinkError>>,
+    {
+        let mut first_error = None;
+        let mut accepted = false;
+        for (idx, sink) in self.sinks.iter().enumerate() {
+            match send(&**sink).await {
+                Ok(()) => accepted = true,
+                Err(e) => {
+                    tracing::warn!(target: DIAGNOSTICS_TARGET, sink = idx, error = %e, "tee log sink failed");
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if !accepted => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Run `op` on every sink, returning the first error.
+    async fn all<'a, F, Fut>(&'a self, op: F) -> Result<(), SinkError>
+    where
+        F: Fn(&'a dyn LogSink) -> Fut,
+        Fut: Future<Output = Result<(), SinkError>>,
+    {
+        let mut result = Ok(());
+        for sink in &self.sinks {
+            result = result.and(op(&**sink).await);
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl LogSink for TeeSink {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.each(|sink| sink.send(record)).await
+    }
+
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.each(|sink| sink.send_serialized(batch, index)).await
+    }
+
+    async fn send_batch(&self, records: &[LogRecord]) -> Result<(), SinkError> {
+        self.each(|sink| sink.send_batch(records)).await
+    }
+
+    /// Flushes every sink, returning the first error.
+    async fn flush(&self) -> Result<(), SinkError> {
+        self.all(|sink| sink.flush()).await
+    }
+
+    /// Fails if any sink is unhealthy.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        self.all(|sink| sink.health_check()).await
+    }
+
+    /// Checks every sink, stopping at the first error.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        let mut drift = Vec::new();
+        for sink in &self.sinks {
+            drift.extend(sink.schema_drift().await?);
+        }
+        Ok(drift)
+    }
+
+    /// Shuts every sink down, returning the first error.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        self.all(|sink| sink.shutdown()).await
+    }
+}
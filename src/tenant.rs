@@ -0,0 +1,134 @@
+use crate::encoding::SerializedBatch;
+use crate::record::LogRecord;
+use crate::schema::SchemaDrift;
+use crate::sink::{LogSink, SinkError};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// [`LogSink`] that sends each record to the sink of its tenant, for
+/// platforms where several tenants share one logging pipeline.
+///
+/// The tenant is the value of the event field `field` (e.g.
+/// `error!(tenant_id = "acme", ..)`), or of the resource attribute with
+/// that name; strings, numbers and booleans are accepted. Records of a
+/// tenant without a route, and records without the field, go to the
+/// default sink.
+///
+/// Routes can point at entirely different backends or at sinks of the same
+/// backend with another table, index or topic. To give every tenant its
+/// own table without a sink per tenant, use a single sink with a
+/// `{fields.tenant_id}` [`DestinationTemplate`] instead.
+///
+/// [`DestinationTemplate`]: crate::destination::DestinationTemplate
+pub struct TenantRouter {
+    field: String,
+    routes: HashMap<String, Arc<dyn LogSink>>,
+    default: Option<Arc<dyn LogSink>>,
+}
+
+impl TenantRouter {
+    /// Route records by the value of `field`.
+    ///
+    /// **Parameters**
+    /// - `field`: name of the event field or resource attribute holding
+    ///   the tenant, e.g. `"tenant_id"`.
+    /// - `routes`: sink per tenant value.
+    /// - `default`: sink for other records; with `None` they are rejected
+    ///   with [`SinkError::Permanent`].
+    pub fn new(
+        field: impl Into<String>,
+        routes: HashMap<String, Arc<dyn LogSink>>,
+        default: Option<Arc<dyn LogSink>>,
+    ) -> Self {
+        Self {
+            field: field.into(),
+            routes,
+            default,
+        }
+    }
+
+    /// Tenant of `record`, if it has one.
+    pub fn tenant_of<'a>(&self, record: &'a LogRecord) -> Option<std::borrow::Cow<'a, str>> {
+        match record.fields.get(&self.field) {
+            Some(serde_json::Value::String(s)) => Some(s.as_str().into()),
+            Some(v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => Some(v.to_string().into()),
+            _ => record.resource.get(&self.field).map(|s| s.as_str().into()),
+        }
+    }
+
+    fn sink_for(&self, record: &LogRecord) -> Result<&Arc<dyn LogSink>, SinkError> {
+        let tenant = self.tenant_of(record);
+        tenant
+            .as_deref()
+            .and_then(|t| self.routes.get(t))
+            .or(self.default.as_ref())
+            .ok_or_else(|| {
+                SinkError::permanent(match &tenant {
+                    Some(tenant) => format!("no route for {} {:?} and no default sink", self.field, tenant),
+                    None => format!("record has no {} and there is no default sink", self.field),
+                })
+            })
+    }
+
+    /// Every distinct sink, each once even if it serves several tenants.
+    fn sinks(&self) -> Vec<&Arc<dyn LogSink>> {
+        let mut sinks: Vec<&Arc<dyn LogSink>> = Vec::new();
+        for sink in self.routes.values().chain(self.default.as_ref()) {
+            if !sinks.iter().any(|s| Arc::ptr_eq(s, sink)) {
+                sinks.push(sink);
+            }
+        }
+        sinks
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl LogSink for TenantRouter {
+    async fn send(&self, record: &LogRecord) -> Result<(), SinkError> {
+        self.sink_for(record)?.send(record).await
+    }
+
+    /// Passes the batch on, so the tenant's sink can reuse its payloads.
+    async fn send_serialized(&self, batch: &SerializedBatch<'_>, index: usize) -> Result<(), SinkError> {
+        self.sink_for(batch.record(index))?.send_serialized(batch, index).await
+    }
+
+    /// Flushes every sink, returning the first error.
+    async fn flush(&self) -> Result<(), SinkError> {
+        let mut result = Ok(());
+        for sink in self.sinks() {
+            let flushed = sink.flush().await;
+            result = result.and(flushed);
+        }
+        result
+    }
+
+    /// Fails if any of the sinks is unhealthy.
+    async fn health_check(&self) -> Result<(), SinkError> {
+        for sink in self.sinks() {
+            sink.health_check().await?;
+        }
+        Ok(())
+    }
+
+    /// Checks every sink, stopping at the first error.
+    async fn schema_drift(&self) -> Result<Vec<SchemaDrift>, SinkError> {
+        let mut drift = Vec::new();
+        for sink in self.sinks() {
+            drift.extend(sink.schema_drift().await?);
+        }
+        Ok(drift)
+    }
+
+    /// Shuts every sink down, returning the first error.
+    async fn shutdown(&self) -> Result<(), SinkError> {
+        let mut result = Ok(());
+        for sink in self.sinks() {
+            let shut_down = sink.shutdown().await;
+            result = result.and(shut_down);
+        }
+        result
+    }
+}
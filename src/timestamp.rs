@@ -0,0 +1,88 @@
+#[cfg(feature = "chrono")]
+use chrono::SecondsFormat;
+
+/// Wall-clock time of a [`LogRecord`], always in UTC.
+///
+/// With the default `chrono` feature this is `chrono::DateTime<Utc>`.
+/// Without it, a `std::time` based type with the same method names and
+/// the same serialized form, for builds that avoid the chrono
+/// dependency.
+///
+/// [`LogRecord`]: crate::record::LogRecord
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::Utc>;
+
+#[cfg(not(feature = "chrono"))]
+pub use crate::std_timestamp::{ParseTimestampError, Timestamp};
+
+/// Current wall-clock time.
+pub(crate) fn now() -> Timestamp {
+    #[cfg(feature = "chrono")]
+    return chrono::Utc::now();
+    #[cfg(not(feature = "chrono"))]
+    return Timestamp::now();
+}
+
+/// Shape in which sinks write [`LogRecord::timestamp`].
+///
+/// Different backends prefer different representations; each built-in
+/// sink exposes this as a config option instead of hardcoding one.
+///
+/// [`LogRecord::timestamp`]: crate::record::LogRecord::timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// RFC 3339 with as many fractional digits as needed,
+    /// e.g. `2024-05-01T12:30:00.123456789+00:00`.
+    #[default]
+    Rfc3339,
+    /// RFC 3339 with millisecond precision and a `Z` suffix,
+    /// e.g. `2024-05-01T12:30:00.123Z`.
+    Rfc3339Millis,
+    /// Milliseconds since the Unix epoch as a JSON number.
+    EpochMillis,
+    /// Microseconds since the Unix epoch as a JSON number.
+    EpochMicros,
+    /// `YYYY-MM-DD hh:mm:ss.fff` in UTC, accepted as-is by ClickHouse
+    /// `DateTime64(3)` columns.
+    DateTime64Millis,
+}
+
+impl TimestampFormat {
+    /// Render `ts` in this format.
+    ///
+    /// **Returns**
+    /// - A JSON string for textual formats, a JSON number for epoch formats.
+    pub fn format(&self, ts: &Timestamp) -> serde_json::Value {
+        match self {
+            TimestampFormat::Rfc3339 => ts.to_rfc3339().into(),
+            TimestampFormat::Rfc3339Millis => rfc3339_millis(ts).into(),
+            TimestampFormat::EpochMillis => ts.timestamp_millis().into(),
+            TimestampFormat::EpochMicros => ts.timestamp_micros().into(),
+            TimestampFormat::DateTime64Millis => datetime64_millis(ts).into(),
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+pub(crate) fn rfc3339_millis(ts: &Timestamp) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(not(feature = "chrono"))]
+pub(crate) fn rfc3339_millis(ts: &Timestamp) -> String {
+    let mut out = String::with_capacity(24);
+    ts.write_to(&mut out, 'T', crate::std_timestamp::Fraction::Millis, "Z");
+    out
+}
+
+#[cfg(feature = "chrono")]
+fn datetime64_millis(ts: &Timestamp) -> String {
+    ts.format("%Y-%m-%d %H:%M:%S%.3f").to_string()
+}
+
+#[cfg(not(feature = "chrono"))]
+fn datetime64_millis(ts: &Timestamp) -> String {
+    let mut out = String::with_capacity(23);
+    ts.write_to(&mut out, ' ', crate::std_timestamp::Fraction::Millis, "");
+    out
+}
@@ -0,0 +1,269 @@
+//! TLS settings shared by the HTTP sinks (ClickHouse, OpenSearch).
+
+use std::path::PathBuf;
+
+/// TLS options of an HTTP sink, e.g. for log gateways that only accept
+/// clients presenting a certificate (mTLS).
+///
+/// The default trusts the built-in web PKI roots and presents no client
+/// certificate. Ignored on `wasm32`, where the host's `fetch` does TLS.
+///
+/// **Fields**
+/// - `ca_bundle`: PEM file with root certificates trusted in addition to
+///   the built-in ones, e.g. the internal CA of the gateway.
+/// - `client_cert`: PEM file with the client certificate chain; may
+///   contain the private key as well.
+/// - `client_key`: PEM file with the private key (PKCS#8, PKCS#1 or
+///   SEC1; only PKCS#8 with `tls-native`), if it is not part of
+///   `client_cert`.
+/// - `server_name`: name sent as SNI and checked against the server
+///   certificate instead of the host in the URL. Requests still go to the
+///   address of the URL host, so a gateway can be reached by IP or an
+///   internal alias while verifying its real name.
+/// - `insecure`: accept any server certificate. Only for tests; this
+///   disables protection against interception.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsOptions {
+    pub ca_bundle: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub server_name: Option<String>,
+    pub insecure: bool,
+}
+
+/// Error building the HTTP client of a sink, usually from bad
+/// [`TlsOptions`].
+#[derive(thiserror::Error, Debug)]
+pub enum TlsError {
+    #[error("failed to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("invalid certificate or key in {path}: {source}")]
+    InvalidPem {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("client_key is set without client_cert")]
+    KeyWithoutCert,
+
+    #[error("cannot use server_name with URL {url:?}: {reason}")]
+    ServerName { url: String, reason: String },
+
+    #[error("invalid proxy {url:?}: {reason}")]
+    Proxy { url: String, reason: String },
+
+    #[error("failed to build HTTP client: {0}")]
+    Client(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[cfg(not(any(target_arch = "wasm32", feature = "hyper-client")))]
+impl TlsOptions {
+    /// Configure `builder` for requests to `base_url`, on the TLS stack of
+    /// the `tls-native` or `tls-rustls` feature.
+    ///
+    /// **Returns**
+    /// - The builder and the base URL to send requests to: `base_url`
+    ///   itself, or with its host replaced by `server_name`, which the
+    ///   builder then resolves to the original host's addresses.
+    pub(crate) fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+        base_url: &str,
+    ) -> Result<(reqwest::ClientBuilder, String), TlsError> {
+        #[cfg(feature = "tls-native")]
+        {
+            builder = builder.use_native_tls();
+        }
+        #[cfg(all(feature = "tls-rustls", not(feature = "tls-native")))]
+        {
+            builder = builder.use_rustls_tls();
+        }
+
+        if let Some(path) = &self.ca_bundle {
+            let certs = reqwest::Certificate::from_pem_bundle(&read(path)?)
+                .map_err(|source| TlsError::InvalidPem { path: path.clone(), source: Box::new(source) })?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        match (&self.client_cert, &self.client_key) {
+            (Some(cert), key) => {
+                let pem = read(cert)?;
+                // native-tls takes the chain and a PKCS#8 key separately,
+                // and looks for the key in the certificate file when there
+                // is no separate key file.
+                #[cfg(feature = "tls-native")]
+                let identity = match key {
+                    Some(key) => reqwest::Identity::from_pkcs8_pem(&pem, &read(key)?),
+                    None => reqwest::Identity::from_pkcs8_pem(&pem, &pem),
+                };
+                #[cfg(not(feature = "tls-native"))]
+                let identity = {
+                    let mut pem = pem;
+                    if let Some(key) = key {
+                        pem.push(b'\n');
+                        pem.extend(read(key)?);
+                    }
+                    reqwest::Identity::from_pem(&pem)
+                };
+                let identity = identity
+                    .map_err(|source| TlsError::InvalidPem { path: cert.clone(), source: Box::new(source) })?;
+                builder = builder.identity(identity);
+            }
+            (None, Some(_)) => return Err(TlsError::KeyWithoutCert),
+            (None, None) => {}
+        }
+
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        match &self.server_name {
+            Some(server_name) => override_server_name(builder, base_url, server_name),
+            None => Ok((builder, base_url.to_string())),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(path: &PathBuf) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|source| TlsError::Read { path: path.clone(), source })
+}
+
+/// Point requests for `server_name` at the addresses of the host in
+/// `base_url` and rewrite the URL to use `server_name`.
+#[cfg(not(any(target_arch = "wasm32", feature = "hyper-client")))]
+fn override_server_name(
+    builder: reqwest::ClientBuilder,
+    base_url: &str,
+    server_name: &str,
+) -> Result<(reqwest::ClientBuilder, String), TlsError> {
+    use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+    let error = |reason: String| TlsError::ServerName { url: base_url.to_string(), reason };
+    let mut url = reqwest::Url::parse(base_url).map_err(|e| error(e.to_string()))?;
+    let port = url.port_or_known_default().ok_or_else(|| error("no port".to_string()))?;
+    let host = url.host_str().ok_or_else(|| error("no host".to_string()))?;
+    let addrs: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => (host, port)
+            .to_socket_addrs()
+            .map_err(|e| error(format!("cannot resolve {host}: {e}")))?
+            .collect(),
+    };
+    url.set_host(Some(server_name)).map_err(|e| error(e.to_string()))?;
+
+    let mut rewritten = url.to_string();
+    // `Url` always has a path; keep the caller's form so sinks can
+    // append to it as before.
+    if !base_url.ends_with('/') && rewritten.ends_with('/') {
+        rewritten.pop();
+    }
+    Ok((builder.resolve_to_addrs(server_name, &addrs), rewritten))
+}
+
+/// The web PKI roots, without client certificate.
+#[cfg(all(feature = "hyper-client", feature = "aws-auth", not(target_arch = "wasm32")))]
+pub(crate) fn default_rustls_config() -> rustls::ClientConfig {
+    rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(web_pki_roots())
+        .with_no_client_auth()
+}
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+fn web_pki_roots() -> rustls::RootCertStore {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    roots
+}
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+impl TlsOptions {
+    /// rustls settings of the hyper client. `server_name` is applied by
+    /// the connector, see [`crate::http::HttpTransport::new`].
+    pub(crate) fn rustls_config(&self) -> Result<rustls::ClientConfig, TlsError> {
+        let invalid = |path: &PathBuf, source: Box<dyn std::error::Error + Send + Sync>| TlsError::InvalidPem {
+            path: path.clone(),
+            source,
+        };
+
+        let mut roots = web_pki_roots();
+        if let Some(path) = &self.ca_bundle {
+            let certs = rustls_pemfile::certs(&mut read(path)?.as_slice()).map_err(|e| invalid(path, Box::new(e)))?;
+            if certs.is_empty() {
+                return Err(invalid(path, "no certificates found".into()));
+            }
+            for cert in certs {
+                roots.add(&rustls::Certificate(cert)).map_err(|e| invalid(path, Box::new(e)))?;
+            }
+        }
+        let builder = rustls::ClientConfig::builder().with_safe_defaults().with_root_certificates(roots);
+
+        let mut config = match (&self.client_cert, &self.client_key) {
+            (Some(cert), key) => {
+                let mut pem = read(cert)?;
+                if let Some(key) = key {
+                    pem.push(b'\n');
+                    pem.extend(read(key)?);
+                }
+                let mut chain = Vec::new();
+                let mut private_key = None;
+                for item in rustls_pemfile::read_all(&mut pem.as_slice()).map_err(|e| invalid(cert, Box::new(e)))? {
+                    match item {
+                        rustls_pemfile::Item::X509Certificate(der) => chain.push(rustls::Certificate(der)),
+                        rustls_pemfile::Item::PKCS8Key(der)
+                        | rustls_pemfile::Item::RSAKey(der)
+                        | rustls_pemfile::Item::ECKey(der) => {
+                            private_key.get_or_insert(rustls::PrivateKey(der));
+                        }
+                        _ => {}
+                    }
+                }
+                let private_key = private_key.ok_or_else(|| invalid(cert, "no private key found".into()))?;
+                builder
+                    .with_client_auth_cert(chain, private_key)
+                    .map_err(|e| invalid(cert, Box::new(e)))?
+            }
+            (None, Some(_)) => return Err(TlsError::KeyWithoutCert),
+            (None, None) => builder.with_no_client_auth(),
+        };
+
+        if self.insecure {
+            config.dangerous().set_certificate_verifier(std::sync::Arc::new(AcceptAnyCert));
+        }
+        Ok(config)
+    }
+}
+
+/// Verifier of [`TlsOptions::insecure`].
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+struct AcceptAnyCert;
+
+#[cfg(all(feature = "hyper-client", not(target_arch = "wasm32")))]
+impl rustls::client::ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::Certificate,
+        _intermediates: &[rustls::Certificate],
+        _server_name: &rustls::ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: std::time::SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
@@ -0,0 +1,259 @@
+//! Recording failed gRPC calls.
+//!
+//! [`GrpcErrors`] wraps a gRPC server's `tower_service::Service` (http
+//! 0.2: tonic up to 0.11) and emits an event for every call ending with a
+//! status other than `OK`, whether the status comes in the response
+//! headers (as for errors returned by unary handlers) or in the trailers
+//! after a streamed body. The events carry the fields `method`
+//! (`/package.Service/Method`), `code`, `status` (e.g. `NOT_FOUND`),
+//! `grpc_message` and `peer_ip`, and become records like any other event.
+//!
+//! ```ignore
+//! tonic::transport::Server::builder()
+//!     .layer(tower::layer::layer_fn(GrpcErrors::new))
+//!     .add_service(GreeterServer::new(greeter))
+//!     .serve(addr)
+//!     .await?;
+//! ```
+//!
+//! Calls failing with codes that usually mean a server fault (`UNKNOWN`,
+//! `DEADLINE_EXCEEDED`, `UNIMPLEMENTED`, `INTERNAL`, `UNAVAILABLE`,
+//! `DATA_LOSS`) are recorded at `ERROR`, the others at `WARN`, so the
+//! layer's `level` decides whether client errors are recorded too. The
+//! event is emitted in the span current when the call started, so behind
+//! [`RequestContext`](crate::tower::RequestContext) it also carries the
+//! request fields.
+
+use http::{HeaderMap, Request, Response};
+use http_body::{Body, SizeHint};
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_service::Service;
+
+/// Target of the events emitted by [`GrpcErrors`].
+pub const GRPC_TARGET: &str = "grpc";
+
+/// Which failed calls [`GrpcErrors`] records.
+///
+/// **Fields**
+/// - `ignore_codes`: status codes not recorded, e.g. `vec![5]` for
+///   `NOT_FOUND`.
+/// - `trust_forwarded_for`: take `peer_ip` from `X-Forwarded-For` /
+///   `X-Real-IP`, as in [`RequestContextConfig`]. Default `false`.
+///
+/// [`RequestContextConfig`]: crate::tower::RequestContextConfig
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GrpcErrorsConfig {
+    pub ignore_codes: Vec<u32>,
+    pub trust_forwarded_for: bool,
+}
+
+/// Service recording failed calls of `S`, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct GrpcErrors<S> {
+    inner: S,
+    config: Arc<GrpcErrorsConfig>,
+}
+
+impl<S> GrpcErrors<S> {
+    /// Wrap `inner`, recording every failed call.
+    pub fn new(inner: S) -> Self {
+        Self::with_config(inner, GrpcErrorsConfig::default())
+    }
+
+    /// Wrap `inner`, recording failed calls as `config` says.
+    pub fn with_config(inner: S, config: GrpcErrorsConfig) -> Self {
+        Self { inner, config: Arc::new(config) }
+    }
+}
+
+impl<S, B, ResBody> Service<Request<B>> for GrpcErrors<S>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    ResBody: Body,
+{
+    type Response = Response<GrpcBody<ResBody>>;
+    type Error = S::Error;
+    type Future = GrpcFuture<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let call = Call {
+            method: req.uri().path().to_string(),
+            peer_ip: crate::tower::peer_ip(&req, self.config.trust_forwarded_for),
+            span: tracing::Span::current(),
+            config: self.config.clone(),
+        };
+        GrpcFuture { inner: self.inner.call(req), call: Some(call) }
+    }
+}
+
+/// What an event about a call needs to know of its request.
+struct Call {
+    method: String,
+    peer_ip: Option<IpAddr>,
+    span: tracing::Span,
+    config: Arc<GrpcErrorsConfig>,
+}
+
+impl Call {
+    /// Record the call if `headers` hold a non-OK `grpc-status`.
+    ///
+    /// **Returns**
+    /// - Whether `headers` held a status at all.
+    fn check(&self, headers: &HeaderMap) -> bool {
+        let Some(code) = headers.get("grpc-status").and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        let code = code.trim().parse().unwrap_or(2);
+        if code != 0 && !self.config.ignore_codes.contains(&code) {
+            let message = headers.get("grpc-message").map(|v| percent_decode(v.as_bytes()));
+            self.record(code, message.as_deref());
+        }
+        true
+    }
+
+    fn record(&self, code: u32, message: Option<&str>) {
+        let _entered = self.span.enter();
+        let status = status_name(code);
+        let peer_ip = self.peer_ip.map(tracing::field::display);
+        if matches!(code, 2 | 4 | 12 | 13 | 14 | 15) {
+            tracing::error!(
+                target: GRPC_TARGET,
+                method = self.method.as_str(),
+                code,
+                status,
+                grpc_message = message,
+                peer_ip,
+                "{} failed with {}",
+                self.method,
+                status
+            );
+        } else {
+            tracing::warn!(
+                target: GRPC_TARGET,
+                method = self.method.as_str(),
+                code,
+                status,
+                grpc_message = message,
+                peer_ip,
+                "{} failed with {}",
+                self.method,
+                status
+            );
+        }
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response future of [`GrpcErrors`].
+    pub struct GrpcFuture<F> {
+        #[pin]
+        inner: F,
+        call: Option<Call>,
+    }
+}
+
+impl<F, ResBody, E> Future for GrpcFuture<F>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+{
+    type Output = Result<Response<GrpcBody<ResBody>>, E>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let response = std::task::ready!(this.inner.poll(cx))?;
+        let mut call = this.call.take();
+        // A trailers-only response carries the status in its headers.
+        if call.as_ref().is_some_and(|call| call.check(response.headers())) {
+            call = None;
+        }
+        Poll::Ready(Ok(response.map(|inner| GrpcBody { inner, call })))
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response body of [`GrpcErrors`], checking the status in the
+    /// trailers.
+    pub struct GrpcBody<B> {
+        #[pin]
+        inner: B,
+        call: Option<Call>,
+    }
+}
+
+impl<B: Body> Body for GrpcBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_data(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        self.project().inner.poll_data(cx)
+    }
+
+    fn poll_trailers(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let this = self.project();
+        let trailers = std::task::ready!(this.inner.poll_trailers(cx))?;
+        if let (Some(call), Some(trailers)) = (this.call.take(), &trailers) {
+            call.check(trailers);
+        }
+        Poll::Ready(Ok(trailers))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Canonical name of a gRPC status code.
+fn status_name(code: u32) -> &'static str {
+    match code {
+        0 => "OK",
+        1 => "CANCELLED",
+        2 => "UNKNOWN",
+        3 => "INVALID_ARGUMENT",
+        4 => "DEADLINE_EXCEEDED",
+        5 => "NOT_FOUND",
+        6 => "ALREADY_EXISTS",
+        7 => "PERMISSION_DENIED",
+        8 => "RESOURCE_EXHAUSTED",
+        9 => "FAILED_PRECONDITION",
+        10 => "ABORTED",
+        11 => "OUT_OF_RANGE",
+        12 => "UNIMPLEMENTED",
+        13 => "INTERNAL",
+        14 => "UNAVAILABLE",
+        15 => "DATA_LOSS",
+        16 => "UNAUTHENTICATED",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Decode the percent-encoding of `grpc-message`.
+fn percent_decode(value: &[u8]) -> String {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        let hex = value.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok());
+        match (value[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
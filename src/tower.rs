@@ -0,0 +1,138 @@
+//! Tower middleware putting HTTP request context on every record.
+//!
+//! [`RequestContext`] wraps a `tower_service::Service` taking
+//! `http::Request`s (http 0.2: axum 0.6, hyper 0.14, tonic up to 0.11)
+//! and runs each request in an `http_request` span with the fields
+//! `method`, `path`, `request_id` and `peer_ip`. With span field
+//! inheritance enabled ([`SpanFieldsConfig`]), records emitted while the
+//! request is handled carry these fields.
+//!
+//! ```ignore
+//! let app = axum::Router::new()
+//!     .route("/", get(handler))
+//!     .layer(tower::layer::layer_fn(RequestContext::new));
+//! ```
+//!
+//! [`SpanFieldsConfig`]: crate::span_fields::SpanFieldsConfig
+
+use http::{HeaderName, HeaderValue, Request};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_service::Service;
+use tracing::instrument::Instrumented;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// How [`RequestContext`] fills in `request_id` and `peer_ip`.
+///
+/// **Fields**
+/// - `request_id_header`: header holding the request id (default
+///   `x-request-id`). Requests without it get a new UUID, which is also
+///   set on the request so handlers and upstream calls see the same id.
+/// - `trust_forwarded_for`: take `peer_ip` from the first address of
+///   `X-Forwarded-For` (or from `X-Real-IP`) when present. Enable it only
+///   behind a proxy that sets these headers, since clients can forge
+///   them. Default `false`.
+///
+/// Otherwise `peer_ip` comes from a [`SocketAddr`] in the request
+/// extensions, as inserted by the server's connection handler; without
+/// one it is left out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestContextConfig {
+    pub request_id_header: HeaderName,
+    pub trust_forwarded_for: bool,
+}
+
+impl Default for RequestContextConfig {
+    fn default() -> Self {
+        Self {
+            request_id_header: HeaderName::from_static("x-request-id"),
+            trust_forwarded_for: false,
+        }
+    }
+}
+
+/// Service running each request of `S` in an `http_request` span, see
+/// the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct RequestContext<S> {
+    inner: S,
+    config: Arc<RequestContextConfig>,
+}
+
+impl<S> RequestContext<S> {
+    /// Wrap `inner` with the default [`RequestContextConfig`].
+    pub fn new(inner: S) -> Self {
+        Self::with_config(inner, RequestContextConfig::default())
+    }
+
+    /// Wrap `inner`, filling in fields as `config` says.
+    pub fn with_config(inner: S, config: RequestContextConfig) -> Self {
+        Self { inner, config: Arc::new(config) }
+    }
+
+    fn span<B>(&self, req: &mut Request<B>) -> tracing::Span {
+        let header = &self.config.request_id_header;
+        let request_id = match req.headers().get(header).and_then(|v| v.to_str().ok()) {
+            Some(id) => id.to_string(),
+            None => {
+                let id = Uuid::now_v7().to_string();
+                if let Ok(value) = HeaderValue::from_str(&id) {
+                    req.headers_mut().insert(header.clone(), value);
+                }
+                id
+            }
+        };
+        let span = tracing::info_span!(
+            "http_request",
+            method = %req.method(),
+            path = req.uri().path(),
+            request_id = request_id.as_str(),
+            peer_ip = tracing::field::Empty,
+        );
+        if let Some(ip) = peer_ip(req, self.config.trust_forwarded_for) {
+            span.record("peer_ip", tracing::field::display(ip));
+        }
+        span
+    }
+}
+
+impl<S, B> Service<Request<B>> for RequestContext<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let span = self.span(&mut req);
+        let future = {
+            let _entered = span.enter();
+            self.inner.call(req)
+        };
+        future.instrument(span)
+    }
+}
+
+/// Client address of `req`: the forwarding headers when they are
+/// trusted, else a [`SocketAddr`] in its extensions.
+pub(crate) fn peer_ip<B>(req: &Request<B>, trust_forwarded_for: bool) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let headers = req.headers();
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .or_else(|| headers.get("x-real-ip").and_then(|v| v.to_str().ok()));
+        if let Some(ip) = forwarded.and_then(|v| v.trim().parse().ok()) {
+            return Some(ip);
+        }
+    }
+    req.extensions().get::<SocketAddr>().map(SocketAddr::ip)
+}
@@ -0,0 +1,221 @@
+//! W3C Trace Context correlation for [`LogRecord`]s.
+//!
+//! When an event, or one of the spans it is emitted in, carries a
+//! `traceparent` field (or a bare `trace_id` / `span_id` pair), the ids are
+//! validated and promoted into [`LogRecord::trace_id`] and
+//! [`LogRecord::span_id`] instead of staying in the `fields` blob. The
+//! event's own fields win over its spans; among spans the nearest one wins.
+//! Values that are not valid W3C ids are left in `fields` untouched.
+//!
+//! [`LogRecord`]: crate::record::LogRecord
+//! [`LogRecord::trace_id`]: crate::record::LogRecord::trace_id
+//! [`LogRecord::span_id`]: crate::record::LogRecord::span_id
+
+use crate::fields::Fields;
+use tracing::field::{Field, Visit};
+
+/// Field holding a full W3C `traceparent` header value.
+pub const TRACEPARENT_FIELD: &str = "traceparent";
+/// Field holding a bare 32-hex-digit trace id.
+pub const TRACE_ID_FIELD: &str = "trace_id";
+/// Field holding a bare 16-hex-digit span (parent) id.
+pub const SPAN_ID_FIELD: &str = "span_id";
+
+/// Trace correlation ids extracted from an event or span.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    /// 32 lowercase hex digits, never all zeros.
+    pub trace_id: String,
+    /// 16 lowercase hex digits, never all zeros.
+    pub span_id: Option<String>,
+}
+
+/// Parse a W3C `traceparent` value
+/// (`{version}-{trace-id}-{parent-id}-{trace-flags}`).
+///
+/// **Returns**
+/// - `Some(..)` for a well-formed value: lowercase hex, version other than
+///   `ff`, non-zero ids, and exactly four parts for version `00` (later
+///   versions may append more parts, which are ignored).
+/// - `None` otherwise.
+pub fn parse_traceparent(value: &str) -> Option<TraceContext> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+
+    let valid = is_hex(version, 2)
+        && version != "ff"
+        && is_trace_id(trace_id)
+        && is_span_id(parent_id)
+        && is_hex(flags, 2)
+        && (version != "00" || parts.next().is_none());
+    valid.then(|| TraceContext {
+        trace_id: trace_id.to_string(),
+        span_id: Some(parent_id.to_string()),
+    })
+}
+
+/// Whether `value` is a valid W3C trace id.
+pub fn is_trace_id(value: &str) -> bool {
+    is_hex(value, 32) && value.bytes().any(|b| b != b'0')
+}
+
+/// Whether `value` is a valid W3C span (parent) id.
+pub fn is_span_id(value: &str) -> bool {
+    is_hex(value, 16) && value.bytes().any(|b| b != b'0')
+}
+
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Resolve a trace context from `traceparent`, or from `trace_id` plus an
+/// optional `span_id`.
+fn resolve(
+    traceparent: Option<&str>,
+    trace_id: Option<&str>,
+    span_id: Option<&str>,
+) -> Option<TraceContext> {
+    if let Some(context) = traceparent.and_then(parse_traceparent) {
+        return Some(context);
+    }
+    let trace_id = trace_id.filter(|id| is_trace_id(id))?;
+    Some(TraceContext {
+        trace_id: trace_id.to_string(),
+        span_id: span_id.filter(|id| is_span_id(id)).map(str::to_string),
+    })
+}
+
+/// Take a valid trace context out of event `fields`, removing the fields
+/// it was built from.
+pub(crate) fn extract(fields: &mut Fields) -> Option<TraceContext> {
+    let text = |key: &str| fields.get(key).and_then(serde_json::Value::as_str);
+    let context = resolve(text(TRACEPARENT_FIELD), text(TRACE_ID_FIELD), text(SPAN_ID_FIELD))?;
+
+    if text(TRACEPARENT_FIELD).is_some_and(|value| parse_traceparent(value).is_some()) {
+        fields.remove(TRACEPARENT_FIELD);
+    } else {
+        fields.remove(TRACE_ID_FIELD);
+        if context.span_id.is_some() {
+            fields.remove(SPAN_ID_FIELD);
+        }
+    }
+    Some(context)
+}
+
+/// Collects the trace correlation fields of a span, stored in the span's
+/// extensions by the layer.
+#[derive(Debug, Default)]
+pub(crate) struct SpanTraceFields {
+    traceparent: Option<String>,
+    trace_id: Option<String>,
+    span_id: Option<String>,
+}
+
+impl SpanTraceFields {
+    /// Whether none of the correlation fields were recorded.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.traceparent.is_none() && self.trace_id.is_none() && self.span_id.is_none()
+    }
+
+    /// Trace context of the span, if its fields form a valid one.
+    pub(crate) fn context(&self) -> Option<TraceContext> {
+        resolve(self.traceparent.as_deref(), self.trace_id.as_deref(), self.span_id.as_deref())
+    }
+
+    fn set(&mut self, field: &Field, value: String) {
+        match field.name() {
+            TRACEPARENT_FIELD => self.traceparent = Some(value),
+            TRACE_ID_FIELD => self.trace_id = Some(value),
+            SPAN_ID_FIELD => self.span_id = Some(value),
+            _ => {}
+        }
+    }
+}
+
+impl Visit for SpanTraceFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set(field, value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if matches!(field.name(), TRACEPARENT_FIELD | TRACE_ID_FIELD | SPAN_ID_FIELD) {
+            self.set(field, format!("{:?}", value));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn parses_a_valid_traceparent() {
+        let expected = Some(TraceContext { trace_id: TRACE_ID.to_string(), span_id: Some(SPAN_ID.to_string()) });
+        for value in [
+            format!("00-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!(" 00-{}-{}-00\n", TRACE_ID, SPAN_ID),
+            // Later versions may append parts.
+            format!("01-{}-{}-01-extra", TRACE_ID, SPAN_ID),
+        ] {
+            assert_eq!(parse_traceparent(&value), expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_traceparents() {
+        let zeros = format!("00-{}-{}-01", "0".repeat(32), SPAN_ID);
+        let zero_span = format!("00-{}-{}-01", TRACE_ID, "0".repeat(16));
+        let cases = [
+            // Invalid or unknown-format versions.
+            format!("ff-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("0-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("zz-{}-{}-01", TRACE_ID, SPAN_ID),
+            // Version 00 has exactly four parts.
+            format!("00-{}-{}-01-extra", TRACE_ID, SPAN_ID),
+            // All-zero ids.
+            zeros,
+            zero_span,
+            // Wrong lengths.
+            format!("00-{}-{}-01", &TRACE_ID[1..], SPAN_ID),
+            format!("00-{}0-{}-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01", TRACE_ID, &SPAN_ID[1..]),
+            format!("00-{}-{}-1", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}", TRACE_ID, SPAN_ID),
+            // Uppercase hex is not allowed.
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), SPAN_ID),
+            String::new(),
+        ];
+        for value in cases {
+            assert_eq!(parse_traceparent(&value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn extract_promotes_valid_ids_and_keeps_invalid_ones() {
+        let mut fields = Fields::from([
+            (TRACEPARENT_FIELD.to_string(), json!(format!("00-{}-{}-01", TRACE_ID, SPAN_ID))),
+            ("user_id".to_string(), json!(7)),
+        ]);
+        assert_eq!(extract(&mut fields).map(|c| c.trace_id), Some(TRACE_ID.to_string()));
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["user_id"]);
+
+        let mut fields = Fields::from([
+            (TRACE_ID_FIELD.to_string(), json!(TRACE_ID)),
+            (SPAN_ID_FIELD.to_string(), json!("not-a-span")),
+        ]);
+        let context = extract(&mut fields).unwrap();
+        assert_eq!(context.span_id, None);
+        assert_eq!(fields.keys().collect::<Vec<_>>(), [SPAN_ID_FIELD]);
+
+        let mut fields = Fields::from([(TRACEPARENT_FIELD.to_string(), json!("00-bogus"))]);
+        assert_eq!(extract(&mut fields), None);
+        assert_eq!(fields.len(), 1);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::errors;
+use crate::fields::Fields;
+use std::error::Error;
+use tracing::field::{Field, Visit};
+
+pub struct FieldVisitor<'a> {
+    pub fields: &'a mut Fields,
+    pub message: &'a mut Option<String>,
+}
+
+impl<'a> Visit for FieldVisitor<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            *self.message = Some(value.to_string());
+        } else {
+            self.fields.insert(field.name().to_string(), serde_json::Value::String(value.to_string()));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        // `serde_json::Number` holds at most 64 bits; wider values, e.g.
+        // request or trace ids, are kept exact as a decimal string.
+        let json = match i64::try_from(value) {
+            Ok(v) => serde_json::Value::from(v),
+            Err(_) => serde_json::Value::String(value.to_string()),
+        };
+        self.fields.insert(field.name().to_string(), json);
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        let json = match u64::try_from(value) {
+            Ok(v) => serde_json::Value::from(v),
+            Err(_) => serde_json::Value::String(value.to_string()),
+        };
+        self.fields.insert(field.name().to_string(), json);
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // NaN and infinities are not valid JSON numbers.
+        let json = serde_json::Number::from_f64(value)
+            .map(serde_json::Value::Number)
+            .unwrap_or_else(|| serde_json::Value::String(value.to_string()));
+        self.fields.insert(field.name().to_string(), json);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), serde_json::Value::from(value));
+    }
+
+    /// Record an error as structured `<field>.message`, `<field>.chain`
+    /// (messages of all `source()` causes, outermost first),
+    /// `<field>.type` and `<field>.kind` fields, see
+    /// [`errors::insert_fields`].
+    ///
+    /// `dyn Error` does not expose its concrete type name, so the type is
+    /// the `ErrorKind` of an `io::Error`, otherwise the leading identifier
+    /// of the error's `Debug` output (e.g. `ParseIntError` for
+    /// `ParseIntError { kind: InvalidDigit }`).
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        errors::insert_fields(self.fields, field.name(), value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // `error!("...")` passes the message as `format_args!`, which is
+        // recorded through this method rather than `record_str`.
+        if field.name() == "message" {
+            *self.message = Some(format!("{:?}", value));
+        } else {
+            self.fields.insert(field.name().to_string(), serde_json::Value::String(format!("{:?}", value)));
+        }
+    }
+}
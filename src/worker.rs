@@ -0,0 +1,709 @@
+use crate::backpressure::{ByteBudget, CapacitySignal};
+use crate::batching::AdaptiveBatching;
+use crate::channel::RecordReceiver;
+use crate::clock::Clock;
+use crate::collapse::Collapser;
+use crate::diagnostics::{DiagnosticKind, Diagnostics};
+use crate::encoding::SerializedBatch;
+use crate::init::WorkerMode;
+use crate::persist::{self, PersistConfig};
+use crate::pool::RecordPool;
+use crate::fingerprint::FingerprintConfig;
+use crate::flatten::FlattenConfig;
+use crate::limits::RecordLimits;
+use crate::processor::{self, ProcessOutcome, RecordFilter, RecordProcessor};
+use crate::record::{LogLevel, LogRecord};
+use crate::reload::{self, DynamicConfig, Route};
+use crate::runtime;
+use crate::sink::{LogSink, SinkError};
+use std::collections::{HashMap, HashSet};
+use std::future;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::cell::Cell;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use tokio::runtime::{Builder, Handle};
+use tokio::sync::{mpsc, oneshot, watch, Semaphore};
+use tokio::task::{self, JoinHandle, JoinSet};
+use tokio::time::{self, sleep, Duration, MissedTickBehavior};
+
+/// Request sent to the worker by [`crate::handle::LayerHandle`].
+#[derive(Debug)]
+pub(crate) enum Command {
+    /// Send everything accepted so far and flush the sink.
+    Flush(oneshot::Sender<()>),
+    /// Drain, send everything, shut the sink down and exit.
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// Background task that drains the layer's channel, runs the processor
+/// chain and ships batches to the sink.
+pub(crate) struct Worker {
+    pub(crate) sink: Arc<dyn LogSink>,
+    pub(crate) receivers: Vec<RecordReceiver>,
+    /// Shards of the `ERROR` lane, empty without
+    /// [`crate::init::LayerConfig::priority_lanes`].
+    pub(crate) error_receivers: Vec<RecordReceiver>,
+    pub(crate) error_weight: usize,
+    pub(crate) control: mpsc::UnboundedReceiver<Command>,
+    pub(crate) capacity: Option<Arc<CapacitySignal>>,
+    /// Released as records leave the channel; see
+    /// [`crate::init::LayerConfig::max_buffered_bytes`].
+    pub(crate) buffered_bytes: Option<Arc<ByteBudget>>,
+    pub(crate) diagnostics: Arc<Diagnostics>,
+    pub(crate) batch_size: usize,
+    pub(crate) max_batch_bytes: Option<usize>,
+    /// [`LogRecord::estimated_size`] of the records in the current batch.
+    pub(crate) batch_bytes: usize,
+    /// Bounds the number of batches handed to the sink at once; see
+    /// [`crate::init::LayerConfig::max_in_flight`].
+    pub(crate) in_flight: Arc<Semaphore>,
+    /// Batches currently being sent; each resolves to its send latency.
+    pub(crate) sends: JoinSet<Duration>,
+    pub(crate) flush_interval: Duration,
+    pub(crate) adaptive: Option<AdaptiveBatching>,
+    pub(crate) fingerprint: Option<FingerprintConfig>,
+    pub(crate) processors: Vec<Arc<dyn RecordProcessor>>,
+    pub(crate) flatten: Option<FlattenConfig>,
+    /// Re-applied after flattening, which can add fields.
+    pub(crate) limits: RecordLimits,
+    pub(crate) filter: Option<RecordFilter>,
+    pub(crate) collapser: Option<Collapser>,
+    /// Runtime settings; `routes` is refreshed from it whenever it changes.
+    pub(crate) dynamic: watch::Receiver<DynamicConfig>,
+    /// See [`crate::init::LayerConfig::routes`].
+    pub(crate) routes: Vec<Route>,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) enqueued_events: Arc<AtomicU64>,
+    pub(crate) filtered_events: Arc<AtomicU64>,
+    pub(crate) worker_restarts: Arc<AtomicU64>,
+    /// Set once a shutdown was requested, so a panic during it ends the
+    /// worker instead of restarting it.
+    pub(crate) stopping: bool,
+    /// See [`crate::stats::LayerStats::last_heartbeat`].
+    pub(crate) heartbeat: Arc<AtomicI64>,
+    /// See [`crate::init::LayerConfig::stall_timeout`].
+    pub(crate) stall_timeout: Option<Duration>,
+    /// Receives sent batches; see [`crate::init::LayerConfig::record_pool_size`].
+    pub(crate) pool: Option<Arc<RecordPool>>,
+    /// See [`crate::init::LayerConfig::persist_unsent`].
+    pub(crate) persist: Option<PersistConfig>,
+    /// Records left unsent by the previous process, sent before anything
+    /// else.
+    pub(crate) replay: Vec<LogRecord>,
+    /// Records of the batches being sent, kept only with `persist`, so
+    /// [`Worker::persist`] can write them to the backlog file too.
+    pub(crate) unacked: HashMap<task::Id, Arc<Vec<LogRecord>>>,
+    /// Sends whose records are in the backlog file; the file is removed
+    /// once they have all finished.
+    pub(crate) backlog: HashSet<task::Id>,
+    /// Set while [`Worker::send_backlog`] spawns sends, which then join
+    /// `backlog`.
+    pub(crate) spooling: bool,
+}
+
+impl Worker {
+    /// Spawn the worker on `runtime`, on the ambient runtime, or, when
+    /// called outside of any runtime, on a shared background runtime
+    /// thread, so the layer can be built from a synchronous `main()`.
+    ///
+    /// With [`WorkerMode::Thread`] the worker always gets its own thread
+    /// and current-thread runtime instead.
+    pub(crate) fn spawn(self, mode: WorkerMode, runtime: Option<&Handle>) -> JoinHandle<()> {
+        if mode == WorkerMode::Thread {
+            return self.spawn_thread();
+        }
+        let handle = match runtime {
+            Some(handle) => handle.clone(),
+            None => Handle::try_current().unwrap_or_else(|_| runtime::background().clone()),
+        };
+        handle.spawn(WorkerScope::new(self.run()))
+    }
+
+    /// Run the worker on a dedicated thread that drives a private
+    /// current-thread runtime until the worker task finishes or is aborted.
+    fn spawn_thread(self) -> JoinHandle<()> {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build log sink worker runtime");
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        let handle = runtime.spawn(WorkerScope::new(async move {
+            self.run().await;
+            let _ = done_tx.send(());
+        }));
+        thread::Builder::new()
+            .name("log-sink-worker".to_string())
+            .spawn(move || {
+                let _ = runtime.block_on(done_rx);
+            })
+            .expect("spawn log sink worker thread");
+        handle
+    }
+
+    /// Run until shut down, restarting the loop on the same channel and
+    /// batch when it panics, e.g. in a record processor or in
+    /// [`LogSink::flush`]; the record being processed is lost.
+    pub(crate) async fn run(mut self) {
+        let _watchdog = self.stall_timeout.map(|timeout| {
+            Watchdog::start(Arc::clone(&self.heartbeat), timeout, Arc::clone(&self.clock), Arc::clone(&self.diagnostics))
+        });
+        self.beat();
+        let mut batch = self.new_batch();
+        let replay = std::mem::take(&mut self.replay);
+        if !replay.is_empty() {
+            self.send_backlog(replay, &mut batch).await;
+        }
+        loop {
+            match CatchUnwind(Box::pin(self.serve(&mut batch))).await {
+                Ok(()) => return,
+                Err(()) if self.stopping => return,
+                Err(()) => {
+                    let restarts = self.worker_restarts.fetch_add(1, Ordering::Relaxed) + 1;
+                    self.diagnostics.emit(DiagnosticKind::WorkerRestarted, || {
+                        format!("log sink worker panicked, restarting it (restart {})", restarts)
+                    });
+                }
+            }
+        }
+    }
+
+    async fn serve(&mut self, batch: &mut Vec<LogRecord>) {
+        // A fixed-rate ticker (rather than a per-iteration sleep) guarantees
+        // a flush at least every `flush_interval` even under steady traffic.
+        let mut cursor = LaneCursor::default();
+        let mut ticker = time::interval(self.flush_interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            self.beat();
+            if ticker.period() != self.flush_interval {
+                ticker = time::interval_at(time::Instant::now() + self.flush_interval, self.flush_interval);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            }
+            tokio::select! {
+                Some(record) = recv_lanes(&mut self.error_receivers, &mut self.receivers, self.error_weight, &mut cursor) => {
+                    self.admit(record, batch);
+                    if self.batch_full(batch) {
+                        self.send_batch(batch).await;
+                    }
+                }
+                _ = ticker.tick() => {
+                    if let Some(collapser) = self.collapser.as_mut() {
+                        collapser.expire(self.clock.instant(), batch);
+                    }
+                    if !batch.is_empty() {
+                        self.send_batch(batch).await;
+                    }
+                }
+                // Joined as they finish while a backlog file waits for them,
+                // so it is removed without waiting for the next batch.
+                Some(result) = self.sends.join_next_with_id(), if !self.backlog.is_empty() => {
+                    self.observe(result);
+                }
+                Some(command) = self.control.recv() => match command {
+                    Command::Flush(ack) => {
+                        self.flush(batch).await;
+                        let _ = ack.send(());
+                    }
+                    Command::Shutdown(ack) => {
+                        self.stopping = true;
+                        self.shutdown(batch).await;
+                        let _ = ack.send(());
+                        return;
+                    }
+                },
+            }
+        }
+    }
+
+    fn beat(&self) {
+        self.heartbeat.store(self.clock.now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// Run one received record through routing, fingerprinting, flattening,
+    /// the filter, the processor chain and the collapser, pushing whatever
+    /// should be sent into `batch`.
+    fn admit(&mut self, mut record: LogRecord, batch: &mut Vec<LogRecord>) {
+        self.enqueued_events.fetch_add(1, Ordering::Relaxed);
+        // `ERROR` records in their own lane were never counted in the budget.
+        let error_lane = !self.error_receivers.is_empty() && record.level == LogLevel::Error;
+        if let Some(budget) = self.buffered_bytes.as_ref().filter(|_| !error_lane) {
+            budget.release(record.estimated_size());
+        }
+        if let Some(capacity) = &self.capacity {
+            capacity.released();
+        }
+        if self.dynamic.has_changed().unwrap_or(false) {
+            self.routes = self.dynamic.borrow_and_update().routes.clone();
+        }
+        if record.service_name.is_none() {
+            if let Some(service) = reload::route(&self.routes, &record.target) {
+                record.service_name = Some(service.to_string());
+            }
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            record.fingerprint = Some(fingerprint.compute(&record));
+        }
+        if let Some(flatten) = &self.flatten {
+            flatten.apply(&mut record.fields);
+            self.limits.apply(&mut record);
+        }
+        if self.filter.as_ref().is_some_and(|keep| !keep(&record))
+            || processor::apply_chain(&self.processors, &mut record) == ProcessOutcome::Drop
+        {
+            self.filtered_events.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let before = batch.len();
+        match self.collapser.as_mut() {
+            Some(collapser) => collapser.admit(record, self.clock.instant(), batch),
+            None => batch.push(record),
+        }
+        if self.max_batch_bytes.is_some() {
+            self.batch_bytes += batch[before..].iter().map(LogRecord::estimated_size).sum::<usize>();
+        }
+    }
+
+    /// Whether the batch reached `batch_size` records or `max_batch_bytes`.
+    fn batch_full(&self, batch: &[LogRecord]) -> bool {
+        batch.len() >= self.batch_size
+            || self.max_batch_bytes.is_some_and(|max| self.batch_bytes >= max)
+    }
+
+    /// Send the records queued in the channel right now, every open
+    /// collapse summary and the current batch, then flush the sink.
+    ///
+    /// Only the records present at the start are taken, so a steady
+    /// stream of new events cannot keep the flush from completing.
+    async fn flush(&mut self, batch: &mut Vec<LogRecord>) {
+        for idx in 0..self.error_receivers.len() + self.receivers.len() {
+            for _ in 0..self.shard(idx).len() {
+                let Ok(record) = self.shard(idx).try_recv() else {
+                    break;
+                };
+                self.admit(record, batch);
+                if self.batch_full(batch) {
+                    self.send_batch(batch).await;
+                }
+            }
+        }
+        if let Some(collapser) = self.collapser.as_mut() {
+            collapser.drain(batch);
+        }
+        if !batch.is_empty() {
+            self.send_batch(batch).await;
+        }
+        self.wait_sends().await;
+        if let Err(e) = self.sink.flush().await {
+            self.diagnostics
+                .emit(DiagnosticKind::FlushFailed, || format!("error flushing log sink: {}", e));
+        }
+    }
+
+    /// Shard `idx` counting the `ERROR` lane first.
+    fn shard(&mut self, idx: usize) -> &mut RecordReceiver {
+        match idx.checked_sub(self.error_receivers.len()) {
+            Some(idx) => &mut self.receivers[idx],
+            None => &mut self.error_receivers[idx],
+        }
+    }
+
+    /// Close the channel, send everything still queued, then shut the
+    /// sink down.
+    async fn shutdown(&mut self, batch: &mut Vec<LogRecord>) {
+        for receiver in self.error_receivers.iter_mut().chain(self.receivers.iter_mut()) {
+            receiver.close();
+        }
+        if let Some(config) = self.persist.clone() {
+            self.persist(&config, batch).await;
+        }
+        self.flush(batch).await;
+        if let Err(e) = self.sink.shutdown().await {
+            self.diagnostics
+                .emit(DiagnosticKind::ShutdownFailed, || format!("error shutting down log sink: {}", e));
+        }
+    }
+
+    /// Write everything not yet delivered to the backlog file: the
+    /// batches being sent, the records still queued and the current
+    /// batch. Then send what was queued and remove the file once all of
+    /// it has been delivered.
+    async fn persist(&mut self, config: &PersistConfig, batch: &mut Vec<LogRecord>) {
+        let mut cursor = LaneCursor::default();
+        while let Some(record) =
+            recv_lanes(&mut self.error_receivers, &mut self.receivers, self.error_weight, &mut cursor).await
+        {
+            self.admit(record, batch);
+        }
+        if let Some(collapser) = self.collapser.as_mut() {
+            collapser.drain(batch);
+        }
+        if batch.is_empty() && self.unacked.is_empty() {
+            return;
+        }
+        let unsent = self.unacked.values().flat_map(|records| records.iter()).chain(batch.iter());
+        if let Err(e) = persist::save(config, unsent) {
+            self.diagnostics.emit(DiagnosticKind::PersistFailed, || {
+                format!("error writing unsent log records to {}: {}", config.path.display(), e)
+            });
+            return;
+        }
+        self.backlog = self.unacked.keys().copied().collect();
+        let backlog = std::mem::replace(batch, self.new_batch());
+        self.send_backlog(backlog, batch).await;
+        self.wait_sends().await;
+    }
+
+    /// Send `records`, which are in the backlog file, and remove the file
+    /// once they and the sends already in `backlog` have finished.
+    async fn send_backlog(&mut self, records: Vec<LogRecord>, batch: &mut Vec<LogRecord>) {
+        self.spooling = true;
+        self.send_all(records, batch).await;
+        self.spooling = false;
+        self.settle_backlog();
+    }
+
+    /// Remove the backlog file if none of its records is being sent any
+    /// more.
+    fn settle_backlog(&mut self) {
+        if self.spooling || !self.backlog.is_empty() {
+            return;
+        }
+        let Some(config) = &self.persist else {
+            return;
+        };
+        if let Err(e) = persist::remove(&config.path) {
+            self.diagnostics.emit(DiagnosticKind::PersistFailed, || {
+                format!("error removing delivered log records from {}: {}", config.path.display(), e)
+            });
+        }
+    }
+
+    /// Send already admitted `records` in batches of `batch_size`.
+    async fn send_all(&mut self, mut records: Vec<LogRecord>, batch: &mut Vec<LogRecord>) {
+        while !records.is_empty() {
+            let rest = records.split_off(records.len().min(self.batch_size));
+            *batch = records;
+            self.send_batch(batch).await;
+            records = rest;
+        }
+    }
+
+    /// Hand `batch` to a background send task and start a new one.
+    ///
+    /// Waits for a free `in_flight` permit first, so at most
+    /// `max_in_flight` batches are held in memory while a slow sink
+    /// catches up, and the channel fills (and drops) instead.
+    async fn send_batch(&mut self, batch: &mut Vec<LogRecord>) {
+        let records = std::mem::replace(batch, self.new_batch());
+        self.batch_bytes = 0;
+        let permit = Arc::clone(&self.in_flight)
+            .acquire_owned()
+            .await
+            .expect("in-flight semaphore is never closed");
+        let records = Arc::new(records);
+        let sink = Arc::clone(&self.sink);
+        let diagnostics = Arc::clone(&self.diagnostics);
+        let clock = Arc::clone(&self.clock);
+        let pool = self.pool.clone();
+        let sending = Arc::clone(&records);
+        let id = self
+            .sends
+            .spawn(WorkerScope::new(async move {
+                let started = clock.instant();
+                send_records(sink.as_ref(), &diagnostics, &sending).await;
+                drop(permit);
+                let elapsed = clock.instant().saturating_duration_since(started);
+                recycle(pool.as_deref(), sending);
+                elapsed
+            }))
+            .id();
+        if self.persist.is_some() {
+            self.unacked.insert(id, records);
+        }
+        if self.spooling {
+            self.backlog.insert(id);
+        }
+        // Joined after the spawn, so `backlog` never runs empty between
+        // the sends of one `send_backlog`.
+        while let Some(result) = self.sends.try_join_next_with_id() {
+            self.observe(result);
+        }
+    }
+
+    /// An empty batch buffer, from the pool when there is one.
+    fn new_batch(&self) -> Vec<LogRecord> {
+        match &self.pool {
+            Some(pool) => pool.batch(self.batch_size),
+            None => Vec::with_capacity(self.batch_size),
+        }
+    }
+
+    /// Wait until every batch handed to [`Worker::send_batch`] is sent.
+    async fn wait_sends(&mut self) {
+        while let Some(result) = self.sends.join_next_with_id().await {
+            self.observe(result);
+        }
+    }
+
+    /// Account for a finished send and feed its latency into adaptive
+    /// batching.
+    ///
+    /// A send that panicked keeps its records in `unacked` and the backlog
+    /// file, so they are persisted again at shutdown.
+    fn observe(&mut self, result: Result<(task::Id, Duration), task::JoinError>) {
+        let Ok((id, latency)) = result else {
+            return;
+        };
+        if let Some(records) = self.unacked.remove(&id) {
+            recycle(self.pool.as_deref(), records);
+        }
+        if self.backlog.remove(&id) {
+            self.settle_backlog();
+        }
+        let Some(adaptive) = &self.adaptive else {
+            return;
+        };
+        (self.batch_size, self.flush_interval) =
+            adaptive.adjust(latency, self.batch_size, self.flush_interval);
+    }
+}
+
+/// Return a sent batch to `pool` once its last holder lets go of it.
+fn recycle(pool: Option<&RecordPool>, records: Arc<Vec<LogRecord>>) {
+    if let (Some(pool), Ok(records)) = (pool, Arc::try_unwrap(records)) {
+        pool.recycle(records);
+    }
+}
+
+/// Send every record of `records`.
+///
+/// A batch of several records goes to [`LogSink::send_batch`] first, so
+/// sinks with a bulk API write it in one request. If that fails with a
+/// retryable error (or panics), the records are sent one by one with
+/// [`LogSink::send_serialized`]; retryable [`SinkError`]s are then
+/// retried with exponential backoff (or the server's `retry_after`),
+/// reset by the next success, resuming at the failed record so records
+/// accepted one by one are not sent twice. Poison records, and records
+/// whose send panicked, are dropped; see the delivery guarantees of
+/// [`LogSink`]. Records are serialized at most once per encoding, see
+/// [`SerializedBatch`].
+async fn send_records(sink: &dyn LogSink, diagnostics: &Diagnostics, records: &[LogRecord]) {
+    if records.len() > 1 {
+        match CatchUnwind(sink.send_batch(records)).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) if !e.is_retryable() => {
+                diagnostics.emit(DiagnosticKind::RecordRejected, || {
+                    format!("dropping log records rejected by sink: {}", e)
+                });
+                return;
+            }
+            Ok(Err(e)) => diagnostics.emit(DiagnosticKind::SendRetry, || {
+                format!("log sink batch send failed ({}), sending its records one by one", e)
+            }),
+            Err(()) => diagnostics.emit(DiagnosticKind::SendRetry, || {
+                "log sink panicked on a batch, sending its records one by one".to_string()
+            }),
+        }
+    }
+    let batch = SerializedBatch::new(records);
+    let initial_backoff = Duration::from_millis(100);
+    let mut backoff = initial_backoff;
+    let max_backoff = Duration::from_secs(10);
+    let mut next = 0;
+
+    while next < records.len() {
+        let result = match CatchUnwind(sink.send_serialized(&batch, next)).await {
+            Ok(result) => result,
+            Err(()) => Err(SinkError::permanent("log sink panicked")),
+        };
+        match result {
+            Ok(()) => {
+                next += 1;
+                // A success ends the outage; the next failure starts over.
+                backoff = initial_backoff;
+            }
+            Err(e) if !e.is_retryable() => {
+                diagnostics.emit(DiagnosticKind::RecordRejected, || {
+                    format!("dropping log record rejected by sink: {}", e)
+                });
+                next += 1;
+            }
+            Err(e) => {
+                let delay = match e {
+                    SinkError::RateLimited { retry_after: Some(retry_after) } => retry_after,
+                    _ => backoff,
+                };
+                diagnostics.emit(DiagnosticKind::SendRetry, || {
+                    format!("log sink send failed ({}), retrying in {:?}", e, delay)
+                });
+                sleep(delay).await;
+                backoff = std::cmp::min(backoff * 2, max_backoff);
+            }
+        }
+    }
+}
+
+/// Reports [`DiagnosticKind::WorkerStalled`] once per stall when the
+/// worker's heartbeat gets older than the timeout. It checks from a
+/// thread of its own, so a sink blocking the worker's thread is noticed
+/// too, and stops when dropped together with the worker.
+struct Watchdog {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::Thread>,
+}
+
+impl Watchdog {
+    fn start(heartbeat: Arc<AtomicI64>, timeout: Duration, clock: Arc<dyn Clock>, diagnostics: Arc<Diagnostics>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let timeout_ms = i64::try_from(timeout.as_millis()).unwrap_or(i64::MAX);
+        let spawned = thread::Builder::new().name("log-sink-watchdog".to_string()).spawn(move || {
+            let mut reported = None;
+            while !stopped.load(Ordering::Relaxed) {
+                let beat = heartbeat.load(Ordering::Relaxed);
+                let age = clock.now().timestamp_millis().saturating_sub(beat);
+                if age > timeout_ms && reported != Some(beat) {
+                    reported = Some(beat);
+                    diagnostics.emit(DiagnosticKind::WorkerStalled, || {
+                        format!("log sink worker made no progress for {:?}", Duration::from_millis(age as u64))
+                    });
+                }
+                thread::park_timeout((timeout / 4).max(Duration::from_millis(10)));
+            }
+        });
+        match spawned {
+            Ok(handle) => Self { stop, thread: Some(handle.thread().clone()) },
+            Err(_) => Self { stop, thread: None },
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = &self.thread {
+            thread.unpark();
+        }
+    }
+}
+
+thread_local! {
+    static IN_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the current thread is polling the worker right now.
+pub(crate) fn in_worker() -> bool {
+    IN_WORKER.with(Cell::get)
+}
+
+/// Marks the current thread as [`in_worker`] while `F` is being polled, so
+/// events emitted by the sink's own code are not captured again.
+pub(crate) struct WorkerScope<F>(Pin<Box<F>>);
+
+impl<F: Future> WorkerScope<F> {
+    pub(crate) fn new(future: F) -> Self {
+        WorkerScope(Box::pin(future))
+    }
+}
+
+impl<F: Future> Future for WorkerScope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let previous = IN_WORKER.with(|flag| flag.replace(true));
+        let result = self.0.as_mut().poll(cx);
+        IN_WORKER.with(|flag| flag.set(previous));
+        result
+    }
+}
+
+/// Resolves to `Err(())` instead of unwinding when `F` panics, so a
+/// bug in a sink costs one record instead of its whole batch.
+struct CatchUnwind<F>(F);
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = Result<F::Output, ()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| Pin::new(&mut self.0).poll(cx))) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(_) => Poll::Ready(Err(())),
+        }
+    }
+}
+
+/// Where [`recv_lanes`] resumes its scan.
+#[derive(Debug, Default)]
+struct LaneCursor {
+    next_error: usize,
+    next_other: usize,
+    /// `ERROR` records taken since the last lower-level one.
+    errors_in_row: usize,
+}
+
+/// Receive from the `ERROR` lane first, unless `weight` records in a row
+/// came from it and the other lane has one ready. Resolves to `None` once
+/// all shards of both lanes are closed.
+async fn recv_lanes(
+    errors: &mut [RecordReceiver],
+    others: &mut [RecordReceiver],
+    weight: usize,
+    cursor: &mut LaneCursor,
+) -> Option<LogRecord> {
+    future::poll_fn(|cx| {
+        let errors_first = cursor.errors_in_row < weight.max(1);
+        let mut closed = 0;
+        for error_lane in [errors_first, !errors_first] {
+            let polled = if error_lane {
+                poll_any(errors, &mut cursor.next_error, cx)
+            } else {
+                poll_any(others, &mut cursor.next_other, cx)
+            };
+            match polled {
+                Poll::Ready(Some(record)) => {
+                    cursor.errors_in_row = if error_lane { cursor.errors_in_row + 1 } else { 0 };
+                    return Poll::Ready(Some(record));
+                }
+                Poll::Ready(None) => closed += 1,
+                Poll::Pending => {}
+            }
+        }
+        if closed == 2 {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Receive from whichever shard has a record ready, starting the scan at
+/// `next` so that no shard is starved. Ready with `None` once all shards
+/// are closed, including when there are none.
+fn poll_any(receivers: &mut [RecordReceiver], next: &mut usize, cx: &mut Context<'_>) -> Poll<Option<LogRecord>> {
+    let count = receivers.len();
+    let mut closed = 0;
+    for offset in 0..count {
+        let idx = (*next + offset) % count;
+        match receivers[idx].poll_recv(cx) {
+            Poll::Ready(Some(record)) => {
+                *next = (idx + 1) % count;
+                return Poll::Ready(Some(record));
+            }
+            Poll::Ready(None) => closed += 1,
+            Poll::Pending => {}
+        }
+    }
+    if closed == count {
+        Poll::Ready(None)
+    } else {
+        Poll::Pending
+    }
+}